@@ -0,0 +1,14 @@
+-- Opt-in keyword search for encrypted chats
+ALTER TABLE chats ADD COLUMN search_enabled BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Blind index tokens (HMAC of normalized keywords, never plaintext)
+CREATE TABLE message_search_index (
+    message_id TEXT NOT NULL,
+    chat_id TEXT NOT NULL,
+    token TEXT NOT NULL,
+    PRIMARY KEY (message_id, token),
+    FOREIGN KEY (message_id) REFERENCES messages(id),
+    FOREIGN KEY (chat_id) REFERENCES chats(id)
+);
+
+CREATE INDEX idx_message_search_index_chat_token ON message_search_index(chat_id, token);
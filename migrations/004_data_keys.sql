@@ -0,0 +1,11 @@
+-- Per-chat and per-user data encryption keys, stored wrapped by the master key.
+-- Deleting a row crypto-shreds everything encrypted under that key.
+CREATE TABLE data_keys (
+    id TEXT PRIMARY KEY NOT NULL,
+    owner_type TEXT NOT NULL,
+    owner_id TEXT NOT NULL,
+    kek_id TEXT NOT NULL,
+    wrapped_key TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    UNIQUE (owner_type, owner_id)
+);
@@ -0,0 +1,34 @@
+-- Chats still encrypted at rest with server-held keys (the fallback mode).
+-- End-to-end encrypted chats only ever store opaque client ciphertext.
+ALTER TABLE chats ADD COLUMN server_side_encryption BOOLEAN NOT NULL DEFAULT TRUE;
+
+-- Messages relayed as client-encrypted envelopes
+ALTER TABLE messages ADD COLUMN is_e2ee BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE messages ADD COLUMN sender_device_id TEXT;
+ALTER TABLE messages ADD COLUMN sender_key_id TEXT;
+
+-- Public identity and signed pre-keys per client device
+CREATE TABLE device_keys (
+    user_id TEXT NOT NULL,
+    device_id TEXT NOT NULL,
+    identity_key TEXT NOT NULL,
+    identity_key_id TEXT NOT NULL,
+    signed_prekey_id INTEGER NOT NULL,
+    signed_prekey TEXT NOT NULL,
+    signed_prekey_signature TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (user_id, device_id),
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
+
+-- One-time pre-keys, each handed out at most once
+CREATE TABLE one_time_prekeys (
+    user_id TEXT NOT NULL,
+    device_id TEXT NOT NULL,
+    key_id INTEGER NOT NULL,
+    public_key TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (user_id, device_id, key_id),
+    FOREIGN KEY (user_id, device_id) REFERENCES device_keys(user_id, device_id)
+);
@@ -0,0 +1,10 @@
+-- Server Ed25519 signatures over the canonical form of each row.
+-- Rows written before signing was introduced stay unsigned (NULL).
+ALTER TABLE threads ADD COLUMN signature TEXT;
+ALTER TABLE threads ADD COLUMN signing_key_id TEXT;
+
+ALTER TABLE posts ADD COLUMN signature TEXT;
+ALTER TABLE posts ADD COLUMN signing_key_id TEXT;
+
+ALTER TABLE messages ADD COLUMN signature TEXT;
+ALTER TABLE messages ADD COLUMN signing_key_id TEXT;
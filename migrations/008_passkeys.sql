@@ -0,0 +1,26 @@
+-- WebAuthn passkeys
+CREATE TABLE passkey_credentials (
+    id TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT NOT NULL,
+    credential_id TEXT UNIQUE NOT NULL,
+    name TEXT NOT NULL,
+    -- Serialized credential (public key and authenticator metadata)
+    credential TEXT NOT NULL,
+    sign_count INTEGER NOT NULL DEFAULT 0,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    last_used_at TEXT,
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
+
+CREATE INDEX idx_passkey_credentials_user_id ON passkey_credentials(user_id);
+
+-- Server-side state of registration and authentication ceremonies in progress
+CREATE TABLE webauthn_ceremonies (
+    id TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    state TEXT NOT NULL,
+    expires_at TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
@@ -0,0 +1,18 @@
+-- Email verification and password resets
+ALTER TABLE users ADD COLUMN email_verified BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Issued email tokens. The token itself is signed and never stored; a row
+-- only records whether it was used, so each token works once.
+CREATE TABLE email_tokens (
+    id TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT NOT NULL,
+    purpose TEXT NOT NULL,
+    -- Address the token was sent to
+    email TEXT NOT NULL,
+    expires_at TEXT NOT NULL,
+    used_at TEXT,
+    created_at TEXT NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
+
+CREATE INDEX idx_email_tokens_user_id ON email_tokens(user_id, purpose);
@@ -0,0 +1,39 @@
+-- Accounts at external OpenID Connect providers, linked to local users
+CREATE TABLE user_identities (
+    id TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT NOT NULL,
+    provider TEXT NOT NULL,
+    -- The provider's stable `sub` claim
+    subject TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    last_login_at TEXT,
+    UNIQUE (provider, subject),
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
+
+CREATE INDEX idx_user_identities_user_id ON user_identities(user_id);
+
+-- Authorization requests awaiting the provider's redirect back
+CREATE TABLE oidc_login_states (
+    id TEXT PRIMARY KEY NOT NULL,
+    provider TEXT NOT NULL,
+    state_hash TEXT UNIQUE NOT NULL,
+    code_verifier TEXT NOT NULL,
+    nonce TEXT NOT NULL,
+    expires_at TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+-- Board moderators. `source` says where the role came from, e.g.
+-- "oidc:<provider>" for roles mapped from identity provider groups
+CREATE TABLE board_moderators (
+    board_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    source TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    PRIMARY KEY (board_id, user_id, source),
+    FOREIGN KEY (board_id) REFERENCES boards(id),
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
+
+CREATE INDEX idx_board_moderators_user_id ON board_moderators(user_id);
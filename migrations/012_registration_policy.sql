@@ -0,0 +1,20 @@
+-- Registration policy: invite codes and an approval queue
+-- "approved" or "pending"; pending accounts can't log in
+ALTER TABLE users ADD COLUMN approval_status TEXT NOT NULL DEFAULT 'approved';
+ALTER TABLE users ADD COLUMN invite_code_id TEXT REFERENCES invite_codes(id);
+
+-- Invite codes (SHA-256 hashes only)
+CREATE TABLE invite_codes (
+    id TEXT PRIMARY KEY NOT NULL,
+    code_hash TEXT UNIQUE NOT NULL,
+    created_by TEXT NOT NULL,
+    max_uses INTEGER NOT NULL,
+    uses INTEGER NOT NULL DEFAULT 0,
+    expires_at TEXT,
+    revoked BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TEXT NOT NULL,
+    FOREIGN KEY (created_by) REFERENCES users(id)
+);
+
+CREATE INDEX idx_invite_codes_created_by ON invite_codes(created_by);
+CREATE INDEX idx_users_approval_status ON users(approval_status);
@@ -0,0 +1,11 @@
+-- Anonymous posting challenges
+-- Per-board challenge difficulty for anonymous threads (NULL = CHALLENGE_DIFFICULTY, 0 = off)
+ALTER TABLE boards ADD COLUMN challenge_difficulty INTEGER;
+
+-- Solved challenges, kept until they expire so none can be reused
+CREATE TABLE used_challenges (
+    id TEXT PRIMARY KEY NOT NULL,
+    expires_at TEXT NOT NULL
+);
+
+CREATE INDEX idx_used_challenges_expires_at ON used_challenges(expires_at);
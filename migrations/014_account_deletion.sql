@@ -0,0 +1,9 @@
+-- Scheduled account deletion
+-- Set when the owner asks for deletion; the account is erased once this passes
+ALTER TABLE users ADD COLUMN deletion_scheduled_for TEXT;
+
+-- What happens to a deleted account's threads and posts on a board:
+-- 'anonymize' keeps them under the tombstone account, 'remove' deletes them
+ALTER TABLE boards ADD COLUMN deleted_author_content TEXT NOT NULL DEFAULT 'anonymize';
+
+CREATE INDEX idx_users_deletion_scheduled_for ON users(deletion_scheduled_for);
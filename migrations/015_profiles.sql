@@ -0,0 +1,10 @@
+-- Editable profiles
+ALTER TABLE users ADD COLUMN display_name TEXT;
+ALTER TABLE users ADD COLUMN bio TEXT;
+
+-- Privacy settings: 'everyone', 'registered' (non-anonymous accounts) or 'nobody'
+ALTER TABLE users ADD COLUMN last_seen_visibility TEXT NOT NULL DEFAULT 'everyone';
+ALTER TABLE users ADD COLUMN dm_policy TEXT NOT NULL DEFAULT 'everyone';
+
+-- Whether the account shows up in user search
+ALTER TABLE users ADD COLUMN discoverable BOOLEAN NOT NULL DEFAULT TRUE;
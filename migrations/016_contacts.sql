@@ -0,0 +1,13 @@
+-- Personal contact lists
+CREATE TABLE contacts (
+    owner_id TEXT NOT NULL,
+    contact_id TEXT NOT NULL,
+    nickname TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (owner_id, contact_id),
+    FOREIGN KEY (owner_id) REFERENCES users(id),
+    FOREIGN KEY (contact_id) REFERENCES users(id)
+);
+
+CREATE INDEX idx_contacts_contact_id ON contacts(contact_id);
+CREATE INDEX idx_users_display_name ON users(display_name);
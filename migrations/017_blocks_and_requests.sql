@@ -0,0 +1,34 @@
+-- Blocked users
+CREATE TABLE blocks (
+    blocker_id TEXT NOT NULL,
+    blocked_id TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (blocker_id, blocked_id),
+    FOREIGN KEY (blocker_id) REFERENCES users(id),
+    FOREIGN KEY (blocked_id) REFERENCES users(id)
+);
+
+CREATE INDEX idx_blocks_blocked_id ON blocks(blocked_id);
+
+-- Direct messages from non-contacts start as requests:
+-- 'accepted', 'pending' or 'declined' for each participant
+ALTER TABLE chat_participants ADD COLUMN request_status TEXT NOT NULL DEFAULT 'accepted';
+
+-- User reports for admins to review
+CREATE TABLE reports (
+    id TEXT PRIMARY KEY NOT NULL,
+    reporter_id TEXT NOT NULL,
+    reported_user_id TEXT NOT NULL,
+    chat_id TEXT,
+    reason TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'open',
+    created_at TEXT NOT NULL,
+    resolved_by TEXT,
+    resolved_at TEXT,
+    FOREIGN KEY (reporter_id) REFERENCES users(id),
+    FOREIGN KEY (reported_user_id) REFERENCES users(id),
+    FOREIGN KEY (chat_id) REFERENCES chats(id),
+    FOREIGN KEY (resolved_by) REFERENCES users(id)
+);
+
+CREATE INDEX idx_reports_status ON reports(status, created_at);
@@ -0,0 +1,4 @@
+-- Group chat owners; NULL for direct messages
+ALTER TABLE chats ADD COLUMN owner_id TEXT REFERENCES users(id);
+
+UPDATE chats SET owner_id = created_by WHERE is_group = TRUE;
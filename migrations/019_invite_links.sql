@@ -0,0 +1,48 @@
+-- Members of private boards
+CREATE TABLE board_members (
+    board_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    joined_at TEXT NOT NULL,
+    PRIMARY KEY (board_id, user_id),
+    FOREIGN KEY (board_id) REFERENCES boards(id),
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
+
+CREATE INDEX idx_board_members_user_id ON board_members(user_id);
+
+-- Creators of existing boards are their first members
+INSERT INTO board_members (board_id, user_id, joined_at)
+SELECT id, created_by, created_at FROM boards;
+
+-- Shareable links into a group chat or a private board. Only a hash of the
+-- token is stored.
+CREATE TABLE invite_links (
+    id TEXT PRIMARY KEY NOT NULL,
+    token_hash TEXT UNIQUE NOT NULL,
+    chat_id TEXT,
+    board_id TEXT,
+    created_by TEXT NOT NULL,
+    max_uses INTEGER,
+    uses INTEGER NOT NULL DEFAULT 0,
+    expires_at TEXT,
+    requires_approval BOOLEAN NOT NULL DEFAULT FALSE,
+    revoked BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TEXT NOT NULL,
+    CHECK ((chat_id IS NULL) != (board_id IS NULL)),
+    FOREIGN KEY (chat_id) REFERENCES chats(id),
+    FOREIGN KEY (board_id) REFERENCES boards(id),
+    FOREIGN KEY (created_by) REFERENCES users(id)
+);
+
+CREATE INDEX idx_invite_links_chat_id ON invite_links(chat_id);
+CREATE INDEX idx_invite_links_board_id ON invite_links(board_id);
+
+-- Users waiting for approval after opening a link that requires it
+CREATE TABLE invite_link_requests (
+    link_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    PRIMARY KEY (link_id, user_id),
+    FOREIGN KEY (link_id) REFERENCES invite_links(id),
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
@@ -0,0 +1,13 @@
+-- Private boards can use an end-to-end encrypted Matrix room
+ALTER TABLE boards ADD COLUMN is_encrypted BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Requests to join a private board, until a manager answers them
+CREATE TABLE board_join_requests (
+    board_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    message TEXT,
+    created_at TEXT NOT NULL,
+    PRIMARY KEY (board_id, user_id),
+    FOREIGN KEY (board_id) REFERENCES boards(id),
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
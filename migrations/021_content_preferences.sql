@@ -0,0 +1,9 @@
+-- What each account wants to see: NSFW boards stay hidden until opted into,
+-- images on NSFW boards are blurred and spoilers collapsed by default
+ALTER TABLE users ADD COLUMN show_nsfw BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE users ADD COLUMN blur_images BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE users ADD COLUMN hide_spoilers BOOLEAN NOT NULL DEFAULT TRUE;
+
+-- Threads and posts their authors marked as spoilers
+ALTER TABLE threads ADD COLUMN is_spoiler BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE posts ADD COLUMN is_spoiler BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,14 @@
+-- Per-board settings, editable by the board's creator and site admins
+-- Posting rules, shown to posters and in the Matrix room topic
+ALTER TABLE boards ADD COLUMN rules TEXT;
+-- Longest thread or reply, in characters
+ALTER TABLE boards ADD COLUMN max_post_length INTEGER NOT NULL DEFAULT 10000;
+-- Whether new threads need an image
+ALTER TABLE boards ADD COLUMN require_image_on_op BOOLEAN NOT NULL DEFAULT FALSE;
+-- Whether anonymous accounts may post, and whether only they may
+ALTER TABLE boards ADD COLUMN allow_anonymous BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE boards ADD COLUMN force_anonymous BOOLEAN NOT NULL DEFAULT FALSE;
+-- Seconds a user waits between threads and replies on the board (0 = none)
+ALTER TABLE boards ADD COLUMN post_cooldown_seconds INTEGER NOT NULL DEFAULT 0;
+-- Space-separated image file extensions, NULL for any
+ALTER TABLE boards ADD COLUMN allowed_file_types TEXT;
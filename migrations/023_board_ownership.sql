@@ -0,0 +1,6 @@
+-- Board owners, transferable; NULL once a deleted owner had nobody to hand over to
+ALTER TABLE boards ADD COLUMN owner_id TEXT REFERENCES users(id);
+
+UPDATE boards SET owner_id = created_by;
+
+CREATE INDEX idx_boards_owner_id ON boards(owner_id);
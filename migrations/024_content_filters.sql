@@ -0,0 +1,38 @@
+-- Word and link filter rules. A NULL board_id applies the rule to every board.
+-- Word rules hold a regex and `replace`, `reject` or `flag`; link rules hold a
+-- domain and `allow` or `deny`
+CREATE TABLE content_filters (
+    id TEXT PRIMARY KEY NOT NULL,
+    board_id TEXT,
+    kind TEXT NOT NULL,
+    pattern TEXT NOT NULL,
+    action TEXT NOT NULL,
+    replacement TEXT,
+    created_by TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    FOREIGN KEY (board_id) REFERENCES boards(id),
+    FOREIGN KEY (created_by) REFERENCES users(id)
+);
+
+CREATE INDEX idx_content_filters_board_id ON content_filters(board_id);
+
+-- Filter hits. thread_id and post_id point at the thread or reply that was
+-- posted, and stay set after it is deleted; both are NULL for rejected posts
+CREATE TABLE moderation_log (
+    id TEXT PRIMARY KEY NOT NULL,
+    board_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    thread_id TEXT,
+    post_id TEXT,
+    filter_id TEXT,
+    kind TEXT NOT NULL,
+    action TEXT NOT NULL,
+    detail TEXT NOT NULL,
+    excerpt TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    FOREIGN KEY (board_id) REFERENCES boards(id),
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
+
+CREATE INDEX idx_moderation_log_board_id ON moderation_log(board_id, created_at);
+CREATE INDEX idx_moderation_log_user_id ON moderation_log(user_id);
@@ -0,0 +1,10 @@
+-- At most one running job of each kind. Jobs still marked running were cut
+-- off by a restart, so they are failed first.
+UPDATE background_jobs
+SET status = 'failed',
+    error = 'Interrupted by restart',
+    updated_at = strftime('%Y-%m-%dT%H:%M:%S+00:00', 'now'),
+    finished_at = strftime('%Y-%m-%dT%H:%M:%S+00:00', 'now')
+WHERE status = 'running';
+
+CREATE UNIQUE INDEX idx_background_jobs_running ON background_jobs(kind) WHERE status = 'running';
@@ -0,0 +1,10 @@
+-- Pre-key bundle claims, kept briefly to rate-limit claims per account
+CREATE TABLE prekey_claims (
+    claimant_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    FOREIGN KEY (claimant_id) REFERENCES users(id),
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
+
+CREATE INDEX idx_prekey_claims_claimant_id ON prekey_claims(claimant_id, created_at);
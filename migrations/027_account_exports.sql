@@ -0,0 +1,8 @@
+-- Data exports started, kept briefly to rate-limit exports per account
+CREATE TABLE account_exports (
+    user_id TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
+
+CREATE INDEX idx_account_exports_user_id ON account_exports(user_id, created_at);
@@ -0,0 +1,4 @@
+-- Threads and replies made on forced-anonymous boards keep their author for
+-- moderation, cooldowns and account deletion, but never show it
+ALTER TABLE threads ADD COLUMN author_hidden BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE posts ADD COLUMN author_hidden BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,3 @@
+-- Who added a participant, so a group request names the member who sent it
+-- rather than the group's creator. NULL for the creator and older rows.
+ALTER TABLE chat_participants ADD COLUMN added_by TEXT REFERENCES users(id);
@@ -28,6 +28,18 @@ if command -v openssl &> /dev/null; then
     ENCRYPTION_KEY=$(openssl rand -base64 32)
     sed -i "s/your-base64-encoded-32-byte-encryption-key-here/$ENCRYPTION_KEY/" .env
     echo "✅ Encryption key generated and added to .env"
+
+    SEARCH_INDEX_KEY=$(openssl rand -base64 32)
+    sed -i "s|your-base64-encoded-32-byte-search-index-key-here|$SEARCH_INDEX_KEY|" .env
+    echo "✅ Search index key generated and added to .env"
+
+    MASTER_KEY=$(openssl rand -base64 32)
+    sed -i "s|your-base64-encoded-32-byte-master-key-here|$MASTER_KEY|" .env
+    echo "✅ Master key generated and added to .env"
+
+    SIGNING_KEY=$(openssl rand -base64 32)
+    sed -i "s|your-base64-encoded-32-byte-signing-key-here|$SIGNING_KEY|" .env
+    echo "✅ Signing key generated and added to .env"
 fi
 
 # Create necessary directories
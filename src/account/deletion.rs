@@ -0,0 +1,154 @@
+use chrono::Utc;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::auth::service::AuthService;
+use crate::board::service::BoardService;
+use crate::chat::service::ChatService;
+use crate::core::error::{AppError, AppResult};
+use crate::core::types::Job;
+use crate::jobs::service::JobService;
+use crate::storage::database::Database;
+
+/// Job kind recorded in `background_jobs`
+pub const DELETE_ACCOUNTS_JOB_KIND: &str = "delete_accounts";
+
+/// How often accounts past their grace period are looked for
+const SWEEP_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Carries out account deletions once their grace period has passed.
+///
+/// For each account, in order:
+/// - its chat messages are redacted on Matrix and deleted, and it leaves
+///   every chat
+/// - its threads and posts are removed or kept anonymized, depending on
+///   each board's `deleted_author_content` policy
+/// - the boards it owns pass to their longest-serving moderator
+/// - its sessions, credentials and keys are purged and the row becomes a
+///   tombstone (`AuthService::erase_account`)
+pub struct AccountDeletionService {
+    db: Arc<Database>,
+    auth: Arc<AuthService>,
+    boards: Arc<BoardService>,
+    chats: Arc<ChatService>,
+    jobs: Arc<JobService>,
+}
+
+impl AccountDeletionService {
+    pub fn new(
+        db: Arc<Database>,
+        auth: Arc<AuthService>,
+        boards: Arc<BoardService>,
+        chats: Arc<ChatService>,
+        jobs: Arc<JobService>,
+    ) -> Self {
+        Self { db, auth, boards, chats, jobs }
+    }
+
+    /// Periodically start a deletion job for accounts that are due
+    pub fn start_scheduler(self: &Arc<Self>) {
+        let service = Arc::clone(self);
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+
+            loop {
+                interval.tick().await;
+
+                if let Err(e) = service.run_due_deletions().await {
+                    warn!("Failed to start account deletion job: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Start a background job deleting every account past its grace period.
+    /// Returns `None` if there is nothing to do or a job is already running.
+    pub async fn run_due_deletions(&self) -> AppResult<Option<Job>> {
+        let user_ids = sqlx::query_scalar!(
+            "SELECT id FROM users WHERE deletion_scheduled_for <= ? ORDER BY deletion_scheduled_for ASC",
+            Utc::now().to_rfc3339()
+        )
+        .fetch_all(self.db.pool())
+        .await?;
+
+        if user_ids.is_empty() {
+            return Ok(None);
+        }
+
+        let Some(job) = self.jobs.start(DELETE_ACCOUNTS_JOB_KIND, user_ids.len() as i64, None).await? else {
+            return Ok(None);
+        };
+        info!("Started account deletion job {} for {} accounts", job.id, user_ids.len());
+
+        let db = Arc::clone(&self.db);
+        let auth = Arc::clone(&self.auth);
+        let boards = Arc::clone(&self.boards);
+        let chats = Arc::clone(&self.chats);
+        let jobs = Arc::clone(&self.jobs);
+        let job_id = job.id;
+
+        tokio::spawn(async move {
+            let mut processed = 0i64;
+            let mut failed = 0i64;
+
+            for user_id in user_ids {
+                processed += 1;
+
+                let result = match Uuid::parse_str(&user_id) {
+                    Ok(user_id) => delete_account(&db, &auth, &boards, &chats, user_id).await,
+                    Err(e) => Err(AppError::Internal(format!("Invalid user ID: {}", e))),
+                };
+
+                if let Err(e) = result {
+                    warn!("Failed to delete account {}: {}", user_id, e);
+                    failed += 1;
+                }
+
+                if let Err(e) = jobs.update_progress(job_id, processed, failed).await {
+                    warn!("Failed to record progress of job {}: {}", job_id, e);
+                }
+            }
+
+            info!("Account deletion job {} finished: {} processed, {} failed", job_id, processed, failed);
+
+            // Failed accounts stay scheduled and are retried by the next sweep
+            if let Err(e) = jobs.finish(job_id, None).await {
+                warn!("Failed to record completion of job {}: {}", job_id, e);
+            }
+        });
+
+        Ok(Some(job))
+    }
+}
+
+async fn delete_account(
+    db: &Database,
+    auth: &AuthService,
+    boards: &BoardService,
+    chats: &ChatService,
+    user_id: Uuid,
+) -> AppResult<()> {
+    // The owner may have cancelled since the job started
+    let user_record = sqlx::query!(
+        "SELECT matrix_user_id FROM users WHERE id = ? AND deletion_scheduled_for <= ?",
+        user_id.to_string(),
+        Utc::now().to_rfc3339()
+    )
+    .fetch_optional(db.pool())
+    .await?;
+
+    let Some(user_record) = user_record else {
+        return Ok(());
+    };
+
+    chats.remove_deleted_member(user_id, &user_record.matrix_user_id).await?;
+    boards.remove_deleted_author_content(user_id).await?;
+    boards.hand_over_owned_boards(user_id).await?;
+    auth.erase_account(user_id).await?;
+
+    info!("Deleted account {}", user_id);
+    Ok(())
+}
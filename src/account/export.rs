@@ -0,0 +1,377 @@
+use chrono::{DateTime, Duration, Utc};
+use ring::digest;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{Seek, SeekFrom, Write};
+use std::sync::Arc;
+use tracing::warn;
+use uuid::Uuid;
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+use crate::auth::service::AuthService;
+use crate::auth::tokens::ApiTokenService;
+use crate::board::service::BoardService;
+use crate::chat::service::ChatService;
+use crate::core::error::{AppError, AppResult};
+use crate::core::types::{Chat, Message, MessageType};
+use crate::crypto::signing::SigningService;
+use crate::matrix::client::MatrixClient;
+use crate::storage::database::Database;
+use crate::user::blocks::BlockService;
+use crate::user::contacts::ContactService;
+use crate::user::service::UserService;
+
+/// Media downloaded into one archive; anything beyond is only listed
+const MAX_MEDIA_BYTES: usize = 256 * 1024 * 1024;
+
+/// Messages fetched per page when exporting a chat
+const MESSAGE_PAGE_SIZE: i64 = 100;
+
+/// An account can start one export per window; each one reads every chat
+/// and may download hundreds of megabytes of media
+const EXPORT_WINDOW_MINUTES: i64 = 60;
+
+#[derive(Serialize)]
+struct ExportedSession {
+    id: String,
+    created_at: String,
+    expires_at: String,
+}
+
+#[derive(Serialize)]
+struct ExportedChat {
+    chat: Chat,
+    messages: Vec<Message>,
+}
+
+#[derive(Serialize)]
+struct ExportedMedia {
+    url: String,
+    /// Path inside the archive, if the file could be included
+    path: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ManifestEntry {
+    path: String,
+    sha256: String,
+    size: usize,
+}
+
+#[derive(Serialize)]
+struct Manifest {
+    user_id: Uuid,
+    generated_at: DateTime<Utc>,
+    files: Vec<ManifestEntry>,
+}
+
+/// Zip archive of everything stored about an account.
+///
+/// Alongside the JSON files and media, the archive holds `manifest.json`
+/// with the SHA-256 of every file, and `manifest.sig.json`, a signature over
+/// the manifest by the content signing key published at
+/// `/api/keys/signing`.
+///
+/// Messages of server-side encrypted chats are decrypted. Messages of
+/// end-to-end encrypted chats are exported as the ciphertext envelopes the
+/// server holds.
+///
+/// The archive is written to an anonymous temporary file, which is removed
+/// once the returned handle is dropped.
+pub struct ExportService {
+    db: Arc<Database>,
+    matrix_client: Arc<MatrixClient>,
+    auth: Arc<AuthService>,
+    users: Arc<UserService>,
+    contacts: Arc<ContactService>,
+    blocks: Arc<BlockService>,
+    api_tokens: Arc<ApiTokenService>,
+    boards: Arc<BoardService>,
+    chats: Arc<ChatService>,
+    signing: Arc<SigningService>,
+}
+
+impl ExportService {
+    pub fn new(
+        db: Arc<Database>,
+        matrix_client: Arc<MatrixClient>,
+        auth: Arc<AuthService>,
+        users: Arc<UserService>,
+        contacts: Arc<ContactService>,
+        blocks: Arc<BlockService>,
+        api_tokens: Arc<ApiTokenService>,
+        boards: Arc<BoardService>,
+        chats: Arc<ChatService>,
+        signing: Arc<SigningService>,
+    ) -> Self {
+        Self { db, matrix_client, auth, users, contacts, blocks, api_tokens, boards, chats, signing }
+    }
+
+    /// Build the archive for a user. Returns the file rewound to the start.
+    pub async fn export_account(&self, user_id: Uuid) -> AppResult<File> {
+        self.record_export(user_id).await?;
+
+        let mut archive = Archive::new().await?;
+
+        let user = self.auth.get_user(user_id).await?;
+        archive.add_json("profile.json", &user).await?;
+        archive.add_json("profile_settings.json", &self.users.get_profile(user_id).await?).await?;
+
+        let session_records = sqlx::query!(
+            "SELECT id, created_at, expires_at FROM sessions WHERE user_id = ? ORDER BY created_at ASC",
+            user_id.to_string()
+        )
+        .fetch_all(self.db.pool())
+        .await?;
+
+        let sessions: Vec<ExportedSession> = session_records
+            .into_iter()
+            .map(|record| ExportedSession {
+                id: record.id,
+                created_at: record.created_at,
+                expires_at: record.expires_at,
+            })
+            .collect();
+        archive.add_json("sessions.json", &sessions).await?;
+
+        archive.add_json("contacts.json", &self.contacts.list_contacts(&user).await?).await?;
+        archive.add_json("blocks.json", &self.blocks.list_blocks(&user).await?).await?;
+        archive.add_json("api_tokens.json", &self.api_tokens.list_tokens(user_id).await?).await?;
+
+        let threads = self.boards.get_user_threads(user_id).await?;
+        archive.add_json("threads.json", &threads).await?;
+
+        let posts = self.boards.get_user_posts(user_id).await?;
+        archive.add_json("posts.json", &posts).await?;
+
+        let mut media_urls: Vec<String> = user.avatar_url.iter().cloned()
+            .chain(threads.iter().filter_map(|thread| thread.image_url.clone()))
+            .chain(posts.iter().filter_map(|post| post.image_url.clone()))
+            .collect();
+
+        for chat in self.chats.get_user_chats(user_id).await? {
+            let messages = self.chat_messages(chat.id, user_id).await?;
+
+            media_urls.extend(
+                messages.iter()
+                    .filter(|message| message.created_by == user_id && !message.is_e2ee)
+                    .filter(|message| !matches!(message.message_type, MessageType::Text | MessageType::System))
+                    .map(|message| message.content.clone()),
+            );
+
+            archive.add_json(&format!("chats/{}.json", chat.id), &ExportedChat { chat, messages }).await?;
+        }
+
+        let media = self.add_media(&mut archive, media_urls).await?;
+        archive.add_json("media.json", &media).await?;
+
+        archive.finish(user_id, &self.signing).await
+    }
+
+    /// Count an export against the account's limit. The check and the
+    /// insert are one statement, so parallel requests can't both start one.
+    async fn record_export(&self, user_id: Uuid) -> AppResult<()> {
+        let now = Utc::now();
+        let window_start = (now - Duration::minutes(EXPORT_WINDOW_MINUTES)).to_rfc3339();
+
+        sqlx::query!(
+            "DELETE FROM account_exports WHERE user_id = ? AND created_at <= ?",
+            user_id.to_string(),
+            window_start
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO account_exports (user_id, created_at)
+            SELECT ?, ?
+            WHERE NOT EXISTS (SELECT 1 FROM account_exports WHERE user_id = ? AND created_at > ?)
+            "#,
+            user_id.to_string(),
+            now.to_rfc3339(),
+            user_id.to_string(),
+            window_start
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::RateLimit);
+        }
+
+        Ok(())
+    }
+
+    /// Every message of a chat, oldest first
+    async fn chat_messages(&self, chat_id: Uuid, user_id: Uuid) -> AppResult<Vec<Message>> {
+        let mut messages = Vec::new();
+        let mut offset = 0;
+
+        loop {
+            let page = self.chats
+                .get_messages(chat_id, user_id, Some(MESSAGE_PAGE_SIZE), Some(offset))
+                .await?;
+            let fetched = page.len() as i64;
+            messages.extend(page);
+
+            if fetched < MESSAGE_PAGE_SIZE {
+                break;
+            }
+            offset += fetched;
+        }
+
+        messages.reverse();
+        Ok(messages)
+    }
+
+    /// Include files hosted on our homeserver. Links to other sites are only
+    /// listed; the server never fetches arbitrary URLs. A file that would
+    /// take the archive past `MAX_MEDIA_BYTES` is only listed too: its
+    /// announced size is checked before downloading, and its actual size
+    /// before adding it.
+    async fn add_media(&self, archive: &mut Archive, mut urls: Vec<String>) -> AppResult<Vec<ExportedMedia>> {
+        urls.sort();
+        urls.dedup();
+
+        let mut media = Vec::with_capacity(urls.len());
+        let mut total_bytes = 0;
+
+        for url in urls {
+            let path = match url.strip_prefix("mxc://") {
+                Some(media_id) if total_bytes < MAX_MEDIA_BYTES => match self.download_within(&url, MAX_MEDIA_BYTES - total_bytes).await {
+                    Some(bytes) => {
+                        let path = format!("media/{}", media_id.replace('/', "_"));
+                        total_bytes += bytes.len();
+                        archive.add(&path, bytes).await?;
+                        Some(path)
+                    }
+                    None => None,
+                },
+                _ => None,
+            };
+
+            media.push(ExportedMedia { url, path });
+        }
+
+        Ok(media)
+    }
+
+    /// Download a file if it's at most `remaining` bytes
+    async fn download_within(&self, url: &str, remaining: usize) -> Option<Vec<u8>> {
+        match self.matrix_client.media_size(url).await {
+            Ok(Some(size)) if size > remaining as u64 => return None,
+            Ok(_) => {}
+            Err(e) => warn!("Failed to look up media {}: {}", url, e),
+        }
+
+        match self.matrix_client.download_media(url).await {
+            Ok(bytes) if bytes.len() <= remaining => Some(bytes),
+            Ok(_) => None,
+            Err(e) => {
+                warn!("Failed to export media {}: {}", url, e);
+                None
+            }
+        }
+    }
+}
+
+/// Zip writer that records each file for the manifest. Writing happens on
+/// the blocking thread pool, as the archive lives in a file on disk.
+struct Archive {
+    /// Lent to the blocking pool while a file is written
+    zip: Option<ZipWriter<File>>,
+    entries: Vec<ManifestEntry>,
+}
+
+impl Archive {
+    async fn new() -> AppResult<Self> {
+        let file = blocking(|| {
+            tempfile::tempfile()
+                .map_err(|e| AppError::Internal(format!("Failed to create export archive: {}", e)))
+        })
+        .await?;
+
+        Ok(Self {
+            zip: Some(ZipWriter::new(file)),
+            entries: Vec::new(),
+        })
+    }
+
+    async fn add(&mut self, path: &str, bytes: Vec<u8>) -> AppResult<()> {
+        let mut zip = self.zip.take()
+            .ok_or_else(|| AppError::Internal("Export archive is already closed".to_string()))?;
+        let path = path.to_string();
+
+        let (zip, entry) = blocking(move || {
+            let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+            zip.start_file(path.as_str(), options)
+                .map_err(|e| AppError::Internal(format!("Failed to write export archive: {}", e)))?;
+            zip.write_all(&bytes)
+                .map_err(|e| AppError::Internal(format!("Failed to write export archive: {}", e)))?;
+
+            let entry = ManifestEntry {
+                sha256: hex(digest::digest(&digest::SHA256, &bytes).as_ref()),
+                size: bytes.len(),
+                path,
+            };
+            Ok((zip, entry))
+        })
+        .await?;
+
+        self.zip = Some(zip);
+        self.entries.push(entry);
+
+        Ok(())
+    }
+
+    async fn add_json<T: Serialize>(&mut self, path: &str, value: &T) -> AppResult<()> {
+        let bytes = serde_json::to_vec_pretty(value)
+            .map_err(|e| AppError::Internal(format!("Failed to serialize {}: {}", path, e)))?;
+        self.add(path, bytes).await
+    }
+
+    /// Write the signed manifest and close the archive
+    async fn finish(mut self, user_id: Uuid, signing: &SigningService) -> AppResult<File> {
+        let manifest = Manifest {
+            user_id,
+            generated_at: Utc::now(),
+            files: std::mem::take(&mut self.entries),
+        };
+        let manifest = serde_json::to_vec_pretty(&manifest)
+            .map_err(|e| AppError::Internal(format!("Failed to serialize manifest: {}", e)))?;
+        let signature = serde_json::to_vec_pretty(&signing.sign(&manifest))
+            .map_err(|e| AppError::Internal(format!("Failed to serialize manifest signature: {}", e)))?;
+
+        self.add("manifest.json", manifest).await?;
+        self.add("manifest.sig.json", signature).await?;
+
+        let mut zip = self.zip.take()
+            .ok_or_else(|| AppError::Internal("Export archive is already closed".to_string()))?;
+
+        blocking(move || {
+            let mut file = zip.finish()
+                .map_err(|e| AppError::Internal(format!("Failed to write export archive: {}", e)))?;
+            file.seek(SeekFrom::Start(0))
+                .map_err(|e| AppError::Internal(format!("Failed to read export archive: {}", e)))?;
+            Ok(file)
+        })
+        .await
+    }
+}
+
+/// Run file I/O on the blocking thread pool
+async fn blocking<T, F>(work: F) -> AppResult<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> AppResult<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(work)
+        .await
+        .map_err(|e| AppError::Internal(format!("Export archive task failed: {}", e)))?
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,316 @@
+use base64::{Engine as _, engine::general_purpose};
+use chrono::{DateTime, Duration, Utc};
+use ring::hmac;
+use std::sync::Arc;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::core::config::SecurityConfig;
+use crate::core::error::{AppError, AppResult};
+use crate::core::types::User;
+use crate::crypto::service::CryptoService;
+use crate::mail::mailer::{Email, Mailer};
+use crate::storage::database::Database;
+
+const VERIFY_EMAIL: &str = "verify_email";
+const RESET_PASSWORD: &str = "reset_password";
+
+/// Minimum time between two emails of the same kind to one account
+const RESEND_INTERVAL_SECONDS: i64 = 60;
+
+/// Email verification and password resets.
+///
+/// Links carry a token of the form `<id>.<expiry>.<signature>`, signed with
+/// a key derived from `SESSION_SECRET`. The signature covers the token's
+/// purpose, so a verification link can't be replayed as a reset link. The
+/// `email_tokens` row makes each token single use.
+pub struct AccountEmailService {
+    db: Arc<Database>,
+    crypto: Arc<CryptoService>,
+    mailer: Arc<dyn Mailer>,
+    signing_key: hmac::Key,
+    base_url: String,
+    config: SecurityConfig,
+}
+
+impl AccountEmailService {
+    pub fn new(
+        db: Arc<Database>,
+        crypto: Arc<CryptoService>,
+        mailer: Arc<dyn Mailer>,
+        base_url: &str,
+        config: SecurityConfig,
+    ) -> Self {
+        let derived = hmac::sign(
+            &hmac::Key::new(hmac::HMAC_SHA256, config.session_secret.as_bytes()),
+            b"amogchan/email-token/v1",
+        );
+
+        Self {
+            db,
+            crypto,
+            mailer,
+            signing_key: hmac::Key::new(hmac::HMAC_SHA256, derived.as_ref()),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            config,
+        }
+    }
+
+    /// Email the user a link confirming their address
+    pub async fn send_verification(&self, user: &User) -> AppResult<()> {
+        let user_record = sqlx::query!(
+            "SELECT email, email_verified FROM users WHERE id = ?",
+            user.id.to_string()
+        )
+        .fetch_optional(self.db.pool())
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+        let email = user_record.email
+            .ok_or_else(|| AppError::InvalidRequest("No email address on this account".to_string()))?;
+        if user_record.email_verified {
+            return Err(AppError::InvalidRequest("Email address is already verified".to_string()));
+        }
+
+        let expires_at = Utc::now() + Duration::hours(self.config.email_verification_ttl_hours);
+        let Some(token) = self.issue_token(user.id, VERIFY_EMAIL, &email, expires_at).await? else {
+            return Ok(());
+        };
+
+        self.deliver(Email {
+            to: email,
+            subject: "Confirm your email address".to_string(),
+            body: format!(
+                "Hi {},\n\nConfirm your email address by opening this link:\n\n{}/verify-email?token={}\n\nThe link expires in {} hours. If you didn't sign up, ignore this email.\n",
+                user.username,
+                self.base_url,
+                token,
+                self.config.email_verification_ttl_hours
+            ),
+        });
+
+        Ok(())
+    }
+
+    /// Mark the address a verification token was sent to as verified
+    pub async fn verify_email(&self, token: &str) -> AppResult<()> {
+        let (user_id, email) = self.redeem_token(token, VERIFY_EMAIL).await?;
+
+        // The address may have changed since the link was sent
+        let result = sqlx::query!(
+            "UPDATE users SET email_verified = TRUE WHERE id = ? AND email = ?",
+            user_id.to_string(),
+            email
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::InvalidRequest("Invalid or expired token".to_string()));
+        }
+
+        info!("User {} verified their email address", user_id);
+        Ok(())
+    }
+
+    /// Email a password reset link to the account with this verified address.
+    ///
+    /// Succeeds whether or not such an account exists, so the endpoint can't
+    /// be used to find out which addresses are registered.
+    pub async fn request_password_reset(&self, email: &str) -> AppResult<()> {
+        let email = email.trim();
+
+        let user_record = sqlx::query!(
+            "SELECT id, username FROM users WHERE email = ? AND email_verified = TRUE AND is_anonymous = FALSE",
+            email
+        )
+        .fetch_optional(self.db.pool())
+        .await?;
+
+        let Some(user_record) = user_record else {
+            return Ok(());
+        };
+
+        let user_id = Uuid::parse_str(&user_record.id)
+            .map_err(|e| AppError::Internal(format!("Invalid user ID: {}", e)))?;
+
+        let expires_at = Utc::now() + Duration::minutes(self.config.password_reset_ttl_minutes);
+        let Some(token) = self.issue_token(user_id, RESET_PASSWORD, email, expires_at).await? else {
+            return Ok(());
+        };
+
+        self.deliver(Email {
+            to: email.to_string(),
+            subject: "Reset your password".to_string(),
+            body: format!(
+                "Hi {},\n\nSomeone asked to reset the password of your account. To choose a new password, open this link:\n\n{}/reset-password?token={}\n\nThe link expires in {} minutes. If it wasn't you, ignore this email; your password stays the same.\n",
+                user_record.username,
+                self.base_url,
+                token,
+                self.config.password_reset_ttl_minutes
+            ),
+        });
+
+        Ok(())
+    }
+
+    /// Set a new password with a reset token, log out every session and
+    /// revoke every personal access token
+    pub async fn reset_password(&self, token: &str, new_password: &str) -> AppResult<()> {
+        if new_password.is_empty() {
+            return Err(AppError::InvalidRequest("Password must not be empty".to_string()));
+        }
+
+        let (user_id, _) = self.redeem_token(token, RESET_PASSWORD).await?;
+        let password_hash = self.crypto.hash_password(new_password)?;
+
+        let mut tx = self.db.pool().begin().await?;
+
+        sqlx::query!(
+            "UPDATE users SET password_hash = ? WHERE id = ?",
+            password_hash,
+            user_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        // Whoever knew the old password may still be logged in
+        sqlx::query!(
+            "DELETE FROM sessions WHERE user_id = ?",
+            user_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM login_challenges WHERE user_id = ?",
+            user_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        // So may tokens they minted with it
+        sqlx::query!(
+            "DELETE FROM api_tokens WHERE user_id = ?",
+            user_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        // Other reset links sent before this one are no longer needed
+        sqlx::query!(
+            "UPDATE email_tokens SET used_at = ? WHERE user_id = ? AND purpose = ? AND used_at IS NULL",
+            Utc::now().to_rfc3339(),
+            user_id.to_string(),
+            RESET_PASSWORD
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        info!("Password of user {} was reset, all sessions and tokens revoked", user_id);
+        Ok(())
+    }
+
+    /// Record and sign a new token. Returns `None` if an email of the same
+    /// kind went out moments ago, so the endpoints can't be used to flood
+    /// someone's inbox.
+    async fn issue_token(&self, user_id: Uuid, purpose: &str, email: &str, expires_at: DateTime<Utc>) -> AppResult<Option<String>> {
+        let now = Utc::now();
+        let recent_cutoff = now - Duration::seconds(RESEND_INTERVAL_SECONDS);
+
+        let recent = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) as "count: i64" FROM email_tokens WHERE user_id = ? AND purpose = ? AND created_at > ?"#,
+            user_id.to_string(),
+            purpose,
+            recent_cutoff.to_rfc3339()
+        )
+        .fetch_one(self.db.pool())
+        .await?;
+
+        if recent > 0 {
+            return Ok(None);
+        }
+
+        let token_id = Uuid::new_v4();
+        sqlx::query!(
+            "INSERT INTO email_tokens (id, user_id, purpose, email, expires_at, created_at) VALUES (?, ?, ?, ?, ?, ?)",
+            token_id.to_string(),
+            user_id.to_string(),
+            purpose,
+            email,
+            expires_at.to_rfc3339(),
+            now.to_rfc3339()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        let payload = format!("{}.{}", token_id.simple(), expires_at.timestamp());
+        let signature = hmac::sign(&self.signing_key, &signed_message(purpose, &payload));
+
+        Ok(Some(format!("{}.{}", payload, general_purpose::URL_SAFE_NO_PAD.encode(signature.as_ref()))))
+    }
+
+    /// Check a token's signature and expiry and mark it used.
+    /// Returns the user and the address the token was sent to.
+    async fn redeem_token(&self, token: &str, purpose: &str) -> AppResult<(Uuid, String)> {
+        let invalid = || AppError::InvalidRequest("Invalid or expired token".to_string());
+
+        let (payload, signature) = token.rsplit_once('.').ok_or_else(invalid)?;
+        let signature = general_purpose::URL_SAFE_NO_PAD.decode(signature).map_err(|_| invalid())?;
+        hmac::verify(&self.signing_key, &signed_message(purpose, payload), &signature).map_err(|_| invalid())?;
+
+        let (token_id, expires_at) = payload.split_once('.').ok_or_else(invalid)?;
+        let token_id = Uuid::parse_str(token_id).map_err(|_| invalid())?;
+        let expires_at: i64 = expires_at.parse().map_err(|_| invalid())?;
+
+        let now = Utc::now();
+        if now.timestamp() >= expires_at {
+            return Err(invalid());
+        }
+
+        let record = sqlx::query!(
+            "SELECT user_id, email FROM email_tokens WHERE id = ? AND purpose = ?",
+            token_id.to_string(),
+            purpose
+        )
+        .fetch_optional(self.db.pool())
+        .await?
+        .ok_or_else(invalid)?;
+
+        // Only the first request to get here wins
+        let result = sqlx::query!(
+            "UPDATE email_tokens SET used_at = ? WHERE id = ? AND used_at IS NULL",
+            now.to_rfc3339(),
+            token_id.to_string()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(invalid());
+        }
+
+        let user_id = Uuid::parse_str(&record.user_id)
+            .map_err(|e| AppError::Internal(format!("Invalid user ID: {}", e)))?;
+
+        Ok((user_id, record.email))
+    }
+
+    /// Send in the background so response times don't depend on the mail
+    /// server or on whether an account exists
+    fn deliver(&self, email: Email) {
+        let mailer = Arc::clone(&self.mailer);
+
+        tokio::spawn(async move {
+            if let Err(e) = mailer.send(&email).await {
+                warn!("Failed to send \"{}\" email: {}", email.subject, e);
+            }
+        });
+    }
+}
+
+fn signed_message(purpose: &str, payload: &str) -> Vec<u8> {
+    format!("{}:{}", purpose, payload).into_bytes()
+}
@@ -0,0 +1,159 @@
+use chrono::{DateTime, Duration, Utc};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::auth::totp;
+use crate::core::config::SecurityConfig;
+use crate::core::error::{AppError, AppResult};
+use crate::core::types::{CreateInviteRequest, InviteCode, User};
+use crate::crypto::service::CryptoService;
+use crate::storage::database::Database;
+
+/// Most registrations a single code made by a regular user can admit
+const MAX_USER_INVITE_USES: i64 = 10;
+
+/// Most registrations a single code made by an admin can admit
+const MAX_ADMIN_INVITE_USES: i64 = 1000;
+
+/// Invite codes for the `invite` and `approval` registration modes.
+///
+/// Admins can mint any number of codes. Other users may hold up to
+/// `INVITE_CODES_PER_USER` usable codes at a time. Codes are redeemed in
+/// `AuthService::register`.
+pub struct InviteService {
+    db: Arc<Database>,
+    crypto: Arc<CryptoService>,
+    config: SecurityConfig,
+}
+
+impl InviteService {
+    pub fn new(db: Arc<Database>, crypto: Arc<CryptoService>, config: SecurityConfig) -> Self {
+        Self { db, crypto, config }
+    }
+
+    /// Mint a code. Returns its metadata and the code itself, shown only once.
+    pub async fn create_invite(&self, user: &User, request: CreateInviteRequest) -> AppResult<(InviteCode, String)> {
+        if user.is_anonymous {
+            return Err(AppError::Authorization("Anonymous accounts can't create invite codes".to_string()));
+        }
+
+        let max_uses = request.max_uses.unwrap_or(1);
+        let use_limit = if user.is_admin { MAX_ADMIN_INVITE_USES } else { MAX_USER_INVITE_USES };
+        if max_uses < 1 || max_uses > use_limit {
+            return Err(AppError::InvalidRequest(format!("Invite codes can be used 1 to {} times", use_limit)));
+        }
+
+        let now = Utc::now();
+        let expires_at = match request.expires_in_days {
+            Some(days) if days < 1 => {
+                return Err(AppError::InvalidRequest("Invite codes must be valid for at least one day".to_string()));
+            }
+            Some(days) => Some(now + Duration::days(days)),
+            None => None,
+        };
+
+        if !user.is_admin {
+            let active_count = sqlx::query_scalar!(
+                r#"
+                SELECT COUNT(*) as "count: i64" FROM invite_codes
+                WHERE created_by = ? AND revoked = FALSE AND uses < max_uses AND (expires_at IS NULL OR expires_at > ?)
+                "#,
+                user.id.to_string(),
+                now.to_rfc3339()
+            )
+            .fetch_one(self.db.pool())
+            .await?;
+
+            if active_count >= self.config.invite_codes_per_user {
+                return Err(AppError::Authorization(format!(
+                    "You can hold at most {} unused invite codes",
+                    self.config.invite_codes_per_user
+                )));
+            }
+        }
+
+        let code = totp::base32_encode(&self.crypto.random_bytes(10)?).to_lowercase();
+        let invite_id = Uuid::new_v4();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO invite_codes (id, code_hash, created_by, max_uses, expires_at, created_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+            invite_id.to_string(),
+            self.crypto.hash_data(&code),
+            user.id.to_string(),
+            max_uses,
+            expires_at.map(|expires_at| expires_at.to_rfc3339()),
+            now.to_rfc3339()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        Ok((
+            InviteCode {
+                id: invite_id,
+                created_by: user.id,
+                max_uses,
+                uses: 0,
+                expires_at,
+                revoked: false,
+                created_at: now,
+            },
+            code,
+        ))
+    }
+
+    /// Codes a user minted, newest first
+    pub async fn list_invites(&self, user_id: Uuid) -> AppResult<Vec<InviteCode>> {
+        let invite_records = sqlx::query!(
+            r#"
+            SELECT id, max_uses, uses, expires_at, revoked, created_at
+            FROM invite_codes WHERE created_by = ? ORDER BY created_at DESC
+            "#,
+            user_id.to_string()
+        )
+        .fetch_all(self.db.pool())
+        .await?;
+
+        let mut invites = Vec::with_capacity(invite_records.len());
+        for record in invite_records {
+            invites.push(InviteCode {
+                id: Uuid::parse_str(&record.id)
+                    .map_err(|e| AppError::Internal(format!("Invalid invite ID: {}", e)))?,
+                created_by: user_id,
+                max_uses: record.max_uses,
+                uses: record.uses,
+                expires_at: record.expires_at.as_deref().map(parse_date).transpose()?,
+                revoked: record.revoked,
+                created_at: parse_date(&record.created_at)?,
+            });
+        }
+
+        Ok(invites)
+    }
+
+    /// Revoke a code. Users can revoke their own codes, admins any code.
+    pub async fn revoke_invite(&self, user: &User, invite_id: Uuid) -> AppResult<()> {
+        let result = sqlx::query!(
+            "UPDATE invite_codes SET revoked = TRUE WHERE id = ? AND (created_by = ? OR ?)",
+            invite_id.to_string(),
+            user.id.to_string(),
+            user.is_admin
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Invite code not found".to_string()));
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_date(value: &str) -> AppResult<DateTime<Utc>> {
+    Ok(DateTime::parse_from_rfc3339(value)
+        .map_err(|e| AppError::Internal(format!("Invalid date: {}", e)))?
+        .with_timezone(&Utc))
+}
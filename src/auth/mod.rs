@@ -1,2 +1,8 @@
 pub mod service;
-pub mod middleware;
\ No newline at end of file
+pub mod middleware;
+pub mod email;
+pub mod invites;
+pub mod oidc;
+pub mod passkeys;
+pub mod tokens;
+pub mod totp;
\ No newline at end of file
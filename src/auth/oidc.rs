@@ -0,0 +1,707 @@
+use base64::{Engine as _, engine::general_purpose};
+use chrono::{Duration, Utc};
+use jsonwebtoken::{jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use reqwest::Url;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::auth::service::{AuthService, Session};
+use crate::core::config::OidcProviderConfig;
+use crate::core::error::{AppError, AppResult};
+use crate::core::types::User;
+use crate::crypto::service::CryptoService;
+use crate::storage::database::Database;
+
+/// How long the user has to finish logging in at the provider
+const LOGIN_STATE_MINUTES: i64 = 10;
+
+/// Signature algorithms accepted for ID tokens. Symmetric algorithms are
+/// excluded so the client secret can never be used to forge a token.
+const ALLOWED_ALGORITHMS: &[Algorithm] = &[
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+    Algorithm::ES256,
+    Algorithm::ES384,
+    Algorithm::EdDSA,
+];
+
+/// The parts of the provider's discovery document we use
+#[derive(Debug, Clone, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+struct DiscoveredProvider {
+    metadata: ProviderMetadata,
+    jwks: JwkSet,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct IdTokenClaims {
+    sub: String,
+    nonce: Option<String>,
+    email: Option<String>,
+    #[serde(default)]
+    email_verified: bool,
+    preferred_username: Option<String>,
+    /// Everything else, including the configurable groups claim
+    #[serde(flatten)]
+    extra: HashMap<String, serde_json::Value>,
+}
+
+/// OpenID Connect login (authorization code flow with PKCE).
+///
+/// External accounts are linked to local users through `user_identities`;
+/// the first login creates the local account. Provider groups listed in the
+/// provider's `MODERATOR_GROUPS` grant moderator roles on boards, re-synced
+/// on every login.
+pub struct OidcService {
+    db: Arc<Database>,
+    auth: Arc<AuthService>,
+    crypto: Arc<CryptoService>,
+    http: reqwest::Client,
+    providers: HashMap<String, OidcProviderConfig>,
+    discovered: RwLock<HashMap<String, Arc<DiscoveredProvider>>>,
+    base_url: String,
+}
+
+impl OidcService {
+    pub fn new(
+        db: Arc<Database>,
+        auth: Arc<AuthService>,
+        crypto: Arc<CryptoService>,
+        providers: &[OidcProviderConfig],
+        base_url: &str,
+    ) -> AppResult<Self> {
+        let mut configured = HashMap::new();
+        for provider in providers {
+            if provider.issuer.is_empty() || provider.client_id.is_empty() {
+                return Err(AppError::Internal(format!(
+                    "OIDC provider {} needs an issuer and a client id",
+                    provider.name
+                )));
+            }
+
+            configured.insert(provider.name.clone(), provider.clone());
+        }
+
+        let http = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(10))
+            .build()
+            .map_err(|e| AppError::Internal(format!("Failed to create HTTP client: {}", e)))?;
+
+        Ok(Self {
+            db,
+            auth,
+            crypto,
+            http,
+            providers: configured,
+            discovered: RwLock::new(HashMap::new()),
+            base_url: base_url.trim_end_matches('/').to_string(),
+        })
+    }
+
+    /// Names of the configured providers
+    pub fn provider_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.providers.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Build the URL that sends the browser to the provider's login page
+    pub async fn start_login(&self, provider_name: &str) -> AppResult<String> {
+        let provider = self.provider(provider_name)?;
+        let discovered = self.discover(provider, false).await?;
+
+        let state = self.crypto.generate_token()?;
+        let nonce = self.crypto.generate_token()?;
+        let code_verifier = general_purpose::URL_SAFE_NO_PAD.encode(self.crypto.random_bytes(32)?);
+        let code_challenge = general_purpose::URL_SAFE_NO_PAD.encode(
+            ring::digest::digest(&ring::digest::SHA256, code_verifier.as_bytes()).as_ref(),
+        );
+
+        sqlx::query!(
+            r#"
+            INSERT INTO oidc_login_states (id, provider, state_hash, code_verifier, nonce, expires_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+            Uuid::new_v4().to_string(),
+            provider.name,
+            self.crypto.hash_data(&state),
+            code_verifier,
+            nonce,
+            (Utc::now() + Duration::minutes(LOGIN_STATE_MINUTES)).to_rfc3339()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        let url = Url::parse_with_params(
+            &discovered.metadata.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", provider.client_id.as_str()),
+                ("redirect_uri", self.redirect_uri(provider).as_str()),
+                ("scope", provider.scopes.as_str()),
+                ("state", state.as_str()),
+                ("nonce", nonce.as_str()),
+                ("code_challenge", code_challenge.as_str()),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .map_err(|e| AppError::Internal(format!("Invalid authorization endpoint: {}", e)))?;
+
+        Ok(url.to_string())
+    }
+
+    /// Handle the provider's redirect back: redeem the code, verify the ID
+    /// token and log in the linked (or newly created) local user
+    pub async fn finish_login(&self, provider_name: &str, code: &str, state: &str) -> AppResult<(User, Session)> {
+        let provider = self.provider(provider_name)?;
+
+        // Login states are single use
+        let state_hash = self.crypto.hash_data(state);
+        let login_state = sqlx::query!(
+            "SELECT id, code_verifier, nonce FROM oidc_login_states WHERE state_hash = ? AND provider = ? AND expires_at > ?",
+            state_hash,
+            provider.name,
+            Utc::now().to_rfc3339()
+        )
+        .fetch_optional(self.db.pool())
+        .await?
+        .ok_or_else(|| AppError::Auth("Invalid or expired login state".to_string()))?;
+
+        let result = sqlx::query!("DELETE FROM oidc_login_states WHERE id = ?", login_state.id)
+            .execute(self.db.pool())
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(AppError::Auth("Invalid or expired login state".to_string()));
+        }
+
+        let discovered = self.discover(provider, false).await?;
+        let id_token = self.exchange_code(provider, &discovered, code, &login_state.code_verifier).await?;
+        let claims = self.verify_id_token(provider, &id_token).await?;
+
+        if claims.nonce.as_deref() != Some(login_state.nonce.as_str()) {
+            return Err(AppError::Auth("ID token nonce mismatch".to_string()));
+        }
+
+        let user_id = self.link_identity(provider, &claims).await?;
+        self.sync_moderator_roles(provider, user_id, &claims).await?;
+
+        self.auth.complete_login(user_id).await
+    }
+
+    fn provider(&self, name: &str) -> AppResult<&OidcProviderConfig> {
+        self.providers
+            .get(name)
+            .ok_or_else(|| AppError::NotFound(format!("Unknown identity provider: {}", name)))
+    }
+
+    fn redirect_uri(&self, provider: &OidcProviderConfig) -> String {
+        format!("{}/api/auth/oidc/{}/callback", self.base_url, provider.name)
+    }
+
+    /// Fetch (or reuse) the provider's discovery document and signing keys
+    async fn discover(&self, provider: &OidcProviderConfig, refresh: bool) -> AppResult<Arc<DiscoveredProvider>> {
+        if !refresh {
+            if let Some(discovered) = self.discovered.read().unwrap().get(&provider.name) {
+                return Ok(Arc::clone(discovered));
+            }
+        }
+
+        let discovery_url = format!("{}/.well-known/openid-configuration", provider.issuer.trim_end_matches('/'));
+        let metadata: ProviderMetadata = self.fetch_json(&discovery_url).await?;
+
+        // The discovery document must describe the issuer we were configured with
+        if metadata.issuer.trim_end_matches('/') != provider.issuer.trim_end_matches('/') {
+            return Err(AppError::Auth(format!("Issuer mismatch for provider {}", provider.name)));
+        }
+
+        let jwks: JwkSet = self.fetch_json(&metadata.jwks_uri).await?;
+
+        let discovered = Arc::new(DiscoveredProvider { metadata, jwks });
+        self.discovered.write().unwrap().insert(provider.name.clone(), Arc::clone(&discovered));
+        Ok(discovered)
+    }
+
+    async fn fetch_json<T: serde::de::DeserializeOwned>(&self, url: &str) -> AppResult<T> {
+        self.http
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| AppError::Internal(format!("Identity provider request failed: {}", e)))?
+            .json()
+            .await
+            .map_err(|e| AppError::Internal(format!("Invalid identity provider response: {}", e)))
+    }
+
+    async fn exchange_code(
+        &self,
+        provider: &OidcProviderConfig,
+        discovered: &DiscoveredProvider,
+        code: &str,
+        code_verifier: &str,
+    ) -> AppResult<String> {
+        let redirect_uri = self.redirect_uri(provider);
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", redirect_uri.as_str()),
+            ("client_id", provider.client_id.as_str()),
+            ("code_verifier", code_verifier),
+        ];
+        if let Some(client_secret) = &provider.client_secret {
+            form.push(("client_secret", client_secret.as_str()));
+        }
+
+        let response = self.http
+            .post(&discovered.metadata.token_endpoint)
+            .form(&form)
+            .send()
+            .await
+            .map_err(|e| AppError::Internal(format!("Token request failed: {}", e)))?;
+
+        if !response.status().is_success() {
+            warn!("Provider {} rejected the authorization code: {}", provider.name, response.status());
+            return Err(AppError::Auth("The identity provider rejected the login".to_string()));
+        }
+
+        let tokens: TokenResponse = response
+            .json()
+            .await
+            .map_err(|e| AppError::Internal(format!("Invalid token response: {}", e)))?;
+
+        Ok(tokens.id_token)
+    }
+
+    async fn verify_id_token(&self, provider: &OidcProviderConfig, id_token: &str) -> AppResult<IdTokenClaims> {
+        let header = jsonwebtoken::decode_header(id_token)
+            .map_err(|e| AppError::Auth(format!("Invalid ID token: {}", e)))?;
+
+        if !ALLOWED_ALGORITHMS.contains(&header.alg) {
+            return Err(AppError::Auth(format!("Unsupported ID token algorithm: {:?}", header.alg)));
+        }
+        let kid = header.kid
+            .ok_or_else(|| AppError::Auth("ID token has no key id".to_string()))?;
+
+        // Providers rotate their keys; refetch once when the key is unknown
+        let mut discovered = self.discover(provider, false).await?;
+        if discovered.jwks.find(&kid).is_none() {
+            discovered = self.discover(provider, true).await?;
+        }
+        let jwk = discovered.jwks
+            .find(&kid)
+            .ok_or_else(|| AppError::Auth("ID token signed with an unknown key".to_string()))?;
+
+        let key = DecodingKey::from_jwk(jwk)
+            .map_err(|e| AppError::Auth(format!("Invalid provider key: {}", e)))?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_audience(&[&provider.client_id]);
+        validation.set_issuer(&[&discovered.metadata.issuer]);
+
+        let token = jsonwebtoken::decode::<IdTokenClaims>(id_token, &key, &validation)
+            .map_err(|e| AppError::Auth(format!("Invalid ID token: {}", e)))?;
+
+        Ok(token.claims)
+    }
+
+    /// Find the local user linked to the external subject, creating the
+    /// account on first login
+    async fn link_identity(&self, provider: &OidcProviderConfig, claims: &IdTokenClaims) -> AppResult<Uuid> {
+        let now = Utc::now();
+
+        let identity = sqlx::query!(
+            "SELECT user_id FROM user_identities WHERE provider = ? AND subject = ?",
+            provider.name,
+            claims.sub
+        )
+        .fetch_optional(self.db.pool())
+        .await?;
+
+        if let Some(identity) = identity {
+            sqlx::query!(
+                "UPDATE user_identities SET last_login_at = ? WHERE provider = ? AND subject = ?",
+                now.to_rfc3339(),
+                provider.name,
+                claims.sub
+            )
+            .execute(self.db.pool())
+            .await?;
+
+            return Uuid::parse_str(&identity.user_id)
+                .map_err(|e| AppError::Internal(format!("Invalid user ID: {}", e)));
+        }
+
+        let preferred_username = claims.preferred_username.as_deref()
+            .or(claims.email.as_deref())
+            .unwrap_or("user");
+        let verified_email = claims.email.as_deref().filter(|_| claims.email_verified);
+
+        let user = self.auth.register_external(preferred_username, verified_email).await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO user_identities (id, user_id, provider, subject, created_at, last_login_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+            Uuid::new_v4().to_string(),
+            user.id.to_string(),
+            provider.name,
+            claims.sub,
+            now.to_rfc3339(),
+            now.to_rfc3339()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        info!("Linked {} identity to new user {}", provider.name, user.username);
+        Ok(user.id)
+    }
+
+    /// Replace the moderator roles this provider granted with the ones the
+    /// user's current groups map to
+    async fn sync_moderator_roles(&self, provider: &OidcProviderConfig, user_id: Uuid, claims: &IdTokenClaims) -> AppResult<()> {
+        if provider.moderator_groups.is_empty() {
+            return Ok(());
+        }
+
+        let groups: Vec<&str> = match claims.extra.get(&provider.groups_claim) {
+            Some(serde_json::Value::Array(values)) => values.iter().filter_map(|value| value.as_str()).collect(),
+            Some(serde_json::Value::String(value)) => vec![value.as_str()],
+            _ => Vec::new(),
+        };
+
+        let source = format!("oidc:{}", provider.name);
+        let now = Utc::now();
+
+        let mut tx = self.db.pool().begin().await?;
+
+        sqlx::query!(
+            "DELETE FROM board_moderators WHERE user_id = ? AND source = ?",
+            user_id.to_string(),
+            source
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        for (group, board_name) in &provider.moderator_groups {
+            if !groups.contains(&group.as_str()) {
+                continue;
+            }
+
+            let board = sqlx::query!("SELECT id FROM boards WHERE name = ?", board_name)
+                .fetch_optional(&mut *tx)
+                .await?;
+
+            let Some(board) = board else {
+                warn!("Group {} maps to unknown board /{}/", group, board_name);
+                continue;
+            };
+
+            sqlx::query!(
+                "INSERT OR IGNORE INTO board_moderators (board_id, user_id, source, created_at) VALUES (?, ?, ?, ?)",
+                board.id,
+                user_id.to_string(),
+                source,
+                now.to_rfc3339()
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::State;
+    use axum::routing::{get, post};
+    use axum::{Json, Router};
+    use jsonwebtoken::{EncodingKey, Header};
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    use crate::challenge::service::ChallengeService;
+    use crate::core::config::{DatabaseConfig, RegistrationMode, SecurityConfig};
+    use crate::crypto::data_keys::DataKeyService;
+    use crate::crypto::kek::LocalKekProvider;
+
+    const PROVIDER: &str = "mock";
+    const CLIENT_ID: &str = "amogchan";
+    const CLIENT_SECRET: &str = "client-secret-shared-with-the-provider";
+    const KEY_ID: &str = "mock-key";
+
+    /// A local OpenID provider serving discovery, its key set and a token
+    /// endpoint that hands out whatever ID token the test minted last
+    struct MockProvider {
+        issuer: String,
+        pkcs8: Vec<u8>,
+        public_key: Vec<u8>,
+        id_token: Mutex<String>,
+    }
+
+    impl MockProvider {
+        async fn start() -> Arc<Self> {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let pkcs8 = Ed25519KeyPair::generate_pkcs8(&ring::rand::SystemRandom::new()).unwrap();
+            let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+
+            let provider = Arc::new(Self {
+                issuer: format!("http://{}", listener.local_addr().unwrap()),
+                pkcs8: pkcs8.as_ref().to_vec(),
+                public_key: key_pair.public_key().as_ref().to_vec(),
+                id_token: Mutex::new(String::new()),
+            });
+
+            let app = Router::new()
+                .route("/.well-known/openid-configuration", get(discovery))
+                .route("/jwks", get(jwks))
+                .route("/token", post(token))
+                .with_state(Arc::clone(&provider));
+            tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+            provider
+        }
+
+        /// Claims of a valid ID token for the login with this nonce
+        fn claims(&self, nonce: &str) -> serde_json::Value {
+            let now = Utc::now().timestamp();
+            json!({
+                "iss": self.issuer,
+                "aud": CLIENT_ID,
+                "sub": "subject-1",
+                "nonce": nonce,
+                "iat": now,
+                "exp": now + 300,
+                "preferred_username": "alice",
+            })
+        }
+
+        fn issue(&self, claims: &serde_json::Value) {
+            let mut header = Header::new(Algorithm::EdDSA);
+            header.kid = Some(KEY_ID.to_string());
+
+            let token = jsonwebtoken::encode(&header, claims, &EncodingKey::from_ed_der(&self.pkcs8)).unwrap();
+            *self.id_token.lock().unwrap() = token;
+        }
+    }
+
+    async fn discovery(State(provider): State<Arc<MockProvider>>) -> Json<serde_json::Value> {
+        Json(json!({
+            "issuer": provider.issuer,
+            "authorization_endpoint": format!("{}/authorize", provider.issuer),
+            "token_endpoint": format!("{}/token", provider.issuer),
+            "jwks_uri": format!("{}/jwks", provider.issuer),
+        }))
+    }
+
+    async fn jwks(State(provider): State<Arc<MockProvider>>) -> Json<serde_json::Value> {
+        Json(json!({
+            "keys": [{
+                "kty": "OKP",
+                "crv": "Ed25519",
+                "x": general_purpose::URL_SAFE_NO_PAD.encode(&provider.public_key),
+                "kid": KEY_ID,
+                "alg": "EdDSA",
+                "use": "sig",
+            }]
+        }))
+    }
+
+    async fn token(State(provider): State<Arc<MockProvider>>) -> Json<serde_json::Value> {
+        Json(json!({ "id_token": *provider.id_token.lock().unwrap() }))
+    }
+
+    fn security_config(registration_mode: RegistrationMode) -> SecurityConfig {
+        SecurityConfig {
+            session_secret: "session-secret-for-tests-only-0123456789".to_string(),
+            rate_limit_per_minute: 60,
+            admin_usernames: Vec::new(),
+            totp_issuer: "amogchan".to_string(),
+            webauthn_rp_id: "localhost".to_string(),
+            webauthn_rp_name: "amogchan".to_string(),
+            webauthn_origin: "http://localhost".to_string(),
+            email_verification_ttl_hours: 24,
+            password_reset_ttl_minutes: 30,
+            oidc_providers: Vec::new(),
+            registration_mode,
+            allow_anonymous_registration: false,
+            invite_codes_per_user: 0,
+            challenge_kind: "pow".to_string(),
+            challenge_difficulty: 0,
+            challenge_ttl_seconds: 300,
+            account_deletion_grace_days: 0,
+        }
+    }
+
+    async fn oidc_service(provider: &MockProvider, registration_mode: RegistrationMode) -> OidcService {
+        let path = std::env::temp_dir().join(format!("amogchan-oidc-{}.db", Uuid::new_v4()));
+        let db = Database::new(&DatabaseConfig {
+            url: format!("sqlite://{}?mode=rwc", path.display()),
+            max_connections: 1,
+        })
+        .await
+        .unwrap();
+        db.migrate().await.unwrap();
+        let db = Arc::new(db);
+
+        let security = security_config(registration_mode);
+        let crypto = Arc::new(CryptoService::new(&crate::crypto::service::test_config()).unwrap());
+        let kek = Arc::new(LocalKekProvider::new("env", &[3u8; 32]).unwrap());
+        let data_keys = Arc::new(DataKeyService::new(Arc::clone(&db), Arc::clone(&crypto), kek));
+        let challenges = Arc::new(ChallengeService::new(Arc::clone(&db), &security).unwrap());
+        let auth = Arc::new(AuthService::new(Arc::clone(&db), Arc::clone(&crypto), data_keys, challenges, security));
+
+        let provider_config = OidcProviderConfig {
+            name: PROVIDER.to_string(),
+            issuer: provider.issuer.clone(),
+            client_id: CLIENT_ID.to_string(),
+            client_secret: Some(CLIENT_SECRET.to_string()),
+            scopes: "openid profile".to_string(),
+            groups_claim: "groups".to_string(),
+            moderator_groups: Vec::new(),
+        };
+
+        OidcService::new(db, auth, crypto, &[provider_config], "http://localhost:3000").unwrap()
+    }
+
+    /// Start a login and return its state and nonce, as the browser would
+    /// carry them to the provider
+    async fn start_login(oidc: &OidcService) -> (String, String) {
+        let url = Url::parse(&oidc.start_login(PROVIDER).await.unwrap()).unwrap();
+        let param = |name: &str| {
+            url.query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+                .unwrap()
+        };
+
+        (param("state"), param("nonce"))
+    }
+
+    #[tokio::test]
+    async fn login_creates_the_account_once_and_states_cannot_be_reused() {
+        let provider = MockProvider::start().await;
+        let oidc = oidc_service(&provider, RegistrationMode::Open).await;
+
+        let (state, nonce) = start_login(&oidc).await;
+        provider.issue(&provider.claims(&nonce));
+
+        let (user, _) = oidc.finish_login(PROVIDER, "code", &state).await.unwrap();
+        assert_eq!(user.username, "alice");
+
+        let replay = oidc.finish_login(PROVIDER, "code", &state).await;
+        assert!(matches!(replay, Err(AppError::Auth(_))));
+
+        // A second login finds the linked account
+        let (state, nonce) = start_login(&oidc).await;
+        provider.issue(&provider.claims(&nonce));
+        let (again, _) = oidc.finish_login(PROVIDER, "code", &state).await.unwrap();
+        assert_eq!(again.id, user.id);
+    }
+
+    #[tokio::test]
+    async fn rejects_a_token_minted_for_another_login() {
+        let provider = MockProvider::start().await;
+        let oidc = oidc_service(&provider, RegistrationMode::Open).await;
+
+        let (state, _) = start_login(&oidc).await;
+        let (_, other_nonce) = start_login(&oidc).await;
+        provider.issue(&provider.claims(&other_nonce));
+
+        let result = oidc.finish_login(PROVIDER, "code", &state).await;
+        assert!(matches!(result, Err(AppError::Auth(message)) if message.contains("nonce")));
+    }
+
+    #[tokio::test]
+    async fn rejects_a_token_from_another_issuer() {
+        let provider = MockProvider::start().await;
+        let oidc = oidc_service(&provider, RegistrationMode::Open).await;
+
+        let (state, nonce) = start_login(&oidc).await;
+        let mut claims = provider.claims(&nonce);
+        claims["iss"] = json!("https://attacker.example");
+        provider.issue(&claims);
+
+        let result = oidc.finish_login(PROVIDER, "code", &state).await;
+        assert!(matches!(result, Err(AppError::Auth(_))));
+    }
+
+    #[tokio::test]
+    async fn rejects_a_token_signed_with_the_client_secret() {
+        let provider = MockProvider::start().await;
+        let oidc = oidc_service(&provider, RegistrationMode::Open).await;
+
+        let (state, nonce) = start_login(&oidc).await;
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some(KEY_ID.to_string());
+        let forged = jsonwebtoken::encode(
+            &header,
+            &provider.claims(&nonce),
+            &EncodingKey::from_secret(CLIENT_SECRET.as_bytes()),
+        )
+        .unwrap();
+        *provider.id_token.lock().unwrap() = forged;
+
+        let result = oidc.finish_login(PROVIDER, "code", &state).await;
+        assert!(matches!(result, Err(AppError::Auth(message)) if message.contains("algorithm")));
+    }
+
+    #[tokio::test]
+    async fn closed_registration_turns_away_new_identities() {
+        let provider = MockProvider::start().await;
+        let oidc = oidc_service(&provider, RegistrationMode::Closed).await;
+
+        let (state, nonce) = start_login(&oidc).await;
+        provider.issue(&provider.claims(&nonce));
+
+        let result = oidc.finish_login(PROVIDER, "code", &state).await;
+        assert!(matches!(result, Err(AppError::Authorization(_))));
+
+        let users = sqlx::query_scalar!(r#"SELECT COUNT(*) as "count: i64" FROM users"#)
+            .fetch_one(oidc.db.pool())
+            .await
+            .unwrap();
+        assert_eq!(users, 0);
+    }
+
+    #[tokio::test]
+    async fn approval_registration_queues_new_identities() {
+        let provider = MockProvider::start().await;
+        let oidc = oidc_service(&provider, RegistrationMode::Approval).await;
+
+        let (state, nonce) = start_login(&oidc).await;
+        provider.issue(&provider.claims(&nonce));
+
+        let result = oidc.finish_login(PROVIDER, "code", &state).await;
+        assert!(matches!(result, Err(AppError::Authorization(message)) if message.contains("approval")));
+
+        let approval_status = sqlx::query_scalar!("SELECT approval_status FROM users WHERE username = 'alice'")
+            .fetch_one(oidc.db.pool())
+            .await
+            .unwrap();
+        assert_eq!(approval_status, "pending");
+    }
+}
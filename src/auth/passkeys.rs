@@ -0,0 +1,711 @@
+use base64::{Engine as _, engine::general_purpose};
+use chrono::{Duration, Utc};
+use std::sync::Arc;
+use tracing::{info, warn};
+use uuid::Uuid;
+use webauthn_rs::prelude::{
+    CreationChallengeResponse, CredentialID, Passkey, PasskeyAuthentication, PasskeyRegistration,
+    PublicKeyCredential, RegisterPublicKeyCredential, RequestChallengeResponse, Url, Webauthn,
+    WebauthnBuilder,
+};
+
+use crate::auth::service::{AuthService, Session};
+use crate::core::config::SecurityConfig;
+use crate::core::error::{AppError, AppResult};
+use crate::core::types::{PasskeyInfo, User};
+use crate::storage::database::Database;
+
+/// How long a browser has to complete a ceremony
+const CEREMONY_MINUTES: i64 = 5;
+
+const REGISTRATION: &str = "registration";
+const AUTHENTICATION: &str = "authentication";
+
+/// WebAuthn passkey registration and login.
+///
+/// A passkey can be the only credential used to log in, or the second step
+/// after a password (see `AuthService::login`).
+pub struct PasskeyService {
+    db: Arc<Database>,
+    auth: Arc<AuthService>,
+    webauthn: Webauthn,
+}
+
+impl PasskeyService {
+    pub fn new(db: Arc<Database>, auth: Arc<AuthService>, config: &SecurityConfig) -> AppResult<Self> {
+        let origin = Url::parse(&config.webauthn_origin)
+            .map_err(|e| AppError::Internal(format!("Invalid WebAuthn origin: {}", e)))?;
+
+        let webauthn = WebauthnBuilder::new(&config.webauthn_rp_id, &origin)
+            .map_err(|e| AppError::Internal(format!("Invalid WebAuthn configuration: {}", e)))?
+            .rp_name(&config.webauthn_rp_name)
+            .build()
+            .map_err(|e| AppError::Internal(format!("Invalid WebAuthn configuration: {}", e)))?;
+
+        Ok(Self { db, auth, webauthn })
+    }
+
+    /// Begin registering a new passkey for a logged-in user
+    pub async fn start_registration(&self, user: &User) -> AppResult<(Uuid, CreationChallengeResponse)> {
+        if user.is_anonymous {
+            return Err(AppError::InvalidRequest("Anonymous accounts can't register passkeys".to_string()));
+        }
+
+        // Don't let the same authenticator register twice
+        let existing: Vec<CredentialID> = self
+            .load_passkeys(user.id)
+            .await?
+            .into_iter()
+            .map(|(_, passkey, _)| passkey.cred_id().clone())
+            .collect();
+
+        let (options, state) = self.webauthn
+            .start_passkey_registration(user.id, &user.username, &user.username, Some(existing))
+            .map_err(|e| AppError::InvalidRequest(format!("Passkey registration failed: {}", e)))?;
+
+        let ceremony_id = self.save_ceremony(user.id, REGISTRATION, &serde_json::to_string(&state)?).await?;
+
+        Ok((ceremony_id, options))
+    }
+
+    /// Verify the authenticator's response and store the new passkey
+    pub async fn finish_registration(
+        &self,
+        user_id: Uuid,
+        ceremony_id: Uuid,
+        name: &str,
+        credential: &RegisterPublicKeyCredential,
+    ) -> AppResult<PasskeyInfo> {
+        let name = name.trim();
+        if name.is_empty() || name.len() > 64 {
+            return Err(AppError::InvalidRequest("Passkey name must be 1-64 characters".to_string()));
+        }
+
+        let state: PasskeyRegistration = serde_json::from_str(&self.take_ceremony(ceremony_id, user_id, REGISTRATION).await?)?;
+
+        let passkey = self.webauthn
+            .finish_passkey_registration(credential, &state)
+            .map_err(|e| AppError::Auth(format!("Passkey registration failed: {}", e)))?;
+
+        let id = Uuid::new_v4();
+        let now = Utc::now();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO passkey_credentials (id, user_id, credential_id, name, credential, sign_count, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            "#,
+            id.to_string(),
+            user_id.to_string(),
+            encode_credential_id(&passkey),
+            name,
+            serde_json::to_string(&passkey)?,
+            0i64,
+            now.to_rfc3339()
+        )
+        .execute(self.db.pool())
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(ref db_error) if db_error.is_unique_violation() => {
+                AppError::InvalidRequest("Passkey is already registered".to_string())
+            }
+            e => e.into(),
+        })?;
+
+        info!("Registered passkey {} for user {}", id, user_id);
+
+        Ok(PasskeyInfo {
+            id,
+            name: name.to_string(),
+            created_at: now,
+            last_used_at: None,
+        })
+    }
+
+    /// Begin a passwordless login
+    pub async fn start_login(&self, username: &str) -> AppResult<(Uuid, RequestChallengeResponse)> {
+        let user_record = sqlx::query!(
+            "SELECT id FROM users WHERE username = ? AND is_anonymous = FALSE",
+            username
+        )
+        .fetch_optional(self.db.pool())
+        .await?
+        .ok_or_else(|| AppError::Auth("No passkeys registered for this account".to_string()))?;
+
+        let user_id = Uuid::parse_str(&user_record.id)
+            .map_err(|e| AppError::Internal(format!("Invalid user ID: {}", e)))?;
+
+        self.start_authentication(user_id).await
+    }
+
+    /// Finish a passwordless login and issue a session
+    pub async fn finish_login(&self, ceremony_id: Uuid, credential: &PublicKeyCredential) -> AppResult<(User, Session)> {
+        let user_id = self.ceremony_user(ceremony_id).await?;
+        self.finish_authentication(user_id, ceremony_id, credential).await?;
+
+        self.auth.complete_login(user_id).await
+    }
+
+    /// Begin the passkey step of a two-factor login
+    pub async fn start_second_factor(&self, challenge_token: &str) -> AppResult<(Uuid, RequestChallengeResponse)> {
+        let user_id = self.auth.redeem_login_challenge_attempt(challenge_token).await?;
+
+        self.start_authentication(user_id).await
+    }
+
+    /// Finish the passkey step of a two-factor login and issue a session
+    pub async fn finish_second_factor(
+        &self,
+        challenge_token: &str,
+        ceremony_id: Uuid,
+        credential: &PublicKeyCredential,
+    ) -> AppResult<(User, Session)> {
+        let user_id = self.auth.redeem_login_challenge_attempt(challenge_token).await?;
+        self.finish_authentication(user_id, ceremony_id, credential).await?;
+
+        self.auth.complete_login_challenge(challenge_token, user_id).await
+    }
+
+    /// List a user's passkeys
+    pub async fn list_passkeys(&self, user_id: Uuid) -> AppResult<Vec<PasskeyInfo>> {
+        let records = sqlx::query!(
+            "SELECT id, name, created_at, last_used_at FROM passkey_credentials WHERE user_id = ? ORDER BY created_at",
+            user_id.to_string()
+        )
+        .fetch_all(self.db.pool())
+        .await?;
+
+        records
+            .into_iter()
+            .map(|record| {
+                Ok(PasskeyInfo {
+                    id: Uuid::parse_str(&record.id)
+                        .map_err(|e| AppError::Internal(format!("Invalid passkey ID: {}", e)))?,
+                    name: record.name,
+                    created_at: chrono::DateTime::parse_from_rfc3339(&record.created_at)
+                        .map_err(|e| AppError::Internal(format!("Invalid date: {}", e)))?
+                        .with_timezone(&Utc),
+                    last_used_at: record.last_used_at.as_ref().map(|s| {
+                        chrono::DateTime::parse_from_rfc3339(s)
+                            .map_err(|e| AppError::Internal(format!("Invalid date: {}", e)))
+                            .unwrap()
+                            .with_timezone(&Utc)
+                    }),
+                })
+            })
+            .collect()
+    }
+
+    /// Remove one of a user's passkeys
+    pub async fn delete_passkey(&self, user_id: Uuid, passkey_id: Uuid) -> AppResult<()> {
+        let result = sqlx::query!(
+            "DELETE FROM passkey_credentials WHERE id = ? AND user_id = ?",
+            passkey_id.to_string(),
+            user_id.to_string()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Passkey not found".to_string()));
+        }
+
+        Ok(())
+    }
+
+    async fn start_authentication(&self, user_id: Uuid) -> AppResult<(Uuid, RequestChallengeResponse)> {
+        let passkeys: Vec<Passkey> = self
+            .load_passkeys(user_id)
+            .await?
+            .into_iter()
+            .map(|(_, passkey, _)| passkey)
+            .collect();
+
+        if passkeys.is_empty() {
+            return Err(AppError::Auth("No passkeys registered for this account".to_string()));
+        }
+
+        let (options, state) = self.webauthn
+            .start_passkey_authentication(&passkeys)
+            .map_err(|e| AppError::InvalidRequest(format!("Passkey authentication failed: {}", e)))?;
+
+        let ceremony_id = self.save_ceremony(user_id, AUTHENTICATION, &serde_json::to_string(&state)?).await?;
+
+        Ok((ceremony_id, options))
+    }
+
+    /// Verify an assertion and record the authenticator's new signature counter
+    async fn finish_authentication(&self, user_id: Uuid, ceremony_id: Uuid, credential: &PublicKeyCredential) -> AppResult<()> {
+        let state: PasskeyAuthentication = serde_json::from_str(&self.take_ceremony(ceremony_id, user_id, AUTHENTICATION).await?)?;
+
+        let result = self.webauthn
+            .finish_passkey_authentication(credential, &state)
+            .map_err(|e| AppError::Auth(format!("Passkey authentication failed: {}", e)))?;
+
+        let (id, mut passkey, sign_count) = self
+            .load_passkeys(user_id)
+            .await?
+            .into_iter()
+            .find(|(_, passkey, _)| passkey.cred_id() == result.cred_id())
+            .ok_or_else(|| AppError::Auth("Unknown passkey".to_string()))?;
+
+        // A counter that doesn't move forward suggests a cloned authenticator.
+        // Authenticators that don't implement counters always report zero.
+        let counter = result.counter() as i64;
+        if counter != 0 && counter <= sign_count {
+            warn!("Passkey {} of user {} reported a stale signature counter", id, user_id);
+            return Err(AppError::Auth("Passkey signature counter did not increase".to_string()));
+        }
+
+        passkey.update_credential(&result);
+
+        // Checked again on write, in case a concurrent login recorded this counter first
+        let updated = sqlx::query!(
+            r#"
+            UPDATE passkey_credentials SET credential = ?1, sign_count = ?2, last_used_at = ?3
+            WHERE id = ?4 AND (?2 = 0 OR sign_count < ?2)
+            "#,
+            serde_json::to_string(&passkey)?,
+            counter,
+            Utc::now().to_rfc3339(),
+            id
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        if updated.rows_affected() == 0 {
+            return Err(AppError::Auth("Passkey signature counter did not increase".to_string()));
+        }
+
+        Ok(())
+    }
+
+    async fn load_passkeys(&self, user_id: Uuid) -> AppResult<Vec<(String, Passkey, i64)>> {
+        let records = sqlx::query!(
+            "SELECT id, credential, sign_count FROM passkey_credentials WHERE user_id = ?",
+            user_id.to_string()
+        )
+        .fetch_all(self.db.pool())
+        .await?;
+
+        records
+            .into_iter()
+            .map(|record| Ok((record.id, serde_json::from_str(&record.credential)?, record.sign_count)))
+            .collect()
+    }
+
+    async fn save_ceremony(&self, user_id: Uuid, kind: &str, state: &str) -> AppResult<Uuid> {
+        let ceremony_id = Uuid::new_v4();
+        let now = Utc::now();
+
+        // Drop this user's abandoned ceremonies along the way
+        sqlx::query!(
+            "DELETE FROM webauthn_ceremonies WHERE user_id = ? AND expires_at < ?",
+            user_id.to_string(),
+            now.to_rfc3339()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        sqlx::query!(
+            "INSERT INTO webauthn_ceremonies (id, user_id, kind, state, expires_at, created_at) VALUES (?, ?, ?, ?, ?, ?)",
+            ceremony_id.to_string(),
+            user_id.to_string(),
+            kind,
+            state,
+            (now + Duration::minutes(CEREMONY_MINUTES)).to_rfc3339(),
+            now.to_rfc3339()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        Ok(ceremony_id)
+    }
+
+    async fn ceremony_user(&self, ceremony_id: Uuid) -> AppResult<Uuid> {
+        let record = sqlx::query!(
+            "SELECT user_id FROM webauthn_ceremonies WHERE id = ?",
+            ceremony_id.to_string()
+        )
+        .fetch_optional(self.db.pool())
+        .await?
+        .ok_or_else(|| AppError::Auth("Invalid or expired passkey ceremony".to_string()))?;
+
+        Uuid::parse_str(&record.user_id)
+            .map_err(|e| AppError::Internal(format!("Invalid user ID: {}", e)))
+    }
+
+    /// Remove and return a ceremony's state; each ceremony can be finished once
+    async fn take_ceremony(&self, ceremony_id: Uuid, user_id: Uuid, kind: &str) -> AppResult<String> {
+        // Removing and reading in one statement, so two requests racing with
+        // the same ceremony can't both get its state
+        let record = sqlx::query!(
+            "DELETE FROM webauthn_ceremonies WHERE id = ? AND user_id = ? AND kind = ? RETURNING state, expires_at",
+            ceremony_id.to_string(),
+            user_id.to_string(),
+            kind
+        )
+        .fetch_optional(self.db.pool())
+        .await?
+        .ok_or_else(|| AppError::Auth("Invalid or expired passkey ceremony".to_string()))?;
+
+        let expires_at = chrono::DateTime::parse_from_rfc3339(&record.expires_at)
+            .map_err(|e| AppError::Internal(format!("Invalid date: {}", e)))?;
+        if expires_at < Utc::now() {
+            return Err(AppError::Auth("Invalid or expired passkey ceremony".to_string()));
+        }
+
+        Ok(record.state)
+    }
+}
+
+/// Credential ids are stored base64url-encoded, as WebAuthn clients send them
+fn encode_credential_id(passkey: &Passkey) -> String {
+    let credential_id: &[u8] = passkey.cred_id().as_ref();
+    general_purpose::URL_SAFE_NO_PAD.encode(credential_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::digest::{digest, SHA256};
+    use ring::rand::SystemRandom;
+    use ring::signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_ASN1_SIGNING};
+    use serde_json::json;
+
+    use crate::challenge::service::ChallengeService;
+    use crate::core::config::{DatabaseConfig, RegistrationMode};
+    use crate::crypto::data_keys::DataKeyService;
+    use crate::crypto::kek::LocalKekProvider;
+    use crate::crypto::service::CryptoService;
+
+    const RP_ID: &str = "example.com";
+    const ORIGIN: &str = "https://example.com";
+
+    /// Flags of the authenticator data: user present, user verified and
+    /// attested credential data included
+    const USER_PRESENT: u8 = 0x01;
+    const USER_VERIFIED: u8 = 0x04;
+    const ATTESTED_DATA: u8 = 0x40;
+
+    /// Just enough CBOR for a "none" attestation object and a COSE key
+    enum Cbor {
+        Int(i64),
+        Bytes(Vec<u8>),
+        Text(&'static str),
+        Map(Vec<(Cbor, Cbor)>),
+    }
+
+    impl Cbor {
+        fn encode(&self, out: &mut Vec<u8>) {
+            match self {
+                Cbor::Int(value) if *value >= 0 => header(0, *value as u64, out),
+                Cbor::Int(value) => header(1, (-1 - *value) as u64, out),
+                Cbor::Bytes(bytes) => {
+                    header(2, bytes.len() as u64, out);
+                    out.extend_from_slice(bytes);
+                }
+                Cbor::Text(text) => {
+                    header(3, text.len() as u64, out);
+                    out.extend_from_slice(text.as_bytes());
+                }
+                Cbor::Map(entries) => {
+                    header(5, entries.len() as u64, out);
+                    for (key, value) in entries {
+                        key.encode(out);
+                        value.encode(out);
+                    }
+                }
+            }
+        }
+    }
+
+    fn header(major: u8, value: u64, out: &mut Vec<u8>) {
+        match value {
+            0..=23 => out.push(major << 5 | value as u8),
+            24..=0xff => out.extend_from_slice(&[major << 5 | 24, value as u8]),
+            _ => {
+                out.push(major << 5 | 25);
+                out.extend_from_slice(&(value as u16).to_be_bytes());
+            }
+        }
+    }
+
+    /// A software authenticator holding one ES256 credential
+    struct SoftAuthenticator {
+        credential_id: Vec<u8>,
+        key_pair: EcdsaKeyPair,
+        counter: u32,
+        rng: SystemRandom,
+    }
+
+    impl SoftAuthenticator {
+        fn new() -> Self {
+            let rng = SystemRandom::new();
+            let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng).unwrap();
+            let key_pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8.as_ref(), &rng).unwrap();
+
+            Self { credential_id: Uuid::new_v4().as_bytes().repeat(2), key_pair, counter: 0, rng }
+        }
+
+        fn register(&mut self, options: &CreationChallengeResponse) -> RegisterPublicKeyCredential {
+            let challenge = serde_json::to_value(options).unwrap()["publicKey"]["challenge"].clone();
+            let client_data = client_data("webauthn.create", &challenge);
+
+            // Uncompressed P-256 point: 0x04 || x || y
+            let point = self.key_pair.public_key().as_ref();
+            let cose_key = Cbor::Map(vec![
+                (Cbor::Int(1), Cbor::Int(2)),
+                (Cbor::Int(3), Cbor::Int(-7)),
+                (Cbor::Int(-1), Cbor::Int(1)),
+                (Cbor::Int(-2), Cbor::Bytes(point[1..33].to_vec())),
+                (Cbor::Int(-3), Cbor::Bytes(point[33..65].to_vec())),
+            ]);
+
+            let mut auth_data = self.auth_data(USER_PRESENT | USER_VERIFIED | ATTESTED_DATA);
+            auth_data.extend_from_slice(&[0u8; 16]);
+            auth_data.extend_from_slice(&(self.credential_id.len() as u16).to_be_bytes());
+            auth_data.extend_from_slice(&self.credential_id);
+            cose_key.encode(&mut auth_data);
+
+            let mut attestation_object = Vec::new();
+            Cbor::Map(vec![
+                (Cbor::Text("fmt"), Cbor::Text("none")),
+                (Cbor::Text("attStmt"), Cbor::Map(Vec::new())),
+                (Cbor::Text("authData"), Cbor::Bytes(auth_data)),
+            ])
+            .encode(&mut attestation_object);
+
+            serde_json::from_value(json!({
+                "id": encode(&self.credential_id),
+                "rawId": encode(&self.credential_id),
+                "response": {
+                    "attestationObject": encode(&attestation_object),
+                    "clientDataJSON": encode(&client_data),
+                },
+                "type": "public-key",
+            }))
+            .unwrap()
+        }
+
+        fn authenticate(&mut self, options: &RequestChallengeResponse) -> PublicKeyCredential {
+            let challenge = serde_json::to_value(options).unwrap()["publicKey"]["challenge"].clone();
+            let client_data = client_data("webauthn.get", &challenge);
+
+            self.counter += 1;
+            let auth_data = self.auth_data(USER_PRESENT | USER_VERIFIED);
+
+            let mut signed = auth_data.clone();
+            signed.extend_from_slice(digest(&SHA256, &client_data).as_ref());
+            let signature = self.key_pair.sign(&self.rng, &signed).unwrap();
+
+            serde_json::from_value(json!({
+                "id": encode(&self.credential_id),
+                "rawId": encode(&self.credential_id),
+                "response": {
+                    "authenticatorData": encode(&auth_data),
+                    "clientDataJSON": encode(&client_data),
+                    "signature": encode(signature.as_ref()),
+                    "userHandle": null,
+                },
+                "type": "public-key",
+            }))
+            .unwrap()
+        }
+
+        /// RP ID hash, flags and signature counter
+        fn auth_data(&self, flags: u8) -> Vec<u8> {
+            let mut auth_data = digest(&SHA256, RP_ID.as_bytes()).as_ref().to_vec();
+            auth_data.push(flags);
+            auth_data.extend_from_slice(&self.counter.to_be_bytes());
+            auth_data
+        }
+    }
+
+    fn client_data(kind: &str, challenge: &serde_json::Value) -> Vec<u8> {
+        json!({ "type": kind, "challenge": challenge, "origin": ORIGIN, "crossOrigin": false })
+            .to_string()
+            .into_bytes()
+    }
+
+    fn encode(bytes: &[u8]) -> String {
+        general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+    }
+
+    fn webauthn() -> Webauthn {
+        WebauthnBuilder::new(RP_ID, &Url::parse(ORIGIN).unwrap())
+            .unwrap()
+            .rp_name("amogchan")
+            .build()
+            .unwrap()
+    }
+
+    fn register(webauthn: &Webauthn, authenticator: &mut SoftAuthenticator) -> Passkey {
+        let (options, state) = webauthn
+            .start_passkey_registration(Uuid::new_v4(), "alice", "alice", None)
+            .unwrap();
+
+        webauthn
+            .finish_passkey_registration(&authenticator.register(&options), &state)
+            .unwrap()
+    }
+
+    #[test]
+    fn software_authenticator_registers_and_logs_in() {
+        let webauthn = webauthn();
+        let mut authenticator = SoftAuthenticator::new();
+        let passkey = register(&webauthn, &mut authenticator);
+
+        assert_eq!(encode_credential_id(&passkey), encode(&authenticator.credential_id));
+
+        for expected_counter in 1..=2 {
+            let (options, state) = webauthn.start_passkey_authentication(std::slice::from_ref(&passkey)).unwrap();
+            let result = webauthn
+                .finish_passkey_authentication(&authenticator.authenticate(&options), &state)
+                .unwrap();
+
+            assert_eq!(result.cred_id(), passkey.cred_id());
+            assert_eq!(result.counter(), expected_counter);
+        }
+    }
+
+    #[test]
+    fn assertions_from_another_authenticator_are_rejected() {
+        let webauthn = webauthn();
+        let mut authenticator = SoftAuthenticator::new();
+        let passkey = register(&webauthn, &mut authenticator);
+
+        // Same credential id, different private key
+        let mut impostor = SoftAuthenticator::new();
+        impostor.credential_id = authenticator.credential_id.clone();
+
+        let (options, state) = webauthn.start_passkey_authentication(&[passkey]).unwrap();
+        assert!(webauthn.finish_passkey_authentication(&impostor.authenticate(&options), &state).is_err());
+    }
+
+    #[test]
+    fn assertions_answer_only_their_own_challenge() {
+        let webauthn = webauthn();
+        let mut authenticator = SoftAuthenticator::new();
+        let passkey = register(&webauthn, &mut authenticator);
+
+        let (first_options, _) = webauthn.start_passkey_authentication(std::slice::from_ref(&passkey)).unwrap();
+        let (_, second_state) = webauthn.start_passkey_authentication(&[passkey]).unwrap();
+
+        let credential = authenticator.authenticate(&first_options);
+        assert!(webauthn.finish_passkey_authentication(&credential, &second_state).is_err());
+    }
+
+    fn security_config() -> SecurityConfig {
+        SecurityConfig {
+            session_secret: "session-secret-for-tests-only-0123456789".to_string(),
+            rate_limit_per_minute: 60,
+            admin_usernames: Vec::new(),
+            totp_issuer: "amogchan".to_string(),
+            webauthn_rp_id: RP_ID.to_string(),
+            webauthn_rp_name: "amogchan".to_string(),
+            webauthn_origin: ORIGIN.to_string(),
+            email_verification_ttl_hours: 24,
+            password_reset_ttl_minutes: 30,
+            oidc_providers: Vec::new(),
+            registration_mode: RegistrationMode::Open,
+            allow_anonymous_registration: false,
+            invite_codes_per_user: 0,
+            challenge_kind: "pow".to_string(),
+            challenge_difficulty: 0,
+            challenge_ttl_seconds: 300,
+            account_deletion_grace_days: 0,
+        }
+    }
+
+    async fn passkey_service() -> (PasskeyService, Arc<AuthService>) {
+        let path = std::env::temp_dir().join(format!("amogchan-passkeys-{}.db", Uuid::new_v4()));
+        let db = Database::new(&DatabaseConfig {
+            url: format!("sqlite://{}?mode=rwc", path.display()),
+            max_connections: 1,
+        })
+        .await
+        .unwrap();
+        db.migrate().await.unwrap();
+        let db = Arc::new(db);
+
+        let security = security_config();
+        let crypto = Arc::new(CryptoService::new(&crate::crypto::service::test_config()).unwrap());
+        let kek = Arc::new(LocalKekProvider::new("env", &[3u8; 32]).unwrap());
+        let data_keys = Arc::new(DataKeyService::new(Arc::clone(&db), Arc::clone(&crypto), kek));
+        let challenges = Arc::new(ChallengeService::new(Arc::clone(&db), &security).unwrap());
+        let auth = Arc::new(AuthService::new(Arc::clone(&db), crypto, data_keys, challenges, security.clone()));
+
+        (PasskeyService::new(db, Arc::clone(&auth), &security).unwrap(), auth)
+    }
+
+    /// A user with a passkey registered through the service
+    async fn enrolled(service: &PasskeyService, auth: &AuthService) -> (User, SoftAuthenticator) {
+        let user = auth.register_external("alice", None).await.unwrap();
+        let mut authenticator = SoftAuthenticator::new();
+
+        let (ceremony_id, options) = service.start_registration(&user).await.unwrap();
+        service
+            .finish_registration(user.id, ceremony_id, "laptop", &authenticator.register(&options))
+            .await
+            .unwrap();
+
+        (user, authenticator)
+    }
+
+    #[tokio::test]
+    async fn service_logs_in_with_a_registered_passkey() {
+        let (service, auth) = passkey_service().await;
+        let (user, mut authenticator) = enrolled(&service, &auth).await;
+
+        for _ in 0..2 {
+            let (ceremony_id, options) = service.start_login(&user.username).await.unwrap();
+            let (logged_in, _) = service.finish_login(ceremony_id, &authenticator.authenticate(&options)).await.unwrap();
+            assert_eq!(logged_in.id, user.id);
+        }
+
+        let passkeys = service.list_passkeys(user.id).await.unwrap();
+        assert_eq!(passkeys.len(), 1);
+        assert!(passkeys[0].last_used_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn ceremonies_can_be_finished_once() {
+        let (service, auth) = passkey_service().await;
+        let (user, mut authenticator) = enrolled(&service, &auth).await;
+
+        let (ceremony_id, options) = service.start_login(&user.username).await.unwrap();
+        let credential = authenticator.authenticate(&options);
+
+        assert!(service.finish_login(ceremony_id, &credential).await.is_ok());
+        assert!(service.finish_login(ceremony_id, &credential).await.is_err());
+
+        // Nor can a registration ceremony be reused for a second passkey
+        let (ceremony_id, options) = service.start_registration(&user).await.unwrap();
+        let mut second = SoftAuthenticator::new();
+        let credential = second.register(&options);
+        service.finish_registration(user.id, ceremony_id, "phone", &credential).await.unwrap();
+        assert!(service.finish_registration(user.id, ceremony_id, "phone", &credential).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn signature_counters_that_go_back_are_refused() {
+        let (service, auth) = passkey_service().await;
+        let (user, mut authenticator) = enrolled(&service, &auth).await;
+
+        for _ in 0..3 {
+            let (ceremony_id, options) = service.start_login(&user.username).await.unwrap();
+            service.finish_login(ceremony_id, &authenticator.authenticate(&options)).await.unwrap();
+        }
+
+        // A clone of the authenticator taken after its first login
+        authenticator.counter = 1;
+        let (ceremony_id, options) = service.start_login(&user.username).await.unwrap();
+        assert!(service.finish_login(ceremony_id, &authenticator.authenticate(&options)).await.is_err());
+
+        // The original, moving on from the recorded counter, still works
+        authenticator.counter = 3;
+        let (ceremony_id, options) = service.start_login(&user.username).await.unwrap();
+        assert!(service.finish_login(ceremony_id, &authenticator.authenticate(&options)).await.is_ok());
+    }
+}
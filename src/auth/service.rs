@@ -1,16 +1,32 @@
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, Utc};
 use std::sync::Arc;
+use tracing::info;
 use uuid::Uuid;
 
-use crate::core::config::SecurityConfig;
+use crate::core::config::{RegistrationMode, SecurityConfig};
 use crate::core::error::{AppError, AppResult};
-use crate::core::types::{User, CreateUserRequest, LoginRequest};
+use crate::auth::totp;
+use crate::challenge::service::{ChallengePurpose, ChallengeService};
+use crate::core::types::{User, CreateUserRequest, LoginRequest, TotpEnrollment};
+use crate::crypto::aad::totp_secret_aad;
+use crate::crypto::data_keys::{DataKeyService, KeyOwner};
 use crate::crypto::service::CryptoService;
 use crate::storage::database::Database;
 
+/// How long the second step of a two-factor login may take
+const LOGIN_CHALLENGE_MINUTES: i64 = 5;
+
+/// Wrong codes allowed per login challenge
+const MAX_CHALLENGE_ATTEMPTS: i64 = 5;
+
+/// Recovery codes issued when two-factor authentication is enabled
+const RECOVERY_CODE_COUNT: usize = 10;
+
 pub struct AuthService {
     db: Arc<Database>,
     crypto: Arc<CryptoService>,
+    data_keys: Arc<DataKeyService>,
+    challenges: Arc<ChallengeService>,
     config: SecurityConfig,
 }
 
@@ -22,17 +38,54 @@ pub struct Session {
     pub expires_at: chrono::DateTime<Utc>,
 }
 
+/// Proof that the password step of a two-factor login succeeded
+#[derive(Debug, Clone)]
+pub struct LoginChallenge {
+    pub token: String,
+    pub expires_at: chrono::DateTime<Utc>,
+    /// Second factors that can complete the login
+    pub methods: Vec<String>,
+}
+
+pub enum LoginOutcome {
+    Authenticated(User, Session),
+    TwoFactorRequired(LoginChallenge),
+}
+
+pub enum RegisterOutcome {
+    Active(User),
+    /// The account exists but can't log in until an admin approves it
+    PendingApproval(User),
+}
+
 impl AuthService {
     pub fn new(
         db: Arc<Database>,
         crypto: Arc<CryptoService>,
+        data_keys: Arc<DataKeyService>,
+        challenges: Arc<ChallengeService>,
         config: SecurityConfig,
     ) -> Self {
-        Self { db, crypto, config }
+        Self { db, crypto, data_keys, challenges, config }
     }
 
-    /// Register a new user
-    pub async fn register(&self, request: CreateUserRequest) -> AppResult<User> {
+    /// Register a new user, subject to `REGISTRATION_MODE` (registered
+    /// accounts) or `ALLOW_ANONYMOUS_REGISTRATION` (anonymous ones)
+    pub async fn register(&self, request: CreateUserRequest) -> AppResult<RegisterOutcome> {
+        let invite_code = request.invite_code.as_deref().map(str::trim).filter(|code| !code.is_empty());
+
+        if request.is_anonymous {
+            if !self.config.allow_anonymous_registration {
+                return Err(AppError::Authorization("Anonymous registration is disabled".to_string()));
+            }
+
+            self.challenges
+                .verify(request.challenge.as_ref(), ChallengePurpose::Register, self.challenges.default_difficulty())
+                .await?;
+        } else {
+            self.check_registration_mode(invite_code.is_some())?;
+        }
+
         // Check if username is already taken
         let existing_user = sqlx::query!(
             "SELECT id FROM users WHERE username = ?",
@@ -75,12 +128,54 @@ impl AuthService {
 
         let user_id = Uuid::new_v4();
         let now = Utc::now();
+        let is_admin = !request.is_anonymous && self.config.admin_usernames.contains(&request.username);
+
+        let mut tx = self.db.pool().begin().await?;
+
+        // Invite codes only matter for registered accounts in modes that use them
+        let uses_invites = !request.is_anonymous
+            && matches!(self.config.registration_mode, RegistrationMode::Invite | RegistrationMode::Approval);
+
+        let mut invite_code_id = None;
+        if let (true, Some(code)) = (uses_invites, invite_code) {
+            let code_hash = self.crypto.hash_data(&code.to_lowercase());
+            let invite = sqlx::query!(
+                r#"
+                SELECT id FROM invite_codes
+                WHERE code_hash = ? AND revoked = FALSE AND uses < max_uses AND (expires_at IS NULL OR expires_at > ?)
+                "#,
+                code_hash,
+                now.to_rfc3339()
+            )
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| AppError::Authorization("Invalid or expired invite code".to_string()))?;
+
+            // Guard against concurrent registrations using the last slot
+            let result = sqlx::query!(
+                "UPDATE invite_codes SET uses = uses + 1 WHERE id = ? AND uses < max_uses",
+                invite.id
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            if result.rows_affected() == 0 {
+                return Err(AppError::Authorization("Invalid or expired invite code".to_string()));
+            }
+
+            invite_code_id = Some(invite.id);
+        }
+
+        let pending = !request.is_anonymous
+            && self.config.registration_mode == RegistrationMode::Approval
+            && invite_code_id.is_none();
+        let approval_status = if pending { "pending" } else { "approved" };
 
         // Insert user into database
         sqlx::query!(
             r#"
-            INSERT INTO users (id, username, email, password_hash, matrix_user_id, is_anonymous, created_at)
-            VALUES (?, ?, ?, ?, ?, ?, ?)
+            INSERT INTO users (id, username, email, password_hash, matrix_user_id, is_anonymous, is_admin, created_at, approval_status, invite_code_id)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
             user_id.to_string(),
             request.username,
@@ -88,28 +183,198 @@ impl AuthService {
             password_hash,
             matrix_user_id,
             request.is_anonymous,
-            now.to_rfc3339()
+            is_admin,
+            now.to_rfc3339(),
+            approval_status,
+            invite_code_id
         )
-        .execute(self.db.pool())
+        .execute(&mut *tx)
         .await?;
 
-        Ok(User {
+        tx.commit().await?;
+
+        let user = User {
             id: user_id,
             username: request.username,
             email: request.email,
+            email_verified: false,
             matrix_user_id,
             avatar_url: None,
             is_anonymous: request.is_anonymous,
+            is_admin,
+            created_at: now,
+            last_seen: None,
+        };
+
+        if pending {
+            info!("Registration of {} is awaiting approval", user.username);
+            Ok(RegisterOutcome::PendingApproval(user))
+        } else {
+            Ok(RegisterOutcome::Active(user))
+        }
+    }
+
+    /// Accounts waiting for an admin to approve them, oldest first
+    pub async fn list_pending_registrations(&self) -> AppResult<Vec<User>> {
+        let pending_records = sqlx::query!(
+            "SELECT id FROM users WHERE approval_status = 'pending' ORDER BY created_at"
+        )
+        .fetch_all(self.db.pool())
+        .await?;
+
+        let mut users = Vec::with_capacity(pending_records.len());
+        for record in pending_records {
+            let user_id = Uuid::parse_str(&record.id)
+                .map_err(|e| AppError::Internal(format!("Invalid user ID: {}", e)))?;
+            users.push(self.get_user(user_id).await?);
+        }
+
+        Ok(users)
+    }
+
+    /// Let a pending account log in
+    pub async fn approve_registration(&self, user_id: Uuid) -> AppResult<()> {
+        let result = sqlx::query!(
+            "UPDATE users SET approval_status = 'approved' WHERE id = ? AND approval_status = 'pending'",
+            user_id.to_string()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("No pending registration for this user".to_string()));
+        }
+
+        info!("Approved registration of user {}", user_id);
+        Ok(())
+    }
+
+    /// Discard a pending account. It never logged in and owns no content,
+    /// so the row is removed and the username becomes available again.
+    pub async fn reject_registration(&self, user_id: Uuid) -> AppResult<()> {
+        let mut tx = self.db.pool().begin().await?;
+
+        // The verification email sent at registration
+        sqlx::query!(
+            "DELETE FROM email_tokens WHERE user_id = ? AND user_id IN (SELECT id FROM users WHERE approval_status = 'pending')",
+            user_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let result = sqlx::query!(
+            "DELETE FROM users WHERE id = ? AND approval_status = 'pending'",
+            user_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("No pending registration for this user".to_string()));
+        }
+
+        tx.commit().await?;
+
+        info!("Rejected registration of user {}", user_id);
+        Ok(())
+    }
+
+    /// Create an account for someone who signed in at an external identity
+    /// provider. The account has no password; its username is derived from
+    /// the provider's suggestion and made unique.
+    ///
+    /// `REGISTRATION_MODE` applies as to `register`. There is no invite code
+    /// to present, so `invite` mode turns such accounts away and `approval`
+    /// mode queues them.
+    pub async fn register_external(&self, preferred_username: &str, verified_email: Option<&str>) -> AppResult<User> {
+        self.check_registration_mode(false)?;
+
+        let base = normalize_username(preferred_username);
+
+        let mut username = base.clone();
+        let mut suffix = 1;
+        while sqlx::query!("SELECT id FROM users WHERE username = ?", username)
+            .fetch_optional(self.db.pool())
+            .await?
+            .is_some()
+        {
+            suffix += 1;
+            username = format!("{}_{}", base, suffix);
+        }
+
+        // Keep the address only if no other account claims it
+        let mut email = None;
+        if let Some(address) = verified_email {
+            let existing_email = sqlx::query!("SELECT id FROM users WHERE email = ?", address)
+                .fetch_optional(self.db.pool())
+                .await?;
+
+            if existing_email.is_none() {
+                email = Some(address.to_string());
+            }
+        }
+
+        let user_id = Uuid::new_v4();
+        let now = Utc::now();
+        let matrix_user_id = format!("@{}:matrix.org", username);
+        let email_verified = email.is_some();
+        let approval_status = if self.config.registration_mode == RegistrationMode::Approval {
+            "pending"
+        } else {
+            "approved"
+        };
+
+        sqlx::query!(
+            r#"
+            INSERT INTO users (id, username, email, email_verified, password_hash, matrix_user_id, is_anonymous, is_admin, created_at, approval_status)
+            VALUES (?, ?, ?, ?, NULL, ?, FALSE, FALSE, ?, ?)
+            "#,
+            user_id.to_string(),
+            username,
+            email,
+            email_verified,
+            matrix_user_id,
+            now.to_rfc3339(),
+            approval_status
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        info!("Created account {} for an external identity", username);
+
+        Ok(User {
+            id: user_id,
+            username,
+            email,
+            email_verified,
+            matrix_user_id,
+            avatar_url: None,
+            is_anonymous: false,
+            is_admin: false,
             created_at: now,
             last_seen: None,
         })
     }
 
-    /// Login a user
-    pub async fn login(&self, request: LoginRequest) -> AppResult<(User, Session)> {
+    /// Whether `REGISTRATION_MODE` allows a new registered account
+    fn check_registration_mode(&self, has_invite_code: bool) -> AppResult<()> {
+        match self.config.registration_mode {
+            RegistrationMode::Closed => Err(AppError::Authorization("Registration is closed".to_string())),
+            RegistrationMode::Invite if !has_invite_code => {
+                Err(AppError::Authorization("An invite code is required to register".to_string()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Login a user.
+    ///
+    /// Accounts with two-factor authentication get a short-lived challenge
+    /// instead of a session; `verify_login_challenge` completes the login.
+    pub async fn login(&self, request: LoginRequest) -> AppResult<LoginOutcome> {
         // Get user from database
         let user_record = sqlx::query!(
-            "SELECT id, username, email, password_hash, matrix_user_id, avatar_url, is_anonymous, created_at, last_seen FROM users WHERE username = ?",
+            "SELECT id, password_hash, is_anonymous, totp_enabled FROM users WHERE username = ?",
             request.username
         )
         .fetch_optional(self.db.pool())
@@ -125,40 +390,409 @@ impl AuthService {
             if !is_valid {
                 return Err(AppError::Auth("Invalid credentials".to_string()));
             }
+
+            // Upgrade hashes made with older parameters while we have the password
+            if self.crypto.password_needs_rehash(&password_hash) {
+                let new_hash = self.crypto.hash_password(&request.password)?;
+                sqlx::query!(
+                    "UPDATE users SET password_hash = ? WHERE id = ? AND password_hash = ?",
+                    new_hash,
+                    user_record.id,
+                    password_hash
+                )
+                .execute(self.db.pool())
+                .await?;
+
+                info!("Rehashed password for user {} with current parameters", user_record.id);
+            }
         }
 
         let user_id = Uuid::parse_str(&user_record.id)
             .map_err(|e| AppError::Internal(format!("Invalid user ID: {}", e)))?;
 
+        let methods = self.second_factor_methods(user_id, user_record.totp_enabled).await?;
+        if !methods.is_empty() {
+            let challenge = self.create_login_challenge(user_id, methods).await?;
+            return Ok(LoginOutcome::TwoFactorRequired(challenge));
+        }
+
+        let (user, session) = self.complete_login(user_id).await?;
+        Ok(LoginOutcome::Authenticated(user, session))
+    }
+
+    /// Finish a two-factor login with a TOTP code or an unused recovery code
+    pub async fn verify_login_challenge(&self, token: &str, code: Option<&str>, recovery_code: Option<&str>) -> AppResult<(User, Session)> {
+        let user_id = self.redeem_login_challenge_attempt(token).await?;
+
+        let is_valid = match (code, recovery_code) {
+            (Some(code), _) => self.verify_totp(user_id, code).await?,
+            (None, Some(recovery_code)) => self.use_recovery_code(user_id, recovery_code).await?,
+            (None, None) => return Err(AppError::InvalidRequest("A code or recovery code is required".to_string())),
+        };
+
+        if !is_valid {
+            return Err(AppError::Auth("Invalid code".to_string()));
+        }
+
+        self.complete_login_challenge(token, user_id).await
+    }
+
+    /// Look up the user behind a pending login challenge and count one
+    /// verification attempt against it
+    pub async fn redeem_login_challenge_attempt(&self, token: &str) -> AppResult<Uuid> {
+        let token_hash = self.crypto.hash_data(token);
+        let now = Utc::now();
+
+        // Counting and checking the attempt in one statement keeps concurrent
+        // guesses from slipping past the limit
+        let challenge = sqlx::query!(
+            r#"
+            UPDATE login_challenges SET attempts = attempts + 1
+            WHERE token_hash = ? AND expires_at > ? AND attempts < ?
+            RETURNING id, user_id
+            "#,
+            token_hash,
+            now.to_rfc3339(),
+            MAX_CHALLENGE_ATTEMPTS
+        )
+        .fetch_optional(self.db.pool())
+        .await?;
+
+        let Some(challenge) = challenge else {
+            let exhausted = sqlx::query!(
+                "DELETE FROM login_challenges WHERE token_hash = ? AND attempts >= ?",
+                token_hash,
+                MAX_CHALLENGE_ATTEMPTS
+            )
+            .execute(self.db.pool())
+            .await?;
+
+            if exhausted.rows_affected() > 0 {
+                return Err(AppError::Auth("Too many attempts, log in again".to_string()));
+            }
+            return Err(AppError::Auth("Invalid or expired login challenge".to_string()));
+        };
+
+        Uuid::parse_str(&challenge.user_id)
+            .map_err(|e| AppError::Internal(format!("Invalid user ID: {}", e)))
+    }
+
+    /// Consume a login challenge whose second factor was verified and issue the session
+    pub async fn complete_login_challenge(&self, token: &str, user_id: Uuid) -> AppResult<(User, Session)> {
+        // Challenges are single use
+        let result = sqlx::query!(
+            "DELETE FROM login_challenges WHERE token_hash = ? AND user_id = ?",
+            self.crypto.hash_data(token),
+            user_id.to_string()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::Auth("Invalid or expired login challenge".to_string()));
+        }
+
+        self.complete_login(user_id).await
+    }
+
+    /// Start TOTP enrolment. The secret stays inactive until confirmed with a code.
+    pub async fn setup_totp(&self, user_id: Uuid) -> AppResult<TotpEnrollment> {
+        let user_record = sqlx::query!(
+            "SELECT username, is_anonymous, totp_enabled FROM users WHERE id = ?",
+            user_id.to_string()
+        )
+        .fetch_optional(self.db.pool())
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+        if user_record.is_anonymous {
+            return Err(AppError::InvalidRequest("Anonymous accounts can't enable two-factor authentication".to_string()));
+        }
+        if user_record.totp_enabled {
+            return Err(AppError::InvalidRequest("Two-factor authentication is already enabled".to_string()));
+        }
+
+        let secret = self.crypto.random_bytes(totp::SECRET_LEN)?;
+        let encoded_secret = totp::base32_encode(&secret);
+
+        let data_key = self.data_keys.get_or_create(KeyOwner::User(user_id)).await?;
+        let encrypted_secret = self.crypto.encrypt_with_data_key(&data_key, &encoded_secret, &totp_secret_aad(user_id))?;
+
+        sqlx::query!(
+            "UPDATE users SET totp_secret = ?, totp_enabled = FALSE, totp_last_step = NULL WHERE id = ?",
+            encrypted_secret,
+            user_id.to_string()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        Ok(TotpEnrollment {
+            provisioning_uri: totp::provisioning_uri(&self.config.totp_issuer, &user_record.username, &secret),
+            secret: encoded_secret,
+        })
+    }
+
+    /// Enable TOTP after the user proves their authenticator works.
+    /// Returns freshly generated recovery codes, shown to the user only once.
+    pub async fn confirm_totp(&self, user_id: Uuid, code: &str) -> AppResult<Vec<String>> {
+        let user_record = sqlx::query!(
+            "SELECT totp_secret, totp_enabled FROM users WHERE id = ?",
+            user_id.to_string()
+        )
+        .fetch_optional(self.db.pool())
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+        if user_record.totp_enabled {
+            return Err(AppError::InvalidRequest("Two-factor authentication is already enabled".to_string()));
+        }
+        if user_record.totp_secret.is_none() {
+            return Err(AppError::InvalidRequest("Two-factor setup has not been started".to_string()));
+        }
+
+        if !self.verify_totp(user_id, code).await? {
+            return Err(AppError::Auth("Invalid code".to_string()));
+        }
+
+        sqlx::query!(
+            "UPDATE users SET totp_enabled = TRUE WHERE id = ?",
+            user_id.to_string()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        self.replace_recovery_codes(user_id).await
+    }
+
+    /// Disable TOTP, confirmed with the password and a current code
+    pub async fn disable_totp(&self, user_id: Uuid, password: &str, code: &str) -> AppResult<()> {
+        let user_record = sqlx::query!(
+            "SELECT password_hash, totp_enabled FROM users WHERE id = ?",
+            user_id.to_string()
+        )
+        .fetch_optional(self.db.pool())
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+        if !user_record.totp_enabled {
+            return Err(AppError::InvalidRequest("Two-factor authentication is not enabled".to_string()));
+        }
+
+        let password_hash = user_record.password_hash
+            .ok_or_else(|| AppError::Auth("Invalid credentials".to_string()))?;
+        if !self.crypto.verify_password(password, &password_hash)? {
+            return Err(AppError::Auth("Invalid credentials".to_string()));
+        }
+
+        if !self.verify_totp(user_id, code).await? {
+            return Err(AppError::Auth("Invalid code".to_string()));
+        }
+
+        let mut tx = self.db.pool().begin().await?;
+
+        sqlx::query!(
+            "UPDATE users SET totp_secret = NULL, totp_enabled = FALSE, totp_last_step = NULL WHERE id = ?",
+            user_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM recovery_codes WHERE user_id = ?",
+            user_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Replace all recovery codes, confirmed with a current TOTP code
+    pub async fn regenerate_recovery_codes(&self, user_id: Uuid, code: &str) -> AppResult<Vec<String>> {
+        // A secret that was set up but never confirmed doesn't count
+        let totp_enabled = sqlx::query_scalar!("SELECT totp_enabled FROM users WHERE id = ?", user_id.to_string())
+            .fetch_optional(self.db.pool())
+            .await?
+            .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+        if !totp_enabled {
+            return Err(AppError::InvalidRequest("Two-factor authentication is not enabled".to_string()));
+        }
+
+        if !self.verify_totp(user_id, code).await? {
+            return Err(AppError::Auth("Invalid code".to_string()));
+        }
+
+        self.replace_recovery_codes(user_id).await
+    }
+
+    /// Second factors the user has set up, empty when a password is enough
+    async fn second_factor_methods(&self, user_id: Uuid, totp_enabled: bool) -> AppResult<Vec<String>> {
+        let mut methods = Vec::new();
+
+        if totp_enabled {
+            methods.push("totp".to_string());
+            methods.push("recovery_code".to_string());
+        }
+
+        let passkey_count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) as "count: i64" FROM passkey_credentials WHERE user_id = ?"#,
+            user_id.to_string()
+        )
+        .fetch_one(self.db.pool())
+        .await?;
+
+        if passkey_count > 0 {
+            methods.push("passkey".to_string());
+        }
+
+        Ok(methods)
+    }
+
+    async fn create_login_challenge(&self, user_id: Uuid, methods: Vec<String>) -> AppResult<LoginChallenge> {
+        let token = self.crypto.generate_token()?;
+        let expires_at = Utc::now() + Duration::minutes(LOGIN_CHALLENGE_MINUTES);
+
+        sqlx::query!(
+            "INSERT INTO login_challenges (id, user_id, token_hash, expires_at) VALUES (?, ?, ?, ?)",
+            Uuid::new_v4().to_string(),
+            user_id.to_string(),
+            self.crypto.hash_data(&token),
+            expires_at.to_rfc3339()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        Ok(LoginChallenge { token, expires_at, methods })
+    }
+
+    /// Issue a session and record the login
+    pub async fn complete_login(&self, user_id: Uuid) -> AppResult<(User, Session)> {
+        // Every login path ends here, so this is where pending accounts are stopped
+        let approval_status = sqlx::query_scalar!(
+            "SELECT approval_status FROM users WHERE id = ?",
+            user_id.to_string()
+        )
+        .fetch_optional(self.db.pool())
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+        if approval_status != "approved" {
+            return Err(AppError::Authorization("Account is awaiting approval".to_string()));
+        }
+
         // Create session
         let session = self.create_session(user_id).await?;
 
         // Update last seen
-        let now = Utc::now();
         sqlx::query!(
             "UPDATE users SET last_seen = ? WHERE id = ?",
-            now.to_rfc3339(),
-            user_record.id
+            Utc::now().to_rfc3339(),
+            user_id.to_string()
         )
         .execute(self.db.pool())
         .await?;
 
-        let user = User {
-            id: user_id,
-            username: user_record.username,
-            email: user_record.email,
-            matrix_user_id: user_record.matrix_user_id,
-            avatar_url: user_record.avatar_url,
-            is_anonymous: user_record.is_anonymous,
-            created_at: chrono::DateTime::parse_from_rfc3339(&user_record.created_at)
-                .map_err(|e| AppError::Internal(format!("Invalid date: {}", e)))?
-                .with_timezone(&Utc),
-            last_seen: Some(now),
-        };
+        let user = self.get_user(user_id).await?;
 
         Ok((user, session))
     }
 
+    /// Check a TOTP code and mark its time step as used
+    async fn verify_totp(&self, user_id: Uuid, code: &str) -> AppResult<bool> {
+        let user_record = sqlx::query!(
+            "SELECT totp_secret, totp_last_step FROM users WHERE id = ?",
+            user_id.to_string()
+        )
+        .fetch_optional(self.db.pool())
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+        let encrypted_secret = user_record.totp_secret
+            .ok_or_else(|| AppError::InvalidRequest("Two-factor authentication is not set up".to_string()))?;
+
+        let data_key = self.data_keys.get(KeyOwner::User(user_id)).await?;
+        let encoded_secret = self.crypto.decrypt_with_data_key(&encrypted_secret, &totp_secret_aad(user_id), data_key.as_deref())?;
+        let secret = totp::base32_decode(&encoded_secret)
+            .ok_or_else(|| AppError::Crypto("Invalid TOTP secret".to_string()))?;
+
+        let last_step = user_record.totp_last_step.map(|step| step as u64);
+        let Some(step) = totp::verify(&secret, code, Utc::now().timestamp() as u64, last_step) else {
+            return Ok(false);
+        };
+
+        // A concurrent request may have used the same step first
+        let step = step as i64;
+        let result = sqlx::query!(
+            "UPDATE users SET totp_last_step = ? WHERE id = ? AND (totp_last_step IS NULL OR totp_last_step < ?)",
+            step,
+            user_id.to_string(),
+            step
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn use_recovery_code(&self, user_id: Uuid, recovery_code: &str) -> AppResult<bool> {
+        let code_hash = self.crypto.hash_data(&normalize_recovery_code(recovery_code));
+
+        let result = sqlx::query!(
+            "UPDATE recovery_codes SET used_at = ? WHERE user_id = ? AND code_hash = ? AND used_at IS NULL",
+            Utc::now().to_rfc3339(),
+            user_id.to_string(),
+            code_hash
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        if result.rows_affected() == 1 {
+            info!("User {} logged in with a recovery code", user_id);
+        }
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn replace_recovery_codes(&self, user_id: Uuid) -> AppResult<Vec<String>> {
+        let mut codes = Vec::with_capacity(RECOVERY_CODE_COUNT);
+        for _ in 0..RECOVERY_CODE_COUNT {
+            let encoded = totp::base32_encode(&self.crypto.random_bytes(10)?).to_lowercase();
+            let groups: Vec<&str> = encoded.as_bytes()
+                .chunks(4)
+                .map(|chunk| std::str::from_utf8(chunk).unwrap_or_default())
+                .collect();
+            codes.push(groups.join("-"));
+        }
+
+        let mut tx = self.db.pool().begin().await?;
+
+        sqlx::query!(
+            "DELETE FROM recovery_codes WHERE user_id = ?",
+            user_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        for code in &codes {
+            sqlx::query!(
+                "INSERT INTO recovery_codes (id, user_id, code_hash, created_at) VALUES (?, ?, ?, ?)",
+                Uuid::new_v4().to_string(),
+                user_id.to_string(),
+                self.crypto.hash_data(&normalize_recovery_code(code)),
+                Utc::now().to_rfc3339()
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(codes)
+    }
+
     /// Create a new session for a user
     pub async fn create_session(&self, user_id: Uuid) -> AppResult<Session> {
         let session_id = Uuid::new_v4();
@@ -191,7 +825,7 @@ impl AuthService {
 
         let session_record = sqlx::query!(
             r#"
-            SELECT s.user_id, u.username, u.email, u.matrix_user_id, u.avatar_url, u.is_anonymous, u.created_at, u.last_seen
+            SELECT s.user_id, u.username, u.email, u.email_verified, u.matrix_user_id, u.avatar_url, u.is_anonymous, u.is_admin, u.created_at, u.last_seen
             FROM sessions s
             JOIN users u ON s.user_id = u.id
             WHERE s.token_hash = ? AND s.expires_at > ?
@@ -210,9 +844,11 @@ impl AuthService {
             id: user_id,
             username: session_record.username,
             email: session_record.email,
+            email_verified: session_record.email_verified,
             matrix_user_id: session_record.matrix_user_id,
             avatar_url: session_record.avatar_url,
             is_anonymous: session_record.is_anonymous,
+            is_admin: session_record.is_admin,
             created_at: chrono::DateTime::parse_from_rfc3339(&session_record.created_at)
                 .map_err(|e| AppError::Internal(format!("Invalid date: {}", e)))?
                 .with_timezone(&Utc),
@@ -242,7 +878,7 @@ impl AuthService {
     /// Get user by ID
     pub async fn get_user(&self, user_id: Uuid) -> AppResult<User> {
         let user_record = sqlx::query!(
-            "SELECT username, email, matrix_user_id, avatar_url, is_anonymous, created_at, last_seen FROM users WHERE id = ?",
+            "SELECT username, email, email_verified, matrix_user_id, avatar_url, is_anonymous, is_admin, created_at, last_seen FROM users WHERE id = ?",
             user_id.to_string()
         )
         .fetch_optional(self.db.pool())
@@ -253,9 +889,11 @@ impl AuthService {
             id: user_id,
             username: user_record.username,
             email: user_record.email,
+            email_verified: user_record.email_verified,
             matrix_user_id: user_record.matrix_user_id,
             avatar_url: user_record.avatar_url,
             is_anonymous: user_record.is_anonymous,
+            is_admin: user_record.is_admin,
             created_at: chrono::DateTime::parse_from_rfc3339(&user_record.created_at)
                 .map_err(|e| AppError::Internal(format!("Invalid date: {}", e)))?
                 .with_timezone(&Utc),
@@ -279,6 +917,331 @@ impl AuthService {
         .execute(self.db.pool())
         .await?;
 
+        sqlx::query!(
+            "DELETE FROM login_challenges WHERE expires_at < ?",
+            now.to_rfc3339()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM email_tokens WHERE expires_at < ?",
+            now.to_rfc3339()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM oidc_login_states WHERE expires_at < ?",
+            now.to_rfc3339()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        Ok(())
+    }
+
+    /// Grant admin privileges to the accounts listed in ADMIN_USERNAMES
+    pub async fn sync_admins(&self) -> AppResult<()> {
+        for username in &self.config.admin_usernames {
+            sqlx::query!(
+                "UPDATE users SET is_admin = TRUE WHERE username = ? AND is_anonymous = FALSE",
+                username
+            )
+            .execute(self.db.pool())
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Schedule an account for deletion after the grace period in
+    /// `ACCOUNT_DELETION_GRACE_DAYS`. Asking again keeps the original date.
+    pub async fn request_account_deletion(&self, user_id: Uuid, password: Option<&str>) -> AppResult<DateTime<Utc>> {
+        let user_record = sqlx::query!(
+            "SELECT password_hash, is_anonymous, deletion_scheduled_for FROM users WHERE id = ?",
+            user_id.to_string()
+        )
+        .fetch_optional(self.db.pool())
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+        // Registered accounts must confirm with their password. Accounts that
+        // only sign in through an identity provider don't have one.
+        let password_hash = user_record.password_hash.filter(|_| !user_record.is_anonymous);
+        if let Some(password_hash) = password_hash {
+            let password = password
+                .ok_or_else(|| AppError::Auth("Password confirmation required".to_string()))?;
+
+            if !self.crypto.verify_password(password, &password_hash)? {
+                return Err(AppError::Auth("Invalid credentials".to_string()));
+            }
+        }
+
+        if let Some(scheduled_for) = user_record.deletion_scheduled_for {
+            return Ok(DateTime::parse_from_rfc3339(&scheduled_for)
+                .map_err(|e| AppError::Internal(format!("Invalid date: {}", e)))?
+                .with_timezone(&Utc));
+        }
+
+        let scheduled_for = Utc::now() + Duration::days(self.config.account_deletion_grace_days.max(0));
+        sqlx::query!(
+            "UPDATE users SET deletion_scheduled_for = ? WHERE id = ?",
+            scheduled_for.to_rfc3339(),
+            user_id.to_string()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        info!("User {} scheduled their account for deletion on {}", user_id, scheduled_for);
+        Ok(scheduled_for)
+    }
+
+    /// Keep an account that was scheduled for deletion
+    pub async fn cancel_account_deletion(&self, user_id: Uuid) -> AppResult<()> {
+        let result = sqlx::query!(
+            "UPDATE users SET deletion_scheduled_for = NULL WHERE id = ? AND deletion_scheduled_for IS NOT NULL",
+            user_id.to_string()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::InvalidRequest("No account deletion is scheduled".to_string()));
+        }
+
+        info!("User {} cancelled the deletion of their account", user_id);
+        Ok(())
+    }
+
+    /// When the account will be deleted, if its deletion was requested
+    pub async fn scheduled_deletion(&self, user_id: Uuid) -> AppResult<Option<DateTime<Utc>>> {
+        let scheduled_for = sqlx::query_scalar!(
+            "SELECT deletion_scheduled_for FROM users WHERE id = ?",
+            user_id.to_string()
+        )
+        .fetch_optional(self.db.pool())
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+        scheduled_for
+            .map(|scheduled_for| {
+                DateTime::parse_from_rfc3339(&scheduled_for)
+                    .map(|date| date.with_timezone(&Utc))
+                    .map_err(|e| AppError::Internal(format!("Invalid date: {}", e)))
+            })
+            .transpose()
+    }
+
+    /// Erase an account's credentials and personal data.
+    ///
+    /// The user's data key is shredded first, making any profile data
+    /// encrypted under it unrecoverable. Secrets still under the global
+    /// keyring are cleared from the row below. The row itself is kept as a
+    /// tombstone so authored board content keeps a valid author reference.
+    /// Content is dealt with beforehand, in `AccountDeletionService`.
+    pub async fn erase_account(&self, user_id: Uuid) -> AppResult<()> {
+        self.data_keys.shred(KeyOwner::User(user_id)).await?;
+
+        let mut tx = self.db.pool().begin().await?;
+
+        sqlx::query!(
+            "DELETE FROM sessions WHERE user_id = ?",
+            user_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM chat_participants WHERE user_id = ?",
+            user_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM recovery_codes WHERE user_id = ?",
+            user_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM login_challenges WHERE user_id = ?",
+            user_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM email_tokens WHERE user_id = ?",
+            user_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE invite_codes SET revoked = TRUE WHERE created_by = ?",
+            user_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM api_tokens WHERE user_id = ?",
+            user_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM user_identities WHERE user_id = ?",
+            user_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM contacts WHERE owner_id = ?1 OR contact_id = ?1",
+            user_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM blocks WHERE blocker_id = ?1 OR blocked_id = ?1",
+            user_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM board_members WHERE user_id = ?",
+            user_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM invite_link_requests WHERE user_id = ?",
+            user_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM board_join_requests WHERE user_id = ?",
+            user_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM board_moderators WHERE user_id = ?",
+            user_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        // Log entries quote the account's posts
+        sqlx::query!(
+            "DELETE FROM moderation_log WHERE user_id = ?",
+            user_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM webauthn_ceremonies WHERE user_id = ?",
+            user_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM passkey_credentials WHERE user_id = ?",
+            user_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM account_exports WHERE user_id = ?",
+            user_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        // Nobody should be able to start new encrypted sessions with a deleted account
+        sqlx::query!(
+            "DELETE FROM prekey_claims WHERE claimant_id = ? OR user_id = ?",
+            user_id.to_string(),
+            user_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM one_time_prekeys WHERE user_id = ?",
+            user_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM device_keys WHERE user_id = ?",
+            user_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let tombstone = format!("deleted_{}", user_id.simple());
+        let matrix_tombstone = format!("@{}:invalid", tombstone);
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET username = ?, email = NULL, email_verified = FALSE, password_hash = NULL, matrix_user_id = ?,
+                avatar_url = NULL, is_admin = FALSE, last_seen = NULL,
+                totp_secret = NULL, totp_enabled = FALSE, totp_last_step = NULL, deletion_scheduled_for = NULL,
+                display_name = NULL, bio = NULL, discoverable = FALSE
+            WHERE id = ?
+            "#,
+            tombstone,
+            matrix_tombstone,
+            user_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
         Ok(())
     }
+}
+
+/// Turn a username suggested by an identity provider into a local one
+fn normalize_username(preferred: &str) -> String {
+    // Email addresses are common here; only keep the local part
+    let preferred = preferred.split('@').next().unwrap_or_default();
+
+    let username: String = preferred
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-' || *c == '.')
+        .map(|c| c.to_ascii_lowercase())
+        .take(24)
+        .collect();
+
+    if username.is_empty() {
+        "user".to_string()
+    } else {
+        username
+    }
+}
+
+/// Recovery codes are compared case- and separator-insensitively
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
 }
\ No newline at end of file
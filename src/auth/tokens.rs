@@ -0,0 +1,203 @@
+use base64::{Engine as _, engine::general_purpose};
+use chrono::{DateTime, Duration, Utc};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::auth::service::AuthService;
+use crate::core::error::{AppError, AppResult};
+use crate::core::types::{ApiToken, CreateApiTokenRequest, TokenScope, User};
+use crate::crypto::service::CryptoService;
+use crate::storage::database::Database;
+
+/// Prefix telling personal access tokens apart from session tokens
+pub const TOKEN_PREFIX: &str = "pat_";
+
+/// Tokens a single account may hold
+const MAX_TOKENS_PER_USER: i64 = 25;
+
+/// `last_used_at` is only written when older than this, so busy bots don't
+/// turn every request into a database write
+const LAST_USED_RESOLUTION_MINUTES: i64 = 5;
+
+/// Personal access tokens with explicit scopes.
+///
+/// Only a SHA-256 hash of each token is stored; the token itself is shown
+/// once, when it is created.
+pub struct ApiTokenService {
+    db: Arc<Database>,
+    crypto: Arc<CryptoService>,
+    auth: Arc<AuthService>,
+}
+
+impl ApiTokenService {
+    pub fn new(db: Arc<Database>, crypto: Arc<CryptoService>, auth: Arc<AuthService>) -> Self {
+        Self { db, crypto, auth }
+    }
+
+    /// Create a token. Returns its metadata and the token itself.
+    pub async fn create_token(&self, user: &User, request: CreateApiTokenRequest) -> AppResult<(ApiToken, String)> {
+        if user.is_anonymous {
+            return Err(AppError::InvalidRequest("Anonymous accounts can't create API tokens".to_string()));
+        }
+
+        let name = request.name.trim().to_string();
+        if name.is_empty() || name.len() > 64 {
+            return Err(AppError::InvalidRequest("Token name must be 1-64 characters".to_string()));
+        }
+
+        let mut scopes = Vec::new();
+        for scope in request.scopes {
+            if !scopes.contains(&scope) {
+                scopes.push(scope);
+            }
+        }
+        if scopes.is_empty() {
+            return Err(AppError::InvalidRequest("A token needs at least one scope".to_string()));
+        }
+
+        let expires_at = match request.expires_in_days {
+            Some(days) if days < 1 => {
+                return Err(AppError::InvalidRequest("Tokens must be valid for at least one day".to_string()));
+            }
+            Some(days) => Some(Utc::now() + Duration::days(days)),
+            None => None,
+        };
+
+        let token_count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) as "count: i64" FROM api_tokens WHERE user_id = ?"#,
+            user.id.to_string()
+        )
+        .fetch_one(self.db.pool())
+        .await?;
+
+        if token_count >= MAX_TOKENS_PER_USER {
+            return Err(AppError::InvalidRequest(format!("At most {} tokens per account", MAX_TOKENS_PER_USER)));
+        }
+
+        let token = format!(
+            "{}{}",
+            TOKEN_PREFIX,
+            general_purpose::URL_SAFE_NO_PAD.encode(self.crypto.random_bytes(32)?)
+        );
+
+        let token_id = Uuid::new_v4();
+        let now = Utc::now();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO api_tokens (id, user_id, name, token_hash, scopes, expires_at, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            "#,
+            token_id.to_string(),
+            user.id.to_string(),
+            name,
+            self.crypto.hash_data(&token),
+            join_scopes(&scopes),
+            expires_at.map(|expires_at| expires_at.to_rfc3339()),
+            now.to_rfc3339()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        Ok((
+            ApiToken {
+                id: token_id,
+                name,
+                scopes,
+                expires_at,
+                last_used_at: None,
+                created_at: now,
+            },
+            token,
+        ))
+    }
+
+    /// Tokens of a user, newest first
+    pub async fn list_tokens(&self, user_id: Uuid) -> AppResult<Vec<ApiToken>> {
+        let token_records = sqlx::query!(
+            "SELECT id, name, scopes, expires_at, last_used_at, created_at FROM api_tokens WHERE user_id = ? ORDER BY created_at DESC",
+            user_id.to_string()
+        )
+        .fetch_all(self.db.pool())
+        .await?;
+
+        let mut tokens = Vec::with_capacity(token_records.len());
+        for record in token_records {
+            tokens.push(ApiToken {
+                id: Uuid::parse_str(&record.id)
+                    .map_err(|e| AppError::Internal(format!("Invalid token ID: {}", e)))?,
+                name: record.name,
+                scopes: parse_scopes(&record.scopes),
+                expires_at: record.expires_at.as_deref().map(parse_date).transpose()?,
+                last_used_at: record.last_used_at.as_deref().map(parse_date).transpose()?,
+                created_at: parse_date(&record.created_at)?,
+            });
+        }
+
+        Ok(tokens)
+    }
+
+    /// Revoke one of the user's tokens
+    pub async fn revoke_token(&self, user_id: Uuid, token_id: Uuid) -> AppResult<()> {
+        let result = sqlx::query!(
+            "DELETE FROM api_tokens WHERE id = ? AND user_id = ?",
+            token_id.to_string(),
+            user_id.to_string()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Token not found".to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// Resolve a token to its user and scopes, recording that it was used
+    pub async fn validate_token(&self, token: &str) -> AppResult<(User, Vec<TokenScope>)> {
+        let now = Utc::now();
+
+        let token_record = sqlx::query!(
+            r#"
+            SELECT id, user_id, scopes FROM api_tokens
+            WHERE token_hash = ? AND (expires_at IS NULL OR expires_at > ?)
+            "#,
+            self.crypto.hash_data(token),
+            now.to_rfc3339()
+        )
+        .fetch_optional(self.db.pool())
+        .await?
+        .ok_or_else(|| AppError::Auth("Invalid or expired token".to_string()))?;
+
+        sqlx::query!(
+            "UPDATE api_tokens SET last_used_at = ? WHERE id = ? AND (last_used_at IS NULL OR last_used_at < ?)",
+            now.to_rfc3339(),
+            token_record.id,
+            (now - Duration::minutes(LAST_USED_RESOLUTION_MINUTES)).to_rfc3339()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        let user_id = Uuid::parse_str(&token_record.user_id)
+            .map_err(|e| AppError::Internal(format!("Invalid user ID: {}", e)))?;
+        let user = self.auth.get_user(user_id).await?;
+
+        Ok((user, parse_scopes(&token_record.scopes)))
+    }
+}
+
+fn join_scopes(scopes: &[TokenScope]) -> String {
+    scopes.iter().map(|scope| scope.as_str()).collect::<Vec<_>>().join(" ")
+}
+
+/// Unknown scopes (e.g. from a newer version) are dropped, never widened
+fn parse_scopes(scopes: &str) -> Vec<TokenScope> {
+    scopes.split_whitespace().filter_map(TokenScope::parse).collect()
+}
+
+fn parse_date(value: &str) -> AppResult<DateTime<Utc>> {
+    Ok(DateTime::parse_from_rfc3339(value)
+        .map_err(|e| AppError::Internal(format!("Invalid date: {}", e)))?
+        .with_timezone(&Utc))
+}
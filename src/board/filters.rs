@@ -0,0 +1,66 @@
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::board::service::BoardService;
+use crate::core::error::{AppError, AppResult};
+use crate::core::types::{Board, ContentFilter, CreateContentFilterRequest, ModerationLogEntry, User};
+use crate::moderation::content_filters::ContentFilterService;
+
+/// A board's own word and link filters and its moderation log, managed by
+/// the board's owner, its moderators and site admins. Global filters are
+/// managed by admins through `ContentFilterService` directly.
+pub struct BoardFilterService {
+    boards: Arc<BoardService>,
+    filters: Arc<ContentFilterService>,
+}
+
+impl BoardFilterService {
+    pub fn new(boards: Arc<BoardService>, filters: Arc<ContentFilterService>) -> Self {
+        Self { boards, filters }
+    }
+
+    pub async fn list_filters(&self, board_name: &str, manager: &User) -> AppResult<Vec<ContentFilter>> {
+        let board = self.managed_board(board_name, manager).await?;
+
+        self.filters.list_filters(Some(board.id)).await
+    }
+
+    pub async fn create_filter(
+        &self,
+        board_name: &str,
+        request: CreateContentFilterRequest,
+        manager: &User,
+    ) -> AppResult<ContentFilter> {
+        let board = self.managed_board(board_name, manager).await?;
+
+        self.filters.create_filter(Some(board.id), request, manager.id).await
+    }
+
+    pub async fn delete_filter(&self, board_name: &str, filter_id: Uuid, manager: &User) -> AppResult<()> {
+        let board = self.managed_board(board_name, manager).await?;
+
+        self.filters.delete_filter(Some(board.id), filter_id).await
+    }
+
+    pub async fn moderation_log(
+        &self,
+        board_name: &str,
+        manager: &User,
+        limit: Option<i64>,
+        offset: Option<i64>,
+    ) -> AppResult<Vec<ModerationLogEntry>> {
+        let board = self.managed_board(board_name, manager).await?;
+
+        self.filters.moderation_log(Some(board.id), limit, offset).await
+    }
+
+    async fn managed_board(&self, board_name: &str, manager: &User) -> AppResult<Board> {
+        let board = self.boards.get_visible_board(board_name, Some(manager)).await?;
+
+        if !self.boards.can_manage(&board, manager).await? {
+            return Err(AppError::Authorization("You can't manage this board's filters".to_string()));
+        }
+
+        Ok(board)
+    }
+}
@@ -0,0 +1,221 @@
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::board::service::BoardService;
+use crate::core::error::{AppError, AppResult};
+use crate::core::types::{AddBoardMemberRequest, Board, BoardJoinRequest, BoardMember, CreateBoardJoinRequest, User};
+use crate::storage::database::Database;
+use crate::user::service::UserService;
+
+/// Longest message a join request can carry
+const MAX_JOIN_MESSAGE_CHARS: usize = 500;
+
+/// Membership of private boards.
+///
+/// Only members (and site admins) can see a private board or post to it.
+/// The board's managers, meaning its owner, its moderators and site admins,
+/// add and remove members directly or answer join requests; people can also
+/// get in through invite links. Members can leave, except the owner.
+pub struct BoardMemberService {
+    db: Arc<Database>,
+    boards: Arc<BoardService>,
+    users: Arc<UserService>,
+}
+
+impl BoardMemberService {
+    pub fn new(db: Arc<Database>, boards: Arc<BoardService>, users: Arc<UserService>) -> Self {
+        Self { db, boards, users }
+    }
+
+    /// A board's members, longest-standing first. Visible to its members.
+    pub async fn list_members(&self, board_name: &str, viewer: &User) -> AppResult<Vec<BoardMember>> {
+        let board = self.boards.get_visible_board(board_name, Some(viewer)).await?;
+
+        let member_records = sqlx::query!(
+            "SELECT user_id, joined_at FROM board_members WHERE board_id = ? ORDER BY joined_at ASC",
+            board.id.to_string()
+        )
+        .fetch_all(self.db.pool())
+        .await?;
+
+        let mut members = Vec::with_capacity(member_records.len());
+        for record in member_records {
+            members.push(BoardMember {
+                user: self.users.get_public_user(parse_id(&record.user_id)?, viewer).await?,
+                joined_at: parse_date(&record.joined_at)?,
+            });
+        }
+
+        Ok(members)
+    }
+
+    /// Add someone to a private board
+    pub async fn add_member(&self, board_name: &str, request: AddBoardMemberRequest, manager: &User) -> AppResult<()> {
+        let board = self.managed_private_board(board_name, manager).await?;
+        let user_id = self.users.resolve_user(&request.user).await?;
+
+        self.boards.add_member(&board, user_id).await?;
+        self.clear_join_request(board.id, user_id).await
+    }
+
+    /// Take someone off a private board. Removing a moderator or a site admin,
+    /// which also ends an appointed moderator's role, is up to the owner and
+    /// site admins, as in `BoardRoleService::remove_moderator`.
+    pub async fn remove_member(&self, board_name: &str, user_id: Uuid, manager: &User) -> AppResult<()> {
+        let board = self.managed_private_board(board_name, manager).await?;
+
+        if self.boards.is_owner_or_moderator(&board, user_id).await? || self.is_site_admin(user_id).await? {
+            self.boards.require_owner(&board, manager)?;
+        }
+
+        self.boards.remove_member(&board, user_id, "Removed from the board").await
+    }
+
+    /// Leave a private board
+    pub async fn leave(&self, board_name: &str, user: &User) -> AppResult<()> {
+        let board = self.boards.get_visible_board(board_name, Some(user)).await?;
+        if !board.is_private {
+            return Err(AppError::InvalidRequest("Public boards have no members to leave".to_string()));
+        }
+
+        self.boards.remove_member(&board, user.id, "Left the board").await
+    }
+
+    /// Ask to join a private board. Unlike its contents, a private board's
+    /// name is something people can be told, so asking needs only the name.
+    pub async fn request_to_join(&self, board_name: &str, request: CreateBoardJoinRequest, user: &User) -> AppResult<()> {
+        let board = self.boards.get_board(board_name).await?;
+        if !board.is_private {
+            return Err(AppError::InvalidRequest("Anyone can post to public boards".to_string()));
+        }
+
+        if self.boards.is_member(board.id, user.id).await? {
+            return Err(AppError::InvalidRequest("Already a member of this board".to_string()));
+        }
+
+        let message = request.message
+            .map(|message| message.trim().to_string())
+            .filter(|message| !message.is_empty());
+        if message.as_ref().is_some_and(|message| message.chars().count() > MAX_JOIN_MESSAGE_CHARS) {
+            return Err(AppError::InvalidRequest(format!(
+                "Join request messages can be at most {} characters",
+                MAX_JOIN_MESSAGE_CHARS
+            )));
+        }
+
+        let result = sqlx::query!(
+            "INSERT OR IGNORE INTO board_join_requests (board_id, user_id, message, created_at) VALUES (?, ?, ?, ?)",
+            board.id.to_string(),
+            user.id.to_string(),
+            message,
+            Utc::now().to_rfc3339()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::InvalidRequest("You already asked to join this board".to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// Pending join requests, oldest first
+    pub async fn list_join_requests(&self, board_name: &str, manager: &User) -> AppResult<Vec<BoardJoinRequest>> {
+        let board = self.managed_private_board(board_name, manager).await?;
+
+        let request_records = sqlx::query!(
+            "SELECT user_id, message, created_at FROM board_join_requests WHERE board_id = ? ORDER BY created_at ASC",
+            board.id.to_string()
+        )
+        .fetch_all(self.db.pool())
+        .await?;
+
+        let mut requests = Vec::with_capacity(request_records.len());
+        for record in request_records {
+            requests.push(BoardJoinRequest {
+                user: self.users.get_public_user(parse_id(&record.user_id)?, manager).await?,
+                message: record.message,
+                created_at: parse_date(&record.created_at)?,
+            });
+        }
+
+        Ok(requests)
+    }
+
+    /// Let a requester in
+    pub async fn approve_join_request(&self, board_name: &str, user_id: Uuid, manager: &User) -> AppResult<()> {
+        let board = self.managed_private_board(board_name, manager).await?;
+
+        self.take_join_request(board.id, user_id).await?;
+        self.boards.add_member(&board, user_id).await
+    }
+
+    /// Turn a requester away
+    pub async fn reject_join_request(&self, board_name: &str, user_id: Uuid, manager: &User) -> AppResult<()> {
+        let board = self.managed_private_board(board_name, manager).await?;
+
+        self.take_join_request(board.id, user_id).await
+    }
+
+    async fn is_site_admin(&self, user_id: Uuid) -> AppResult<bool> {
+        let is_admin = sqlx::query_scalar!("SELECT is_admin FROM users WHERE id = ?", user_id.to_string())
+            .fetch_optional(self.db.pool())
+            .await?;
+
+        Ok(is_admin.unwrap_or(false))
+    }
+
+    async fn managed_private_board(&self, board_name: &str, manager: &User) -> AppResult<Board> {
+        let board = self.boards.get_visible_board(board_name, Some(manager)).await?;
+
+        if !self.boards.can_manage(&board, manager).await? {
+            return Err(AppError::Authorization("You can't manage this board's members".to_string()));
+        }
+
+        if !board.is_private {
+            return Err(AppError::InvalidRequest("Public boards have no members to manage".to_string()));
+        }
+
+        Ok(board)
+    }
+
+    async fn take_join_request(&self, board_id: Uuid, user_id: Uuid) -> AppResult<()> {
+        let result = sqlx::query!(
+            "DELETE FROM board_join_requests WHERE board_id = ? AND user_id = ?",
+            board_id.to_string(),
+            user_id.to_string()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Join request not found".to_string()));
+        }
+
+        Ok(())
+    }
+
+    async fn clear_join_request(&self, board_id: Uuid, user_id: Uuid) -> AppResult<()> {
+        sqlx::query!(
+            "DELETE FROM board_join_requests WHERE board_id = ? AND user_id = ?",
+            board_id.to_string(),
+            user_id.to_string()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        Ok(())
+    }
+}
+
+fn parse_id(value: &str) -> AppResult<Uuid> {
+    Uuid::parse_str(value).map_err(|e| AppError::Internal(format!("Invalid ID: {}", e)))
+}
+
+fn parse_date(value: &str) -> AppResult<DateTime<Utc>> {
+    Ok(DateTime::parse_from_rfc3339(value)
+        .map_err(|e| AppError::Internal(format!("Invalid date: {}", e)))?
+        .with_timezone(&Utc))
+}
@@ -1 +1,6 @@
-pub mod service;
\ No newline at end of file
+pub mod filters;
+pub mod members;
+pub mod policy;
+pub mod roles;
+pub mod service;
+pub mod settings;
@@ -0,0 +1,95 @@
+use crate::core::config::BoardsConfig;
+use crate::core::error::{AppError, AppResult};
+
+/// Longest board name
+const MAX_NAME_CHARS: usize = 24;
+
+/// Names only admins can give a board, so nobody passes a board off as
+/// official
+const RESERVED_NAMES: &[&str] = &[
+    "admin", "admins", "administrator", "all", "announcements", "api", "help", "meta", "mod", "mods",
+    "moderator", "moderators", "news", "official", "root", "rules", "staff", "support", "system",
+];
+
+/// Check a new board's name: 1 to 24 lowercase letters and digits, free of
+/// blocked words, and not reserved unless the creator is an admin
+pub fn validate_name(name: &str, config: &BoardsConfig, is_admin: bool) -> AppResult<()> {
+    if name.is_empty()
+        || name.len() > MAX_NAME_CHARS
+        || !name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+    {
+        return Err(AppError::InvalidRequest(format!(
+            "Board names must be 1 to {} lowercase letters and digits",
+            MAX_NAME_CHARS
+        )));
+    }
+
+    if config.blocked_words.iter().any(|word| name.contains(word.as_str())) {
+        return Err(AppError::InvalidRequest("This board name isn't allowed".to_string()));
+    }
+
+    let reserved = RESERVED_NAMES.contains(&name) || config.reserved_names.iter().any(|reserved| reserved == name);
+    if reserved && !is_admin {
+        return Err(AppError::InvalidRequest("This board name is reserved".to_string()));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::config::BoardCreationMode;
+
+    fn config() -> BoardsConfig {
+        BoardsConfig {
+            creation_mode: BoardCreationMode::Registered,
+            min_account_age_days: 0,
+            min_karma: 0,
+            boards_per_user: 0,
+            reserved_names: vec!["amogchan".to_string()],
+            blocked_words: vec!["slur".to_string()],
+        }
+    }
+
+    #[test]
+    fn names_are_lowercase_letters_and_digits() {
+        let config = config();
+
+        assert!(validate_name("b", &config, false).is_ok());
+        assert!(validate_name("retro80s", &config, false).is_ok());
+        assert!(validate_name("", &config, false).is_err());
+        assert!(validate_name("Retro", &config, false).is_err());
+        assert!(validate_name("retro-games", &config, false).is_err());
+        assert!(validate_name("retro games", &config, false).is_err());
+        assert!(validate_name("café", &config, false).is_err());
+    }
+
+    #[test]
+    fn names_are_at_most_24_characters() {
+        let config = config();
+
+        assert!(validate_name(&"a".repeat(MAX_NAME_CHARS), &config, false).is_ok());
+        assert!(validate_name(&"a".repeat(MAX_NAME_CHARS + 1), &config, true).is_err());
+    }
+
+    #[test]
+    fn blocked_words_are_refused_anywhere_in_the_name_even_for_admins() {
+        let config = config();
+
+        assert!(validate_name("slur", &config, false).is_err());
+        assert!(validate_name("myslurboard", &config, false).is_err());
+        assert!(validate_name("myslurboard", &config, true).is_err());
+    }
+
+    #[test]
+    fn reserved_names_are_left_to_admins() {
+        let config = config();
+
+        for name in ["admin", "official", "amogchan"] {
+            assert!(validate_name(name, &config, false).is_err());
+            assert!(validate_name(name, &config, true).is_ok());
+        }
+        assert!(validate_name("admins2", &config, false).is_ok());
+    }
+}
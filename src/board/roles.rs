@@ -0,0 +1,240 @@
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::board::service::BoardService;
+use crate::core::error::{AppError, AppResult};
+use crate::core::types::{Board, BoardModerator, User};
+use crate::matrix::client::MatrixClient;
+use crate::storage::database::Database;
+use crate::user::service::UserService;
+
+/// Matrix power levels mirroring board roles, below the bot's 100 so it can
+/// still manage the room
+pub(crate) const OWNER_POWER_LEVEL: i64 = 75;
+const MODERATOR_POWER_LEVEL: i64 = 50;
+const MEMBER_POWER_LEVEL: i64 = 0;
+
+/// Source of moderator roles appointed by a board's owner
+pub(crate) const MANUAL_SOURCE: &str = "manual";
+
+/// Board owners and volunteer moderators.
+///
+/// Every board has one owner, who can hand the board to someone else and
+/// appoint moderators. Moderators help manage members and invite links.
+/// Appointed roles sit next to those mapped from identity provider groups;
+/// only appointed ones can be removed here. Roles are mirrored to the
+/// board's Matrix room as power levels where the bot can set them.
+pub struct BoardRoleService {
+    db: Arc<Database>,
+    matrix_client: Arc<MatrixClient>,
+    boards: Arc<BoardService>,
+    users: Arc<UserService>,
+}
+
+impl BoardRoleService {
+    pub fn new(
+        db: Arc<Database>,
+        matrix_client: Arc<MatrixClient>,
+        boards: Arc<BoardService>,
+        users: Arc<UserService>,
+    ) -> Self {
+        Self { db, matrix_client, boards, users }
+    }
+
+    /// A board's moderators, longest-serving first
+    pub async fn list_moderators(&self, board_name: &str, viewer: &User) -> AppResult<Vec<BoardModerator>> {
+        let board = self.boards.get_visible_board(board_name, Some(viewer)).await?;
+
+        let moderator_records = sqlx::query!(
+            "SELECT user_id, source, created_at FROM board_moderators WHERE board_id = ? ORDER BY created_at ASC",
+            board.id.to_string()
+        )
+        .fetch_all(self.db.pool())
+        .await?;
+
+        let mut moderators = Vec::with_capacity(moderator_records.len());
+        for record in moderator_records {
+            moderators.push(BoardModerator {
+                user: self.users.get_public_user(parse_id(&record.user_id)?, viewer).await?,
+                source: record.source,
+                created_at: parse_date(&record.created_at)?,
+            });
+        }
+
+        Ok(moderators)
+    }
+
+    /// Appoint a moderator (the owner and site admins)
+    pub async fn add_moderator(&self, board_name: &str, user_id: Uuid, owner: &User) -> AppResult<()> {
+        let board = self.boards.get_visible_board(board_name, Some(owner)).await?;
+        self.boards.require_owner(&board, owner)?;
+
+        if board.owner_id == Some(user_id) {
+            return Err(AppError::InvalidRequest("The owner already manages this board".to_string()));
+        }
+
+        let matrix_user_id = self.eligible_user(&board, user_id).await?;
+
+        let result = sqlx::query!(
+            "INSERT OR IGNORE INTO board_moderators (board_id, user_id, source, created_at) VALUES (?, ?, ?, ?)",
+            board.id.to_string(),
+            user_id.to_string(),
+            MANUAL_SOURCE,
+            Utc::now().to_rfc3339()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::InvalidRequest("Already a moderator of this board".to_string()));
+        }
+
+        self.set_power_level(&board, &matrix_user_id, MODERATOR_POWER_LEVEL).await;
+
+        Ok(())
+    }
+
+    /// Remove an appointed moderator: the owner and site admins can remove
+    /// anyone, moderators can step down
+    pub async fn remove_moderator(&self, board_name: &str, user_id: Uuid, actor: &User) -> AppResult<()> {
+        let board = self.boards.get_visible_board(board_name, Some(actor)).await?;
+        if actor.id != user_id {
+            self.boards.require_owner(&board, actor)?;
+        }
+
+        let result = sqlx::query!(
+            "DELETE FROM board_moderators WHERE board_id = ? AND user_id = ? AND source = ?",
+            board.id.to_string(),
+            user_id.to_string(),
+            MANUAL_SOURCE
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Not an appointed moderator of this board".to_string()));
+        }
+
+        // Roles mapped from identity provider groups keep their power level
+        let still_moderator = sqlx::query!(
+            "SELECT user_id FROM board_moderators WHERE board_id = ? AND user_id = ?",
+            board.id.to_string(),
+            user_id.to_string()
+        )
+        .fetch_optional(self.db.pool())
+        .await?
+        .is_some();
+
+        if !still_moderator {
+            let matrix_user_id = self.matrix_user_id(user_id).await?;
+            self.set_power_level(&board, &matrix_user_id, MEMBER_POWER_LEVEL).await;
+        }
+
+        Ok(())
+    }
+
+    /// Hand the board to someone else (the owner and site admins). The
+    /// previous owner stays on as a moderator.
+    pub async fn transfer_ownership(&self, board_name: &str, new_owner_id: Uuid, owner: &User) -> AppResult<Board> {
+        let mut board = self.boards.get_visible_board(board_name, Some(owner)).await?;
+        self.boards.require_owner(&board, owner)?;
+
+        if board.owner_id == Some(new_owner_id) {
+            return Err(AppError::InvalidRequest("Already the owner of this board".to_string()));
+        }
+
+        let new_owner_matrix_id = self.eligible_user(&board, new_owner_id).await?;
+        let previous_owner_id = board.owner_id;
+
+        let mut tx = self.db.pool().begin().await?;
+
+        sqlx::query!(
+            "UPDATE boards SET owner_id = ? WHERE id = ?",
+            new_owner_id.to_string(),
+            board.id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM board_moderators WHERE board_id = ? AND user_id = ? AND source = ?",
+            board.id.to_string(),
+            new_owner_id.to_string(),
+            MANUAL_SOURCE
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        if let Some(previous_owner_id) = previous_owner_id {
+            sqlx::query!(
+                "INSERT OR IGNORE INTO board_moderators (board_id, user_id, source, created_at) VALUES (?, ?, ?, ?)",
+                board.id.to_string(),
+                previous_owner_id.to_string(),
+                MANUAL_SOURCE,
+                Utc::now().to_rfc3339()
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        self.set_power_level(&board, &new_owner_matrix_id, OWNER_POWER_LEVEL).await;
+        if let Some(previous_owner_id) = previous_owner_id {
+            let previous_owner_matrix_id = self.matrix_user_id(previous_owner_id).await?;
+            self.set_power_level(&board, &previous_owner_matrix_id, MODERATOR_POWER_LEVEL).await;
+        }
+
+        board.owner_id = Some(new_owner_id);
+        Ok(board)
+    }
+
+    /// Registered accounts can hold roles; on private boards only members.
+    /// Returns the user's Matrix ID.
+    async fn eligible_user(&self, board: &Board, user_id: Uuid) -> AppResult<String> {
+        let user_record = sqlx::query!(
+            "SELECT matrix_user_id, is_anonymous FROM users WHERE id = ?",
+            user_id.to_string()
+        )
+        .fetch_optional(self.db.pool())
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+        if user_record.is_anonymous {
+            return Err(AppError::InvalidRequest("Anonymous accounts can't own or moderate boards".to_string()));
+        }
+
+        if board.is_private && !self.boards.is_member(board.id, user_id).await? {
+            return Err(AppError::InvalidRequest("User is not a member of this board".to_string()));
+        }
+
+        Ok(user_record.matrix_user_id)
+    }
+
+    async fn matrix_user_id(&self, user_id: Uuid) -> AppResult<String> {
+        let matrix_user_id = sqlx::query_scalar!("SELECT matrix_user_id FROM users WHERE id = ?", user_id.to_string())
+            .fetch_one(self.db.pool())
+            .await?;
+
+        Ok(matrix_user_id)
+    }
+
+    /// Roles don't depend on Matrix, so failures are logged
+    async fn set_power_level(&self, board: &Board, matrix_user_id: &str, level: i64) {
+        if let Err(e) = self.matrix_client.set_power_level(&board.matrix_room_id, matrix_user_id, level).await {
+            warn!("Failed to set power level of {} in Matrix room of board {}: {}", matrix_user_id, board.name, e);
+        }
+    }
+}
+
+fn parse_id(value: &str) -> AppResult<Uuid> {
+    Uuid::parse_str(value).map_err(|e| AppError::Internal(format!("Invalid ID: {}", e)))
+}
+
+fn parse_date(value: &str) -> AppResult<DateTime<Utc>> {
+    Ok(DateTime::parse_from_rfc3339(value)
+        .map_err(|e| AppError::Internal(format!("Invalid date: {}", e)))?
+        .with_timezone(&Utc))
+}
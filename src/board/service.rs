@@ -1,26 +1,58 @@
 use chrono::Utc;
 use std::sync::Arc;
+use tracing::warn;
 use uuid::Uuid;
 
+use crate::board::roles::{MANUAL_SOURCE, OWNER_POWER_LEVEL};
+use crate::board::{policy, settings};
+use crate::core::config::{BoardCreationMode, BoardsConfig};
 use crate::core::error::{AppError, AppResult};
+use crate::challenge::service::{ChallengePurpose, ChallengeService, MAX_DIFFICULTY};
 use crate::core::types::{
-    Board, Thread, Post, CreateBoardRequest, CreateThreadRequest, CreatePostRequest
+    Board, Thread, Post, User, CreateBoardRequest, CreateThreadRequest, CreatePostRequest,
+    DeletedAuthorContent, ContentRating, BoardSettings, UpdateBoardRequest
 };
+use crate::crypto::signing::SigningService;
 use crate::matrix::client::MatrixClient;
+use crate::moderation::content_filters::ContentFilterService;
 use crate::storage::database::Database;
 
+/// Longest a board title can be
+const MAX_BOARD_TITLE_CHARS: usize = 100;
+
+/// Longest a board description can be
+const MAX_BOARD_DESCRIPTION_CHARS: usize = 1000;
+
 pub struct BoardService {
     db: Arc<Database>,
     matrix_client: Arc<MatrixClient>,
+    signing: Arc<SigningService>,
+    challenges: Arc<ChallengeService>,
+    filters: Arc<ContentFilterService>,
+    config: BoardsConfig,
 }
 
 impl BoardService {
-    pub fn new(db: Arc<Database>, matrix_client: Arc<MatrixClient>) -> Self {
-        Self { db, matrix_client }
+    pub fn new(
+        db: Arc<Database>,
+        matrix_client: Arc<MatrixClient>,
+        signing: Arc<SigningService>,
+        challenges: Arc<ChallengeService>,
+        filters: Arc<ContentFilterService>,
+        config: BoardsConfig,
+    ) -> Self {
+        Self { db, matrix_client, signing, challenges, filters, config }
     }
 
-    /// Create a new board
-    pub async fn create_board(&self, request: CreateBoardRequest, creator_id: Uuid) -> AppResult<Board> {
+    /// Create a new board, owned by its creator
+    pub async fn create_board(&self, request: CreateBoardRequest, creator: &User) -> AppResult<Board> {
+        let creator_id = creator.id;
+
+        policy::validate_name(&request.name, &self.config, creator.is_admin)?;
+        let title = validate_title(&request.title)?;
+        let description = request.description.as_deref().map(validate_description).transpose()?.flatten();
+        self.check_creation_policy(creator).await?;
+
         // Check if board name is already taken
         let existing_board = sqlx::query!(
             "SELECT id FROM boards WHERE name = ?",
@@ -33,50 +65,200 @@ impl BoardService {
             return Err(AppError::InvalidRequest("Board name already taken".to_string()));
         }
 
+        if request.is_encrypted && !request.is_private {
+            return Err(AppError::InvalidRequest("Only private boards can be encrypted".to_string()));
+        }
+
+        let board_settings = settings::apply_update(BoardSettings::default(), request.settings)?;
+        let topic = settings::room_topic(description.as_deref(), board_settings.rules.as_deref());
+
         // Create Matrix room for the board
         let matrix_room_id = self.matrix_client
-            .create_room(&request.title, request.description.as_deref(), false)
+            .create_board_room(&title, topic.as_deref(), request.is_private, request.is_encrypted)
             .await?;
 
         let board_id = Uuid::new_v4();
         let now = Utc::now();
+        let unlimited = creator.is_admin || self.config.boards_per_user <= 0;
 
-        // Insert board into database
-        sqlx::query!(
+        let mut tx = self.db.pool().begin().await?;
+
+        // Parallel requests all pass the checks above, so the quota is checked
+        // again as part of the insert. A request that loses out, or to a board
+        // of the same name, abandons the room it created.
+        let result = sqlx::query!(
             r#"
-            INSERT INTO boards (id, name, title, description, matrix_room_id, is_nsfw, is_private, created_at, created_by)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            INSERT INTO boards (id, name, title, description, matrix_room_id, is_nsfw, is_private, is_encrypted, created_at, created_by, owner_id,
+                                rules, max_post_length, require_image_on_op, allow_anonymous, force_anonymous, post_cooldown_seconds, allowed_file_types)
+            SELECT ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?
+            WHERE ? OR (SELECT COUNT(*) FROM boards WHERE owner_id = ?) < ?
             "#,
             board_id.to_string(),
             request.name,
-            request.title,
-            request.description,
+            title,
+            description,
             matrix_room_id,
             request.is_nsfw,
             request.is_private,
+            request.is_encrypted,
             now.to_rfc3339(),
-            creator_id.to_string()
+            creator_id.to_string(),
+            creator_id.to_string(),
+            board_settings.rules,
+            board_settings.max_post_length,
+            board_settings.require_image_on_op,
+            board_settings.allow_anonymous,
+            board_settings.force_anonymous,
+            board_settings.post_cooldown_seconds,
+            settings::join_file_types(&board_settings.allowed_file_types),
+            unlimited,
+            creator_id.to_string(),
+            self.config.boards_per_user
         )
-        .execute(self.db.pool())
+        .execute(&mut *tx)
+        .await;
+
+        let rejection = match result {
+            Ok(result) if result.rows_affected() > 0 => None,
+            Ok(_) => Some(AppError::Authorization(format!(
+                "You can own at most {} boards",
+                self.config.boards_per_user
+            ))),
+            Err(sqlx::Error::Database(ref e)) if e.is_unique_violation() => {
+                Some(AppError::InvalidRequest("Board name already taken".to_string()))
+            }
+            Err(e) => Some(e.into()),
+        };
+
+        if let Some(rejection) = rejection {
+            if let Err(e) = self.matrix_client.leave_room(&matrix_room_id).await {
+                warn!("Failed to leave Matrix room of abandoned board {}: {}", request.name, e);
+            }
+            return Err(rejection);
+        }
+
+        sqlx::query!(
+            "INSERT INTO board_members (board_id, user_id, joined_at) VALUES (?, ?, ?)",
+            board_id.to_string(),
+            creator_id.to_string(),
+            now.to_rfc3339()
+        )
+        .execute(&mut *tx)
         .await?;
 
-        Ok(Board {
+        tx.commit().await?;
+
+        let board = Board {
             id: board_id,
             name: request.name,
-            title: request.title,
-            description: request.description,
+            title,
+            description,
             matrix_room_id,
             is_nsfw: request.is_nsfw,
             is_private: request.is_private,
+            is_encrypted: request.is_encrypted,
             created_at: now,
             created_by: creator_id,
-        })
+            owner_id: Some(creator_id),
+            settings: board_settings,
+        };
+
+        // Nobody can join an invite-only room uninvited, not even its creator
+        if board.is_private {
+            self.invite_to_room(&board, creator_id).await;
+        }
+
+        Ok(board)
     }
 
-    /// Get all boards
-    pub async fn get_boards(&self) -> AppResult<Vec<Board>> {
+    /// Admins can always create boards. Other registered accounts can when
+    /// `BOARD_CREATION` allows it, once old and active enough and while
+    /// under their quota; anonymous accounts never can.
+    async fn check_creation_policy(&self, creator: &User) -> AppResult<()> {
+        if creator.is_admin {
+            return Ok(());
+        }
+
+        if creator.is_anonymous {
+            return Err(AppError::Authorization("Anonymous accounts can't create boards".to_string()));
+        }
+
+        if self.config.creation_mode == BoardCreationMode::Admins {
+            return Err(AppError::Authorization("Only admins can create boards".to_string()));
+        }
+
+        let account_age = Utc::now() - creator.created_at;
+        if account_age < chrono::Duration::days(self.config.min_account_age_days) {
+            return Err(AppError::Authorization(format!(
+                "Accounts must be {} days old to create boards",
+                self.config.min_account_age_days
+            )));
+        }
+
+        let karma = sqlx::query_scalar!(
+            r#"
+            SELECT (SELECT COUNT(*) FROM threads WHERE created_by = ?) + (SELECT COUNT(*) FROM posts WHERE created_by = ?) AS "karma!: i64"
+            "#,
+            creator.id.to_string(),
+            creator.id.to_string()
+        )
+        .fetch_one(self.db.pool())
+        .await?;
+
+        if karma < self.config.min_karma {
+            return Err(AppError::Authorization(format!(
+                "Write at least {} threads or replies before creating a board",
+                self.config.min_karma
+            )));
+        }
+
+        if self.config.boards_per_user > 0 {
+            let owned = sqlx::query_scalar!(
+                r#"SELECT COUNT(*) AS "count!: i64" FROM boards WHERE owner_id = ?"#,
+                creator.id.to_string()
+            )
+            .fetch_one(self.db.pool())
+            .await?;
+
+            if owned >= self.config.boards_per_user {
+                return Err(AppError::Authorization(format!(
+                    "You can own at most {} boards",
+                    self.config.boards_per_user
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get all boards the viewer can see: public boards, and private boards
+    /// they are a member of (all of them for site admins). NSFW boards are
+    /// only listed for viewers who opted into them; `rating` narrows the list
+    /// to SFW or NSFW boards.
+    pub async fn get_boards(&self, viewer: Option<&User>, age_confirmed: bool, rating: Option<ContentRating>) -> AppResult<Vec<Board>> {
+        let viewer_id = viewer.map(|viewer| viewer.id.to_string());
+        let viewer_is_admin = viewer.is_some_and(|viewer| viewer.is_admin);
+
+        let shows_nsfw = self.shows_nsfw(viewer, age_confirmed).await?;
+        if rating == Some(ContentRating::Nsfw) && !shows_nsfw {
+            return Err(nsfw_gate());
+        }
+        let include_sfw = rating != Some(ContentRating::Nsfw);
+        let include_nsfw = shows_nsfw && rating != Some(ContentRating::Sfw);
+
         let board_records = sqlx::query!(
-            "SELECT id, name, title, description, matrix_room_id, is_nsfw, is_private, created_at, created_by FROM boards ORDER BY created_at DESC"
+            r#"
+            SELECT id, name, title, description, matrix_room_id, is_nsfw, is_private, is_encrypted, created_at, created_by, owner_id,
+                   rules, max_post_length, require_image_on_op, allow_anonymous, force_anonymous, post_cooldown_seconds, allowed_file_types
+            FROM boards
+            WHERE (is_private = FALSE OR ? OR id IN (SELECT board_id FROM board_members WHERE user_id = ?))
+            AND ((is_nsfw = FALSE AND ?) OR (is_nsfw = TRUE AND ?))
+            ORDER BY created_at DESC
+            "#,
+            viewer_is_admin,
+            viewer_id,
+            include_sfw,
+            include_nsfw
         )
         .fetch_all(self.db.pool())
         .await?;
@@ -93,11 +275,25 @@ impl BoardService {
                     matrix_room_id: record.matrix_room_id,
                     is_nsfw: record.is_nsfw,
                     is_private: record.is_private,
+                    is_encrypted: record.is_encrypted,
                     created_at: chrono::DateTime::parse_from_rfc3339(&record.created_at)
                         .map_err(|e| AppError::Internal(format!("Invalid date: {}", e)))?
                         .with_timezone(&Utc),
                     created_by: Uuid::parse_str(&record.created_by)
                         .map_err(|e| AppError::Internal(format!("Invalid user ID: {}", e)))?,
+                    owner_id: record.owner_id.as_deref()
+                        .map(Uuid::parse_str)
+                        .transpose()
+                        .map_err(|e| AppError::Internal(format!("Invalid user ID: {}", e)))?,
+                    settings: BoardSettings {
+                        rules: record.rules,
+                        max_post_length: record.max_post_length,
+                        require_image_on_op: record.require_image_on_op,
+                        allow_anonymous: record.allow_anonymous,
+                        force_anonymous: record.force_anonymous,
+                        post_cooldown_seconds: record.post_cooldown_seconds,
+                        allowed_file_types: settings::parse_file_types(record.allowed_file_types.as_deref()),
+                    },
                 })
             })
             .collect::<AppResult<Vec<_>>>()?;
@@ -105,10 +301,70 @@ impl BoardService {
         Ok(boards)
     }
 
-    /// Get a board by name
+    /// Get a board by name, if the viewer may see it
+    pub async fn get_visible_board(&self, name: &str, viewer: Option<&User>) -> AppResult<Board> {
+        let board = self.get_board(name).await?;
+        self.check_access(&board, viewer).await?;
+        Ok(board)
+    }
+
+    /// Get a board by name, if the viewer may see it and read its contents
+    pub async fn get_readable_board(&self, name: &str, viewer: Option<&User>, age_confirmed: bool) -> AppResult<Board> {
+        let board = self.get_visible_board(name, viewer).await?;
+        self.check_nsfw(&board, viewer, age_confirmed).await?;
+        Ok(board)
+    }
+
+    /// Whether the viewer opted into NSFW content: accounts through their
+    /// content preferences, visitors by confirming their age
+    pub async fn shows_nsfw(&self, viewer: Option<&User>, age_confirmed: bool) -> AppResult<bool> {
+        let Some(viewer) = viewer else {
+            return Ok(age_confirmed);
+        };
+
+        let show_nsfw = sqlx::query_scalar!("SELECT show_nsfw FROM users WHERE id = ?", viewer.id.to_string())
+            .fetch_one(self.db.pool())
+            .await?;
+
+        Ok(show_nsfw)
+    }
+
+    /// The contents of NSFW boards are only shown to viewers who opted in
+    pub async fn check_nsfw(&self, board: &Board, viewer: Option<&User>, age_confirmed: bool) -> AppResult<()> {
+        if board.is_nsfw && !self.shows_nsfw(viewer, age_confirmed).await? {
+            return Err(nsfw_gate());
+        }
+
+        Ok(())
+    }
+
+    /// Private boards are only visible to members and site admins. Others
+    /// get the same error as for a board that doesn't exist.
+    pub async fn check_access(&self, board: &Board, viewer: Option<&User>) -> AppResult<()> {
+        if !board.is_private {
+            return Ok(());
+        }
+
+        let allowed = match viewer {
+            Some(viewer) => viewer.is_admin || self.is_member(board.id, viewer.id).await?,
+            None => false,
+        };
+
+        if !allowed {
+            return Err(AppError::NotFound("Board not found".to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// Get a board by name, without checking access
     pub async fn get_board(&self, name: &str) -> AppResult<Board> {
         let board_record = sqlx::query!(
-            "SELECT id, name, title, description, matrix_room_id, is_nsfw, is_private, created_at, created_by FROM boards WHERE name = ?",
+            r#"
+            SELECT id, name, title, description, matrix_room_id, is_nsfw, is_private, is_encrypted, created_at, created_by, owner_id,
+                   rules, max_post_length, require_image_on_op, allow_anonymous, force_anonymous, post_cooldown_seconds, allowed_file_types
+            FROM boards WHERE name = ?
+            "#,
             name
         )
         .fetch_optional(self.db.pool())
@@ -124,90 +380,518 @@ impl BoardService {
             matrix_room_id: board_record.matrix_room_id,
             is_nsfw: board_record.is_nsfw,
             is_private: board_record.is_private,
+            is_encrypted: board_record.is_encrypted,
             created_at: chrono::DateTime::parse_from_rfc3339(&board_record.created_at)
                 .map_err(|e| AppError::Internal(format!("Invalid date: {}", e)))?
                 .with_timezone(&Utc),
             created_by: Uuid::parse_str(&board_record.created_by)
                 .map_err(|e| AppError::Internal(format!("Invalid user ID: {}", e)))?,
+            owner_id: board_record.owner_id.as_deref()
+                .map(Uuid::parse_str)
+                .transpose()
+                .map_err(|e| AppError::Internal(format!("Invalid user ID: {}", e)))?,
+            settings: BoardSettings {
+                rules: board_record.rules,
+                max_post_length: board_record.max_post_length,
+                require_image_on_op: board_record.require_image_on_op,
+                allow_anonymous: board_record.allow_anonymous,
+                force_anonymous: board_record.force_anonymous,
+                post_cooldown_seconds: board_record.post_cooldown_seconds,
+                allowed_file_types: settings::parse_file_types(board_record.allowed_file_types.as_deref()),
+            },
         })
     }
 
+    /// Change a board's title, description, NSFW flag and settings. Only
+    /// its owner and site admins can. The title, description and rules are
+    /// mirrored to the Matrix room's name and topic.
+    pub async fn update_board(&self, name: &str, request: UpdateBoardRequest, user: &User) -> AppResult<Board> {
+        let mut board = self.get_visible_board(name, Some(user)).await?;
+        self.require_owner(&board, user)?;
+
+        let old_title = board.title.clone();
+        let old_topic = settings::room_topic(board.description.as_deref(), board.settings.rules.as_deref());
+
+        if let Some(title) = request.title {
+            board.title = validate_title(&title)?;
+        }
+
+        if let Some(description) = request.description {
+            board.description = validate_description(&description)?;
+        }
+
+        if let Some(is_nsfw) = request.is_nsfw {
+            board.is_nsfw = is_nsfw;
+        }
+
+        board.settings = settings::apply_update(board.settings, request.settings)?;
+
+        sqlx::query!(
+            r#"
+            UPDATE boards SET title = ?, description = ?, is_nsfw = ?, rules = ?, max_post_length = ?, require_image_on_op = ?,
+                              allow_anonymous = ?, force_anonymous = ?, post_cooldown_seconds = ?, allowed_file_types = ?
+            WHERE id = ?
+            "#,
+            board.title,
+            board.description,
+            board.is_nsfw,
+            board.settings.rules,
+            board.settings.max_post_length,
+            board.settings.require_image_on_op,
+            board.settings.allow_anonymous,
+            board.settings.force_anonymous,
+            board.settings.post_cooldown_seconds,
+            settings::join_file_types(&board.settings.allowed_file_types),
+            board.id.to_string()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        // The board is updated either way, the room catches up on the next change
+        if board.title != old_title {
+            if let Err(e) = self.matrix_client.set_room_name(&board.matrix_room_id, &board.title).await {
+                warn!("Failed to rename Matrix room of board {}: {}", board.name, e);
+            }
+        }
+
+        let topic = settings::room_topic(board.description.as_deref(), board.settings.rules.as_deref());
+        if topic != old_topic {
+            if let Err(e) = self.matrix_client.set_room_topic(&board.matrix_room_id, topic.as_deref()).await {
+                warn!("Failed to set topic of Matrix room of board {}: {}", board.name, e);
+            }
+        }
+
+        Ok(board)
+    }
+
+    /// Delete a board with all its threads, posts, members, invite links,
+    /// filters and moderation log.
+    /// Only its owner and site admins can.
+    pub async fn delete_board(&self, name: &str, user: &User) -> AppResult<()> {
+        let board = self.get_visible_board(name, Some(user)).await?;
+        self.require_owner(&board, user)?;
+
+        let board_id = board.id.to_string();
+        let mut tx = self.db.pool().begin().await?;
+
+        // Replies can point at each other, so drop those links first
+        sqlx::query!("UPDATE posts SET reply_to = NULL WHERE board_id = ?", board_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query!("DELETE FROM posts WHERE board_id = ?", board_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query!("DELETE FROM threads WHERE board_id = ?", board_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query!(
+            "DELETE FROM invite_link_requests WHERE link_id IN (SELECT id FROM invite_links WHERE board_id = ?)",
+            board_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!("DELETE FROM invite_links WHERE board_id = ?", board_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query!("DELETE FROM board_join_requests WHERE board_id = ?", board_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query!("DELETE FROM board_members WHERE board_id = ?", board_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query!("DELETE FROM board_moderators WHERE board_id = ?", board_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query!("DELETE FROM content_filters WHERE board_id = ?", board_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query!("DELETE FROM moderation_log WHERE board_id = ?", board_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query!("DELETE FROM boards WHERE id = ?", board_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        if let Err(e) = self.matrix_client.leave_room(&board.matrix_room_id).await {
+            warn!("Failed to leave Matrix room of deleted board {}: {}", board.name, e);
+        }
+
+        Ok(())
+    }
+
+    /// Only the board's owner and site admins may change its settings,
+    /// moderators and ownership, or delete it
+    pub fn require_owner(&self, board: &Board, user: &User) -> AppResult<()> {
+        if !user.is_admin && board.owner_id != Some(user.id) {
+            return Err(AppError::Authorization("Only the board's owner or an admin can do this".to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// Get a board by ID
+    pub async fn get_board_by_id(&self, board_id: Uuid) -> AppResult<Board> {
+        let name = sqlx::query_scalar!("SELECT name FROM boards WHERE id = ?", board_id.to_string())
+            .fetch_optional(self.db.pool())
+            .await?
+            .ok_or_else(|| AppError::NotFound("Board not found".to_string()))?;
+
+        self.get_board(&name).await
+    }
+
+    /// Whether the user may manage the board's members and invite links:
+    /// its owner, its moderators and site admins
+    pub async fn can_manage(&self, board: &Board, user: &User) -> AppResult<bool> {
+        if user.is_admin {
+            return Ok(true);
+        }
+
+        self.is_owner_or_moderator(board, user.id).await
+    }
+
+    /// Whether the user owns or moderates the board
+    pub async fn is_owner_or_moderator(&self, board: &Board, user_id: Uuid) -> AppResult<bool> {
+        if board.owner_id == Some(user_id) {
+            return Ok(true);
+        }
+
+        let moderator = sqlx::query!(
+            "SELECT user_id FROM board_moderators WHERE board_id = ? AND user_id = ?",
+            board.id.to_string(),
+            user_id.to_string()
+        )
+        .fetch_optional(self.db.pool())
+        .await?;
+
+        Ok(moderator.is_some())
+    }
+
+    /// Whether the user is a member of the board
+    pub async fn is_member(&self, board_id: Uuid, user_id: Uuid) -> AppResult<bool> {
+        let member = sqlx::query!(
+            "SELECT user_id FROM board_members WHERE board_id = ? AND user_id = ?",
+            board_id.to_string(),
+            user_id.to_string()
+        )
+        .fetch_optional(self.db.pool())
+        .await?;
+
+        Ok(member.is_some())
+    }
+
+    /// Add a member and invite them to the board's Matrix room
+    pub async fn add_member(&self, board: &Board, user_id: Uuid) -> AppResult<()> {
+        let tx = self.db.pool().begin().await?;
+        self.add_member_with(tx, board, user_id).await
+    }
+
+    /// Like `add_member`, committing `tx` along with the membership, so the
+    /// caller's writes only stick if the user gets in
+    pub async fn add_member_with(
+        &self,
+        mut tx: sqlx::Transaction<'static, sqlx::Sqlite>,
+        board: &Board,
+        user_id: Uuid,
+    ) -> AppResult<()> {
+        let result = sqlx::query!(
+            "INSERT OR IGNORE INTO board_members (board_id, user_id, joined_at) VALUES (?, ?, ?)",
+            board.id.to_string(),
+            user_id.to_string(),
+            Utc::now().to_rfc3339()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::InvalidRequest("Already a member of this board".to_string()));
+        }
+
+        tx.commit().await?;
+
+        self.invite_to_room(board, user_id).await;
+
+        Ok(())
+    }
+
+    /// Invite a user's Matrix account to the board's room. Membership doesn't
+    /// depend on Matrix, so failures are logged.
+    async fn invite_to_room(&self, board: &Board, user_id: Uuid) {
+        let matrix_user_id = match sqlx::query_scalar!("SELECT matrix_user_id FROM users WHERE id = ?", user_id.to_string())
+            .fetch_one(self.db.pool())
+            .await
+        {
+            Ok(matrix_user_id) => matrix_user_id,
+            Err(e) => {
+                warn!("Failed to look up Matrix ID of {}: {}", user_id, e);
+                return;
+            }
+        };
+
+        if let Err(e) = self.matrix_client.invite_user(&board.matrix_room_id, &matrix_user_id).await {
+            warn!("Failed to invite {} to Matrix room of board {}: {}", matrix_user_id, board.name, e);
+        }
+    }
+
+    /// Remove a member and kick them from the board's Matrix room. The
+    /// board's owner can't be removed; moderators lose their appointed role.
+    pub async fn remove_member(&self, board: &Board, user_id: Uuid, reason: &str) -> AppResult<()> {
+        if board.owner_id == Some(user_id) {
+            return Err(AppError::InvalidRequest("The board's owner can't be removed".to_string()));
+        }
+
+        let result = sqlx::query!(
+            "DELETE FROM board_members WHERE board_id = ? AND user_id = ?",
+            board.id.to_string(),
+            user_id.to_string()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Not a member of this board".to_string()));
+        }
+
+        sqlx::query!(
+            "DELETE FROM board_moderators WHERE board_id = ? AND user_id = ? AND source = 'manual'",
+            board.id.to_string(),
+            user_id.to_string()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        let matrix_user_id = sqlx::query_scalar!("SELECT matrix_user_id FROM users WHERE id = ?", user_id.to_string())
+            .fetch_one(self.db.pool())
+            .await?;
+
+        if let Err(e) = self.matrix_client.kick_user(&board.matrix_room_id, &matrix_user_id, Some(reason)).await {
+            warn!("Failed to remove {} from Matrix room of board {}: {}", matrix_user_id, board.name, e);
+        }
+
+        Ok(())
+    }
+
+    /// Challenge difficulty for anonymous threads on a board, 0 if none is needed
+    pub async fn thread_challenge_difficulty(&self, board_name: &str) -> AppResult<(Uuid, u8)> {
+        let board_record = sqlx::query!(
+            "SELECT id, challenge_difficulty FROM boards WHERE name = ?",
+            board_name
+        )
+        .fetch_optional(self.db.pool())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Board not found".to_string()))?;
+
+        let board_id = Uuid::parse_str(&board_record.id)
+            .map_err(|e| AppError::Internal(format!("Invalid board ID: {}", e)))?;
+        let difficulty = board_record.challenge_difficulty
+            .map(|difficulty| difficulty.clamp(0, MAX_DIFFICULTY as i64) as u8)
+            .unwrap_or_else(|| self.challenges.default_difficulty());
+
+        Ok((board_id, difficulty))
+    }
+
+    /// Set the challenge difficulty for anonymous threads on a board.
+    /// `None` falls back to `CHALLENGE_DIFFICULTY`, zero turns it off.
+    pub async fn set_thread_challenge_difficulty(&self, board_name: &str, difficulty: Option<u8>) -> AppResult<()> {
+        if difficulty.is_some_and(|difficulty| difficulty > MAX_DIFFICULTY) {
+            return Err(AppError::InvalidRequest(format!("Difficulty can be at most {}", MAX_DIFFICULTY)));
+        }
+
+        let difficulty = difficulty.map(|difficulty| difficulty as i64);
+        let result = sqlx::query!(
+            "UPDATE boards SET challenge_difficulty = ? WHERE name = ?",
+            difficulty,
+            board_name
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Board not found".to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// Set what happens to a deleted account's content on a board
+    pub async fn set_deleted_author_content(&self, board_name: &str, policy: DeletedAuthorContent) -> AppResult<()> {
+        let result = sqlx::query!(
+            "UPDATE boards SET deleted_author_content = ? WHERE name = ?",
+            policy.as_str(),
+            board_name
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Board not found".to_string()));
+        }
+
+        Ok(())
+    }
+
     /// Create a new thread in a board
-    pub async fn create_thread(&self, board_name: &str, request: CreateThreadRequest, creator_id: Uuid) -> AppResult<Thread> {
+    pub async fn create_thread(&self, board_name: &str, request: CreateThreadRequest, creator: &User) -> AppResult<Thread> {
         // Get board
-        let board = self.get_board(board_name).await?;
+        let board = self.get_readable_board(board_name, Some(creator), false).await?;
+        self.check_posting_rules(&board, creator, &request.content, request.image_url.as_deref(), true).await?;
+
+        // Anonymous posters prove they aren't a script flooding the board,
+        // before anything they send can end up in the moderation log
+        if creator.is_anonymous {
+            let (_, difficulty) = self.thread_challenge_difficulty(board_name).await?;
+            self.challenges
+                .verify(request.challenge.as_ref(), ChallengePurpose::Thread(board.id), difficulty)
+                .await?;
+        }
+
+        let screened = self.filters
+            .screen(board.id, creator.id, request.title.as_deref(), &request.content, request.image_url.as_deref())
+            .await?;
 
         // Post to Matrix room
         let matrix_event_id = if let Some(ref image_url) = request.image_url {
             self.matrix_client
-                .send_message_with_image(&board.matrix_room_id, &request.content, image_url)
+                .send_message_with_image(&board.matrix_room_id, &screened.content, image_url)
                 .await?
         } else {
             self.matrix_client
-                .send_message(&board.matrix_room_id, &request.content)
+                .send_message(&board.matrix_room_id, &screened.content)
                 .await?
         };
 
-        let thread_id = Uuid::new_v4();
-        let now = Utc::now();
+        let mut thread = Thread {
+            id: Uuid::new_v4(),
+            board_id: board.id,
+            title: screened.title,
+            content: screened.content,
+            image_url: request.image_url,
+            matrix_event_id,
+            is_pinned: false,
+            is_locked: false,
+            is_spoiler: request.is_spoiler,
+            created_at: Utc::now(),
+            created_by: creator.id,
+            reply_count: 0,
+            last_reply_at: None,
+            signature: None,
+            signing_key_id: None,
+            author_hidden: board.settings.force_anonymous,
+        };
+
+        // Sign the canonical form so later tampering in the database is detectable
+        let signature = self.signing.sign_thread(&thread);
+        thread.signature = Some(signature.signature);
+        thread.signing_key_id = Some(signature.key_id);
 
         // Insert thread into database
         sqlx::query!(
             r#"
-            INSERT INTO threads (id, board_id, title, content, image_url, matrix_event_id, created_at, created_by)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            INSERT INTO threads (id, board_id, title, content, image_url, matrix_event_id, is_spoiler, created_at, created_by, signature, signing_key_id, author_hidden)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
-            thread_id.to_string(),
-            board.id.to_string(),
-            request.title,
-            request.content,
-            request.image_url,
-            matrix_event_id,
-            now.to_rfc3339(),
-            creator_id.to_string()
+            thread.id.to_string(),
+            thread.board_id.to_string(),
+            thread.title,
+            thread.content,
+            thread.image_url,
+            thread.matrix_event_id,
+            thread.is_spoiler,
+            thread.created_at.to_rfc3339(),
+            thread.created_by.to_string(),
+            thread.signature,
+            thread.signing_key_id,
+            thread.author_hidden
         )
         .execute(self.db.pool())
         .await?;
 
-        Ok(Thread {
-            id: thread_id,
-            board_id: board.id,
-            title: request.title,
-            content: request.content,
-            image_url: request.image_url,
-            matrix_event_id,
-            is_pinned: false,
-            is_locked: false,
-            created_at: now,
-            created_by: creator_id,
-            reply_count: 0,
-            last_reply_at: None,
-        })
+        self.filters
+            .record_hits(board.id, creator.id, Some(thread.id), None, &screened.hits, &request.content)
+            .await?;
+
+        thread.created_by = thread.public_author();
+        Ok(thread)
+    }
+
+    /// Check a new thread or reply against the board's settings: who may
+    /// post, what the post may contain and how soon after the poster's last
+    async fn check_posting_rules(&self, board: &Board, creator: &User, content: &str, image_url: Option<&str>, is_thread: bool) -> AppResult<()> {
+        if creator.is_anonymous && !board.settings.allow_anonymous {
+            return Err(AppError::Authorization("This board doesn't allow anonymous posting".to_string()));
+        }
+
+        settings::check_post(&board.settings, content, image_url, is_thread)?;
+
+        if board.settings.post_cooldown_seconds > 0 {
+            let last_posted_at = sqlx::query_scalar!(
+                r#"
+                SELECT MAX(created_at) AS "created_at?: String" FROM (
+                    SELECT created_at FROM threads WHERE board_id = ? AND created_by = ?
+                    UNION ALL
+                    SELECT created_at FROM posts WHERE board_id = ? AND created_by = ?
+                )
+                "#,
+                board.id.to_string(),
+                creator.id.to_string(),
+                board.id.to_string(),
+                creator.id.to_string()
+            )
+            .fetch_one(self.db.pool())
+            .await?;
+
+            if let Some(last_posted_at) = last_posted_at {
+                let last_posted_at = chrono::DateTime::parse_from_rfc3339(&last_posted_at)
+                    .map_err(|e| AppError::Internal(format!("Invalid date: {}", e)))?
+                    .with_timezone(&Utc);
+
+                if last_posted_at + chrono::Duration::seconds(board.settings.post_cooldown_seconds) > Utc::now() {
+                    return Err(AppError::RateLimit);
+                }
+            }
+        }
+
+        Ok(())
     }
 
     /// Get threads in a board
-    pub async fn get_threads(&self, board_name: &str, limit: Option<i64>, offset: Option<i64>) -> AppResult<Vec<Thread>> {
-        let board = self.get_board(board_name).await?;
+    pub async fn get_threads(&self, board_name: &str, viewer: Option<&User>, age_confirmed: bool, limit: Option<i64>, offset: Option<i64>) -> AppResult<Vec<Thread>> {
+        let board = self.get_readable_board(board_name, viewer, age_confirmed).await?;
         let limit = limit.unwrap_or(50).min(100); // Max 100 threads per request
         let offset = offset.unwrap_or(0);
+        // Threads by users the viewer blocked are left out
+        let viewer_id = viewer.map(|viewer| viewer.id.to_string());
 
         let thread_records = sqlx::query!(
             r#"
-            SELECT id, board_id, title, content, image_url, matrix_event_id, is_pinned, is_locked, 
-                   created_at, created_by, reply_count, last_reply_at
+            SELECT id, board_id, title, content, image_url, matrix_event_id, is_pinned, is_locked, is_spoiler,
+                   created_at, created_by, reply_count, last_reply_at, signature, signing_key_id, author_hidden
             FROM threads 
             WHERE board_id = ? 
+            AND (author_hidden = TRUE OR created_by NOT IN (SELECT blocked_id FROM blocks WHERE blocker_id = ?))
             ORDER BY is_pinned DESC, COALESCE(last_reply_at, created_at) DESC
             LIMIT ? OFFSET ?
             "#,
             board.id.to_string(),
+            viewer_id,
             limit,
             offset
         )
         .fetch_all(self.db.pool())
         .await?;
 
-        let threads = thread_records
+        let mut threads = thread_records
             .into_iter()
             .map(|record| {
                 Ok(Thread {
@@ -221,6 +905,7 @@ impl BoardService {
                     matrix_event_id: record.matrix_event_id,
                     is_pinned: record.is_pinned,
                     is_locked: record.is_locked,
+                    is_spoiler: record.is_spoiler,
                     created_at: chrono::DateTime::parse_from_rfc3339(&record.created_at)
                         .map_err(|e| AppError::Internal(format!("Invalid date: {}", e)))?
                         .with_timezone(&Utc),
@@ -233,19 +918,40 @@ impl BoardService {
                             .unwrap()
                             .with_timezone(&Utc)
                     }),
+                    signature: record.signature,
+                    signing_key_id: record.signing_key_id,
+                    author_hidden: record.author_hidden,
                 })
             })
             .collect::<AppResult<Vec<_>>>()?;
 
+        for thread in &mut threads {
+            thread.created_by = thread.public_author();
+        }
+
         Ok(threads)
     }
 
-    /// Get a specific thread
+    /// Get a specific thread, if the viewer may see its board and read its contents
+    pub async fn get_visible_thread(&self, thread_id: Uuid, viewer: Option<&User>, age_confirmed: bool) -> AppResult<Thread> {
+        let mut thread = self.get_thread(thread_id).await?;
+        let board = self.get_board_by_id(thread.board_id).await?;
+
+        // Threads of hidden boards don't exist either
+        self.check_access(&board, viewer).await
+            .map_err(|_| AppError::NotFound("Thread not found".to_string()))?;
+        self.check_nsfw(&board, viewer, age_confirmed).await?;
+
+        thread.created_by = thread.public_author();
+        Ok(thread)
+    }
+
+    /// Get a thread, without checking access
     pub async fn get_thread(&self, thread_id: Uuid) -> AppResult<Thread> {
         let thread_record = sqlx::query!(
             r#"
-            SELECT id, board_id, title, content, image_url, matrix_event_id, is_pinned, is_locked, 
-                   created_at, created_by, reply_count, last_reply_at
+            SELECT id, board_id, title, content, image_url, matrix_event_id, is_pinned, is_locked, is_spoiler,
+                   created_at, created_by, reply_count, last_reply_at, signature, signing_key_id, author_hidden
             FROM threads WHERE id = ?
             "#,
             thread_id.to_string()
@@ -264,6 +970,7 @@ impl BoardService {
             matrix_event_id: thread_record.matrix_event_id,
             is_pinned: thread_record.is_pinned,
             is_locked: thread_record.is_locked,
+            is_spoiler: thread_record.is_spoiler,
             created_at: chrono::DateTime::parse_from_rfc3339(&thread_record.created_at)
                 .map_err(|e| AppError::Internal(format!("Invalid date: {}", e)))?
                 .with_timezone(&Utc),
@@ -276,54 +983,80 @@ impl BoardService {
                     .unwrap()
                     .with_timezone(&Utc)
             }),
+            signature: thread_record.signature,
+            signing_key_id: thread_record.signing_key_id,
+            author_hidden: thread_record.author_hidden,
         })
     }
 
     /// Create a post (reply to thread)
-    pub async fn create_post(&self, thread_id: Uuid, request: CreatePostRequest, creator_id: Uuid) -> AppResult<Post> {
+    pub async fn create_post(&self, thread_id: Uuid, request: CreatePostRequest, creator: &User) -> AppResult<Post> {
+        let creator_id = creator.id;
+
         // Get thread and board
-        let thread = self.get_thread(thread_id).await?;
-        let board_record = sqlx::query!(
-            "SELECT matrix_room_id FROM boards WHERE id = ?",
-            thread.board_id.to_string()
-        )
-        .fetch_one(self.db.pool())
-        .await?;
+        let thread = self.get_visible_thread(thread_id, Some(creator), false).await?;
+        let board = self.get_board_by_id(thread.board_id).await?;
 
         // Check if thread is locked
         if thread.is_locked {
             return Err(AppError::InvalidRequest("Thread is locked".to_string()));
         }
 
+        self.check_posting_rules(&board, creator, &request.content, request.image_url.as_deref(), false).await?;
+        let screened = self.filters
+            .screen(board.id, creator_id, None, &request.content, request.image_url.as_deref())
+            .await?;
+
         // Post to Matrix room
         let matrix_event_id = if let Some(ref image_url) = request.image_url {
             self.matrix_client
-                .send_message_with_image(&board_record.matrix_room_id, &request.content, image_url)
+                .send_message_with_image(&board.matrix_room_id, &screened.content, image_url)
                 .await?
         } else {
             self.matrix_client
-                .send_message(&board_record.matrix_room_id, &request.content)
+                .send_message(&board.matrix_room_id, &screened.content)
                 .await?
         };
 
-        let post_id = Uuid::new_v4();
-        let now = Utc::now();
+        let mut post = Post {
+            id: Uuid::new_v4(),
+            thread_id: Some(thread_id),
+            board_id: thread.board_id,
+            content: screened.content,
+            image_url: request.image_url,
+            matrix_event_id,
+            reply_to: request.reply_to,
+            is_spoiler: request.is_spoiler,
+            created_at: Utc::now(),
+            created_by: creator_id,
+            signature: None,
+            signing_key_id: None,
+            author_hidden: board.settings.force_anonymous,
+        };
+
+        let signature = self.signing.sign_post(&post);
+        post.signature = Some(signature.signature);
+        post.signing_key_id = Some(signature.key_id);
 
         // Insert post into database
         sqlx::query!(
             r#"
-            INSERT INTO posts (id, thread_id, board_id, content, image_url, matrix_event_id, reply_to, created_at, created_by)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            INSERT INTO posts (id, thread_id, board_id, content, image_url, matrix_event_id, reply_to, is_spoiler, created_at, created_by, signature, signing_key_id, author_hidden)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
-            post_id.to_string(),
+            post.id.to_string(),
             thread_id.to_string(),
-            thread.board_id.to_string(),
-            request.content,
-            request.image_url,
-            matrix_event_id,
-            request.reply_to.map(|id| id.to_string()),
-            now.to_rfc3339(),
-            creator_id.to_string()
+            post.board_id.to_string(),
+            post.content,
+            post.image_url,
+            post.matrix_event_id,
+            post.reply_to.map(|id| id.to_string()),
+            post.is_spoiler,
+            post.created_at.to_rfc3339(),
+            post.created_by.to_string(),
+            post.signature,
+            post.signing_key_id,
+            post.author_hidden
         )
         .execute(self.db.pool())
         .await?;
@@ -331,46 +1064,47 @@ impl BoardService {
         // Update thread reply count and last reply time
         sqlx::query!(
             "UPDATE threads SET reply_count = reply_count + 1, last_reply_at = ? WHERE id = ?",
-            now.to_rfc3339(),
+            post.created_at.to_rfc3339(),
             thread_id.to_string()
         )
         .execute(self.db.pool())
         .await?;
 
-        Ok(Post {
-            id: post_id,
-            thread_id: Some(thread_id),
-            board_id: thread.board_id,
-            content: request.content,
-            image_url: request.image_url,
-            matrix_event_id,
-            reply_to: request.reply_to,
-            created_at: now,
-            created_by: creator_id,
-        })
+        self.filters
+            .record_hits(board.id, creator_id, Some(thread_id), Some(post.id), &screened.hits, &request.content)
+            .await?;
+
+        post.created_by = post.public_author();
+        Ok(post)
     }
 
     /// Get posts in a thread
-    pub async fn get_posts(&self, thread_id: Uuid, limit: Option<i64>, offset: Option<i64>) -> AppResult<Vec<Post>> {
+    pub async fn get_posts(&self, thread_id: Uuid, viewer: Option<&User>, age_confirmed: bool, limit: Option<i64>, offset: Option<i64>) -> AppResult<Vec<Post>> {
+        self.get_visible_thread(thread_id, viewer, age_confirmed).await?;
+
         let limit = limit.unwrap_or(50).min(100); // Max 100 posts per request
         let offset = offset.unwrap_or(0);
+        // Posts by users the viewer blocked are left out
+        let viewer_id = viewer.map(|viewer| viewer.id.to_string());
 
         let post_records = sqlx::query!(
             r#"
-            SELECT id, thread_id, board_id, content, image_url, matrix_event_id, reply_to, created_at, created_by
+            SELECT id, thread_id, board_id, content, image_url, matrix_event_id, reply_to, is_spoiler, created_at, created_by, signature, signing_key_id, author_hidden
             FROM posts 
             WHERE thread_id = ? 
+            AND (author_hidden = TRUE OR created_by NOT IN (SELECT blocked_id FROM blocks WHERE blocker_id = ?))
             ORDER BY created_at ASC
             LIMIT ? OFFSET ?
             "#,
             thread_id.to_string(),
+            viewer_id,
             limit,
             offset
         )
         .fetch_all(self.db.pool())
         .await?;
 
-        let posts = post_records
+        let mut posts = post_records
             .into_iter()
             .map(|record| {
                 Ok(Post {
@@ -391,15 +1125,286 @@ impl BoardService {
                             .map_err(|e| AppError::Internal(format!("Invalid reply_to ID: {}", e)))
                             .unwrap()
                     }),
+                    is_spoiler: record.is_spoiler,
                     created_at: chrono::DateTime::parse_from_rfc3339(&record.created_at)
                         .map_err(|e| AppError::Internal(format!("Invalid date: {}", e)))?
                         .with_timezone(&Utc),
                     created_by: Uuid::parse_str(&record.created_by)
                         .map_err(|e| AppError::Internal(format!("Invalid user ID: {}", e)))?,
+                    signature: record.signature,
+                    signing_key_id: record.signing_key_id,
+                    author_hidden: record.author_hidden,
+                })
+            })
+            .collect::<AppResult<Vec<_>>>()?;
+
+        for post in &mut posts {
+            post.created_by = post.public_author();
+        }
+
+        Ok(posts)
+    }
+
+    /// Get every thread a user started, oldest first
+    pub async fn get_user_threads(&self, user_id: Uuid) -> AppResult<Vec<Thread>> {
+        let thread_ids = sqlx::query_scalar!(
+            "SELECT id FROM threads WHERE created_by = ? ORDER BY created_at ASC",
+            user_id.to_string()
+        )
+        .fetch_all(self.db.pool())
+        .await?;
+
+        let mut threads = Vec::with_capacity(thread_ids.len());
+        for thread_id in thread_ids {
+            let thread_id = Uuid::parse_str(&thread_id)
+                .map_err(|e| AppError::Internal(format!("Invalid thread ID: {}", e)))?;
+            threads.push(self.get_thread(thread_id).await?);
+        }
+
+        Ok(threads)
+    }
+
+    /// Get every post a user wrote, oldest first
+    pub async fn get_user_posts(&self, user_id: Uuid) -> AppResult<Vec<Post>> {
+        let post_records = sqlx::query!(
+            r#"
+            SELECT id, thread_id, board_id, content, image_url, matrix_event_id, reply_to, is_spoiler, created_at, created_by, signature, signing_key_id, author_hidden
+            FROM posts
+            WHERE created_by = ?
+            ORDER BY created_at ASC
+            "#,
+            user_id.to_string()
+        )
+        .fetch_all(self.db.pool())
+        .await?;
+
+        let posts = post_records
+            .into_iter()
+            .map(|record| {
+                Ok(Post {
+                    id: Uuid::parse_str(&record.id)
+                        .map_err(|e| AppError::Internal(format!("Invalid post ID: {}", e)))?,
+                    thread_id: record.thread_id.as_deref()
+                        .map(Uuid::parse_str)
+                        .transpose()
+                        .map_err(|e| AppError::Internal(format!("Invalid thread ID: {}", e)))?,
+                    board_id: Uuid::parse_str(&record.board_id)
+                        .map_err(|e| AppError::Internal(format!("Invalid board ID: {}", e)))?,
+                    content: record.content,
+                    image_url: record.image_url,
+                    matrix_event_id: record.matrix_event_id,
+                    reply_to: record.reply_to.as_deref()
+                        .map(Uuid::parse_str)
+                        .transpose()
+                        .map_err(|e| AppError::Internal(format!("Invalid reply_to ID: {}", e)))?,
+                    is_spoiler: record.is_spoiler,
+                    created_at: chrono::DateTime::parse_from_rfc3339(&record.created_at)
+                        .map_err(|e| AppError::Internal(format!("Invalid date: {}", e)))?
+                        .with_timezone(&Utc),
+                    created_by: user_id,
+                    signature: record.signature,
+                    signing_key_id: record.signing_key_id,
+                    author_hidden: record.author_hidden,
                 })
             })
             .collect::<AppResult<Vec<_>>>()?;
 
         Ok(posts)
     }
-}
\ No newline at end of file
+
+    /// Remove a deleted account's threads and posts from boards whose policy
+    /// is `remove`. Content on other boards stays, under the tombstone.
+    ///
+    /// Threads other people replied to are blanked rather than deleted, so
+    /// the replies keep their place.
+    pub async fn remove_deleted_author_content(&self, user_id: Uuid) -> AppResult<()> {
+        let post_records = sqlx::query!(
+            r#"
+            SELECT p.id, p.thread_id, p.matrix_event_id, b.matrix_room_id
+            FROM posts p JOIN boards b ON b.id = p.board_id
+            WHERE p.created_by = ? AND b.deleted_author_content = 'remove'
+            "#,
+            user_id.to_string()
+        )
+        .fetch_all(self.db.pool())
+        .await?;
+
+        for record in post_records {
+            if let Err(e) = self.matrix_client
+                .redact_event(&record.matrix_room_id, &record.matrix_event_id, Some("Author deleted their account"))
+                .await
+            {
+                warn!("Failed to redact Matrix event of post {}: {}", record.id, e);
+            }
+
+            let mut tx = self.db.pool().begin().await?;
+
+            sqlx::query!(
+                "UPDATE posts SET reply_to = NULL WHERE reply_to = ?",
+                record.id
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            sqlx::query!(
+                "DELETE FROM posts WHERE id = ?",
+                record.id
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            sqlx::query!(
+                "UPDATE threads SET reply_count = MAX(reply_count - 1, 0) WHERE id = ?",
+                record.thread_id
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            tx.commit().await?;
+        }
+
+        let thread_records = sqlx::query!(
+            r#"
+            SELECT t.id, t.matrix_event_id, b.matrix_room_id,
+                   (SELECT COUNT(*) FROM posts WHERE thread_id = t.id) as "replies!: i64"
+            FROM threads t JOIN boards b ON b.id = t.board_id
+            WHERE t.created_by = ? AND b.deleted_author_content = 'remove'
+            "#,
+            user_id.to_string()
+        )
+        .fetch_all(self.db.pool())
+        .await?;
+
+        for record in thread_records {
+            if let Err(e) = self.matrix_client
+                .redact_event(&record.matrix_room_id, &record.matrix_event_id, Some("Author deleted their account"))
+                .await
+            {
+                warn!("Failed to redact Matrix event of thread {}: {}", record.id, e);
+            }
+
+            if record.replies == 0 {
+                sqlx::query!(
+                    "DELETE FROM threads WHERE id = ?",
+                    record.id
+                )
+                .execute(self.db.pool())
+                .await?;
+                continue;
+            }
+
+            let thread_id = Uuid::parse_str(&record.id)
+                .map_err(|e| AppError::Internal(format!("Invalid thread ID: {}", e)))?;
+            let mut thread = self.get_thread(thread_id).await?;
+            thread.title = None;
+            thread.content = "[deleted]".to_string();
+            thread.image_url = None;
+
+            // Re-sign so the blanked thread still verifies
+            let signature = self.signing.sign_thread(&thread);
+
+            sqlx::query!(
+                "UPDATE threads SET title = NULL, content = ?, image_url = NULL, signature = ?, signing_key_id = ? WHERE id = ?",
+                thread.content,
+                signature.signature,
+                signature.key_id,
+                record.id
+            )
+            .execute(self.db.pool())
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Hand the boards a deleted account owned to their longest-serving
+    /// moderator, like `BoardRoleService::transfer_ownership` does. Boards
+    /// without one are left ownerless for admins to reassign.
+    pub async fn hand_over_owned_boards(&self, user_id: Uuid) -> AppResult<()> {
+        let board_records = sqlx::query!(
+            "SELECT id, name, matrix_room_id FROM boards WHERE owner_id = ?",
+            user_id.to_string()
+        )
+        .fetch_all(self.db.pool())
+        .await?;
+
+        for record in board_records {
+            let successor = sqlx::query!(
+                r#"
+                SELECT m.user_id, u.matrix_user_id FROM board_moderators m JOIN users u ON u.id = m.user_id
+                WHERE m.board_id = ? AND m.user_id != ? AND u.is_anonymous = FALSE AND u.deletion_scheduled_for IS NULL
+                ORDER BY m.created_at ASC LIMIT 1
+                "#,
+                record.id,
+                user_id.to_string()
+            )
+            .fetch_optional(self.db.pool())
+            .await?;
+
+            let mut tx = self.db.pool().begin().await?;
+
+            sqlx::query!(
+                "UPDATE boards SET owner_id = ? WHERE id = ?",
+                successor.as_ref().map(|successor| &successor.user_id),
+                record.id
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            // As with a transfer, the owner's role supersedes an appointment
+            if let Some(successor) = &successor {
+                sqlx::query!(
+                    "DELETE FROM board_moderators WHERE board_id = ? AND user_id = ? AND source = ?",
+                    record.id,
+                    successor.user_id,
+                    MANUAL_SOURCE
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+
+            tx.commit().await?;
+
+            if let Some(successor) = successor {
+                if let Err(e) = self.matrix_client
+                    .set_power_level(&record.matrix_room_id, &successor.matrix_user_id, OWNER_POWER_LEVEL)
+                    .await
+                {
+                    warn!("Failed to set power level of {} in Matrix room of board {}: {}", successor.matrix_user_id, record.name, e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A board title, trimmed
+fn validate_title(title: &str) -> AppResult<String> {
+    let title = title.trim();
+    if title.is_empty() || title.chars().count() > MAX_BOARD_TITLE_CHARS {
+        return Err(AppError::InvalidRequest(format!(
+            "Board titles must be 1 to {} characters",
+            MAX_BOARD_TITLE_CHARS
+        )));
+    }
+    Ok(title.to_string())
+}
+
+/// A board description, trimmed; `None` if it's blank
+fn validate_description(description: &str) -> AppResult<Option<String>> {
+    let description = description.trim();
+    if description.chars().count() > MAX_BOARD_DESCRIPTION_CHARS {
+        return Err(AppError::InvalidRequest(format!(
+            "Board descriptions can be at most {} characters",
+            MAX_BOARD_DESCRIPTION_CHARS
+        )));
+    }
+    Ok((!description.is_empty()).then(|| description.to_string()))
+}
+
+fn nsfw_gate() -> AppError {
+    AppError::Authorization(
+        "This board is NSFW. Turn on show_nsfw in your content preferences, or confirm your age, to see it".to_string(),
+    )
+}
@@ -0,0 +1,145 @@
+use crate::core::error::{AppError, AppResult};
+use crate::core::types::{BoardSettings, BoardSettingsUpdate};
+
+/// Upper bound for a board's `max_post_length`
+pub const MAX_POST_LENGTH: i64 = 10_000;
+
+/// Longest a board's rules can be
+const MAX_RULES_CHARS: usize = 5000;
+
+/// Longest cooldown a board can set between posts
+const MAX_COOLDOWN_SECONDS: i64 = 24 * 60 * 60;
+
+/// Most file types a board can allow
+const MAX_FILE_TYPES: usize = 20;
+
+/// Longest file extension
+const MAX_FILE_TYPE_CHARS: usize = 10;
+
+/// Apply a settings update, validating the result
+pub fn apply_update(mut settings: BoardSettings, update: BoardSettingsUpdate) -> AppResult<BoardSettings> {
+    if let Some(rules) = update.rules {
+        let rules = rules.trim();
+        if rules.chars().count() > MAX_RULES_CHARS {
+            return Err(AppError::InvalidRequest(format!("Rules can be at most {} characters", MAX_RULES_CHARS)));
+        }
+        settings.rules = (!rules.is_empty()).then(|| rules.to_string());
+    }
+
+    if let Some(max_post_length) = update.max_post_length {
+        if !(1..=MAX_POST_LENGTH).contains(&max_post_length) {
+            return Err(AppError::InvalidRequest(format!(
+                "The maximum post length must be between 1 and {} characters",
+                MAX_POST_LENGTH
+            )));
+        }
+        settings.max_post_length = max_post_length;
+    }
+
+    if let Some(require_image_on_op) = update.require_image_on_op {
+        settings.require_image_on_op = require_image_on_op;
+    }
+
+    if let Some(allow_anonymous) = update.allow_anonymous {
+        settings.allow_anonymous = allow_anonymous;
+    }
+
+    if let Some(force_anonymous) = update.force_anonymous {
+        settings.force_anonymous = force_anonymous;
+    }
+
+    if let Some(cooldown) = update.post_cooldown_seconds {
+        if !(0..=MAX_COOLDOWN_SECONDS).contains(&cooldown) {
+            return Err(AppError::InvalidRequest(format!(
+                "The cooldown must be between 0 and {} seconds",
+                MAX_COOLDOWN_SECONDS
+            )));
+        }
+        settings.post_cooldown_seconds = cooldown;
+    }
+
+    if let Some(file_types) = update.allowed_file_types {
+        settings.allowed_file_types = normalize_file_types(&file_types)?;
+    }
+
+    Ok(settings)
+}
+
+/// Lowercased extensions without leading dots, each listed once
+fn normalize_file_types(file_types: &[String]) -> AppResult<Vec<String>> {
+    let mut normalized: Vec<String> = Vec::with_capacity(file_types.len());
+
+    for file_type in file_types {
+        let file_type = file_type.trim().trim_start_matches('.').to_lowercase();
+        if file_type.is_empty()
+            || file_type.len() > MAX_FILE_TYPE_CHARS
+            || !file_type.chars().all(|c| c.is_ascii_alphanumeric())
+        {
+            return Err(AppError::InvalidRequest(format!("Invalid file type: {}", file_type)));
+        }
+
+        if !normalized.contains(&file_type) {
+            normalized.push(file_type);
+        }
+    }
+
+    if normalized.len() > MAX_FILE_TYPES {
+        return Err(AppError::InvalidRequest(format!("A board can allow at most {} file types", MAX_FILE_TYPES)));
+    }
+
+    Ok(normalized)
+}
+
+/// Check a new thread or reply against the board's content settings
+pub fn check_post(settings: &BoardSettings, content: &str, image_url: Option<&str>, is_thread: bool) -> AppResult<()> {
+    if content.chars().count() as i64 > settings.max_post_length {
+        return Err(AppError::InvalidRequest(format!(
+            "Posts on this board can be at most {} characters",
+            settings.max_post_length
+        )));
+    }
+
+    if is_thread && settings.require_image_on_op && image_url.is_none() {
+        return Err(AppError::InvalidRequest("New threads on this board need an image".to_string()));
+    }
+
+    if let Some(image_url) = image_url {
+        if !settings.allowed_file_types.is_empty() {
+            let allowed = file_extension(image_url)
+                .is_some_and(|extension| settings.allowed_file_types.contains(&extension));
+            if !allowed {
+                return Err(AppError::InvalidRequest(format!(
+                    "This board only takes {} files",
+                    settings.allowed_file_types.join(", ")
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The lowercased extension of the last path segment of a URL
+fn file_extension(url: &str) -> Option<String> {
+    let path = url.split(['?', '#']).next()?;
+    let file_name = path.rsplit('/').next()?;
+    let (_, extension) = file_name.rsplit_once('.')?;
+
+    (!extension.is_empty()).then(|| extension.to_lowercase())
+}
+
+/// The Matrix room topic of a board: its description, followed by its rules
+pub fn room_topic(description: Option<&str>, rules: Option<&str>) -> Option<String> {
+    let parts: Vec<&str> = [description, rules].into_iter().flatten().collect();
+
+    (!parts.is_empty()).then(|| parts.join("\n\n"))
+}
+
+/// File types as stored in the database
+pub fn join_file_types(file_types: &[String]) -> Option<String> {
+    (!file_types.is_empty()).then(|| file_types.join(" "))
+}
+
+pub fn parse_file_types(value: Option<&str>) -> Vec<String> {
+    value.map(|value| value.split_whitespace().map(str::to_string).collect()).unwrap_or_default()
+}
@@ -0,0 +1,245 @@
+use base64::{Engine as _, engine::general_purpose};
+use chrono::{DateTime, Duration, Utc};
+use ring::{
+    digest, hmac,
+    rand::{SecureRandom, SystemRandom},
+};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::core::config::SecurityConfig;
+use crate::core::error::{AppError, AppResult};
+use crate::core::types::{Challenge, ChallengeSolution};
+use crate::storage::database::Database;
+
+/// Proof-of-work difficulty above which solving takes unreasonably long
+pub const MAX_DIFFICULTY: u8 = 32;
+
+const POW: &str = "pow";
+const TEXT: &str = "text";
+
+const NUMBER_WORDS: [&str; 21] = [
+    "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten",
+    "eleven", "twelve", "thirteen", "fourteen", "fifteen", "sixteen", "seventeen", "eighteen",
+    "nineteen", "twenty",
+];
+
+/// What a challenge unlocks. A solution only counts for the purpose it was
+/// issued for.
+#[derive(Debug, Clone, Copy)]
+pub enum ChallengePurpose {
+    Register,
+    Thread(Uuid),
+}
+
+impl ChallengePurpose {
+    fn tag(&self) -> String {
+        match self {
+            ChallengePurpose::Register => "register".to_string(),
+            ChallengePurpose::Thread(board_id) => format!("thread:{}", board_id.simple()),
+        }
+    }
+}
+
+/// Self-hosted challenges for anonymous registration and posting.
+///
+/// Challenges are stateless: everything needed to check a solution is in
+/// the token, signed with a key derived from `SESSION_SECRET`. Every
+/// attempt is recorded in `used_challenges`, so each token is tried once.
+///
+/// - `pow`: hashcash-style; find a nonce such that
+///   `SHA-256(token ":" nonce)` starts with `difficulty` zero bits
+/// - `text`: a small arithmetic question in words. The answer is only in
+///   the token as a MAC, so it can't be read or brute-forced offline.
+///   Difficulty only matters for `pow`; text questions are all alike, and
+///   a non-zero difficulty merely turns them on.
+pub struct ChallengeService {
+    db: Arc<Database>,
+    key: hmac::Key,
+    rng: SystemRandom,
+    kind: String,
+    default_difficulty: u8,
+    ttl: Duration,
+}
+
+impl ChallengeService {
+    pub fn new(db: Arc<Database>, config: &SecurityConfig) -> AppResult<Self> {
+        if config.challenge_kind != POW && config.challenge_kind != TEXT {
+            return Err(AppError::Internal(format!("Unknown challenge kind: {}", config.challenge_kind)));
+        }
+        if config.challenge_difficulty > MAX_DIFFICULTY {
+            return Err(AppError::Internal(format!("Challenge difficulty can be at most {}", MAX_DIFFICULTY)));
+        }
+
+        let derived = hmac::sign(
+            &hmac::Key::new(hmac::HMAC_SHA256, config.session_secret.as_bytes()),
+            b"amogchan/challenge/v1",
+        );
+
+        Ok(Self {
+            db,
+            key: hmac::Key::new(hmac::HMAC_SHA256, derived.as_ref()),
+            rng: SystemRandom::new(),
+            kind: config.challenge_kind.clone(),
+            default_difficulty: config.challenge_difficulty,
+            ttl: Duration::seconds(config.challenge_ttl_seconds),
+        })
+    }
+
+    /// Difficulty used where nothing more specific is configured.
+    /// Zero turns challenges off.
+    pub fn default_difficulty(&self) -> u8 {
+        self.default_difficulty
+    }
+
+    /// Issue a challenge for the purpose
+    pub fn issue(&self, purpose: ChallengePurpose, difficulty: u8) -> AppResult<Challenge> {
+        let id = Uuid::new_v4();
+        let expires_at = Utc::now() + self.ttl;
+
+        let (question, answer_mac) = if self.kind == TEXT {
+            let (question, answer) = self.text_question()?;
+            let mac = hmac::sign(&self.key, format!("answer:{}:{}", id.simple(), answer).as_bytes());
+            (Some(question), general_purpose::URL_SAFE_NO_PAD.encode(mac.as_ref()))
+        } else {
+            (None, String::new())
+        };
+
+        let payload = format!(
+            "{}.{}.{}.{}.{}.{}",
+            id.simple(),
+            self.kind,
+            purpose.tag(),
+            difficulty,
+            expires_at.timestamp(),
+            answer_mac
+        );
+        let signature = hmac::sign(&self.key, payload.as_bytes());
+
+        Ok(Challenge {
+            token: format!(
+                "{}.{}",
+                general_purpose::URL_SAFE_NO_PAD.encode(payload.as_bytes()),
+                general_purpose::URL_SAFE_NO_PAD.encode(signature.as_ref())
+            ),
+            kind: self.kind.clone(),
+            difficulty,
+            question,
+            expires_at,
+        })
+    }
+
+    /// Check a solution for the purpose and mark the challenge used.
+    /// Passes without a solution when `required_difficulty` is zero.
+    pub async fn verify(&self, solution: Option<&ChallengeSolution>, purpose: ChallengePurpose, required_difficulty: u8) -> AppResult<()> {
+        if required_difficulty == 0 {
+            return Ok(());
+        }
+
+        let solution = solution
+            .ok_or_else(|| AppError::InvalidRequest("A solved challenge is required".to_string()))?;
+        let invalid = || AppError::InvalidRequest("Invalid or expired challenge".to_string());
+
+        let (encoded_payload, encoded_signature) = solution.token.split_once('.').ok_or_else(invalid)?;
+        let payload = general_purpose::URL_SAFE_NO_PAD.decode(encoded_payload).map_err(|_| invalid())?;
+        let signature = general_purpose::URL_SAFE_NO_PAD.decode(encoded_signature).map_err(|_| invalid())?;
+        hmac::verify(&self.key, &payload, &signature).map_err(|_| invalid())?;
+
+        let payload = String::from_utf8(payload).map_err(|_| invalid())?;
+        let fields: Vec<&str> = payload.split('.').collect();
+        let [id, kind, tag, difficulty, expires_at, answer_mac] = fields[..] else {
+            return Err(invalid());
+        };
+
+        let difficulty: u8 = difficulty.parse().map_err(|_| invalid())?;
+        let expires_at = expires_at.parse::<i64>().ok()
+            .and_then(|timestamp| DateTime::from_timestamp(timestamp, 0))
+            .ok_or_else(invalid)?;
+
+        // Board settings may have been raised since the challenge was issued
+        if tag != purpose.tag() || expires_at <= Utc::now() || (kind == POW && difficulty < required_difficulty) {
+            return Err(invalid());
+        }
+
+        // Each challenge gets one attempt: use it up before checking the
+        // solution, or a text question could be retried until guessed
+        let result = sqlx::query!(
+            "INSERT OR IGNORE INTO used_challenges (id, expires_at) VALUES (?, ?)",
+            id,
+            expires_at.to_rfc3339()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(invalid());
+        }
+
+        sqlx::query!(
+            "DELETE FROM used_challenges WHERE expires_at < ?",
+            Utc::now().to_rfc3339()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        let solved = match kind {
+            POW => solution.solution.len() <= 64
+                && leading_zero_bits(digest::digest(&digest::SHA256, format!("{}:{}", solution.token, solution.solution).as_bytes()).as_ref()) >= difficulty as u32,
+            TEXT => {
+                let answer_mac = general_purpose::URL_SAFE_NO_PAD.decode(answer_mac).map_err(|_| invalid())?;
+                parse_answer(&solution.solution)
+                    .map(|answer| hmac::verify(&self.key, format!("answer:{}:{}", id, answer).as_bytes(), &answer_mac).is_ok())
+                    .unwrap_or(false)
+            }
+            _ => false,
+        };
+
+        if !solved {
+            return Err(AppError::InvalidRequest("Incorrect challenge solution".to_string()));
+        }
+
+        Ok(())
+    }
+
+    fn text_question(&self) -> AppResult<(String, i64)> {
+        let mut bytes = [0u8; 3];
+        self.rng.fill(&mut bytes)
+            .map_err(|e| AppError::Crypto(format!("Failed to generate challenge: {}", e)))?;
+
+        let a = (bytes[0] % 11) as i64 + 1;
+        let b = (bytes[1] % 10) as i64 + 1;
+
+        Ok(match bytes[2] % 3 {
+            0 => (format!("What is {} plus {}?", NUMBER_WORDS[a as usize], NUMBER_WORDS[b as usize]), a + b),
+            1 => {
+                let (a, b) = if a >= b { (a, b) } else { (b, a) };
+                (format!("What is {} minus {}?", NUMBER_WORDS[a as usize], NUMBER_WORDS[b as usize]), a - b)
+            }
+            _ => (format!("What is {} times {}?", NUMBER_WORDS[a as usize], NUMBER_WORDS[b as usize % 4 + 1]), a * (b % 4 + 1)),
+        })
+    }
+}
+
+fn leading_zero_bits(hash: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in hash {
+        if *byte == 0 {
+            bits += 8;
+        } else {
+            bits += byte.leading_zeros();
+            break;
+        }
+    }
+    bits
+}
+
+/// Answers may be given in digits or as a number word
+fn parse_answer(answer: &str) -> Option<i64> {
+    let answer = answer.trim().to_lowercase();
+
+    answer.parse().ok().or_else(|| {
+        NUMBER_WORDS.iter()
+            .position(|word| *word == answer)
+            .map(|position| position as i64)
+    })
+}
@@ -0,0 +1,55 @@
+// Keyword tokenizer for blind-index message search
+//
+// Message content is encrypted at rest, so the server never stores searchable
+// plaintext. Instead each message is split into normalized keywords here, and
+// every keyword is turned into an HMAC blind index token by
+// `CryptoService::blind_index`. A search query goes through the exact same
+// normalization, so equal words always produce equal tokens.
+//
+// Normalization rules:
+// - Text is lowercased (Unicode-aware)
+// - Words are split on any character that is not alphanumeric
+// - Apostrophes inside words are dropped ("don't" -> "dont")
+// - Words shorter than MIN_TOKEN_CHARS are ignored
+// - Words longer than MAX_TOKEN_CHARS are truncated
+// - Duplicate words are removed, at most MAX_TOKENS_PER_MESSAGE are kept
+
+use std::collections::HashSet;
+
+/// Shortest word that is indexed
+pub const MIN_TOKEN_CHARS: usize = 2;
+
+/// Longer words are truncated to this many characters
+pub const MAX_TOKEN_CHARS: usize = 64;
+
+/// Upper bound on indexed words per message
+pub const MAX_TOKENS_PER_MESSAGE: usize = 256;
+
+/// Split text into normalized, de-duplicated search keywords
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut tokens = Vec::new();
+
+    let lowered = text.to_lowercase();
+    let without_apostrophes: String = lowered
+        .chars()
+        .filter(|c| *c != '\'' && *c != '\u{2019}')
+        .collect();
+
+    for word in without_apostrophes.split(|c: char| !c.is_alphanumeric()) {
+        if word.chars().count() < MIN_TOKEN_CHARS {
+            continue;
+        }
+
+        let word: String = word.chars().take(MAX_TOKEN_CHARS).collect();
+        if seen.insert(word.clone()) {
+            tokens.push(word);
+        }
+
+        if tokens.len() >= MAX_TOKENS_PER_MESSAGE {
+            break;
+        }
+    }
+
+    tokens
+}
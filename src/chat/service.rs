@@ -1,19 +1,73 @@
 use chrono::Utc;
+use std::collections::HashMap;
 use std::sync::Arc;
+use tracing::warn;
 use uuid::Uuid;
 
 use crate::core::error::{AppError, AppResult};
+use crate::chat::search;
+use crate::crypto::aad::message_aad;
 use crate::core::types::{
-    Chat, Message, CreateChatRequest, SendMessageRequest
+    Chat, ChatMember, ChatRequest, Message, MessageType, CreateChatRequest, SendMessageRequest, User
 };
+use crate::crypto::data_keys::{DataKey, DataKeyService, KeyOwner};
 use crate::crypto::service::CryptoService;
+use crate::crypto::signing::SigningService;
+use crate::e2ee::service::E2eeService;
 use crate::matrix::client::MatrixClient;
 use crate::storage::database::Database;
+use crate::user::blocks::BlockService;
+use crate::user::service::UserService;
+
+/// `chat_participants.request_status` of members who are in the chat
+const REQUEST_ACCEPTED: &str = "accepted";
+/// A direct message from a non-contact the recipient hasn't answered yet
+const REQUEST_PENDING: &str = "pending";
+/// A direct message the recipient turned down; the chat is hidden from them
+const REQUEST_DECLINED: &str = "declined";
+
+/// Matrix power levels of group roles. The bot, which created every room,
+/// keeps 100 so it can still change everyone's level.
+const OWNER_POWER_LEVEL: i64 = 75;
+const ADMIN_POWER_LEVEL: i64 = 50;
+const MEMBER_POWER_LEVEL: i64 = 0;
+
+const MAX_CHAT_NAME_CHARS: usize = 100;
+
+/// A participant's role in a group chat
+struct Membership {
+    is_admin: bool,
+    is_owner: bool,
+}
+
+/// A stored message as search reads it, before decryption
+#[derive(sqlx::FromRow)]
+struct MessageRow {
+    id: String,
+    chat_id: String,
+    content: String,
+    message_type: String,
+    matrix_event_id: String,
+    reply_to: Option<String>,
+    is_encrypted: bool,
+    is_e2ee: bool,
+    sender_device_id: Option<String>,
+    sender_key_id: Option<String>,
+    created_at: String,
+    created_by: String,
+    signature: Option<String>,
+    signing_key_id: Option<String>,
+}
 
 pub struct ChatService {
     db: Arc<Database>,
     matrix_client: Arc<MatrixClient>,
     crypto: Arc<CryptoService>,
+    data_keys: Arc<DataKeyService>,
+    e2ee: Arc<E2eeService>,
+    signing: Arc<SigningService>,
+    users: Arc<UserService>,
+    blocks: Arc<BlockService>,
 }
 
 impl ChatService {
@@ -21,37 +75,70 @@ impl ChatService {
         db: Arc<Database>,
         matrix_client: Arc<MatrixClient>,
         crypto: Arc<CryptoService>,
+        data_keys: Arc<DataKeyService>,
+        e2ee: Arc<E2eeService>,
+        signing: Arc<SigningService>,
+        users: Arc<UserService>,
+        blocks: Arc<BlockService>,
     ) -> Self {
         Self {
             db,
             matrix_client,
             crypto,
+            data_keys,
+            e2ee,
+            signing,
+            users,
+            blocks,
         }
     }
 
-    /// Create a new chat (direct message or group)
+    /// Create a new chat (direct message or group).
+    ///
+    /// A new direct message or group to someone who doesn't have the creator
+    /// in their contacts arrives as a request the recipient has to accept first.
     pub async fn create_chat(&self, request: CreateChatRequest, creator_id: Uuid) -> AppResult<Chat> {
+        let participants = self.users.resolve_users(&request.participants).await?;
+        let mut request_statuses = HashMap::new();
+
         // For direct messages, check if chat already exists
-        if !request.is_group && request.participants.len() == 1 {
-            let other_user_id = request.participants[0];
-            
-            // Check if DM already exists between these users
+        if !request.is_group && participants.len() == 1 {
+            let other_user_id = participants[0];
+
+            if self.blocks.is_blocked_either_way(creator_id, other_user_id).await? {
+                return Err(AppError::Authorization("You can't message this user".to_string()));
+            }
+
+            // Check if a DM with the same encryption mode already exists between these users
             let existing_chat = sqlx::query!(
                 r#"
-                SELECT c.id, c.name, c.matrix_room_id, c.is_group, c.is_encrypted, c.created_at, c.created_by
+                SELECT c.id, c.name, c.matrix_room_id, c.is_group, c.is_encrypted, c.server_side_encryption, c.search_enabled, c.created_at, c.created_by
                 FROM chats c
                 JOIN chat_participants cp1 ON c.id = cp1.chat_id
                 JOIN chat_participants cp2 ON c.id = cp2.chat_id
                 WHERE c.is_group = false 
                 AND cp1.user_id = ? AND cp2.user_id = ?
+                AND c.server_side_encryption = ?
                 "#,
                 creator_id.to_string(),
-                other_user_id.to_string()
+                other_user_id.to_string(),
+                !request.end_to_end
             )
             .fetch_optional(self.db.pool())
             .await?;
 
             if let Some(existing) = existing_chat {
+                // Writing again takes back an earlier decline of the other side's request
+                sqlx::query!(
+                    "UPDATE chat_participants SET request_status = ? WHERE chat_id = ? AND user_id = ? AND request_status = ?",
+                    REQUEST_ACCEPTED,
+                    existing.id,
+                    creator_id.to_string(),
+                    REQUEST_DECLINED
+                )
+                .execute(self.db.pool())
+                .await?;
+
                 return Ok(Chat {
                     id: Uuid::parse_str(&existing.id)
                         .map_err(|e| AppError::Internal(format!("Invalid chat ID: {}", e)))?,
@@ -59,6 +146,8 @@ impl ChatService {
                     matrix_room_id: existing.matrix_room_id,
                     is_group: existing.is_group,
                     is_encrypted: existing.is_encrypted,
+                    server_side_encryption: existing.server_side_encryption,
+                    search_enabled: existing.search_enabled,
                     created_at: chrono::DateTime::parse_from_rfc3339(&existing.created_at)
                         .map_err(|e| AppError::Internal(format!("Invalid date: {}", e)))?
                         .with_timezone(&Utc),
@@ -66,6 +155,34 @@ impl ChatService {
                         .map_err(|e| AppError::Internal(format!("Invalid user ID: {}", e)))?,
                 });
             }
+
+            // Only new conversations are subject to the recipient's DM policy
+            self.users.check_direct_message_allowed(creator_id, other_user_id).await?;
+
+            if !self.is_contact(other_user_id, creator_id).await? {
+                request_statuses.insert(other_user_id, REQUEST_PENDING);
+            }
+        } else {
+            for participant_id in participants.iter().filter(|id| **id != creator_id) {
+                if self.blocks.is_blocked(*participant_id, creator_id).await? {
+                    return Err(AppError::Authorization(format!("User {} can't be added to this chat", participant_id)));
+                }
+
+                let status = self.request_status_for_added(creator_id, *participant_id).await?;
+                request_statuses.insert(*participant_id, status);
+            }
+        }
+
+        // Every participant needs a registered device to receive end-to-end encrypted messages
+        if request.end_to_end {
+            for participant_id in std::iter::once(&creator_id).chain(participants.iter()) {
+                if !self.e2ee.has_devices(*participant_id).await? {
+                    return Err(AppError::InvalidRequest(format!(
+                        "User {} has no registered device keys",
+                        participant_id
+                    )));
+                }
+            }
         }
 
         // Create Matrix room
@@ -78,7 +195,7 @@ impl ChatService {
             // For DMs, get the other user's Matrix ID and create DM
             let other_user_record = sqlx::query!(
                 "SELECT matrix_user_id FROM users WHERE id = ?",
-                request.participants[0].to_string()
+                participants[0].to_string()
             )
             .fetch_one(self.db.pool())
             .await
@@ -95,20 +212,29 @@ impl ChatService {
         // Insert chat into database
         sqlx::query!(
             r#"
-            INSERT INTO chats (id, name, matrix_room_id, is_group, is_encrypted, created_at, created_by)
-            VALUES (?, ?, ?, ?, ?, ?, ?)
+            INSERT INTO chats (id, name, matrix_room_id, is_group, is_encrypted, server_side_encryption, created_at, created_by, owner_id)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
             chat_id.to_string(),
             request.name,
             matrix_room_id,
             request.is_group,
             true, // All chats are encrypted
+            !request.end_to_end,
             now.to_rfc3339(),
-            creator_id.to_string()
+            creator_id.to_string(),
+            request.is_group.then(|| creator_id.to_string())
         )
         .execute(self.db.pool())
         .await?;
 
+        if request.is_group {
+            let creator_matrix_id = self.matrix_user_id(creator_id).await?;
+            if let Err(e) = self.matrix_client.set_power_level(&matrix_room_id, &creator_matrix_id, OWNER_POWER_LEVEL).await {
+                warn!("Failed to set Matrix power level of chat owner {}: {}", creator_id, e);
+            }
+        }
+
         // Add creator as participant
         sqlx::query!(
             "INSERT INTO chat_participants (chat_id, user_id, is_admin) VALUES (?, ?, ?)",
@@ -120,13 +246,15 @@ impl ChatService {
         .await?;
 
         // Add other participants
-        for participant_id in &request.participants {
+        for participant_id in &participants {
             if *participant_id != creator_id {
                 sqlx::query!(
-                    "INSERT INTO chat_participants (chat_id, user_id, is_admin) VALUES (?, ?, ?)",
+                    "INSERT INTO chat_participants (chat_id, user_id, is_admin, request_status, added_by) VALUES (?, ?, ?, ?, ?)",
                     chat_id.to_string(),
                     participant_id.to_string(),
-                    false
+                    false,
+                    request_statuses.get(participant_id).copied().unwrap_or(REQUEST_ACCEPTED),
+                    creator_id.to_string()
                 )
                 .execute(self.db.pool())
                 .await?;
@@ -151,22 +279,25 @@ impl ChatService {
             matrix_room_id,
             is_group: request.is_group,
             is_encrypted: true,
+            server_side_encryption: !request.end_to_end,
+            search_enabled: false,
             created_at: now,
             created_by: creator_id,
         })
     }
 
-    /// Get user's chats
+    /// Get user's chats, leaving out chat requests
     pub async fn get_user_chats(&self, user_id: Uuid) -> AppResult<Vec<Chat>> {
         let chat_records = sqlx::query!(
             r#"
-            SELECT c.id, c.name, c.matrix_room_id, c.is_group, c.is_encrypted, c.created_at, c.created_by
+            SELECT c.id, c.name, c.matrix_room_id, c.is_group, c.is_encrypted, c.server_side_encryption, c.search_enabled, c.created_at, c.created_by
             FROM chats c
             JOIN chat_participants cp ON c.id = cp.chat_id
-            WHERE cp.user_id = ?
+            WHERE cp.user_id = ? AND cp.request_status = ?
             ORDER BY c.created_at DESC
             "#,
-            user_id.to_string()
+            user_id.to_string(),
+            REQUEST_ACCEPTED
         )
         .fetch_all(self.db.pool())
         .await?;
@@ -181,6 +312,8 @@ impl ChatService {
                     matrix_room_id: record.matrix_room_id,
                     is_group: record.is_group,
                     is_encrypted: record.is_encrypted,
+                    server_side_encryption: record.server_side_encryption,
+                    search_enabled: record.search_enabled,
                     created_at: chrono::DateTime::parse_from_rfc3339(&record.created_at)
                         .map_err(|e| AppError::Internal(format!("Invalid date: {}", e)))?
                         .with_timezone(&Utc),
@@ -193,23 +326,29 @@ impl ChatService {
         Ok(chats)
     }
 
-    /// Get a specific chat
+    /// Get a specific chat. Pending requests can be read before answering
+    /// them; declined ones can't.
     pub async fn get_chat(&self, chat_id: Uuid, user_id: Uuid) -> AppResult<Chat> {
         // Verify user is a participant
         let participant = sqlx::query!(
-            "SELECT user_id FROM chat_participants WHERE chat_id = ? AND user_id = ?",
+            "SELECT request_status FROM chat_participants WHERE chat_id = ? AND user_id = ?",
             chat_id.to_string(),
             user_id.to_string()
         )
         .fetch_optional(self.db.pool())
         .await?;
 
-        if participant.is_none() {
+        if participant.map_or(true, |participant| participant.request_status == REQUEST_DECLINED) {
             return Err(AppError::Authorization("Not a member of this chat".to_string()));
         }
 
+        self.load_chat(chat_id).await
+    }
+
+    /// Load a chat without checking membership
+    async fn load_chat(&self, chat_id: Uuid) -> AppResult<Chat> {
         let chat_record = sqlx::query!(
-            "SELECT id, name, matrix_room_id, is_group, is_encrypted, created_at, created_by FROM chats WHERE id = ?",
+            "SELECT id, name, matrix_room_id, is_group, is_encrypted, server_side_encryption, search_enabled, created_at, created_by FROM chats WHERE id = ?",
             chat_id.to_string()
         )
         .fetch_optional(self.db.pool())
@@ -222,6 +361,8 @@ impl ChatService {
             matrix_room_id: chat_record.matrix_room_id,
             is_group: chat_record.is_group,
             is_encrypted: chat_record.is_encrypted,
+            server_side_encryption: chat_record.server_side_encryption,
+            search_enabled: chat_record.search_enabled,
             created_at: chrono::DateTime::parse_from_rfc3339(&chat_record.created_at)
                 .map_err(|e| AppError::Internal(format!("Invalid date: {}", e)))?
                 .with_timezone(&Utc),
@@ -232,12 +373,54 @@ impl ChatService {
 
     /// Send a message to a chat
     pub async fn send_message(&self, chat_id: Uuid, request: SendMessageRequest, sender_id: Uuid) -> AppResult<Message> {
+        if matches!(request.message_type, MessageType::System) {
+            return Err(AppError::InvalidRequest("System messages can't be sent".to_string()));
+        }
+
         // Get chat and verify user is a participant
         let chat = self.get_chat(chat_id, sender_id).await?;
 
-        // Encrypt message content if it's an encrypted chat
+        if !chat.is_group {
+            let other_user_id = sqlx::query_scalar!(
+                "SELECT user_id FROM chat_participants WHERE chat_id = ? AND user_id != ?",
+                chat_id.to_string(),
+                sender_id.to_string()
+            )
+            .fetch_optional(self.db.pool())
+            .await?;
+
+            if let Some(other_user_id) = other_user_id {
+                let other_user_id = Uuid::parse_str(&other_user_id)
+                    .map_err(|e| AppError::Internal(format!("Invalid user ID: {}", e)))?;
+
+                if self.blocks.is_blocked_either_way(sender_id, other_user_id).await? {
+                    return Err(AppError::Authorization("You can't message this user".to_string()));
+                }
+            }
+        }
+
+        // Replying to a chat request accepts it
+        sqlx::query!(
+            "UPDATE chat_participants SET request_status = ? WHERE chat_id = ? AND user_id = ? AND request_status = ?",
+            REQUEST_ACCEPTED,
+            chat_id.to_string(),
+            sender_id.to_string(),
+            REQUEST_PENDING
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        if !chat.server_side_encryption {
+            return self.relay_e2ee_message(chat, request, sender_id).await;
+        }
+
+        let message_id = Uuid::new_v4();
+        let now = Utc::now();
+
+        // Encrypt message content under the chat's data key, bound to this row
         let content = if chat.is_encrypted {
-            self.crypto.encrypt(&request.content)?
+            let data_key = self.data_keys.get_or_create(KeyOwner::Chat(chat_id)).await?;
+            self.crypto.encrypt_with_data_key(&data_key, &request.content, &message_aad(chat_id, message_id, sender_id))?
         } else {
             request.content.clone()
         };
@@ -247,58 +430,160 @@ impl ChatService {
             .send_message(&chat.matrix_room_id, &request.content) // Send unencrypted to Matrix (Matrix handles its own encryption)
             .await?;
 
-        let message_id = Uuid::new_v4();
-        let now = Utc::now();
+        let mut message = Message {
+            id: message_id,
+            chat_id,
+            content: request.content, // Return original unencrypted content
+            message_type: request.message_type,
+            matrix_event_id,
+            reply_to: request.reply_to,
+            is_encrypted: chat.is_encrypted,
+            is_e2ee: false,
+            sender_device_id: None,
+            sender_key_id: None,
+            created_at: now,
+            created_by: sender_id,
+            signature: None,
+            signing_key_id: None,
+            content_digest: None,
+            decryption_error: None,
+        };
+
+        // Signed over a keyed digest, so the signature doesn't give the plaintext away.
+        // The digest is returned with the message so readers can check the signature.
+        let content_digest = self.crypto.content_digest(message_id, &message.content);
+        let signature = self.signing.sign_message(&message, &content_digest);
+        message.signature = Some(signature.signature);
+        message.signing_key_id = Some(signature.key_id);
+        message.content_digest = Some(content_digest);
 
         // Insert message into database (store encrypted content)
         sqlx::query!(
             r#"
-            INSERT INTO messages (id, chat_id, content, message_type, matrix_event_id, reply_to, is_encrypted, created_at, created_by)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            INSERT INTO messages (id, chat_id, content, message_type, matrix_event_id, reply_to, is_encrypted, created_at, created_by, signature, signing_key_id)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
             message_id.to_string(),
             chat_id.to_string(),
             content, // This is encrypted if chat.is_encrypted is true
-            format!("{:?}", request.message_type).to_lowercase(),
-            matrix_event_id,
-            request.reply_to.map(|id| id.to_string()),
+            format!("{:?}", message.message_type).to_lowercase(),
+            message.matrix_event_id,
+            message.reply_to.map(|id| id.to_string()),
             chat.is_encrypted,
             now.to_rfc3339(),
-            sender_id.to_string()
+            sender_id.to_string(),
+            message.signature,
+            message.signing_key_id
         )
         .execute(self.db.pool())
         .await?;
 
-        Ok(Message {
+        // Index keywords for opt-in search (text messages only)
+        if chat.search_enabled && matches!(message.message_type, MessageType::Text) {
+            self.index_message(message_id, chat_id, &message.content).await?;
+        }
+
+        Ok(message)
+    }
+
+    /// Store and relay a client-encrypted envelope in an end-to-end encrypted chat.
+    ///
+    /// The content is opaque ciphertext produced by the sender's device. It is
+    /// never decrypted, re-encrypted, indexed or sent to Matrix as a text message.
+    async fn relay_e2ee_message(&self, chat: Chat, request: SendMessageRequest, sender_id: Uuid) -> AppResult<Message> {
+        let sender_device_id = request.sender_device_id
+            .ok_or_else(|| AppError::InvalidRequest("sender_device_id is required in end-to-end encrypted chats".to_string()))?;
+        let sender_key_id = request.sender_key_id
+            .ok_or_else(|| AppError::InvalidRequest("sender_key_id is required in end-to-end encrypted chats".to_string()))?;
+
+        // The envelope must come from a device the sender registered
+        let identity_key_id = self.e2ee.identity_key_id(sender_id, &sender_device_id).await?;
+        if identity_key_id != sender_key_id {
+            return Err(AppError::InvalidRequest("sender_key_id does not match the device's identity key".to_string()));
+        }
+
+        if request.content.is_empty() {
+            return Err(AppError::InvalidRequest("Encrypted envelope is empty".to_string()));
+        }
+
+        let message_id = Uuid::new_v4();
+        let now = Utc::now();
+
+        let envelope = e2ee_envelope(message_id, &sender_device_id, &sender_key_id, &request.content);
+        let matrix_event_id = self.matrix_client
+            .send_e2ee_envelope(&chat.matrix_room_id, envelope)
+            .await?;
+
+        let mut message = Message {
             id: message_id,
-            chat_id,
-            content: request.content, // Return original unencrypted content
+            chat_id: chat.id,
+            content: request.content,
             message_type: request.message_type,
             matrix_event_id,
             reply_to: request.reply_to,
-            is_encrypted: chat.is_encrypted,
+            is_encrypted: false,
+            is_e2ee: true,
+            sender_device_id: Some(sender_device_id),
+            sender_key_id: Some(sender_key_id),
             created_at: now,
             created_by: sender_id,
-        })
+            signature: None,
+            signing_key_id: None,
+            content_digest: None,
+            decryption_error: None,
+        };
+
+        // Signed over the envelope; the server can vouch for nothing more
+        let signature = self.signing.sign_message(&message, &message.content);
+        message.signature = Some(signature.signature);
+        message.signing_key_id = Some(signature.key_id);
+
+        sqlx::query!(
+            r#"
+            INSERT INTO messages (id, chat_id, content, message_type, matrix_event_id, reply_to, is_encrypted, is_e2ee, sender_device_id, sender_key_id, created_at, created_by, signature, signing_key_id)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+            message_id.to_string(),
+            chat.id.to_string(),
+            message.content,
+            format!("{:?}", message.message_type).to_lowercase(),
+            message.matrix_event_id,
+            message.reply_to.map(|id| id.to_string()),
+            false, // Not encrypted by the server
+            true,
+            message.sender_device_id,
+            message.sender_key_id,
+            now.to_rfc3339(),
+            sender_id.to_string(),
+            message.signature,
+            message.signing_key_id
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        Ok(message)
     }
 
-    /// Get messages from a chat
+    /// Get messages from a chat, leaving out those of users the reader blocked
     pub async fn get_messages(&self, chat_id: Uuid, user_id: Uuid, limit: Option<i64>, offset: Option<i64>) -> AppResult<Vec<Message>> {
         // Verify user is a participant
         self.get_chat(chat_id, user_id).await?;
+        let data_key = self.data_keys.get(KeyOwner::Chat(chat_id)).await?;
 
         let limit = limit.unwrap_or(50).min(100); // Max 100 messages per request
         let offset = offset.unwrap_or(0);
 
         let message_records = sqlx::query!(
             r#"
-            SELECT id, chat_id, content, message_type, matrix_event_id, reply_to, is_encrypted, created_at, created_by
+            SELECT id, chat_id, content, message_type, matrix_event_id, reply_to, is_encrypted, is_e2ee, sender_device_id, sender_key_id, created_at, created_by, signature, signing_key_id
             FROM messages 
             WHERE chat_id = ? 
+            AND created_by NOT IN (SELECT blocked_id FROM blocks WHERE blocker_id = ?)
             ORDER BY created_at DESC
             LIMIT ? OFFSET ?
             "#,
             chat_id.to_string(),
+            user_id.to_string(),
             limit,
             offset
         )
@@ -308,12 +593,19 @@ impl ChatService {
         let messages = message_records
             .into_iter()
             .map(|record| {
-                // Decrypt content if it's encrypted
-                let content = if record.is_encrypted {
-                    self.crypto.decrypt(&record.content).unwrap_or_else(|_| "[Encrypted]".to_string())
+                // Decrypt content if it's encrypted, reporting failures per message
+                let (content, decryption_error) = if record.is_encrypted {
+                    match self.decrypt_content(&record.chat_id, &record.id, &record.created_by, &record.content, data_key.as_deref()) {
+                        Ok(plaintext) => (plaintext, None),
+                        Err(e) => {
+                            warn!("Failed to decrypt message {}: {}", record.id, e);
+                            (String::new(), Some(e.to_string()))
+                        }
+                    }
                 } else {
-                    record.content
+                    (record.content, None)
                 };
+                let content_digest = self.signed_digest(&record.id, record.is_e2ee, &content, &decryption_error)?;
 
                 let message_type = match record.message_type.as_str() {
                     "text" => crate::core::types::MessageType::Text,
@@ -321,6 +613,7 @@ impl ChatService {
                     "file" => crate::core::types::MessageType::File,
                     "audio" => crate::core::types::MessageType::Audio,
                     "video" => crate::core::types::MessageType::Video,
+                    "system" => crate::core::types::MessageType::System,
                     _ => crate::core::types::MessageType::Text,
                 };
 
@@ -338,11 +631,18 @@ impl ChatService {
                             .unwrap()
                     }),
                     is_encrypted: record.is_encrypted,
+                    is_e2ee: record.is_e2ee,
+                    sender_device_id: record.sender_device_id,
+                    sender_key_id: record.sender_key_id,
                     created_at: chrono::DateTime::parse_from_rfc3339(&record.created_at)
                         .map_err(|e| AppError::Internal(format!("Invalid date: {}", e)))?
                         .with_timezone(&Utc),
                     created_by: Uuid::parse_str(&record.created_by)
                         .map_err(|e| AppError::Internal(format!("Invalid user ID: {}", e)))?,
+                    signature: record.signature,
+                    signing_key_id: record.signing_key_id,
+                    content_digest,
+                    decryption_error,
                 })
             })
             .collect::<AppResult<Vec<_>>>()?;
@@ -379,15 +679,27 @@ impl ChatService {
             return Err(AppError::InvalidRequest("User is already a member".to_string()));
         }
 
+        if self.blocks.is_blocked(user_id, admin_id).await? {
+            return Err(AppError::Authorization("This user can't be added to this chat".to_string()));
+        }
+
         // Get chat info
         let chat = self.get_chat(chat_id, admin_id).await?;
+        if !chat.is_group {
+            return Err(AppError::InvalidRequest("Only group chats can have members added".to_string()));
+        }
+
+        self.require_devices(&chat, user_id).await?;
+        let request_status = self.request_status_for_added(admin_id, user_id).await?;
 
         // Add user to database
         sqlx::query!(
-            "INSERT INTO chat_participants (chat_id, user_id, is_admin) VALUES (?, ?, ?)",
+            "INSERT INTO chat_participants (chat_id, user_id, is_admin, request_status, added_by) VALUES (?, ?, ?, ?, ?)",
             chat_id.to_string(),
             user_id.to_string(),
-            false
+            false,
+            request_status,
+            admin_id.to_string()
         )
         .execute(self.db.pool())
         .await?;
@@ -404,37 +716,1077 @@ impl ChatService {
             .invite_user(&chat.matrix_room_id, &user_record.matrix_user_id)
             .await?;
 
+        let text = format!("{} added {}", self.user_label(admin_id).await?, self.user_label(user_id).await?);
+        self.record_system_event(&chat, admin_id, &text).await;
+
         Ok(())
     }
 
-    /// Remove a user from a group chat
+    /// Remove a user from a group chat and kick them from its Matrix room.
+    ///
+    /// Admins can remove members; only the owner can remove other admins. The
+    /// owner can't be removed, and nobody removes themselves (see `leave_chat`).
     pub async fn remove_user_from_chat(&self, chat_id: Uuid, user_id: Uuid, admin_id: Uuid) -> AppResult<()> {
-        // Verify admin is a member and has admin privileges
-        let admin_participant = sqlx::query!(
-            "SELECT is_admin FROM chat_participants WHERE chat_id = ? AND user_id = ?",
+        let (chat, admin) = self.group_membership(chat_id, admin_id).await?;
+        if !admin.is_admin {
+            return Err(AppError::Authorization("Admin privileges required".to_string()));
+        }
+
+        if user_id == admin_id {
+            return Err(AppError::InvalidRequest("Leave the chat instead of removing yourself".to_string()));
+        }
+
+        let member = self.membership(chat_id, user_id).await?
+            .ok_or_else(|| AppError::NotFound("User is not a member of this chat".to_string()))?;
+
+        if member.is_owner {
+            return Err(AppError::Authorization("The owner can't be removed".to_string()));
+        }
+        if member.is_admin && !admin.is_owner {
+            return Err(AppError::Authorization("Only the owner can remove admins".to_string()));
+        }
+
+        let matrix_user_id = self.matrix_user_id(user_id).await?;
+        self.matrix_client
+            .kick_user(&chat.matrix_room_id, &matrix_user_id, Some("Removed from the chat"))
+            .await?;
+
+        sqlx::query!(
+            "DELETE FROM chat_participants WHERE chat_id = ? AND user_id = ?",
             chat_id.to_string(),
-            admin_id.to_string()
+            user_id.to_string()
         )
-        .fetch_optional(self.db.pool())
-        .await?
-        .ok_or_else(|| AppError::Authorization("Not a member of this chat".to_string()))?;
+        .execute(self.db.pool())
+        .await?;
 
-        if !admin_participant.is_admin {
-            return Err(AppError::Authorization("Admin privileges required".to_string()));
+        let text = format!("{} removed {}", self.user_label(admin_id).await?, self.user_label(user_id).await?);
+        self.record_system_event(&chat, admin_id, &text).await;
+
+        Ok(())
+    }
+
+    /// Whether the user is an admin of the group chat
+    pub async fn is_group_admin(&self, chat_id: Uuid, user_id: Uuid) -> AppResult<bool> {
+        let (_, membership) = self.group_membership(chat_id, user_id).await?;
+        Ok(membership.is_admin)
+    }
+
+    /// Whether the user is still an admin of the chat, for checks on behalf
+    /// of someone else where `is_group_admin`'s errors don't fit
+    pub async fn is_current_admin(&self, chat_id: Uuid, user_id: Uuid) -> AppResult<bool> {
+        Ok(self.membership(chat_id, user_id).await?.is_some_and(|membership| membership.is_admin))
+    }
+
+    /// Add someone who joined through a link created by `invited_by`,
+    /// inviting them to the Matrix room. Their client still has to accept
+    /// the Matrix invite.
+    ///
+    /// `tx` is committed along with the membership, so the caller's writes
+    /// (the link's use count) only stick if the user gets in.
+    pub async fn join_via_link(
+        &self,
+        mut tx: sqlx::Transaction<'static, sqlx::Sqlite>,
+        chat_id: Uuid,
+        user_id: Uuid,
+        invited_by: Uuid,
+    ) -> AppResult<Chat> {
+        let chat = self.load_chat(chat_id).await?;
+        if !chat.is_group {
+            return Err(AppError::InvalidRequest("Only group chats can be joined".to_string()));
+        }
+
+        if self.blocks.is_blocked_either_way(user_id, invited_by).await? {
+            return Err(AppError::Authorization("You can't join this chat".to_string()));
+        }
+
+        self.require_devices(&chat, user_id).await?;
+
+        let result = sqlx::query!(
+            "INSERT OR IGNORE INTO chat_participants (chat_id, user_id, is_admin) VALUES (?, ?, ?)",
+            chat_id.to_string(),
+            user_id.to_string(),
+            false
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::InvalidRequest("Already a member of this chat".to_string()));
         }
 
-        // Remove user from database
+        tx.commit().await?;
+
+        let matrix_user_id = self.matrix_user_id(user_id).await?;
+        if let Err(e) = self.matrix_client.invite_user(&chat.matrix_room_id, &matrix_user_id).await {
+            warn!("Failed to invite {} to Matrix room of chat {}: {}", matrix_user_id, chat_id, e);
+        }
+
+        let text = format!("{} joined using an invite link", self.user_label(user_id).await?);
+        self.record_system_event(&chat, user_id, &text).await;
+
+        Ok(chat)
+    }
+
+    /// Leave a group chat. If the owner leaves, the longest-standing admin
+    /// (or member) takes over.
+    pub async fn leave_chat(&self, chat_id: Uuid, user_id: Uuid) -> AppResult<()> {
+        let (chat, _) = self.group_membership(chat_id, user_id).await?;
+
+        let matrix_user_id = self.matrix_user_id(user_id).await?;
+        if let Err(e) = self.matrix_client
+            .kick_user(&chat.matrix_room_id, &matrix_user_id, Some("Left the chat"))
+            .await
+        {
+            warn!("Failed to remove {} from Matrix room of chat {}: {}", matrix_user_id, chat_id, e);
+        }
+
+        let mut tx = self.db.pool().begin().await?;
+
         sqlx::query!(
             "DELETE FROM chat_participants WHERE chat_id = ? AND user_id = ?",
             chat_id.to_string(),
             user_id.to_string()
         )
+        .execute(&mut *tx)
+        .await?;
+
+        let new_owner_id = assign_vacant_owner(&mut tx, chat_id).await?;
+
+        tx.commit().await?;
+
+        let text = format!("{} left", self.user_label(user_id).await?);
+        self.record_system_event(&chat, user_id, &text).await;
+
+        if let Some(new_owner_id) = new_owner_id {
+            self.announce_new_owner(&chat, new_owner_id).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Rename a group chat (admins only)
+    pub async fn rename_chat(&self, chat_id: Uuid, name: &str, admin_id: Uuid) -> AppResult<Chat> {
+        let (chat, admin) = self.group_membership(chat_id, admin_id).await?;
+        if !admin.is_admin {
+            return Err(AppError::Authorization("Admin privileges required".to_string()));
+        }
+
+        let name = name.trim();
+        if name.is_empty() || name.chars().count() > MAX_CHAT_NAME_CHARS || name.chars().any(char::is_control) {
+            return Err(AppError::InvalidRequest(format!(
+                "Chat names must be 1 to {} characters, without control characters",
+                MAX_CHAT_NAME_CHARS
+            )));
+        }
+
+        self.matrix_client.set_room_name(&chat.matrix_room_id, name).await?;
+
+        sqlx::query!(
+            "UPDATE chats SET name = ? WHERE id = ?",
+            name,
+            chat_id.to_string()
+        )
         .execute(self.db.pool())
         .await?;
 
-        // Note: Matrix room removal would require additional Matrix SDK calls
-        // For now, we just remove from our database
+        let text = format!("{} renamed the chat to \"{}\"", self.user_label(admin_id).await?, name);
+        self.record_system_event(&chat, admin_id, &text).await;
+
+        Ok(Chat { name: Some(name.to_string()), ..chat })
+    }
+
+    /// Participants of a chat with their roles
+    pub async fn list_members(&self, chat_id: Uuid, viewer: &User) -> AppResult<Vec<ChatMember>> {
+        self.get_chat(chat_id, viewer.id).await?;
+
+        let member_records = sqlx::query!(
+            r#"
+            SELECT cp.user_id, cp.is_admin, c.owner_id
+            FROM chat_participants cp JOIN chats c ON c.id = cp.chat_id
+            WHERE cp.chat_id = ? AND cp.request_status != ?
+            ORDER BY cp.joined_at ASC
+            "#,
+            chat_id.to_string(),
+            REQUEST_DECLINED
+        )
+        .fetch_all(self.db.pool())
+        .await?;
+
+        let mut members = Vec::with_capacity(member_records.len());
+        for record in member_records {
+            let user_id = Uuid::parse_str(&record.user_id)
+                .map_err(|e| AppError::Internal(format!("Invalid user ID: {}", e)))?;
+
+            members.push(ChatMember {
+                user: self.users.get_public_user(user_id, viewer).await?,
+                is_admin: record.is_admin,
+                is_owner: record.owner_id.as_deref() == Some(record.user_id.as_str()),
+            });
+        }
+
+        Ok(members)
+    }
+
+    /// Make a member an admin (admins only)
+    pub async fn promote_member(&self, chat_id: Uuid, user_id: Uuid, admin_id: Uuid) -> AppResult<()> {
+        let (chat, admin) = self.group_membership(chat_id, admin_id).await?;
+        if !admin.is_admin {
+            return Err(AppError::Authorization("Admin privileges required".to_string()));
+        }
+
+        let member = self.membership(chat_id, user_id).await?
+            .ok_or_else(|| AppError::NotFound("User is not a member of this chat".to_string()))?;
+        if member.is_admin {
+            return Err(AppError::InvalidRequest("User is already an admin".to_string()));
+        }
+
+        let matrix_user_id = self.matrix_user_id(user_id).await?;
+        self.matrix_client
+            .set_power_level(&chat.matrix_room_id, &matrix_user_id, ADMIN_POWER_LEVEL)
+            .await?;
+
+        self.set_admin(chat_id, user_id, true).await?;
+
+        let text = format!("{} made {} an admin", self.user_label(admin_id).await?, self.user_label(user_id).await?);
+        self.record_system_event(&chat, admin_id, &text).await;
+
+        Ok(())
+    }
+
+    /// Take admin rights away. The owner can demote any admin; other admins
+    /// can only step down themselves. The owner has to transfer ownership
+    /// before stepping down.
+    pub async fn demote_member(&self, chat_id: Uuid, user_id: Uuid, actor_id: Uuid) -> AppResult<()> {
+        let (chat, actor) = self.group_membership(chat_id, actor_id).await?;
+        if !actor.is_owner && user_id != actor_id {
+            return Err(AppError::Authorization("Only the owner can demote other admins".to_string()));
+        }
+
+        let member = self.membership(chat_id, user_id).await?
+            .ok_or_else(|| AppError::NotFound("User is not a member of this chat".to_string()))?;
+        if member.is_owner {
+            return Err(AppError::InvalidRequest("Transfer ownership before stepping down".to_string()));
+        }
+        if !member.is_admin {
+            return Err(AppError::InvalidRequest("User is not an admin".to_string()));
+        }
+
+        let matrix_user_id = self.matrix_user_id(user_id).await?;
+        self.matrix_client
+            .set_power_level(&chat.matrix_room_id, &matrix_user_id, MEMBER_POWER_LEVEL)
+            .await?;
+
+        self.set_admin(chat_id, user_id, false).await?;
+
+        let text = if user_id == actor_id {
+            format!("{} is no longer an admin", self.user_label(user_id).await?)
+        } else {
+            format!("{} removed {} as admin", self.user_label(actor_id).await?, self.user_label(user_id).await?)
+        };
+        self.record_system_event(&chat, actor_id, &text).await;
+
+        Ok(())
+    }
+
+    /// Hand the chat to another member, who also becomes an admin. The
+    /// previous owner stays an admin.
+    pub async fn transfer_ownership(&self, chat_id: Uuid, new_owner_id: Uuid, owner_id: Uuid) -> AppResult<()> {
+        let (chat, owner) = self.group_membership(chat_id, owner_id).await?;
+        if !owner.is_owner {
+            return Err(AppError::Authorization("Only the owner can transfer the chat".to_string()));
+        }
+
+        if new_owner_id == owner_id {
+            return Err(AppError::InvalidRequest("You already own this chat".to_string()));
+        }
+
+        self.membership(chat_id, new_owner_id).await?
+            .ok_or_else(|| AppError::NotFound("User is not a member of this chat".to_string()))?;
+
+        let new_owner_matrix_id = self.matrix_user_id(new_owner_id).await?;
+        self.matrix_client
+            .set_power_level(&chat.matrix_room_id, &new_owner_matrix_id, OWNER_POWER_LEVEL)
+            .await?;
+
+        let owner_matrix_id = self.matrix_user_id(owner_id).await?;
+        self.matrix_client
+            .set_power_level(&chat.matrix_room_id, &owner_matrix_id, ADMIN_POWER_LEVEL)
+            .await?;
+
+        self.set_admin(chat_id, new_owner_id, true).await?;
+        self.set_owner(chat_id, new_owner_id).await?;
+
+        let text = format!("{} made {} the owner", self.user_label(owner_id).await?, self.user_label(new_owner_id).await?);
+        self.record_system_event(&chat, owner_id, &text).await;
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// Delete a chat and crypto-shred its data key. As this destroys every
+    /// member's history, only the owner of a group, the creator of a direct
+    /// chat and site admins may do it; ownership of a group moves, its
+    /// creator may long have left.
+    ///
+    /// The data key is destroyed first, so even if a later step fails or the
+    /// rows survive in a backup, messages sealed under it can no longer be
+    /// decrypted. Messages the re-encryption job hasn't yet moved off the
+    /// global keyring (`v1`/`v2`) are only deleted here; copies of those in
+    /// backups stay readable until that keyring key is retired.
+    pub async fn delete_chat(&self, chat_id: Uuid, user: &User) -> AppResult<()> {
+        let chat = self.load_chat(chat_id).await?;
+
+        if !user.is_admin {
+            if chat.is_group {
+                let is_owner = self.membership(chat_id, user.id).await?
+                    .is_some_and(|membership| membership.is_owner);
+                if !is_owner {
+                    return Err(AppError::Authorization("Only the group's owner or a site admin can delete it".to_string()));
+                }
+            } else if chat.created_by != user.id {
+                return Err(AppError::Authorization("Only the chat's creator or a site admin can delete it".to_string()));
+            }
+        }
+
+        self.data_keys.shred(KeyOwner::Chat(chat_id)).await?;
+
+        let mut tx = self.db.pool().begin().await?;
+
+        sqlx::query!(
+            "DELETE FROM message_search_index WHERE chat_id = ?",
+            chat_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM messages WHERE chat_id = ?",
+            chat_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM chat_participants WHERE chat_id = ?",
+            chat_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM chats WHERE id = ?",
+            chat_id.to_string()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        if let Err(e) = self.matrix_client.leave_room(&chat.matrix_room_id).await {
+            warn!("Failed to leave Matrix room of deleted chat {}: {}", chat_id, e);
+        }
+
+        Ok(())
+    }
+
+    /// Take a deleted account out of all its chats.
+    ///
+    /// Its messages are redacted on Matrix and deleted, its Matrix user is
+    /// kicked from each room, and group chats it owned or was the last admin
+    /// of get a new owner or admin (see `assign_vacant_owner`).
+    pub async fn remove_deleted_member(&self, user_id: Uuid, matrix_user_id: &str) -> AppResult<()> {
+        let message_records = sqlx::query!(
+            r#"
+            SELECT m.id, m.matrix_event_id, c.matrix_room_id
+            FROM messages m JOIN chats c ON c.id = m.chat_id
+            WHERE m.created_by = ?
+            "#,
+            user_id.to_string()
+        )
+        .fetch_all(self.db.pool())
+        .await?;
+
+        for record in message_records {
+            if let Err(e) = self.matrix_client
+                .redact_event(&record.matrix_room_id, &record.matrix_event_id, Some("Sender deleted their account"))
+                .await
+            {
+                warn!("Failed to redact Matrix event of message {}: {}", record.id, e);
+            }
+
+            let mut tx = self.db.pool().begin().await?;
+
+            sqlx::query!(
+                "DELETE FROM message_search_index WHERE message_id = ?",
+                record.id
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            sqlx::query!(
+                "UPDATE messages SET reply_to = NULL WHERE reply_to = ?",
+                record.id
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            sqlx::query!(
+                "DELETE FROM messages WHERE id = ?",
+                record.id
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            tx.commit().await?;
+        }
+
+        let chat_records = sqlx::query!(
+            r#"
+            SELECT c.id, c.matrix_room_id, c.is_group
+            FROM chats c JOIN chat_participants cp ON cp.chat_id = c.id
+            WHERE cp.user_id = ?
+            "#,
+            user_id.to_string()
+        )
+        .fetch_all(self.db.pool())
+        .await?;
+
+        for record in chat_records {
+            if let Err(e) = self.matrix_client
+                .kick_user(&record.matrix_room_id, matrix_user_id, Some("Account deleted"))
+                .await
+            {
+                warn!("Failed to remove {} from Matrix room of chat {}: {}", matrix_user_id, record.id, e);
+            }
+
+            let chat_id = Uuid::parse_str(&record.id)
+                .map_err(|e| AppError::Internal(format!("Invalid chat ID: {}", e)))?;
+
+            let mut tx = self.db.pool().begin().await?;
+
+            sqlx::query!(
+                "DELETE FROM chat_participants WHERE chat_id = ? AND user_id = ?",
+                record.id,
+                user_id.to_string()
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            let new_owner_id = if record.is_group {
+                assign_vacant_owner(&mut tx, chat_id).await?
+            } else {
+                None
+            };
+
+            tx.commit().await?;
+
+            if let Some(new_owner_id) = new_owner_id {
+                let chat = self.load_chat(chat_id).await?;
+                self.announce_new_owner(&chat, new_owner_id).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The user's role in a chat, if they are in it
+    async fn membership(&self, chat_id: Uuid, user_id: Uuid) -> AppResult<Option<Membership>> {
+        let record = sqlx::query!(
+            r#"
+            SELECT cp.is_admin, c.owner_id
+            FROM chat_participants cp JOIN chats c ON c.id = cp.chat_id
+            WHERE cp.chat_id = ? AND cp.user_id = ? AND cp.request_status != ?
+            "#,
+            chat_id.to_string(),
+            user_id.to_string(),
+            REQUEST_DECLINED
+        )
+        .fetch_optional(self.db.pool())
+        .await?;
+
+        Ok(record.map(|record| Membership {
+            is_admin: record.is_admin,
+            is_owner: record.owner_id == Some(user_id.to_string()),
+        }))
+    }
+
+    /// End-to-end encrypted chats only take members with a registered
+    /// device to receive messages on
+    async fn require_devices(&self, chat: &Chat, user_id: Uuid) -> AppResult<()> {
+        if !chat.server_side_encryption && !self.e2ee.has_devices(user_id).await? {
+            return Err(AppError::InvalidRequest(format!("User {} has no registered device keys", user_id)));
+        }
+
+        Ok(())
+    }
+
+    /// A group chat and the user's role in it
+    async fn group_membership(&self, chat_id: Uuid, user_id: Uuid) -> AppResult<(Chat, Membership)> {
+        let chat = self.get_chat(chat_id, user_id).await?;
+        if !chat.is_group {
+            return Err(AppError::InvalidRequest("Only group chats have members and admins".to_string()));
+        }
+
+        let membership = self.membership(chat_id, user_id).await?
+            .ok_or_else(|| AppError::Authorization("Not a member of this chat".to_string()))?;
+
+        Ok((chat, membership))
+    }
+
+    async fn set_admin(&self, chat_id: Uuid, user_id: Uuid, is_admin: bool) -> AppResult<()> {
+        sqlx::query!(
+            "UPDATE chat_participants SET is_admin = ? WHERE chat_id = ? AND user_id = ?",
+            is_admin,
+            chat_id.to_string(),
+            user_id.to_string()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        Ok(())
+    }
+
+    async fn set_owner(&self, chat_id: Uuid, owner_id: Uuid) -> AppResult<()> {
+        sqlx::query!(
+            "UPDATE chats SET owner_id = ? WHERE id = ?",
+            owner_id.to_string(),
+            chat_id.to_string()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        Ok(())
+    }
+
+    /// Give a new owner picked by `assign_vacant_owner` their Matrix power
+    /// level and tell the chat
+    async fn announce_new_owner(&self, chat: &Chat, new_owner_id: Uuid) -> AppResult<()> {
+        let matrix_user_id = self.matrix_user_id(new_owner_id).await?;
+        if let Err(e) = self.matrix_client.set_power_level(&chat.matrix_room_id, &matrix_user_id, OWNER_POWER_LEVEL).await {
+            warn!("Failed to set Matrix power level of new owner of chat {}: {}", chat.id, e);
+        }
+
+        let text = format!("{} is now the owner", self.user_label(new_owner_id).await?);
+        self.record_system_event(chat, new_owner_id, &text).await;
+
+        Ok(())
+    }
+
+    async fn matrix_user_id(&self, user_id: Uuid) -> AppResult<String> {
+        sqlx::query_scalar!("SELECT matrix_user_id FROM users WHERE id = ?", user_id.to_string())
+            .fetch_optional(self.db.pool())
+            .await?
+            .ok_or_else(|| AppError::NotFound("User not found".to_string()))
+    }
+
+    /// How a user is named in system messages
+    async fn user_label(&self, user_id: Uuid) -> AppResult<String> {
+        let record = sqlx::query!("SELECT username, display_name FROM users WHERE id = ?", user_id.to_string())
+            .fetch_optional(self.db.pool())
+            .await?
+            .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+        Ok(record.display_name.unwrap_or(record.username))
+    }
+
+    /// Record a membership or settings change in the chat's timeline, as a
+    /// Matrix notice and a system message. The change has already happened,
+    /// so failures are logged rather than returned.
+    async fn record_system_event(&self, chat: &Chat, actor_id: Uuid, text: &str) {
+        if let Err(e) = self.insert_system_message(chat, actor_id, text).await {
+            warn!("Failed to record system message in chat {}: {}", chat.id, e);
+        }
+    }
+
+    async fn insert_system_message(&self, chat: &Chat, actor_id: Uuid, text: &str) -> AppResult<()> {
+        let matrix_event_id = self.matrix_client.send_notice(&chat.matrix_room_id, text).await?;
+
+        let mut message = Message {
+            id: Uuid::new_v4(),
+            chat_id: chat.id,
+            content: text.to_string(),
+            message_type: MessageType::System,
+            matrix_event_id,
+            reply_to: None,
+            is_encrypted: false,
+            is_e2ee: false,
+            sender_device_id: None,
+            sender_key_id: None,
+            created_at: Utc::now(),
+            created_by: actor_id,
+            signature: None,
+            signing_key_id: None,
+            content_digest: None,
+            decryption_error: None,
+        };
+
+        let content_digest = self.crypto.content_digest(message.id, &message.content);
+        let signature = self.signing.sign_message(&message, &content_digest);
+        message.signature = Some(signature.signature);
+        message.signing_key_id = Some(signature.key_id);
+        message.content_digest = Some(content_digest);
+
+        sqlx::query!(
+            r#"
+            INSERT INTO messages (id, chat_id, content, message_type, matrix_event_id, is_encrypted, created_at, created_by, signature, signing_key_id)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+            message.id.to_string(),
+            chat.id.to_string(),
+            message.content,
+            "system",
+            message.matrix_event_id,
+            false,
+            message.created_at.to_rfc3339(),
+            actor_id.to_string(),
+            message.signature,
+            message.signing_key_id
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        Ok(())
+    }
+
+    /// Direct messages and groups from non-contacts waiting for the user's answer
+    pub async fn list_chat_requests(&self, user: &User) -> AppResult<Vec<ChatRequest>> {
+        let chat_ids = sqlx::query_scalar!(
+            r#"
+            SELECT c.id FROM chats c
+            JOIN chat_participants cp ON c.id = cp.chat_id
+            WHERE cp.user_id = ? AND cp.request_status = ?
+            ORDER BY c.created_at DESC
+            "#,
+            user.id.to_string(),
+            REQUEST_PENDING
+        )
+        .fetch_all(self.db.pool())
+        .await?;
+
+        let mut requests = Vec::with_capacity(chat_ids.len());
+        for chat_id in chat_ids {
+            let chat_id = Uuid::parse_str(&chat_id)
+                .map_err(|e| AppError::Internal(format!("Invalid chat ID: {}", e)))?;
+            let chat = self.get_chat(chat_id, user.id).await?;
+
+            let sender_id = self.request_sender(&chat, user.id).await?;
+            requests.push(ChatRequest {
+                from: self.users.get_public_user(sender_id, user).await?,
+                chat,
+            });
+        }
+
+        Ok(requests)
+    }
+
+    /// Move a chat request into the user's chats
+    pub async fn accept_chat_request(&self, chat_id: Uuid, user_id: Uuid) -> AppResult<Chat> {
+        self.answer_chat_request(chat_id, user_id, REQUEST_ACCEPTED).await?;
+        self.get_chat(chat_id, user_id).await
+    }
+
+    /// Turn down a chat request. The chat disappears for the user unless the
+    /// sender is written to again. Returns the declined chat.
+    pub async fn decline_chat_request(&self, chat_id: Uuid, user_id: Uuid) -> AppResult<Chat> {
+        let chat = self.get_chat(chat_id, user_id).await?;
+        self.answer_chat_request(chat_id, user_id, REQUEST_DECLINED).await?;
+        Ok(chat)
+    }
+
+    /// Who a chat request came from: whoever added the user, or the chat's
+    /// creator for rows from before that was recorded
+    pub async fn request_sender(&self, chat: &Chat, user_id: Uuid) -> AppResult<Uuid> {
+        let added_by = sqlx::query_scalar!(
+            "SELECT added_by FROM chat_participants WHERE chat_id = ? AND user_id = ?",
+            chat.id.to_string(),
+            user_id.to_string()
+        )
+        .fetch_optional(self.db.pool())
+        .await?
+        .flatten();
+
+        match added_by {
+            Some(added_by) => Uuid::parse_str(&added_by)
+                .map_err(|e| AppError::Internal(format!("Invalid user ID: {}", e))),
+            None => Ok(chat.created_by),
+        }
+    }
+
+    async fn answer_chat_request(&self, chat_id: Uuid, user_id: Uuid, status: &str) -> AppResult<()> {
+        let result = sqlx::query!(
+            "UPDATE chat_participants SET request_status = ? WHERE chat_id = ? AND user_id = ? AND request_status = ?",
+            status,
+            chat_id.to_string(),
+            user_id.to_string(),
+            REQUEST_PENDING
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Chat request not found".to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// How someone added to a group by `adder` joins it: directly if they have
+    /// the adder in their contacts, otherwise as a chat request, provided
+    /// their DM policy lets the adder reach them at all
+    async fn request_status_for_added(&self, adder_id: Uuid, user_id: Uuid) -> AppResult<&'static str> {
+        if self.is_contact(user_id, adder_id).await? {
+            return Ok(REQUEST_ACCEPTED);
+        }
+
+        self.users.check_direct_message_allowed(adder_id, user_id).await?;
+        Ok(REQUEST_PENDING)
+    }
+
+    /// Whether `owner` has `contact` in their contacts
+    async fn is_contact(&self, owner_id: Uuid, contact_id: Uuid) -> AppResult<bool> {
+        let contact = sqlx::query!(
+            "SELECT owner_id FROM contacts WHERE owner_id = ? AND contact_id = ?",
+            owner_id.to_string(),
+            contact_id.to_string()
+        )
+        .fetch_optional(self.db.pool())
+        .await?;
+
+        Ok(contact.is_some())
+    }
+
+    /// Enable or disable keyword search for a chat (admins only)
+    pub async fn set_search_enabled(&self, chat_id: Uuid, enabled: bool, admin_id: Uuid) -> AppResult<Chat> {
+        let admin_participant = sqlx::query!(
+            "SELECT is_admin FROM chat_participants WHERE chat_id = ? AND user_id = ?",
+            chat_id.to_string(),
+            admin_id.to_string()
+        )
+        .fetch_optional(self.db.pool())
+        .await?
+        .ok_or_else(|| AppError::Authorization("Not a member of this chat".to_string()))?;
+
+        if !admin_participant.is_admin {
+            return Err(AppError::Authorization("Admin privileges required".to_string()));
+        }
+
+        let mut chat = self.get_chat(chat_id, admin_id).await?;
+        if enabled && !chat.server_side_encryption {
+            return Err(AppError::InvalidRequest("Search is not available in end-to-end encrypted chats".to_string()));
+        }
+        if chat.search_enabled == enabled {
+            return Ok(chat);
+        }
+
+        sqlx::query!(
+            "UPDATE chats SET search_enabled = ? WHERE id = ?",
+            enabled,
+            chat_id.to_string()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        if enabled {
+            let data_key = self.data_keys.get(KeyOwner::Chat(chat_id)).await?;
+
+            // Backfill the index for existing text messages
+            let message_records = sqlx::query!(
+                "SELECT id, chat_id, content, is_encrypted, created_by FROM messages WHERE chat_id = ? AND message_type = 'text'",
+                chat_id.to_string()
+            )
+            .fetch_all(self.db.pool())
+            .await?;
+
+            for record in message_records {
+                let message_id = Uuid::parse_str(&record.id)
+                    .map_err(|e| AppError::Internal(format!("Invalid message ID: {}", e)))?;
+                let content = if record.is_encrypted {
+                    match self.decrypt_content(&record.chat_id, &record.id, &record.created_by, &record.content, data_key.as_deref()) {
+                        Ok(plaintext) => plaintext,
+                        Err(e) => {
+                            warn!("Skipping undecryptable message {} while indexing: {}", record.id, e);
+                            continue;
+                        }
+                    }
+                } else {
+                    record.content
+                };
+
+                self.index_message(message_id, chat_id, &content).await?;
+            }
+        } else {
+            // Drop all tokens so nothing about the chat remains searchable
+            sqlx::query!(
+                "DELETE FROM message_search_index WHERE chat_id = ?",
+                chat_id.to_string()
+            )
+            .execute(self.db.pool())
+            .await?;
+        }
+
+        chat.search_enabled = enabled;
+        Ok(chat)
+    }
+
+    /// Search messages by keyword in one chat, or in all of the user's chats.
+    ///
+    /// Every keyword in the query must match. Only chats the user participates
+    /// in and that have search enabled are considered, and messages of users
+    /// they blocked are left out.
+    pub async fn search_messages(&self, user_id: Uuid, chat_id: Option<Uuid>, query: &str, limit: Option<i64>) -> AppResult<Vec<Message>> {
+        let terms = search::tokenize(query);
+        if terms.is_empty() {
+            return Err(AppError::InvalidRequest("Search query has no searchable words".to_string()));
+        }
+
+        let limit = limit.unwrap_or(50).clamp(1, 100); // Max 100 results per request
+
+        let chats = match chat_id {
+            Some(chat_id) => {
+                let chat = self.get_chat(chat_id, user_id).await?;
+                if !chat.search_enabled {
+                    return Err(AppError::InvalidRequest("Search is not enabled for this chat".to_string()));
+                }
+                vec![chat]
+            }
+            None => self
+                .get_user_chats(user_id)
+                .await?
+                .into_iter()
+                .filter(|chat| chat.search_enabled)
+                .collect(),
+        };
+
+        let mut results = Vec::new();
+
+        for chat in chats {
+            let scope = chat.id.to_string();
+            let tokens: Vec<String> = terms
+                .iter()
+                .map(|term| self.crypto.blind_index(&scope, term))
+                .collect();
+            let data_key = self.data_keys.get(KeyOwner::Chat(chat.id)).await?;
+
+            let mut builder = sqlx::QueryBuilder::<sqlx::Sqlite>::new(
+                r#"
+                SELECT m.id, m.chat_id, m.content, m.message_type, m.matrix_event_id, m.reply_to, m.is_encrypted, m.is_e2ee,
+                       m.sender_device_id, m.sender_key_id, m.created_at, m.created_by, m.signature, m.signing_key_id
+                FROM message_search_index i JOIN messages m ON m.id = i.message_id WHERE i.chat_id = "#,
+            );
+            builder.push_bind(scope.clone());
+            builder.push(" AND i.token IN (");
+            let mut separated = builder.separated(", ");
+            for token in &tokens {
+                separated.push_bind(token.clone());
+            }
+            separated.push_unseparated(")");
+            builder.push(" AND m.created_by NOT IN (SELECT blocked_id FROM blocks WHERE blocker_id = ");
+            builder.push_bind(user_id.to_string());
+            builder.push(")");
+            builder.push(" GROUP BY m.id HAVING COUNT(DISTINCT i.token) = ");
+            builder.push_bind(tokens.len() as i64);
+            builder.push(" ORDER BY m.created_at DESC LIMIT ");
+            builder.push_bind(limit);
+
+            let message_rows: Vec<MessageRow> = builder
+                .build_query_as()
+                .fetch_all(self.db.pool())
+                .await?;
+
+            for row in message_rows {
+                results.push(self.decode_message(row, data_key.as_deref())?);
+            }
+        }
+
+        results.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        results.truncate(limit as usize);
+
+        Ok(results)
+    }
+
+    /// Decrypt a message row found by search
+    fn decode_message(&self, row: MessageRow, data_key: Option<&DataKey>) -> AppResult<Message> {
+        let (content, decryption_error) = if row.is_encrypted {
+            match self.decrypt_content(&row.chat_id, &row.id, &row.created_by, &row.content, data_key) {
+                Ok(plaintext) => (plaintext, None),
+                Err(e) => {
+                    warn!("Failed to decrypt message {}: {}", row.id, e);
+                    (String::new(), Some(e.to_string()))
+                }
+            }
+        } else {
+            (row.content, None)
+        };
+        let content_digest = self.signed_digest(&row.id, row.is_e2ee, &content, &decryption_error)?;
+
+        let message_type = match row.message_type.as_str() {
+            "text" => MessageType::Text,
+            "image" => MessageType::Image,
+            "file" => MessageType::File,
+            "audio" => MessageType::Audio,
+            "video" => MessageType::Video,
+            "system" => MessageType::System,
+            _ => MessageType::Text,
+        };
+
+        Ok(Message {
+            id: Uuid::parse_str(&row.id)
+                .map_err(|e| AppError::Internal(format!("Invalid message ID: {}", e)))?,
+            chat_id: Uuid::parse_str(&row.chat_id)
+                .map_err(|e| AppError::Internal(format!("Invalid chat ID: {}", e)))?,
+            content,
+            message_type,
+            matrix_event_id: row.matrix_event_id,
+            reply_to: row.reply_to
+                .as_deref()
+                .map(Uuid::parse_str)
+                .transpose()
+                .map_err(|e| AppError::Internal(format!("Invalid reply_to ID: {}", e)))?,
+            is_encrypted: row.is_encrypted,
+            is_e2ee: row.is_e2ee,
+            sender_device_id: row.sender_device_id,
+            sender_key_id: row.sender_key_id,
+            created_at: chrono::DateTime::parse_from_rfc3339(&row.created_at)
+                .map_err(|e| AppError::Internal(format!("Invalid date: {}", e)))?
+                .with_timezone(&Utc),
+            created_by: Uuid::parse_str(&row.created_by)
+                .map_err(|e| AppError::Internal(format!("Invalid user ID: {}", e)))?,
+            signature: row.signature,
+            signing_key_id: row.signing_key_id,
+            content_digest,
+            decryption_error,
+        })
+    }
+
+    /// The digest a message's signature covers, for messages the server can read
+    fn signed_digest(&self, message_id: &str, is_e2ee: bool, content: &str, decryption_error: &Option<String>) -> AppResult<Option<String>> {
+        if is_e2ee || decryption_error.is_some() {
+            return Ok(None);
+        }
+
+        let message_id = Uuid::parse_str(message_id)
+            .map_err(|e| AppError::Internal(format!("Invalid message ID: {}", e)))?;
+        Ok(Some(self.crypto.content_digest(message_id, content)))
+    }
+
+    /// Decrypt stored message content using the row's identifiers as AAD
+    fn decrypt_content(&self, chat_id: &str, message_id: &str, sender_id: &str, content: &str, data_key: Option<&DataKey>) -> AppResult<String> {
+        let chat_id = Uuid::parse_str(chat_id)
+            .map_err(|e| AppError::Internal(format!("Invalid chat ID: {}", e)))?;
+        let message_id = Uuid::parse_str(message_id)
+            .map_err(|e| AppError::Internal(format!("Invalid message ID: {}", e)))?;
+        let sender_id = Uuid::parse_str(sender_id)
+            .map_err(|e| AppError::Internal(format!("Invalid user ID: {}", e)))?;
+
+        self.crypto.decrypt_with_data_key(content, &message_aad(chat_id, message_id, sender_id), data_key)
+    }
+
+    /// Store blind index tokens for a message's keywords
+    async fn index_message(&self, message_id: Uuid, chat_id: Uuid, plaintext: &str) -> AppResult<()> {
+        let scope = chat_id.to_string();
+
+        for term in search::tokenize(plaintext) {
+            let token = self.crypto.blind_index(&scope, &term);
+
+            sqlx::query!(
+                "INSERT OR IGNORE INTO message_search_index (message_id, chat_id, token) VALUES (?, ?, ?)",
+                message_id.to_string(),
+                scope,
+                token
+            )
+            .execute(self.db.pool())
+            .await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// After someone left a group: if its owner is gone, the longest-standing
+/// admin, or else the longest-standing member, becomes owner and admin.
+/// Runs inside the caller's transaction and returns the new owner, whose
+/// Matrix power level is up to the caller once it has committed.
+async fn assign_vacant_owner(conn: &mut sqlx::SqliteConnection, chat_id: Uuid) -> AppResult<Option<Uuid>> {
+    let member_records = sqlx::query!(
+        "SELECT user_id, is_admin FROM chat_participants WHERE chat_id = ? AND request_status = ? ORDER BY joined_at ASC",
+        chat_id.to_string(),
+        REQUEST_ACCEPTED
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let Some(oldest) = member_records.first() else {
+        return Ok(None);
+    };
+
+    let owner_id = sqlx::query_scalar!("SELECT owner_id FROM chats WHERE id = ?", chat_id.to_string())
+        .fetch_one(&mut *conn)
+        .await?;
+    if owner_id.as_ref().is_some_and(|owner_id| member_records.iter().any(|record| &record.user_id == owner_id)) {
+        return Ok(None);
+    }
+
+    let new_owner = member_records.iter().find(|record| record.is_admin).unwrap_or(oldest);
+
+    sqlx::query!(
+        "UPDATE chat_participants SET is_admin = TRUE WHERE chat_id = ? AND user_id = ?",
+        chat_id.to_string(),
+        new_owner.user_id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query!(
+        "UPDATE chats SET owner_id = ? WHERE id = ?",
+        new_owner.user_id,
+        chat_id.to_string()
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Uuid::parse_str(&new_owner.user_id)
+        .map(Some)
+        .map_err(|e| AppError::Internal(format!("Invalid user ID: {}", e)))
+}
+
+/// The Matrix event relaying an end-to-end encrypted message. It carries the
+/// client's ciphertext as-is and no `body`, so nothing readable reaches Matrix.
+fn e2ee_envelope(message_id: Uuid, sender_device_id: &str, sender_key_id: &str, ciphertext: &str) -> serde_json::Value {
+    serde_json::json!({
+        "message_id": message_id,
+        "sender_device_id": sender_device_id,
+        "sender_key_id": sender_key_id,
+        "ciphertext": ciphertext,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::{Engine as _, engine::general_purpose};
+    use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM};
+
+    /// Stand-in for a client device sealing a message with a key the server never sees
+    fn client_encrypt(plaintext: &str) -> String {
+        let key = LessSafeKey::new(UnboundKey::new(&AES_256_GCM, &[7u8; 32]).unwrap());
+        let mut sealed = plaintext.as_bytes().to_vec();
+        key.seal_in_place_append_tag(Nonce::assume_unique_for_key([0u8; 12]), Aad::empty(), &mut sealed)
+            .unwrap();
+        general_purpose::STANDARD.encode(sealed)
+    }
+
+    #[test]
+    fn e2ee_envelope_relays_ciphertext_without_plaintext() {
+        let plaintext = "meet at the harbour at noon";
+        let ciphertext = client_encrypt(plaintext);
+
+        let envelope = e2ee_envelope(Uuid::new_v4(), "phone", "key-1", &ciphertext);
+        let serialized = envelope.to_string();
+
+        assert_eq!(envelope["ciphertext"], ciphertext.as_str());
+        assert!(envelope.get("body").is_none());
+        assert!(!serialized.contains(plaintext));
+        for word in plaintext.split_whitespace().filter(|word| word.len() > 3) {
+            assert!(!serialized.contains(word));
+        }
+    }
+}
@@ -2,15 +2,39 @@ use anyhow::Result;
 use axum::Router;
 use std::net::SocketAddr;
 use std::sync::Arc;
-use tracing::info;
+use tracing::{info, warn};
 
 use crate::core::config::Config;
 use crate::storage::database::Database;
 use crate::matrix::client::MatrixClient;
+use crate::account::deletion::AccountDeletionService;
+use crate::account::export::ExportService;
+use crate::auth::email::AccountEmailService;
+use crate::auth::invites::InviteService;
+use crate::auth::oidc::OidcService;
+use crate::auth::passkeys::PasskeyService;
 use crate::auth::service::AuthService;
+use crate::auth::tokens::ApiTokenService;
+use crate::board::filters::BoardFilterService;
+use crate::board::members::BoardMemberService;
+use crate::board::roles::BoardRoleService;
 use crate::board::service::BoardService;
+use crate::challenge::service::ChallengeService;
 use crate::chat::service::ChatService;
+use crate::crypto::data_keys::DataKeyService;
+use crate::crypto::kek;
+use crate::crypto::rotation::KeyRotationService;
 use crate::crypto::service::CryptoService;
+use crate::crypto::signing::SigningService;
+use crate::e2ee::service::E2eeService;
+use crate::invite_links::service::InviteLinkService;
+use crate::jobs::service::JobService;
+use crate::mail::mailer;
+use crate::moderation::content_filters::ContentFilterService;
+use crate::moderation::reports::ReportService;
+use crate::user::blocks::BlockService;
+use crate::user::contacts::ContactService;
+use crate::user::service::UserService;
 use crate::web::routes;
 
 pub struct App {
@@ -18,9 +42,31 @@ pub struct App {
     db: Arc<Database>,
     matrix_client: Arc<MatrixClient>,
     auth_service: Arc<AuthService>,
+    passkey_service: Arc<PasskeyService>,
+    account_email_service: Arc<AccountEmailService>,
+    oidc_service: Arc<OidcService>,
+    api_token_service: Arc<ApiTokenService>,
+    invite_service: Arc<InviteService>,
     board_service: Arc<BoardService>,
+    board_member_service: Arc<BoardMemberService>,
+    board_role_service: Arc<BoardRoleService>,
+    board_filter_service: Arc<BoardFilterService>,
+    content_filter_service: Arc<ContentFilterService>,
     chat_service: Arc<ChatService>,
+    user_service: Arc<UserService>,
+    contact_service: Arc<ContactService>,
+    block_service: Arc<BlockService>,
+    report_service: Arc<ReportService>,
+    invite_link_service: Arc<InviteLinkService>,
+    challenge_service: Arc<ChallengeService>,
     crypto_service: Arc<CryptoService>,
+    signing_service: Arc<SigningService>,
+    data_key_service: Arc<DataKeyService>,
+    e2ee_service: Arc<E2eeService>,
+    job_service: Arc<JobService>,
+    key_rotation_service: Arc<KeyRotationService>,
+    export_service: Arc<ExportService>,
+    account_deletion_service: Arc<AccountDeletionService>,
 }
 
 impl App {
@@ -35,36 +81,224 @@ impl App {
 
         // Initialize crypto service
         let crypto_service = Arc::new(CryptoService::new(&config.crypto)?);
+        let signing_service = Arc::new(SigningService::new(&config.crypto)?);
+
+        // Warn when password hashing would make logins noticeably slow
+        let hash_duration = crypto_service.benchmark_password_hash()?;
+        if hash_duration.as_millis() > config.crypto.password_hash_target_ms as u128 {
+            warn!(
+                "Password hashing takes {} ms, above the {} ms target; consider lowering PASSWORD_HASH_MEMORY_KIB or PASSWORD_HASH_ITERATIONS",
+                hash_duration.as_millis(),
+                config.crypto.password_hash_target_ms
+            );
+        } else {
+            info!("Password hashing takes {} ms", hash_duration.as_millis());
+        }
+
+        // Initialize envelope encryption
+        let kek_provider = kek::from_config(&config.crypto)?;
+        let data_key_service = Arc::new(DataKeyService::new(
+            Arc::clone(&db),
+            Arc::clone(&crypto_service),
+            kek_provider,
+        ));
 
         // Initialize Matrix client
         let matrix_client = Arc::new(MatrixClient::new(&config.matrix).await?);
 
         // Initialize services
+        let challenge_service = Arc::new(ChallengeService::new(Arc::clone(&db), &config.security)?);
+
         let auth_service = Arc::new(AuthService::new(
+            Arc::clone(&db),
+            Arc::clone(&crypto_service),
+            Arc::clone(&data_key_service),
+            Arc::clone(&challenge_service),
+            config.security.clone(),
+        ));
+
+        let passkey_service = Arc::new(PasskeyService::new(
+            Arc::clone(&db),
+            Arc::clone(&auth_service),
+            &config.security,
+        )?);
+
+        let account_email_service = Arc::new(AccountEmailService::new(
+            Arc::clone(&db),
+            Arc::clone(&crypto_service),
+            mailer::from_config(&config.mail)?,
+            &config.server.base_url,
+            config.security.clone(),
+        ));
+
+        let oidc_service = Arc::new(OidcService::new(
+            Arc::clone(&db),
+            Arc::clone(&auth_service),
+            Arc::clone(&crypto_service),
+            &config.security.oidc_providers,
+            &config.server.base_url,
+        )?);
+
+        let api_token_service = Arc::new(ApiTokenService::new(
+            Arc::clone(&db),
+            Arc::clone(&crypto_service),
+            Arc::clone(&auth_service),
+        ));
+
+        let invite_service = Arc::new(InviteService::new(
             Arc::clone(&db),
             Arc::clone(&crypto_service),
             config.security.clone(),
         ));
 
+        let content_filter_service = Arc::new(ContentFilterService::new(
+            Arc::clone(&db),
+            config.filters.clone(),
+        ));
+
         let board_service = Arc::new(BoardService::new(
             Arc::clone(&db),
             Arc::clone(&matrix_client),
+            Arc::clone(&signing_service),
+            Arc::clone(&challenge_service),
+            Arc::clone(&content_filter_service),
+            config.boards.clone(),
+        ));
+
+        let e2ee_service = Arc::new(E2eeService::new(Arc::clone(&db)));
+
+        let user_service = Arc::new(UserService::new(
+            Arc::clone(&db),
+            Arc::clone(&matrix_client),
+        ));
+
+        let contact_service = Arc::new(ContactService::new(
+            Arc::clone(&db),
+            Arc::clone(&user_service),
+        ));
+
+        let block_service = Arc::new(BlockService::new(
+            Arc::clone(&db),
+            Arc::clone(&matrix_client),
+            Arc::clone(&user_service),
         ));
 
         let chat_service = Arc::new(ChatService::new(
             Arc::clone(&db),
             Arc::clone(&matrix_client),
             Arc::clone(&crypto_service),
+            Arc::clone(&data_key_service),
+            Arc::clone(&e2ee_service),
+            Arc::clone(&signing_service),
+            Arc::clone(&user_service),
+            Arc::clone(&block_service),
+        ));
+
+        let report_service = Arc::new(ReportService::new(
+            Arc::clone(&db),
+            Arc::clone(&chat_service),
+            Arc::clone(&block_service),
+        ));
+
+        let board_member_service = Arc::new(BoardMemberService::new(
+            Arc::clone(&db),
+            Arc::clone(&board_service),
+            Arc::clone(&user_service),
+        ));
+
+        let board_role_service = Arc::new(BoardRoleService::new(
+            Arc::clone(&db),
+            Arc::clone(&matrix_client),
+            Arc::clone(&board_service),
+            Arc::clone(&user_service),
+        ));
+
+        let board_filter_service = Arc::new(BoardFilterService::new(
+            Arc::clone(&board_service),
+            Arc::clone(&content_filter_service),
+        ));
+
+        let invite_link_service = Arc::new(InviteLinkService::new(
+            Arc::clone(&db),
+            Arc::clone(&crypto_service),
+            Arc::clone(&chat_service),
+            Arc::clone(&board_service),
+            Arc::clone(&user_service),
+        ));
+
+        let job_service = Arc::new(JobService::new(Arc::clone(&db)));
+
+        let key_rotation_service = Arc::new(KeyRotationService::new(
+            Arc::clone(&db),
+            Arc::clone(&crypto_service),
+            Arc::clone(&data_key_service),
+            Arc::clone(&job_service),
+        ));
+
+        let export_service = Arc::new(ExportService::new(
+            Arc::clone(&db),
+            Arc::clone(&matrix_client),
+            Arc::clone(&auth_service),
+            Arc::clone(&user_service),
+            Arc::clone(&contact_service),
+            Arc::clone(&block_service),
+            Arc::clone(&api_token_service),
+            Arc::clone(&board_service),
+            Arc::clone(&chat_service),
+            Arc::clone(&signing_service),
+        ));
+
+        let account_deletion_service = Arc::new(AccountDeletionService::new(
+            Arc::clone(&db),
+            Arc::clone(&auth_service),
+            Arc::clone(&board_service),
+            Arc::clone(&chat_service),
+            Arc::clone(&job_service),
         ));
 
+        // Promote configured administrators
+        auth_service.sync_admins().await?;
+
+        // Jobs can't survive a restart, don't leave them reported as running
+        job_service.fail_interrupted().await?;
+
+        if config.crypto.reencrypt_on_startup {
+            key_rotation_service.start_reencryption(None).await?;
+        }
+
+        // Carry out account deletions whose grace period has passed
+        account_deletion_service.start_scheduler();
+
         Ok(Self {
             config,
             db,
             matrix_client,
             auth_service,
+            passkey_service,
+            account_email_service,
+            oidc_service,
+            api_token_service,
+            invite_service,
             board_service,
+            board_member_service,
+            board_role_service,
+            board_filter_service,
+            content_filter_service,
             chat_service,
+            user_service,
+            contact_service,
+            block_service,
+            report_service,
+            invite_link_service,
+            challenge_service,
             crypto_service,
+            signing_service,
+            data_key_service,
+            e2ee_service,
+            job_service,
+            key_rotation_service,
+            export_service,
+            account_deletion_service,
         })
     }
 
@@ -73,9 +307,31 @@ impl App {
             db: self.db,
             matrix_client: self.matrix_client,
             auth_service: self.auth_service,
+            passkey_service: self.passkey_service,
+            account_email_service: self.account_email_service,
+            oidc_service: self.oidc_service,
+            api_token_service: self.api_token_service,
+            invite_service: self.invite_service,
             board_service: self.board_service,
+            board_member_service: self.board_member_service,
+            board_role_service: self.board_role_service,
+            board_filter_service: self.board_filter_service,
+            content_filter_service: self.content_filter_service,
             chat_service: self.chat_service,
+            user_service: self.user_service,
+            contact_service: self.contact_service,
+            block_service: self.block_service,
+            report_service: self.report_service,
+            invite_link_service: self.invite_link_service,
+            challenge_service: self.challenge_service,
             crypto_service: self.crypto_service,
+            signing_service: self.signing_service,
+            data_key_service: self.data_key_service,
+            e2ee_service: self.e2ee_service,
+            job_service: self.job_service,
+            key_rotation_service: self.key_rotation_service,
+            export_service: self.export_service,
+            account_deletion_service: self.account_deletion_service,
             config: self.config.clone(),
         };
 
@@ -96,8 +352,30 @@ pub struct AppState {
     pub db: Arc<Database>,
     pub matrix_client: Arc<MatrixClient>,
     pub auth_service: Arc<AuthService>,
+    pub passkey_service: Arc<PasskeyService>,
+    pub account_email_service: Arc<AccountEmailService>,
+    pub oidc_service: Arc<OidcService>,
+    pub api_token_service: Arc<ApiTokenService>,
+    pub invite_service: Arc<InviteService>,
     pub board_service: Arc<BoardService>,
+    pub board_member_service: Arc<BoardMemberService>,
+    pub board_role_service: Arc<BoardRoleService>,
+    pub board_filter_service: Arc<BoardFilterService>,
+    pub content_filter_service: Arc<ContentFilterService>,
     pub chat_service: Arc<ChatService>,
+    pub user_service: Arc<UserService>,
+    pub contact_service: Arc<ContactService>,
+    pub block_service: Arc<BlockService>,
+    pub report_service: Arc<ReportService>,
+    pub invite_link_service: Arc<InviteLinkService>,
+    pub challenge_service: Arc<ChallengeService>,
     pub crypto_service: Arc<CryptoService>,
+    pub signing_service: Arc<SigningService>,
+    pub data_key_service: Arc<DataKeyService>,
+    pub e2ee_service: Arc<E2eeService>,
+    pub job_service: Arc<JobService>,
+    pub key_rotation_service: Arc<KeyRotationService>,
+    pub export_service: Arc<ExportService>,
+    pub account_deletion_service: Arc<AccountDeletionService>,
     pub config: Config,
 }
\ No newline at end of file
@@ -9,6 +9,9 @@ pub struct Config {
     pub database: DatabaseConfig,
     pub crypto: CryptoConfig,
     pub security: SecurityConfig,
+    pub mail: MailConfig,
+    pub boards: BoardsConfig,
+    pub filters: FiltersConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,14 +38,143 @@ pub struct DatabaseConfig {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CryptoConfig {
     pub encryption_key: String,
+    pub encryption_key_id: String,
+    pub previous_encryption_keys: Vec<String>,
+    pub reencrypt_on_startup: bool,
+    pub require_aad: bool,
+    pub kek_provider: String,
+    pub master_key: Option<String>,
+    pub master_key_file: Option<String>,
+    pub kms_key_name: String,
     pub signing_key: String,
+    pub signing_key_id: String,
+    pub retired_signing_keys: Vec<String>,
+    pub search_index_key: String,
+    pub password_memory_kib: u32,
+    pub password_iterations: u32,
+    pub password_parallelism: u32,
+    pub password_pepper: Option<String>,
+    pub password_hash_target_ms: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SecurityConfig {
     pub session_secret: String,
-    pub bcrypt_cost: u32,
     pub rate_limit_per_minute: u32,
+    pub admin_usernames: Vec<String>,
+    pub totp_issuer: String,
+    pub webauthn_rp_id: String,
+    pub webauthn_rp_name: String,
+    pub webauthn_origin: String,
+    pub email_verification_ttl_hours: i64,
+    pub password_reset_ttl_minutes: i64,
+    pub oidc_providers: Vec<OidcProviderConfig>,
+    pub registration_mode: RegistrationMode,
+    pub allow_anonymous_registration: bool,
+    pub invite_codes_per_user: i64,
+    pub challenge_kind: String,
+    pub challenge_difficulty: u8,
+    pub challenge_ttl_seconds: i64,
+    pub account_deletion_grace_days: i64,
+}
+
+/// Who may create a registered account
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RegistrationMode {
+    /// Anyone
+    Open,
+    /// Only with a valid invite code
+    Invite,
+    /// Anyone, but an admin must approve the account before it can log in.
+    /// A valid invite code skips the queue.
+    Approval,
+    /// Nobody
+    Closed,
+}
+
+impl RegistrationMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "open" => Some(RegistrationMode::Open),
+            "invite" => Some(RegistrationMode::Invite),
+            "approval" => Some(RegistrationMode::Approval),
+            "closed" => Some(RegistrationMode::Closed),
+            _ => None,
+        }
+    }
+}
+
+/// An OpenID Connect identity provider users can log in with
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OidcProviderConfig {
+    /// Short name used in login URLs
+    pub name: String,
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub scopes: String,
+    /// ID token claim listing the user's groups
+    pub groups_claim: String,
+    /// IdP groups mapped to the names of boards their members moderate
+    pub moderator_groups: Vec<(String, String)>,
+}
+
+/// Who may create boards, and how many
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BoardsConfig {
+    pub creation_mode: BoardCreationMode,
+    /// Days a non-admin account must exist before creating a board
+    pub min_account_age_days: i64,
+    /// Threads and replies a non-admin account must have written first
+    pub min_karma: i64,
+    /// Boards a non-admin may own at once (0 = no limit)
+    pub boards_per_user: i64,
+    /// Names only admins can give a board, on top of the built-in ones
+    pub reserved_names: Vec<String>,
+    /// Words no board name may contain
+    pub blocked_words: Vec<String>,
+}
+
+/// Who may create boards
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BoardCreationMode {
+    /// Registered accounts meeting the age, karma and quota limits
+    Registered,
+    /// Only admins
+    Admins,
+}
+
+impl BoardCreationMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "registered" => Some(BoardCreationMode::Registered),
+            "admins" => Some(BoardCreationMode::Admins),
+            _ => None,
+        }
+    }
+}
+
+/// Spam heuristics applied to every new thread and reply
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FiltersConfig {
+    /// Threads and replies an account may post within `flood_window_seconds` (0 = no limit)
+    pub flood_max_posts: i64,
+    pub flood_window_seconds: i64,
+    /// How far back identical content counts as a duplicate (0 = off)
+    pub duplicate_window_seconds: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MailConfig {
+    pub transport: String,
+    pub from: String,
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub smtp_username: Option<String>,
+    pub smtp_password: Option<String>,
+    pub file_dir: String,
 }
 
 impl Config {
@@ -75,23 +207,221 @@ impl Config {
             crypto: CryptoConfig {
                 encryption_key: env::var("ENCRYPTION_KEY")
                     .unwrap_or_else(|_| "your-32-char-encryption-key-here".to_string()),
+                encryption_key_id: env::var("ENCRYPTION_KEY_ID")
+                    .unwrap_or_else(|_| "k1".to_string()),
+                previous_encryption_keys: env::var("ENCRYPTION_PREVIOUS_KEYS")
+                    .map(|keys| {
+                        keys.split(',')
+                            .map(|key| key.trim().to_string())
+                            .filter(|key| !key.is_empty())
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                reencrypt_on_startup: env::var("ENCRYPTION_REENCRYPT_ON_STARTUP")
+                    .unwrap_or_else(|_| "false".to_string())
+                    .parse()
+                    .unwrap_or(false),
+                require_aad: env::var("ENCRYPTION_REQUIRE_AAD")
+                    .unwrap_or_else(|_| "true".to_string())
+                    .parse()
+                    .unwrap_or(true),
+                kek_provider: env::var("KEK_PROVIDER")
+                    .unwrap_or_else(|_| "env".to_string()),
+                master_key: env::var("MASTER_KEY").ok(),
+                master_key_file: env::var("MASTER_KEY_FILE").ok(),
+                kms_key_name: env::var("KMS_KEY_NAME")
+                    .unwrap_or_else(|_| "amogchan-master".to_string()),
                 signing_key: env::var("SIGNING_KEY")
-                    .unwrap_or_else(|_| "your-signing-key-here".to_string()),
+                    .unwrap_or_else(|_| "your-base64-encoded-32-byte-signing-key-here".to_string()),
+                signing_key_id: env::var("SIGNING_KEY_ID")
+                    .unwrap_or_else(|_| "s1".to_string()),
+                retired_signing_keys: env::var("SIGNING_RETIRED_PUBLIC_KEYS")
+                    .map(|keys| {
+                        keys.split(',')
+                            .map(|key| key.trim().to_string())
+                            .filter(|key| !key.is_empty())
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                search_index_key: env::var("SEARCH_INDEX_KEY")
+                    .unwrap_or_else(|_| "your-base64-encoded-32-byte-search-index-key-here".to_string()),
+                password_memory_kib: env::var("PASSWORD_HASH_MEMORY_KIB")
+                    .unwrap_or_else(|_| "19456".to_string())
+                    .parse()
+                    .unwrap_or(19456),
+                password_iterations: env::var("PASSWORD_HASH_ITERATIONS")
+                    .unwrap_or_else(|_| "2".to_string())
+                    .parse()
+                    .unwrap_or(2),
+                password_parallelism: env::var("PASSWORD_HASH_PARALLELISM")
+                    .unwrap_or_else(|_| "1".to_string())
+                    .parse()
+                    .unwrap_or(1),
+                password_pepper: env::var("PASSWORD_PEPPER").ok().filter(|pepper| !pepper.is_empty()),
+                password_hash_target_ms: env::var("PASSWORD_HASH_TARGET_MS")
+                    .unwrap_or_else(|_| "500".to_string())
+                    .parse()
+                    .unwrap_or(500),
             },
             security: SecurityConfig {
                 session_secret: env::var("SESSION_SECRET")
                     .unwrap_or_else(|_| "your-session-secret-here".to_string()),
-                bcrypt_cost: env::var("BCRYPT_COST")
-                    .unwrap_or_else(|_| "12".to_string())
-                    .parse()
-                    .unwrap_or(12),
                 rate_limit_per_minute: env::var("RATE_LIMIT_PER_MINUTE")
                     .unwrap_or_else(|_| "60".to_string())
                     .parse()
                     .unwrap_or(60),
+                admin_usernames: env::var("ADMIN_USERNAMES")
+                    .map(|names| {
+                        names.split(',')
+                            .map(|name| name.trim().to_string())
+                            .filter(|name| !name.is_empty())
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                totp_issuer: env::var("TOTP_ISSUER")
+                    .unwrap_or_else(|_| "amogchan".to_string()),
+                webauthn_rp_id: env::var("WEBAUTHN_RP_ID")
+                    .unwrap_or_else(|_| "localhost".to_string()),
+                webauthn_rp_name: env::var("WEBAUTHN_RP_NAME")
+                    .unwrap_or_else(|_| "amogchan".to_string()),
+                webauthn_origin: env::var("WEBAUTHN_ORIGIN")
+                    .or_else(|_| env::var("BASE_URL"))
+                    .unwrap_or_else(|_| "http://localhost:3000".to_string()),
+                email_verification_ttl_hours: env::var("EMAIL_VERIFICATION_TTL_HOURS")
+                    .unwrap_or_else(|_| "24".to_string())
+                    .parse()
+                    .unwrap_or(24),
+                password_reset_ttl_minutes: env::var("PASSWORD_RESET_TTL_MINUTES")
+                    .unwrap_or_else(|_| "30".to_string())
+                    .parse()
+                    .unwrap_or(30),
+                oidc_providers: load_oidc_providers(),
+                registration_mode: RegistrationMode::parse(
+                    &env::var("REGISTRATION_MODE").unwrap_or_else(|_| "open".to_string()),
+                )
+                .ok_or_else(|| anyhow::anyhow!("REGISTRATION_MODE must be open, invite, approval or closed"))?,
+                allow_anonymous_registration: env::var("ALLOW_ANONYMOUS_REGISTRATION")
+                    .unwrap_or_else(|_| "true".to_string())
+                    .parse()
+                    .unwrap_or(true),
+                invite_codes_per_user: env::var("INVITE_CODES_PER_USER")
+                    .unwrap_or_else(|_| "3".to_string())
+                    .parse()
+                    .unwrap_or(3),
+                challenge_kind: env::var("CHALLENGE_KIND")
+                    .unwrap_or_else(|_| "pow".to_string()),
+                challenge_difficulty: env::var("CHALLENGE_DIFFICULTY")
+                    .unwrap_or_else(|_| "20".to_string())
+                    .parse()
+                    .unwrap_or(20),
+                challenge_ttl_seconds: env::var("CHALLENGE_TTL_SECONDS")
+                    .unwrap_or_else(|_| "300".to_string())
+                    .parse()
+                    .unwrap_or(300),
+                account_deletion_grace_days: env::var("ACCOUNT_DELETION_GRACE_DAYS")
+                    .unwrap_or_else(|_| "14".to_string())
+                    .parse()
+                    .unwrap_or(14),
+            },
+            mail: MailConfig {
+                transport: env::var("MAIL_TRANSPORT")
+                    .unwrap_or_else(|_| "log".to_string()),
+                from: env::var("MAIL_FROM")
+                    .unwrap_or_else(|_| "amogchan <noreply@localhost>".to_string()),
+                smtp_host: env::var("SMTP_HOST")
+                    .unwrap_or_else(|_| "localhost".to_string()),
+                smtp_port: env::var("SMTP_PORT")
+                    .unwrap_or_else(|_| "587".to_string())
+                    .parse()
+                    .unwrap_or(587),
+                smtp_username: env::var("SMTP_USERNAME").ok().filter(|username| !username.is_empty()),
+                smtp_password: env::var("SMTP_PASSWORD").ok().filter(|password| !password.is_empty()),
+                file_dir: env::var("MAIL_FILE_DIR")
+                    .unwrap_or_else(|_| "./mail".to_string()),
+            },
+            boards: BoardsConfig {
+                creation_mode: BoardCreationMode::parse(
+                    &env::var("BOARD_CREATION").unwrap_or_else(|_| "registered".to_string()),
+                )
+                .ok_or_else(|| anyhow::anyhow!("BOARD_CREATION must be registered or admins"))?,
+                min_account_age_days: env::var("BOARD_CREATION_MIN_ACCOUNT_AGE_DAYS")
+                    .unwrap_or_else(|_| "7".to_string())
+                    .parse()
+                    .unwrap_or(7),
+                min_karma: env::var("BOARD_CREATION_MIN_KARMA")
+                    .unwrap_or_else(|_| "10".to_string())
+                    .parse()
+                    .unwrap_or(10),
+                boards_per_user: env::var("BOARDS_PER_USER")
+                    .unwrap_or_else(|_| "3".to_string())
+                    .parse()
+                    .unwrap_or(3),
+                reserved_names: env::var("RESERVED_BOARD_NAMES")
+                    .map(|names| {
+                        names.split(',')
+                            .map(|name| name.trim().to_lowercase())
+                            .filter(|name| !name.is_empty())
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                blocked_words: env::var("BLOCKED_BOARD_NAME_WORDS")
+                    .map(|words| {
+                        words.split(',')
+                            .map(|word| word.trim().to_lowercase())
+                            .filter(|word| !word.is_empty())
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            },
+            filters: FiltersConfig {
+                flood_max_posts: env::var("FLOOD_MAX_POSTS")
+                    .unwrap_or_else(|_| "10".to_string())
+                    .parse()
+                    .unwrap_or(10),
+                flood_window_seconds: env::var("FLOOD_WINDOW_SECONDS")
+                    .unwrap_or_else(|_| "60".to_string())
+                    .parse()
+                    .unwrap_or(60),
+                duplicate_window_seconds: env::var("DUPLICATE_WINDOW_SECONDS")
+                    .unwrap_or_else(|_| "3600".to_string())
+                    .parse()
+                    .unwrap_or(3600),
             },
         };
 
         Ok(config)
     }
-}
\ No newline at end of file
+}
+
+/// Providers listed in `OIDC_PROVIDERS`, each configured through
+/// `OIDC_<NAME>_*` variables
+fn load_oidc_providers() -> Vec<OidcProviderConfig> {
+    let names = env::var("OIDC_PROVIDERS").unwrap_or_default();
+
+    names.split(',')
+        .map(|name| name.trim().to_lowercase())
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            let prefix = format!("OIDC_{}_", name.to_uppercase().replace('-', "_"));
+            let var = |key: &str| env::var(format!("{}{}", prefix, key)).ok().filter(|value| !value.is_empty());
+
+            OidcProviderConfig {
+                issuer: var("ISSUER").unwrap_or_default(),
+                client_id: var("CLIENT_ID").unwrap_or_default(),
+                client_secret: var("CLIENT_SECRET"),
+                scopes: var("SCOPES").unwrap_or_else(|| "openid profile email".to_string()),
+                groups_claim: var("GROUPS_CLAIM").unwrap_or_else(|| "groups".to_string()),
+                // Formatted as "group:board,group:board"
+                moderator_groups: var("MODERATOR_GROUPS")
+                    .map(|groups| {
+                        groups.split(',')
+                            .filter_map(|entry| entry.trim().rsplit_once(':'))
+                            .map(|(group, board)| (group.trim().to_string(), board.trim().to_string()))
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                name,
+            }
+        })
+        .collect()
+}
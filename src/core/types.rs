@@ -7,13 +7,165 @@ pub struct User {
     pub id: Uuid,
     pub username: String,
     pub email: Option<String>,
+    pub email_verified: bool,
     pub matrix_user_id: String,
     pub avatar_url: Option<String>,
     pub is_anonymous: bool,
+    pub is_admin: bool,
     pub created_at: DateTime<Utc>,
     pub last_seen: Option<DateTime<Utc>>,
 }
 
+/// What other users may see of an account
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PublicUser {
+    pub id: Uuid,
+    pub username: String,
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub avatar_url: Option<String>,
+    pub matrix_user_id: String,
+    pub is_anonymous: bool,
+    pub created_at: DateTime<Utc>,
+    /// Only present if the account's privacy settings allow the viewer to see it
+    pub last_seen: Option<DateTime<Utc>>,
+}
+
+/// Who a privacy setting applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Audience {
+    Everyone,
+    /// Registered accounts, not anonymous ones
+    Registered,
+    Nobody,
+}
+
+impl Audience {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Audience::Everyone => "everyone",
+            Audience::Registered => "registered",
+            Audience::Nobody => "nobody",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "everyone" => Audience::Everyone,
+            "registered" => Audience::Registered,
+            _ => Audience::Nobody,
+        }
+    }
+
+    /// Whether a viewer other than the account itself is included
+    pub fn includes(&self, viewer: &User) -> bool {
+        match self {
+            Audience::Everyone => true,
+            Audience::Registered => !viewer.is_anonymous,
+            Audience::Nobody => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrivacySettings {
+    /// Who can see when the account was last active
+    pub last_seen_visibility: Audience,
+    /// Who can start a direct message with the account
+    pub dm_policy: Audience,
+    /// Whether the account shows up in user search
+    pub discoverable: bool,
+}
+
+/// The account's own view of its profile
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub avatar_url: Option<String>,
+    pub privacy: PrivacySettings,
+    pub content: ContentPreferences,
+}
+
+/// What the account wants to see on boards
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContentPreferences {
+    /// Whether NSFW boards are shown; turning this on confirms the account
+    /// holder is an adult
+    pub show_nsfw: bool,
+    /// Whether clients should blur images on NSFW boards until clicked
+    pub blur_images: bool,
+    /// Whether clients should collapse threads and posts marked as spoilers
+    pub hide_spoilers: bool,
+}
+
+/// Omitted fields stay unchanged
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateContentPreferencesRequest {
+    pub show_nsfw: Option<bool>,
+    pub blur_images: Option<bool>,
+    pub hide_spoilers: Option<bool>,
+}
+
+/// Which boards to list
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentRating {
+    Sfw,
+    Nsfw,
+}
+
+/// Omitted fields stay unchanged, empty strings clear them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateProfileRequest {
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+}
+
+/// Omitted fields stay unchanged
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdatePrivacyRequest {
+    pub last_seen_visibility: Option<Audience>,
+    pub dm_policy: Option<Audience>,
+    pub discoverable: Option<bool>,
+}
+
+/// Someone on a user's contact list
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Contact {
+    pub user: PublicUser,
+    /// Only visible to the list's owner
+    pub nickname: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddContactRequest {
+    /// User ID, username or Matrix ID
+    pub user: String,
+    pub nickname: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateContactRequest {
+    /// Empty or omitted clears the nickname
+    pub nickname: Option<String>,
+}
+
+/// Someone a user has blocked
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockedUser {
+    pub user: PublicUser,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockUserRequest {
+    /// User ID, username or Matrix ID
+    pub user: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct Board {
     pub id: Uuid,
@@ -22,9 +174,138 @@ pub struct Board {
     pub description: Option<String>,
     pub matrix_room_id: String,
     pub is_nsfw: bool,
+    /// Only members can see and post
     pub is_private: bool,
+    /// Whether the board's Matrix room is end-to-end encrypted
+    pub is_encrypted: bool,
     pub created_at: DateTime<Utc>,
     pub created_by: Uuid,
+    /// Manages the board's settings, moderators and members; `None` once the
+    /// owner's account was deleted with no moderator to take over
+    pub owner_id: Option<Uuid>,
+    #[sqlx(skip)]
+    pub settings: BoardSettings,
+}
+
+/// A moderator of a board
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BoardModerator {
+    pub user: PublicUser,
+    /// Where the role came from: "manual" when appointed by the board's
+    /// owner, "oidc:<provider>" when mapped from identity provider groups
+    pub source: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// How a board is run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BoardSettings {
+    /// Posting rules, shown to posters
+    pub rules: Option<String>,
+    /// Longest thread or reply, in characters
+    pub max_post_length: i64,
+    /// Whether new threads need an image
+    pub require_image_on_op: bool,
+    /// Whether anonymous accounts may post
+    pub allow_anonymous: bool,
+    /// Whether new threads and replies hide their author
+    pub force_anonymous: bool,
+    /// Seconds a user waits between threads and replies on the board
+    pub post_cooldown_seconds: i64,
+    /// Image file extensions allowed, any when empty
+    pub allowed_file_types: Vec<String>,
+}
+
+impl Default for BoardSettings {
+    fn default() -> Self {
+        Self {
+            rules: None,
+            max_post_length: 10_000,
+            require_image_on_op: false,
+            allow_anonymous: true,
+            force_anonymous: false,
+            post_cooldown_seconds: 0,
+            allowed_file_types: Vec::new(),
+        }
+    }
+}
+
+/// Board settings to change; omitted fields stay unchanged
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BoardSettingsUpdate {
+    /// An empty string clears the rules
+    pub rules: Option<String>,
+    pub max_post_length: Option<i64>,
+    pub require_image_on_op: Option<bool>,
+    pub allow_anonymous: Option<bool>,
+    pub force_anonymous: Option<bool>,
+    pub post_cooldown_seconds: Option<i64>,
+    /// An empty list allows any file type
+    pub allowed_file_types: Option<Vec<String>>,
+}
+
+/// Omitted fields stay unchanged
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateBoardRequest {
+    pub title: Option<String>,
+    /// An empty string clears the description
+    pub description: Option<String>,
+    pub is_nsfw: Option<bool>,
+    #[serde(flatten)]
+    pub settings: BoardSettingsUpdate,
+}
+
+/// A member of a private board
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BoardMember {
+    pub user: PublicUser,
+    pub joined_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddBoardMemberRequest {
+    /// User ID, username or Matrix ID
+    pub user: String,
+}
+
+/// Someone asking to join a private board
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BoardJoinRequest {
+    pub user: PublicUser,
+    pub message: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateBoardJoinRequest {
+    /// Shown to the board's managers
+    pub message: Option<String>,
+}
+
+/// What happens to a deleted account's threads and posts on a board
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeletedAuthorContent {
+    /// Keep the content, attributed to the account's tombstone
+    Anonymize,
+    /// Delete the content and redact its Matrix events
+    Remove,
+}
+
+impl DeletedAuthorContent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeletedAuthorContent::Anonymize => "anonymize",
+            DeletedAuthorContent::Remove => "remove",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "remove" => DeletedAuthorContent::Remove,
+            _ => DeletedAuthorContent::Anonymize,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
@@ -37,10 +318,24 @@ pub struct Thread {
     pub matrix_event_id: String,
     pub is_pinned: bool,
     pub is_locked: bool,
+    pub is_spoiler: bool,
     pub created_at: DateTime<Utc>,
     pub created_by: Uuid,
     pub reply_count: i32,
     pub last_reply_at: Option<DateTime<Utc>>,
+    pub signature: Option<String>,
+    pub signing_key_id: Option<String>,
+    /// Posted on a forced-anonymous board; `created_by` is nil outside
+    /// the server
+    #[serde(skip)]
+    pub author_hidden: bool,
+}
+
+impl Thread {
+    /// The author as shown and signed: nil if hidden
+    pub fn public_author(&self) -> Uuid {
+        if self.author_hidden { Uuid::nil() } else { self.created_by }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
@@ -52,8 +347,22 @@ pub struct Post {
     pub image_url: Option<String>,
     pub matrix_event_id: String,
     pub reply_to: Option<Uuid>,
+    pub is_spoiler: bool,
     pub created_at: DateTime<Utc>,
     pub created_by: Uuid,
+    pub signature: Option<String>,
+    pub signing_key_id: Option<String>,
+    /// Posted on a forced-anonymous board; `created_by` is nil outside
+    /// the server
+    #[serde(skip)]
+    pub author_hidden: bool,
+}
+
+impl Post {
+    /// The author as shown and signed: nil if hidden
+    pub fn public_author(&self) -> Uuid {
+        if self.author_hidden { Uuid::nil() } else { self.created_by }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
@@ -63,10 +372,189 @@ pub struct Chat {
     pub matrix_room_id: String,
     pub is_group: bool,
     pub is_encrypted: bool,
+    pub server_side_encryption: bool,
+    pub search_enabled: bool,
     pub created_at: DateTime<Utc>,
     pub created_by: Uuid,
 }
 
+/// A participant of a chat and their role
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatMember {
+    pub user: PublicUser,
+    pub is_admin: bool,
+    pub is_owner: bool,
+}
+
+/// A direct message from someone not in the recipient's contacts, waiting
+/// to be accepted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatRequest {
+    pub chat: Chat,
+    pub from: PublicUser,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportChatRequest {
+    pub reason: String,
+    /// Also block the sender
+    #[serde(default)]
+    pub block: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportStatus {
+    Open,
+    Resolved,
+}
+
+impl ReportStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReportStatus::Open => "open",
+            ReportStatus::Resolved => "resolved",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "resolved" => ReportStatus::Resolved,
+            _ => ReportStatus::Open,
+        }
+    }
+}
+
+/// A user reported to the admins
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Report {
+    pub id: Uuid,
+    pub reporter_id: Uuid,
+    pub reported_user_id: Uuid,
+    pub chat_id: Option<Uuid>,
+    pub reason: String,
+    pub status: ReportStatus,
+    pub created_at: DateTime<Utc>,
+    pub resolved_by: Option<Uuid>,
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+/// What a content filter rule or moderation log entry is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterKind {
+    /// A regex matched against titles and content
+    Word,
+    /// A domain matched against links and image URLs
+    Link,
+    /// Content already posted recently
+    Duplicate,
+    /// Too many posts in a short time
+    Flood,
+}
+
+impl FilterKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FilterKind::Word => "word",
+            FilterKind::Link => "link",
+            FilterKind::Duplicate => "duplicate",
+            FilterKind::Flood => "flood",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "link" => FilterKind::Link,
+            "duplicate" => FilterKind::Duplicate,
+            "flood" => FilterKind::Flood,
+            _ => FilterKind::Word,
+        }
+    }
+}
+
+/// What a filter does when it matches. Word rules replace, reject or flag;
+/// link rules allow or deny.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterAction {
+    /// Rewrite the match and post the result
+    Replace,
+    /// Refuse the post
+    Reject,
+    /// Post it, but log it for moderators to review
+    Flag,
+    /// Once any allow rule applies, only links to allowed domains can be posted
+    Allow,
+    /// Refuse posts linking to the domain
+    Deny,
+}
+
+impl FilterAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FilterAction::Replace => "replace",
+            FilterAction::Reject => "reject",
+            FilterAction::Flag => "flag",
+            FilterAction::Allow => "allow",
+            FilterAction::Deny => "deny",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "replace" => FilterAction::Replace,
+            "flag" => FilterAction::Flag,
+            "allow" => FilterAction::Allow,
+            "deny" => FilterAction::Deny,
+            _ => FilterAction::Reject,
+        }
+    }
+}
+
+/// A word or link filter rule, for one board or (without `board_id`) all of them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContentFilter {
+    pub id: Uuid,
+    pub board_id: Option<Uuid>,
+    pub kind: FilterKind,
+    /// A regex for word rules, a domain (subdomains included) for link rules
+    pub pattern: String,
+    pub action: FilterAction,
+    /// What `replace` rules put in place of a match
+    pub replacement: Option<String>,
+    pub created_by: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateContentFilterRequest {
+    pub kind: FilterKind,
+    pub pattern: String,
+    pub action: FilterAction,
+    /// Defaults to `***`
+    pub replacement: Option<String>,
+}
+
+/// A filter hit. Rejected posts have neither `thread_id` nor `post_id`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModerationLogEntry {
+    pub id: Uuid,
+    pub board_id: Uuid,
+    pub user_id: Uuid,
+    pub thread_id: Option<Uuid>,
+    pub post_id: Option<Uuid>,
+    /// The rule that matched, for word and link filters
+    pub filter_id: Option<Uuid>,
+    pub kind: FilterKind,
+    pub action: FilterAction,
+    /// What matched: the text, the domain, or the number of recent posts
+    pub detail: String,
+    /// The start of the original content
+    pub excerpt: String,
+    pub created_at: DateTime<Utc>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct Message {
     pub id: Uuid,
@@ -76,8 +564,21 @@ pub struct Message {
     pub matrix_event_id: String,
     pub reply_to: Option<Uuid>,
     pub is_encrypted: bool,
+    pub is_e2ee: bool,
+    pub sender_device_id: Option<String>,
+    pub sender_key_id: Option<String>,
     pub created_at: DateTime<Utc>,
     pub created_by: Uuid,
+    pub signature: Option<String>,
+    pub signing_key_id: Option<String>,
+    /// What the signature covers in place of the plaintext for messages that
+    /// aren't end-to-end encrypted (see `canonical::message`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[sqlx(default)]
+    pub content_digest: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[sqlx(default)]
+    pub decryption_error: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::Type)]
@@ -88,6 +589,49 @@ pub enum MessageType {
     File,
     Audio,
     Video,
+    /// Recorded by the server for membership and settings changes; clients
+    /// can't send these
+    System,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: Uuid,
+    pub kind: String,
+    pub status: JobStatus,
+    pub total: i64,
+    pub processed: i64,
+    pub failed: i64,
+    pub error: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Running,
+    Completed,
+    Failed,
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Running => "running",
+            JobStatus::Completed => "completed",
+            JobStatus::Failed => "failed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "completed" => JobStatus::Completed,
+            "failed" => JobStatus::Failed,
+            _ => JobStatus::Running,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -96,6 +640,15 @@ pub struct CreateUserRequest {
     pub email: Option<String>,
     pub password: String,
     pub is_anonymous: bool,
+    pub invite_code: Option<String>,
+    /// Required for anonymous accounts
+    pub challenge: Option<ChallengeSolution>,
+}
+
+/// When a requested account deletion will be carried out
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountDeletion {
+    pub scheduled_for: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -104,6 +657,190 @@ pub struct LoginRequest {
     pub password: String,
 }
 
+/// TOTP secret awaiting confirmation, for manual entry or a QR code
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TotpEnrollment {
+    pub secret: String,
+    pub provisioning_uri: String,
+}
+
+/// A registered passkey, without its key material
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasskeyInfo {
+    pub id: Uuid,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+/// An invite code, without the code itself
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InviteCode {
+    pub id: Uuid,
+    pub created_by: Uuid,
+    pub max_uses: i64,
+    pub uses: i64,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub revoked: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateInviteRequest {
+    /// Defaults to a single use
+    pub max_uses: Option<i64>,
+    /// Never expires when omitted
+    pub expires_in_days: Option<i64>,
+}
+
+/// A shareable link into a group chat or a private board, without its token
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InviteLink {
+    pub id: Uuid,
+    pub chat_id: Option<Uuid>,
+    pub board_id: Option<Uuid>,
+    pub created_by: Uuid,
+    /// Unlimited when `None`
+    pub max_uses: Option<i64>,
+    pub uses: i64,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub requires_approval: bool,
+    pub revoked: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateInviteLinkRequest {
+    /// Unlimited when omitted
+    pub max_uses: Option<i64>,
+    /// Never expires when omitted
+    pub expires_in_hours: Option<i64>,
+    /// Joins wait for an admin's approval
+    #[serde(default)]
+    pub requires_approval: bool,
+}
+
+/// What an invite link leads to, shown before joining
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InviteLinkPreview {
+    pub chat_id: Option<Uuid>,
+    pub board_id: Option<Uuid>,
+    pub name: Option<String>,
+    pub requires_approval: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JoinStatus {
+    Joined,
+    /// Waiting for an admin to approve
+    Pending,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JoinResult {
+    pub status: JoinStatus,
+    pub chat_id: Option<Uuid>,
+    pub board_id: Option<Uuid>,
+}
+
+/// Someone waiting for approval to join through a link
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InviteLinkRequest {
+    pub link_id: Uuid,
+    pub user: PublicUser,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A signed challenge to solve before an anonymous action
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Challenge {
+    pub token: String,
+    /// "pow" or "text"
+    pub kind: String,
+    /// Leading zero bits the proof-of-work hash needs
+    pub difficulty: u8,
+    /// Question to answer, for text challenges
+    pub question: Option<String>,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChallengeSolution {
+    pub token: String,
+    /// The nonce for proof-of-work, the answer for text challenges
+    pub solution: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateChallengeRequest {
+    /// "register" or "thread"
+    pub purpose: String,
+    /// Board the thread will be posted to
+    pub board: Option<String>,
+}
+
+/// What a personal access token may be used for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TokenScope {
+    #[serde(rename = "boards:read")]
+    ReadBoards,
+    #[serde(rename = "boards:write")]
+    PostBoards,
+    #[serde(rename = "chats:read")]
+    ReadChats,
+    #[serde(rename = "chats:write")]
+    SendMessages,
+    #[serde(rename = "chats:manage")]
+    ManageChats,
+    #[serde(rename = "moderate")]
+    Moderate,
+}
+
+impl TokenScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenScope::ReadBoards => "boards:read",
+            TokenScope::PostBoards => "boards:write",
+            TokenScope::ReadChats => "chats:read",
+            TokenScope::SendMessages => "chats:write",
+            TokenScope::ManageChats => "chats:manage",
+            TokenScope::Moderate => "moderate",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "boards:read" => Some(TokenScope::ReadBoards),
+            "boards:write" => Some(TokenScope::PostBoards),
+            "chats:read" => Some(TokenScope::ReadChats),
+            "chats:write" => Some(TokenScope::SendMessages),
+            "chats:manage" => Some(TokenScope::ManageChats),
+            "moderate" => Some(TokenScope::Moderate),
+            _ => None,
+        }
+    }
+}
+
+/// A personal access token, without the secret
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiToken {
+    pub id: Uuid,
+    pub name: String,
+    pub scopes: Vec<TokenScope>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateApiTokenRequest {
+    pub name: String,
+    pub scopes: Vec<TokenScope>,
+    /// Never expires when omitted
+    pub expires_in_days: Option<i64>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateBoardRequest {
     pub name: String,
@@ -111,6 +848,11 @@ pub struct CreateBoardRequest {
     pub description: Option<String>,
     pub is_nsfw: bool,
     pub is_private: bool,
+    /// Private boards only
+    #[serde(default)]
+    pub is_encrypted: bool,
+    #[serde(flatten)]
+    pub settings: BoardSettingsUpdate,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -118,6 +860,10 @@ pub struct CreateThreadRequest {
     pub title: Option<String>,
     pub content: String,
     pub image_url: Option<String>,
+    #[serde(default)]
+    pub is_spoiler: bool,
+    /// Required for anonymous posters on boards with a challenge
+    pub challenge: Option<ChallengeSolution>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -125,18 +871,61 @@ pub struct CreatePostRequest {
     pub content: String,
     pub image_url: Option<String>,
     pub reply_to: Option<Uuid>,
+    #[serde(default)]
+    pub is_spoiler: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateChatRequest {
     pub name: Option<String>,
     pub is_group: bool,
-    pub participants: Vec<Uuid>,
+    /// User IDs, usernames or Matrix IDs
+    pub participants: Vec<String>,
+    /// Create an end-to-end encrypted chat instead of server-side encryption
+    #[serde(default)]
+    pub end_to_end: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SendMessageRequest {
+    /// Plaintext, or the opaque ciphertext envelope in end-to-end encrypted chats
     pub content: String,
     pub message_type: MessageType,
     pub reply_to: Option<Uuid>,
+    #[serde(default)]
+    pub sender_device_id: Option<String>,
+    #[serde(default)]
+    pub sender_key_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedPreKey {
+    pub key_id: i64,
+    pub public_key: String,
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OneTimePreKey {
+    pub key_id: i64,
+    pub public_key: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadKeysRequest {
+    pub identity_key: String,
+    pub signed_prekey: SignedPreKey,
+    #[serde(default)]
+    pub one_time_prekeys: Vec<OneTimePreKey>,
+}
+
+/// Public keys a client needs to start an encrypted session with one device
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreKeyBundle {
+    pub user_id: Uuid,
+    pub device_id: String,
+    pub identity_key: String,
+    pub identity_key_id: String,
+    pub signed_prekey: SignedPreKey,
+    pub one_time_prekey: Option<OneTimePreKey>,
 }
\ No newline at end of file
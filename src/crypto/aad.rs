@@ -0,0 +1,29 @@
+// Associated data for context-bound ciphertexts
+//
+// AAD is authenticated but not encrypted or stored. Building it from the
+// identifiers of the row a ciphertext belongs to means the ciphertext only
+// decrypts in that exact place.
+
+use uuid::Uuid;
+
+/// Version of the chat message AAD layout. Bump it when the fields change.
+pub const MESSAGE_AAD_VERSION: u8 = 1;
+
+/// Associated data for a chat message's `content`
+pub fn message_aad(chat_id: Uuid, message_id: Uuid, sender_id: Uuid) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(16 + 1 + 16 * 3);
+    aad.extend_from_slice(b"amogchan/message");
+    aad.push(MESSAGE_AAD_VERSION);
+    aad.extend_from_slice(chat_id.as_bytes());
+    aad.extend_from_slice(message_id.as_bytes());
+    aad.extend_from_slice(sender_id.as_bytes());
+    aad
+}
+
+/// Associated data for a user's encrypted TOTP secret
+pub fn totp_secret_aad(user_id: Uuid) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(13 + 16);
+    aad.extend_from_slice(b"amogchan/totp");
+    aad.extend_from_slice(user_id.as_bytes());
+    aad
+}
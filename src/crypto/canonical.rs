@@ -0,0 +1,142 @@
+// Canonical byte representations of signed content
+//
+// Each representation starts with a versioned type tag, followed by the
+// content's immutable fields in a fixed order. Every field is encoded as a
+// presence byte and, when present, a big-endian u32 length and the UTF-8
+// bytes, so no two different field sets share an encoding. Mutable
+// bookkeeping such as reply counts, pins and locks is deliberately left out.
+
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::core::types::{Message, Post, Thread};
+
+const THREAD_TAG: &[u8] = b"amogchan/thread/v1";
+const POST_TAG: &[u8] = b"amogchan/post/v1";
+const MESSAGE_TAG: &[u8] = b"amogchan/message/v2";
+
+struct Canonical {
+    bytes: Vec<u8>,
+}
+
+impl Canonical {
+    fn new(tag: &[u8]) -> Self {
+        let mut bytes = Vec::with_capacity(256);
+        bytes.extend_from_slice(tag);
+        bytes.push(0);
+        Self { bytes }
+    }
+
+    fn field(mut self, value: Option<&str>) -> Self {
+        match value {
+            Some(value) => {
+                self.bytes.push(1);
+                self.bytes.extend_from_slice(&(value.len() as u32).to_be_bytes());
+                self.bytes.extend_from_slice(value.as_bytes());
+            }
+            None => self.bytes.push(0),
+        }
+        self
+    }
+
+    fn text(self, value: &str) -> Self {
+        self.field(Some(value))
+    }
+
+    fn id(self, value: Uuid) -> Self {
+        self.text(&value.to_string())
+    }
+
+    fn optional_id(self, value: Option<Uuid>) -> Self {
+        self.field(value.map(|id| id.to_string()).as_deref())
+    }
+
+    fn timestamp(self, value: DateTime<Utc>) -> Self {
+        self.text(&value.to_rfc3339())
+    }
+
+    fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+pub fn thread(thread: &Thread) -> Vec<u8> {
+    Canonical::new(THREAD_TAG)
+        .id(thread.id)
+        .id(thread.board_id)
+        .field(thread.title.as_deref())
+        .text(&thread.content)
+        .field(thread.image_url.as_deref())
+        .text(&thread.matrix_event_id)
+        .timestamp(thread.created_at)
+        .id(thread.public_author())
+        .finish()
+}
+
+pub fn post(post: &Post) -> Vec<u8> {
+    Canonical::new(POST_TAG)
+        .id(post.id)
+        .optional_id(post.thread_id)
+        .id(post.board_id)
+        .text(&post.content)
+        .field(post.image_url.as_deref())
+        .text(&post.matrix_event_id)
+        .optional_id(post.reply_to)
+        .timestamp(post.created_at)
+        .id(post.public_author())
+        .finish()
+}
+
+/// Covers `content` in place of the message's own: the ciphertext envelope
+/// in end-to-end encrypted chats, and otherwise the keyed digest of the
+/// plaintext (`CryptoService::content_digest`), so a signature reveals
+/// nothing about a server-side encrypted message
+pub fn message(message: &Message, content: &str) -> Vec<u8> {
+    Canonical::new(MESSAGE_TAG)
+        .id(message.id)
+        .id(message.chat_id)
+        .text(&format!("{:?}", message.message_type).to_lowercase())
+        .text(content)
+        .text(&message.matrix_event_id)
+        .optional_id(message.reply_to)
+        .text(if message.is_e2ee { "e2ee" } else { "server" })
+        .field(message.sender_device_id.as_deref())
+        .field(message.sender_key_id.as_deref())
+        .timestamp(message.created_at)
+        .id(message.created_by)
+        .finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::types::MessageType;
+
+    #[test]
+    fn message_encoding_covers_the_given_content_not_the_plaintext() {
+        let plaintext = "meet at the harbour at noon";
+        let signed = Message {
+            id: Uuid::new_v4(),
+            chat_id: Uuid::new_v4(),
+            content: plaintext.to_string(),
+            message_type: MessageType::Text,
+            matrix_event_id: "$event".to_string(),
+            reply_to: None,
+            is_encrypted: true,
+            is_e2ee: false,
+            sender_device_id: None,
+            sender_key_id: None,
+            created_at: Utc::now(),
+            created_by: Uuid::new_v4(),
+            signature: None,
+            signing_key_id: None,
+            content_digest: None,
+            decryption_error: None,
+        };
+
+        let encoded = message(&signed, "digest");
+
+        assert!(!encoded.windows(plaintext.len()).any(|window| window == plaintext.as_bytes()));
+        assert_ne!(encoded, message(&signed, "other digest"));
+    }
+}
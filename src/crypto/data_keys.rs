@@ -0,0 +1,332 @@
+use base64::{Engine as _, engine::general_purpose};
+use chrono::Utc;
+use ring::aead::{LessSafeKey, UnboundKey, AES_256_GCM};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::core::error::{AppError, AppResult};
+use crate::crypto::kek::KekProvider;
+use crate::crypto::service::CryptoService;
+use crate::storage::database::Database;
+
+/// Context every DEK was wrapped under before wrapped keys were bound to
+/// their owner. Such keys are rewrapped the first time they are read.
+const LEGACY_WRAPPING_CONTEXT: &[u8] = b"amogchan/dek";
+
+/// What a data encryption key protects
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyOwner {
+    Chat(Uuid),
+    User(Uuid),
+}
+
+impl KeyOwner {
+    fn owner_type(&self) -> &'static str {
+        match self {
+            KeyOwner::Chat(_) => "chat",
+            KeyOwner::User(_) => "user",
+        }
+    }
+
+    fn owner_id(&self) -> Uuid {
+        match self {
+            KeyOwner::Chat(id) | KeyOwner::User(id) => *id,
+        }
+    }
+
+    /// What the owner's wrapped DEK is bound to, so a wrapped key copied
+    /// into another owner's row doesn't unwrap
+    fn wrapping_context(&self) -> Vec<u8> {
+        let mut context = LEGACY_WRAPPING_CONTEXT.to_vec();
+        context.push(0);
+        context.extend_from_slice(self.owner_type().as_bytes());
+        context.push(0);
+        context.extend_from_slice(self.owner_id().as_bytes());
+        context
+    }
+}
+
+/// An unwrapped data encryption key
+pub struct DataKey {
+    pub id: Uuid,
+    pub key: LessSafeKey,
+}
+
+/// Per-chat and per-user data encryption keys (envelope encryption).
+///
+/// DEKs are stored only wrapped by the KEK provider's master key. Destroying
+/// the wrapped DEK (crypto-shredding) makes everything encrypted under it
+/// permanently unreadable, including copies in old backups.
+pub struct DataKeyService {
+    db: Arc<Database>,
+    crypto: Arc<CryptoService>,
+    kek: Arc<dyn KekProvider>,
+    cache: RwLock<HashMap<KeyOwner, Arc<DataKey>>>,
+}
+
+impl DataKeyService {
+    pub fn new(db: Arc<Database>, crypto: Arc<CryptoService>, kek: Arc<dyn KekProvider>) -> Self {
+        Self {
+            db,
+            crypto,
+            kek,
+            cache: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Get the owner's data key, if one was ever created and not shredded
+    pub async fn get(&self, owner: KeyOwner) -> AppResult<Option<Arc<DataKey>>> {
+        if let Some(data_key) = self.cache.read().unwrap().get(&owner) {
+            return Ok(Some(Arc::clone(data_key)));
+        }
+
+        let owner_id = owner.owner_id().to_string();
+        let record = sqlx::query!(
+            "SELECT id, wrapped_key FROM data_keys WHERE owner_type = ? AND owner_id = ?",
+            owner.owner_type(),
+            owner_id
+        )
+        .fetch_optional(self.db.pool())
+        .await?;
+
+        let Some(record) = record else {
+            return Ok(None);
+        };
+
+        let wrapped = general_purpose::STANDARD
+            .decode(&record.wrapped_key)
+            .map_err(|e| AppError::Crypto(format!("Invalid wrapped key: {}", e)))?;
+        let key_bytes = match self.kek.unwrap(&wrapped, &owner.wrapping_context()).await {
+            Ok(key_bytes) => key_bytes,
+            Err(e) => self.upgrade_legacy_key(owner, &record.id, &record.wrapped_key, &wrapped)
+                .await?
+                .ok_or(e)?,
+        };
+
+        let data_key = Arc::new(DataKey {
+            id: Uuid::parse_str(&record.id)
+                .map_err(|e| AppError::Internal(format!("Invalid data key ID: {}", e)))?,
+            key: build_key(&key_bytes)?,
+        });
+
+        self.cache.write().unwrap().insert(owner, Arc::clone(&data_key));
+        Ok(Some(data_key))
+    }
+
+    /// Get the owner's data key, creating it on first use
+    pub async fn get_or_create(&self, owner: KeyOwner) -> AppResult<Arc<DataKey>> {
+        if let Some(data_key) = self.get(owner).await? {
+            return Ok(data_key);
+        }
+
+        let key_id = Uuid::new_v4();
+        let key_bytes = self.crypto.generate_key_bytes()?;
+        let wrapped = self.kek.wrap(&key_bytes, &owner.wrapping_context()).await?;
+
+        // A concurrent request may have created the key first; keep theirs
+        sqlx::query!(
+            r#"
+            INSERT OR IGNORE INTO data_keys (id, owner_type, owner_id, kek_id, wrapped_key, created_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+            key_id.to_string(),
+            owner.owner_type(),
+            owner.owner_id().to_string(),
+            self.kek.kek_id(),
+            general_purpose::STANDARD.encode(&wrapped),
+            Utc::now().to_rfc3339()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        self.get(owner)
+            .await?
+            .ok_or_else(|| AppError::Internal("Data key disappeared after creation".to_string()))
+    }
+
+    /// Destroy the owner's wrapped data key. This cannot be undone.
+    ///
+    /// Only ciphertexts sealed under this key (`v3`) become unreadable.
+    /// Older `v1`/`v2` ciphertexts are under the global keyring until the
+    /// re-encryption job has moved them, so callers must delete those rows
+    /// themselves, and copies in backups stay readable until that keyring
+    /// key is retired.
+    pub async fn shred(&self, owner: KeyOwner) -> AppResult<()> {
+        self.cache.write().unwrap().remove(&owner);
+
+        sqlx::query!(
+            "DELETE FROM data_keys WHERE owner_type = ? AND owner_id = ?",
+            owner.owner_type(),
+            owner.owner_id().to_string()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        info!("Shredded {} data key for {}", owner.owner_type(), owner.owner_id());
+        Ok(())
+    }
+
+    /// Unwrap a DEK wrapped before keys were bound to their owner and rewrap
+    /// it with the owner's context. Returns `None` if it isn't such a key.
+    async fn upgrade_legacy_key(&self, owner: KeyOwner, key_id: &str, stored: &str, wrapped: &[u8]) -> AppResult<Option<Vec<u8>>> {
+        let Ok(key_bytes) = self.kek.unwrap(wrapped, LEGACY_WRAPPING_CONTEXT).await else {
+            return Ok(None);
+        };
+
+        let rewrapped = self.kek.wrap(&key_bytes, &owner.wrapping_context()).await?;
+
+        // Only replaces the row that was read, in case it was shredded meanwhile
+        sqlx::query!(
+            "UPDATE data_keys SET wrapped_key = ?, kek_id = ? WHERE id = ? AND wrapped_key = ?",
+            general_purpose::STANDARD.encode(&rewrapped),
+            self.kek.kek_id(),
+            key_id,
+            stored
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        info!("Bound the {} data key of {} to its owner", owner.owner_type(), owner.owner_id());
+        Ok(Some(key_bytes))
+    }
+}
+
+fn build_key(key_bytes: &[u8]) -> AppResult<LessSafeKey> {
+    let unbound_key = UnboundKey::new(&AES_256_GCM, key_bytes)
+        .map_err(|e| AppError::Crypto(format!("Failed to create data key: {}", e)))?;
+
+    Ok(LessSafeKey::new(unbound_key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::config::DatabaseConfig;
+    use crate::crypto::kek::LocalKekProvider;
+
+    struct Fixture {
+        db: Arc<Database>,
+        crypto: Arc<CryptoService>,
+        kek: Arc<LocalKekProvider>,
+        data_keys: DataKeyService,
+    }
+
+    async fn fixture() -> Fixture {
+        let path = std::env::temp_dir().join(format!("amogchan-data-keys-{}.db", Uuid::new_v4()));
+        let db = Database::new(&DatabaseConfig {
+            url: format!("sqlite://{}?mode=rwc", path.display()),
+            max_connections: 1,
+        })
+        .await
+        .unwrap();
+        db.migrate().await.unwrap();
+        let db = Arc::new(db);
+
+        let crypto = Arc::new(CryptoService::new(&crate::crypto::service::test_config()).unwrap());
+        let kek = Arc::new(LocalKekProvider::new("env", &[3u8; 32]).unwrap());
+        let data_keys = DataKeyService::new(Arc::clone(&db), Arc::clone(&crypto), kek.clone());
+
+        Fixture { db, crypto, kek, data_keys }
+    }
+
+    async fn wrapped_key(db: &Database, owner: KeyOwner) -> String {
+        sqlx::query_scalar!(
+            "SELECT wrapped_key FROM data_keys WHERE owner_type = ? AND owner_id = ?",
+            owner.owner_type(),
+            owner.owner_id().to_string()
+        )
+        .fetch_one(db.pool())
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn data_keys_are_created_once_and_unwrap_after_a_restart() {
+        let fixture = fixture().await;
+        let owner = KeyOwner::Chat(Uuid::new_v4());
+
+        let created = fixture.data_keys.get_or_create(owner).await.unwrap();
+        let ciphertext = fixture.crypto.encrypt_with_data_key(&created, "hello", b"aad").unwrap();
+        assert_eq!(fixture.data_keys.get_or_create(owner).await.unwrap().id, created.id);
+
+        // A fresh service has nothing cached and has to unwrap the stored key
+        let restarted = DataKeyService::new(Arc::clone(&fixture.db), Arc::clone(&fixture.crypto), fixture.kek.clone());
+        let unwrapped = restarted.get(owner).await.unwrap().unwrap();
+
+        assert_eq!(unwrapped.id, created.id);
+        assert_eq!(fixture.crypto.decrypt_with_data_key(&ciphertext, b"aad", Some(&unwrapped)).unwrap(), "hello");
+    }
+
+    #[tokio::test]
+    async fn shredded_keys_are_gone() {
+        let fixture = fixture().await;
+        let owner = KeyOwner::User(Uuid::new_v4());
+
+        let data_key = fixture.data_keys.get_or_create(owner).await.unwrap();
+        let ciphertext = fixture.crypto.encrypt_with_data_key(&data_key, "secret", b"aad").unwrap();
+
+        fixture.data_keys.shred(owner).await.unwrap();
+
+        let restarted = DataKeyService::new(Arc::clone(&fixture.db), Arc::clone(&fixture.crypto), fixture.kek.clone());
+        assert!(fixture.data_keys.get(owner).await.unwrap().is_none());
+        assert!(restarted.get(owner).await.unwrap().is_none());
+        assert!(fixture.crypto.decrypt_with_data_key(&ciphertext, b"aad", None).is_err());
+
+        // A new key is a different key
+        let recreated = fixture.data_keys.get_or_create(owner).await.unwrap();
+        assert_ne!(recreated.id, data_key.id);
+        assert!(fixture.crypto.decrypt_with_data_key(&ciphertext, b"aad", Some(&recreated)).is_err());
+    }
+
+    #[tokio::test]
+    async fn wrapped_keys_do_not_unwrap_for_another_owner() {
+        let fixture = fixture().await;
+        let victim = KeyOwner::Chat(Uuid::new_v4());
+        let attacker = KeyOwner::Chat(Uuid::new_v4());
+
+        fixture.data_keys.get_or_create(victim).await.unwrap();
+        fixture.data_keys.get_or_create(attacker).await.unwrap();
+
+        // Copy the victim's wrapped key over the attacker's row
+        sqlx::query!(
+            "UPDATE data_keys SET wrapped_key = ? WHERE owner_type = 'chat' AND owner_id = ?",
+            wrapped_key(&fixture.db, victim).await,
+            attacker.owner_id().to_string()
+        )
+        .execute(fixture.db.pool())
+        .await
+        .unwrap();
+
+        let restarted = DataKeyService::new(Arc::clone(&fixture.db), Arc::clone(&fixture.crypto), fixture.kek.clone());
+        assert!(restarted.get(attacker).await.is_err());
+        assert!(restarted.get(victim).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn legacy_wrapped_keys_are_bound_on_first_read() {
+        let fixture = fixture().await;
+        let owner = KeyOwner::Chat(Uuid::new_v4());
+
+        let legacy = fixture.kek.wrap(&[9u8; 32], LEGACY_WRAPPING_CONTEXT).await.unwrap();
+        let legacy = general_purpose::STANDARD.encode(&legacy);
+        sqlx::query!(
+            "INSERT INTO data_keys (id, owner_type, owner_id, kek_id, wrapped_key, created_at) VALUES (?, 'chat', ?, ?, ?, ?)",
+            Uuid::new_v4().to_string(),
+            owner.owner_id().to_string(),
+            fixture.kek.kek_id(),
+            legacy,
+            Utc::now().to_rfc3339()
+        )
+        .execute(fixture.db.pool())
+        .await
+        .unwrap();
+
+        assert!(fixture.data_keys.get(owner).await.unwrap().is_some());
+
+        let rewrapped = general_purpose::STANDARD.decode(wrapped_key(&fixture.db, owner).await).unwrap();
+        assert_eq!(fixture.kek.unwrap(&rewrapped, &owner.wrapping_context()).await.unwrap(), [9u8; 32]);
+        assert!(fixture.kek.unwrap(&rewrapped, LEGACY_WRAPPING_CONTEXT).await.is_err());
+    }
+}
@@ -0,0 +1,290 @@
+// Key encryption key (KEK) providers
+//
+// Data encryption keys (DEKs) are only ever stored wrapped by a master key.
+// Where that master key lives is pluggable: an environment variable, a local
+// key file, or a KMS reached through the `KmsClient` trait. `LocalKms` is a
+// stand-in KMS for development and single-node deployments.
+
+use async_trait::async_trait;
+use base64::{Engine as _, engine::general_purpose};
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM},
+    rand::{SecureRandom, SystemRandom},
+};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::info;
+
+use crate::core::config::CryptoConfig;
+use crate::core::error::{AppError, AppResult};
+
+/// Wraps and unwraps data encryption keys with a master key
+#[async_trait]
+pub trait KekProvider: Send + Sync {
+    /// Identifier of the master key, stored next to every wrapped DEK
+    fn kek_id(&self) -> String;
+
+    /// Wrap a DEK bound to `context`, which names its owner. A wrapped DEK
+    /// copied to another owner's row then fails to unwrap.
+    async fn wrap(&self, dek: &[u8], context: &[u8]) -> AppResult<Vec<u8>>;
+
+    async fn unwrap(&self, wrapped: &[u8], context: &[u8]) -> AppResult<Vec<u8>>;
+}
+
+/// Minimal KMS interface: encrypt and decrypt small blobs under a named key,
+/// authenticating `aad` (the encryption context, in most KMS APIs)
+#[async_trait]
+pub trait KmsClient: Send + Sync {
+    async fn encrypt(&self, key_name: &str, plaintext: &[u8], aad: &[u8]) -> AppResult<Vec<u8>>;
+
+    async fn decrypt(&self, key_name: &str, ciphertext: &[u8], aad: &[u8]) -> AppResult<Vec<u8>>;
+}
+
+/// Build the KEK provider selected by `KEK_PROVIDER`
+pub fn from_config(config: &CryptoConfig) -> AppResult<Arc<dyn KekProvider>> {
+    match config.kek_provider.as_str() {
+        "env" => {
+            let master_key = config.master_key.as_deref()
+                .ok_or_else(|| AppError::Crypto("MASTER_KEY is required for the env KEK provider".to_string()))?;
+            let key_bytes = general_purpose::STANDARD
+                .decode(master_key)
+                .map_err(|e| AppError::Crypto(format!("Invalid master key: {}", e)))?;
+
+            info!("Using master key from environment");
+            Ok(Arc::new(LocalKekProvider::new("env", &key_bytes)?))
+        }
+        "file" => {
+            let path = config.master_key_file.as_deref()
+                .ok_or_else(|| AppError::Crypto("MASTER_KEY_FILE is required for the file KEK provider".to_string()))?;
+            let key_bytes = read_key_file(Path::new(path))?;
+
+            info!("Using master key from file: {}", path);
+            Ok(Arc::new(LocalKekProvider::new("file", &key_bytes)?))
+        }
+        "local-kms" => {
+            let path = config.master_key_file.as_deref()
+                .ok_or_else(|| AppError::Crypto("MASTER_KEY_FILE is required for the local KMS".to_string()))?;
+            let kms = LocalKms::open(PathBuf::from(path))?;
+
+            info!("Using local KMS stand-in with key: {}", config.kms_key_name);
+            Ok(Arc::new(KmsKekProvider::new(Arc::new(kms), &config.kms_key_name)))
+        }
+        other => Err(AppError::Crypto(format!("Unknown KEK provider: {}", other))),
+    }
+}
+
+/// AES-256-GCM key wrapping with a master key held in process memory
+pub struct LocalKekProvider {
+    kek_id: String,
+    key: LessSafeKey,
+    rng: SystemRandom,
+}
+
+impl LocalKekProvider {
+    pub fn new(source: &str, key_bytes: &[u8]) -> AppResult<Self> {
+        if key_bytes.len() != 32 {
+            return Err(AppError::Crypto("Master key must be 32 bytes".to_string()));
+        }
+
+        let unbound_key = UnboundKey::new(&AES_256_GCM, key_bytes)
+            .map_err(|e| AppError::Crypto(format!("Failed to create master key: {}", e)))?;
+
+        // Fingerprint lets us tell which master key wrapped a DEK without revealing it
+        let fingerprint = ring::digest::digest(&ring::digest::SHA256, key_bytes);
+        let kek_id = format!(
+            "{}:{}",
+            source,
+            general_purpose::URL_SAFE_NO_PAD.encode(&fingerprint.as_ref()[..8])
+        );
+
+        Ok(Self {
+            kek_id,
+            key: LessSafeKey::new(unbound_key),
+            rng: SystemRandom::new(),
+        })
+    }
+}
+
+#[async_trait]
+impl KekProvider for LocalKekProvider {
+    fn kek_id(&self) -> String {
+        self.kek_id.clone()
+    }
+
+    async fn wrap(&self, dek: &[u8], context: &[u8]) -> AppResult<Vec<u8>> {
+        seal(&self.key, &self.rng, dek, context)
+    }
+
+    async fn unwrap(&self, wrapped: &[u8], context: &[u8]) -> AppResult<Vec<u8>> {
+        open(&self.key, wrapped, context)
+    }
+}
+
+/// Delegates key wrapping to a KMS
+pub struct KmsKekProvider {
+    kms: Arc<dyn KmsClient>,
+    key_name: String,
+}
+
+impl KmsKekProvider {
+    pub fn new(kms: Arc<dyn KmsClient>, key_name: &str) -> Self {
+        Self {
+            kms,
+            key_name: key_name.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl KekProvider for KmsKekProvider {
+    fn kek_id(&self) -> String {
+        format!("kms:{}", self.key_name)
+    }
+
+    async fn wrap(&self, dek: &[u8], context: &[u8]) -> AppResult<Vec<u8>> {
+        self.kms.encrypt(&self.key_name, dek, context).await
+    }
+
+    async fn unwrap(&self, wrapped: &[u8], context: &[u8]) -> AppResult<Vec<u8>> {
+        self.kms.decrypt(&self.key_name, wrapped, context).await
+    }
+}
+
+/// Local stand-in for a KMS. The key material never leaves this type and is
+/// persisted in a single key file that is created on first use.
+pub struct LocalKms {
+    key: LessSafeKey,
+    rng: SystemRandom,
+}
+
+impl LocalKms {
+    pub fn open(path: PathBuf) -> AppResult<Self> {
+        let rng = SystemRandom::new();
+
+        let key_bytes = if path.exists() {
+            read_key_file(&path)?
+        } else {
+            let mut key_bytes = vec![0u8; 32];
+            rng.fill(&mut key_bytes)
+                .map_err(|e| AppError::Crypto(format!("Failed to generate KMS key: {}", e)))?;
+            std::fs::write(&path, general_purpose::STANDARD.encode(&key_bytes))?;
+            info!("Created local KMS key file: {}", path.display());
+            key_bytes
+        };
+
+        let unbound_key = UnboundKey::new(&AES_256_GCM, &key_bytes)
+            .map_err(|e| AppError::Crypto(format!("Failed to create KMS key: {}", e)))?;
+
+        Ok(Self {
+            key: LessSafeKey::new(unbound_key),
+            rng,
+        })
+    }
+}
+
+#[async_trait]
+impl KmsClient for LocalKms {
+    async fn encrypt(&self, _key_name: &str, plaintext: &[u8], aad: &[u8]) -> AppResult<Vec<u8>> {
+        seal(&self.key, &self.rng, plaintext, aad)
+    }
+
+    async fn decrypt(&self, _key_name: &str, ciphertext: &[u8], aad: &[u8]) -> AppResult<Vec<u8>> {
+        open(&self.key, ciphertext, aad)
+    }
+}
+
+fn read_key_file(path: &Path) -> AppResult<Vec<u8>> {
+    let encoded = std::fs::read_to_string(path)?;
+    let key_bytes = general_purpose::STANDARD
+        .decode(encoded.trim())
+        .map_err(|e| AppError::Crypto(format!("Invalid key file {}: {}", path.display(), e)))?;
+
+    if key_bytes.len() != 32 {
+        return Err(AppError::Crypto(format!("Key file {} must contain 32 bytes", path.display())));
+    }
+
+    Ok(key_bytes)
+}
+
+/// nonce || ciphertext || tag
+fn seal(key: &LessSafeKey, rng: &SystemRandom, plaintext: &[u8], aad: &[u8]) -> AppResult<Vec<u8>> {
+    let mut nonce_bytes = [0u8; 12];
+    rng.fill(&mut nonce_bytes)
+        .map_err(|e| AppError::Crypto(format!("Failed to generate nonce: {}", e)))?;
+
+    let mut in_out = plaintext.to_vec();
+    key.seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce_bytes), Aad::from(aad), &mut in_out)
+        .map_err(|e| AppError::Crypto(format!("Key wrapping failed: {}", e)))?;
+
+    let mut result = nonce_bytes.to_vec();
+    result.extend_from_slice(&in_out);
+    Ok(result)
+}
+
+fn open(key: &LessSafeKey, wrapped: &[u8], aad: &[u8]) -> AppResult<Vec<u8>> {
+    if wrapped.len() < 12 {
+        return Err(AppError::Crypto("Wrapped key too short".to_string()));
+    }
+
+    let (nonce_bytes, ciphertext_with_tag) = wrapped.split_at(12);
+    let nonce = Nonce::try_assume_unique_for_key(nonce_bytes)
+        .map_err(|e| AppError::Crypto(format!("Invalid nonce: {}", e)))?;
+
+    let mut in_out = ciphertext_with_tag.to_vec();
+    let plaintext = key.open_in_place(nonce, Aad::from(aad), &mut in_out)
+        .map_err(|e| AppError::Crypto(format!("Key unwrapping failed: {}", e)))?;
+
+    Ok(plaintext.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn wrapped_keys_unwrap_with_their_context() {
+        let kek = LocalKekProvider::new("env", &[3u8; 32]).unwrap();
+        let dek = [7u8; 32];
+
+        let wrapped = kek.wrap(&dek, b"chat:a").await.unwrap();
+
+        assert_eq!(kek.unwrap(&wrapped, b"chat:a").await.unwrap(), dek);
+        assert!(kek.unwrap(&wrapped, b"chat:b").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn wrapped_keys_only_unwrap_with_their_master_key() {
+        let kek = LocalKekProvider::new("env", &[3u8; 32]).unwrap();
+        let other = LocalKekProvider::new("env", &[4u8; 32]).unwrap();
+
+        let wrapped = kek.wrap(&[7u8; 32], b"chat:a").await.unwrap();
+
+        assert!(other.unwrap(&wrapped, b"chat:a").await.is_err());
+        assert_ne!(kek.kek_id(), other.kek_id());
+    }
+
+    #[tokio::test]
+    async fn tampered_wrapped_keys_are_rejected() {
+        let kek = LocalKekProvider::new("env", &[3u8; 32]).unwrap();
+
+        let mut wrapped = kek.wrap(&[7u8; 32], b"chat:a").await.unwrap();
+        let last = wrapped.len() - 1;
+        wrapped[last] ^= 1;
+
+        assert!(kek.unwrap(&wrapped, b"chat:a").await.is_err());
+        assert!(kek.unwrap(&wrapped[..8], b"chat:a").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn local_kms_keeps_its_key_across_restarts() {
+        let path = std::env::temp_dir().join(format!("amogchan-kms-{}.key", uuid::Uuid::new_v4()));
+        let provider = KmsKekProvider::new(Arc::new(LocalKms::open(path.clone()).unwrap()), "test");
+        let wrapped = provider.wrap(&[7u8; 32], b"user:a").await.unwrap();
+
+        let reopened = KmsKekProvider::new(Arc::new(LocalKms::open(path.clone()).unwrap()), "test");
+        assert_eq!(reopened.unwrap(&wrapped, b"user:a").await.unwrap(), [7u8; 32]);
+        assert!(reopened.unwrap(&wrapped, b"user:b").await.is_err());
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
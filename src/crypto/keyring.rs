@@ -0,0 +1,97 @@
+use base64::{Engine as _, engine::general_purpose};
+use ring::aead::{LessSafeKey, UnboundKey, AES_256_GCM};
+use std::collections::HashMap;
+
+use crate::core::config::CryptoConfig;
+use crate::core::error::{AppError, AppResult};
+
+/// Set of AES-256-GCM keys identified by short key ids.
+///
+/// Exactly one key is active and used for new ciphertexts. All keys
+/// (active and retired) can decrypt, so stored data stays readable while
+/// a re-encryption job moves it over to the active key.
+pub struct Keyring {
+    active_id: String,
+    keys: HashMap<String, LessSafeKey>,
+}
+
+impl Keyring {
+    pub fn from_config(config: &CryptoConfig) -> AppResult<Self> {
+        validate_key_id(&config.encryption_key_id)?;
+
+        let mut keys = HashMap::new();
+        keys.insert(config.encryption_key_id.clone(), parse_key(&config.encryption_key)?);
+
+        // Retired keys, formatted as "id:base64key,id:base64key"
+        for entry in config.previous_encryption_keys.iter() {
+            let (key_id, key) = entry
+                .split_once(':')
+                .ok_or_else(|| AppError::Crypto("Previous encryption keys must be formatted as id:key".to_string()))?;
+
+            validate_key_id(key_id)?;
+            if keys.contains_key(key_id) {
+                return Err(AppError::Crypto(format!("Duplicate encryption key id: {}", key_id)));
+            }
+
+            keys.insert(key_id.to_string(), parse_key(key)?);
+        }
+
+        Ok(Self {
+            active_id: config.encryption_key_id.clone(),
+            keys,
+        })
+    }
+
+    /// Id of the key used for new ciphertexts
+    pub fn active_id(&self) -> &str {
+        &self.active_id
+    }
+
+    /// The key used for new ciphertexts
+    pub fn active(&self) -> &LessSafeKey {
+        &self.keys[&self.active_id]
+    }
+
+    /// Look up a key by id
+    pub fn get(&self, key_id: &str) -> Option<&LessSafeKey> {
+        self.keys.get(key_id)
+    }
+
+    /// All keys, active first, for ciphertexts that carry no key id
+    pub fn candidates(&self) -> impl Iterator<Item = &LessSafeKey> {
+        std::iter::once(self.active()).chain(
+            self.keys
+                .iter()
+                .filter(move |(key_id, _)| **key_id != self.active_id)
+                .map(|(_, key)| key),
+        )
+    }
+}
+
+fn parse_key(encoded: &str) -> AppResult<LessSafeKey> {
+    let key_bytes = general_purpose::STANDARD
+        .decode(encoded)
+        .map_err(|e| AppError::Crypto(format!("Invalid encryption key: {}", e)))?;
+
+    if key_bytes.len() != 32 {
+        return Err(AppError::Crypto("Encryption key must be 32 bytes".to_string()));
+    }
+
+    let unbound_key = UnboundKey::new(&AES_256_GCM, &key_bytes)
+        .map_err(|e| AppError::Crypto(format!("Failed to create key: {}", e)))?;
+
+    Ok(LessSafeKey::new(unbound_key))
+}
+
+/// Key ids end up in ciphertext headers, so keep them short and separator-free
+fn validate_key_id(key_id: &str) -> AppResult<()> {
+    let valid = !key_id.is_empty()
+        && key_id.len() <= 32
+        && key_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    if !valid {
+        return Err(AppError::Crypto(format!("Invalid encryption key id: {:?}", key_id)));
+    }
+
+    Ok(())
+}
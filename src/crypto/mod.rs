@@ -1 +1,8 @@
-pub mod service;
\ No newline at end of file
+pub mod service;
+pub mod aad;
+pub mod canonical;
+pub mod data_keys;
+pub mod kek;
+pub mod keyring;
+pub mod rotation;
+pub mod signing;
\ No newline at end of file
@@ -0,0 +1,175 @@
+use std::sync::Arc;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::core::error::{AppError, AppResult};
+use crate::core::types::Job;
+use crate::crypto::aad::message_aad;
+use crate::crypto::data_keys::{DataKeyService, KeyOwner};
+use crate::crypto::service::CryptoService;
+use crate::jobs::service::JobService;
+use crate::storage::database::Database;
+
+/// Job kind recorded in `background_jobs`
+pub const REENCRYPT_JOB_KIND: &str = "reencrypt_messages";
+
+/// Messages re-encrypted per database round trip
+const BATCH_SIZE: i64 = 100;
+
+/// Re-encrypts stored data under the current keys and ciphertext format
+pub struct KeyRotationService {
+    db: Arc<Database>,
+    crypto: Arc<CryptoService>,
+    data_keys: Arc<DataKeyService>,
+    jobs: Arc<JobService>,
+}
+
+impl KeyRotationService {
+    pub fn new(
+        db: Arc<Database>,
+        crypto: Arc<CryptoService>,
+        data_keys: Arc<DataKeyService>,
+        jobs: Arc<JobService>,
+    ) -> Self {
+        Self { db, crypto, data_keys, jobs }
+    }
+
+    /// Start a background job moving every message under its chat's data key
+    pub async fn start_reencryption(&self, created_by: Option<Uuid>) -> AppResult<Job> {
+        let total = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) as "count: i64" FROM messages WHERE is_encrypted = TRUE AND is_e2ee = FALSE"#
+        )
+        .fetch_one(self.db.pool())
+        .await?;
+
+        let job = self.jobs
+            .start(REENCRYPT_JOB_KIND, total, created_by)
+            .await?
+            .ok_or_else(|| AppError::InvalidRequest("A re-encryption job is already running".to_string()))?;
+        info!(
+            "Started re-encryption job {} for {} messages (active key {})",
+            job.id,
+            total,
+            self.crypto.active_key_id()
+        );
+
+        let db = Arc::clone(&self.db);
+        let crypto = Arc::clone(&self.crypto);
+        let data_keys = Arc::clone(&self.data_keys);
+        let jobs = Arc::clone(&self.jobs);
+        let job_id = job.id;
+
+        tokio::spawn(async move {
+            let result = reencrypt_messages(&db, &crypto, &data_keys, &jobs, job_id).await;
+
+            let error = match result {
+                Ok((processed, failed)) => {
+                    info!("Re-encryption job {} finished: {} processed, {} failed", job_id, processed, failed);
+                    None
+                }
+                Err(e) => {
+                    warn!("Re-encryption job {} aborted: {}", job_id, e);
+                    Some(e.to_string())
+                }
+            };
+
+            if let Err(e) = jobs.finish(job_id, error).await {
+                warn!("Failed to record completion of job {}: {}", job_id, e);
+            }
+        });
+
+        Ok(job)
+    }
+}
+
+/// Walk all server-encrypted messages in id order and re-encrypt the stale ones.
+///
+/// Rows still under the global keyring (with or without AAD binding) are
+/// moved under their chat's data key in the context-bound format.
+///
+/// Rows that fail to decrypt are counted and logged but left untouched, so a
+/// missing retired key never destroys data.
+async fn reencrypt_messages(
+    db: &Database,
+    crypto: &CryptoService,
+    data_keys: &DataKeyService,
+    jobs: &JobService,
+    job_id: Uuid,
+) -> AppResult<(i64, i64)> {
+    let mut processed = 0i64;
+    let mut failed = 0i64;
+    let mut last_id = String::new();
+
+    loop {
+        let message_records = sqlx::query!(
+            r#"
+            SELECT id, chat_id, content, created_by
+            FROM messages
+            WHERE is_encrypted = TRUE AND is_e2ee = FALSE AND id > ?
+            ORDER BY id
+            LIMIT ?
+            "#,
+            last_id,
+            BATCH_SIZE
+        )
+        .fetch_all(db.pool())
+        .await?;
+
+        if message_records.is_empty() {
+            break;
+        }
+
+        for record in message_records {
+            last_id = record.id.clone();
+            processed += 1;
+
+            let chat_id = Uuid::parse_str(&record.chat_id)
+                .map_err(|e| AppError::Internal(format!("Invalid chat ID: {}", e)))?;
+            let data_key = data_keys.get_or_create(KeyOwner::Chat(chat_id)).await?;
+
+            if !crypto.needs_data_key_reencryption(&record.content, &data_key) {
+                continue;
+            }
+
+            let reencrypted = message_aad_for(&record.chat_id, &record.id, &record.created_by)
+                .and_then(|aad| {
+                    crypto
+                        .decrypt_with_data_key(&record.content, &aad, Some(&data_key))
+                        .and_then(|plaintext| crypto.encrypt_with_data_key(&data_key, &plaintext, &aad))
+                });
+
+            match reencrypted {
+                Ok(content) => {
+                    // Only replace the row if nobody changed it in the meantime
+                    sqlx::query!(
+                        "UPDATE messages SET content = ? WHERE id = ? AND content = ?",
+                        content,
+                        record.id,
+                        record.content
+                    )
+                    .execute(db.pool())
+                    .await?;
+                }
+                Err(e) => {
+                    failed += 1;
+                    warn!("Failed to re-encrypt message {}: {}", record.id, e);
+                }
+            }
+        }
+
+        jobs.update_progress(job_id, processed, failed).await?;
+    }
+
+    Ok((processed, failed))
+}
+
+fn message_aad_for(chat_id: &str, message_id: &str, sender_id: &str) -> AppResult<Vec<u8>> {
+    let chat_id = Uuid::parse_str(chat_id)
+        .map_err(|e| AppError::Internal(format!("Invalid chat ID: {}", e)))?;
+    let message_id = Uuid::parse_str(message_id)
+        .map_err(|e| AppError::Internal(format!("Invalid message ID: {}", e)))?;
+    let sender_id = Uuid::parse_str(sender_id)
+        .map_err(|e| AppError::Internal(format!("Invalid user ID: {}", e)))?;
+
+    Ok(message_aad(chat_id, message_id, sender_id))
+}
@@ -1,42 +1,259 @@
 use anyhow::Result;
-use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use argon2::{Algorithm, Argon2, KeyId, Params, ParamsBuilder, PasswordHash, PasswordHasher, PasswordVerifier, Version};
 use argon2::password_hash::{rand_core::OsRng, SaltString};
 use base64::{Engine as _, engine::general_purpose};
 use ring::{
-    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM},
+    aead::{Aad, LessSafeKey, Nonce},
+    hmac,
     rand::{SecureRandom, SystemRandom},
 };
+use std::time::{Duration, Instant};
+use tracing::warn;
+use uuid::Uuid;
 
 use crate::core::config::CryptoConfig;
 use crate::core::error::{AppError, AppResult};
+use crate::crypto::data_keys::DataKey;
+use crate::crypto::keyring::Keyring;
+
+/// Domain separation prefix for blind index tokens
+const BLIND_INDEX_CONTEXT: &[u8] = b"amogchan/blind-index/v1";
+
+/// Blind index tokens are truncated to this many bytes
+const BLIND_INDEX_LEN: usize = 16;
+
+/// Domain separation for keyed digests of signed message content
+const CONTENT_DIGEST_CONTEXT: &[u8] = b"amogchan/content-digest/v1";
+
+/// Header of ciphertexts sealed without associated data: "v1:<key id>:<payload>".
+/// Older ciphertexts are a bare base64 payload; base64 never contains ':'.
+const CIPHERTEXT_V1: &str = "v1";
+
+/// Header of ciphertexts sealed with caller-supplied associated data:
+/// "v2:<key id>:<payload>". They only open with the exact same AAD.
+const CIPHERTEXT_V2: &str = "v2";
+
+/// Header of ciphertexts sealed under a per-owner data key, with AAD:
+/// "v3:<data key id>:<payload>"
+const CIPHERTEXT_V3: &str = "v3";
+
+/// Length of the pepper fingerprint stored as the Argon2 `keyid` parameter
+const PEPPER_ID_LEN: usize = 4;
 
 pub struct CryptoService {
-    key: LessSafeKey,
+    keyring: Keyring,
+    index_key: hmac::Key,
+    require_aad: bool,
+    password_params: Params,
+    pepper: Option<Vec<u8>>,
+    pepper_id: Option<Vec<u8>>,
     rng: SystemRandom,
 }
 
 impl CryptoService {
     pub fn new(config: &CryptoConfig) -> Result<Self> {
-        // Convert the encryption key from base64 to bytes
-        let key_bytes = general_purpose::STANDARD
-            .decode(&config.encryption_key)
-            .map_err(|e| AppError::Crypto(format!("Invalid encryption key: {}", e)))?;
+        let keyring = Keyring::from_config(config)?;
 
-        if key_bytes.len() != 32 {
-            return Err(AppError::Crypto("Encryption key must be 32 bytes".to_string()).into());
+        // The search index key must be independent of the encryption keys
+        let index_key_bytes = general_purpose::STANDARD
+            .decode(&config.search_index_key)
+            .map_err(|e| AppError::Crypto(format!("Invalid search index key: {}", e)))?;
+
+        if index_key_bytes.len() < 32 {
+            return Err(AppError::Crypto("Search index key must be at least 32 bytes".to_string()).into());
         }
 
-        let unbound_key = UnboundKey::new(&AES_256_GCM, &key_bytes)
-            .map_err(|e| AppError::Crypto(format!("Failed to create key: {}", e)))?;
-        
-        let key = LessSafeKey::new(unbound_key);
+        // Compared as decoded bytes, against retired keys too
+        let encryption_keys = std::iter::once(config.encryption_key.as_str()).chain(
+            config.previous_encryption_keys
+                .iter()
+                .filter_map(|entry| entry.split_once(':').map(|(_, key)| key)),
+        );
+        for encryption_key in encryption_keys {
+            let key_bytes = general_purpose::STANDARD
+                .decode(encryption_key)
+                .map_err(|e| AppError::Crypto(format!("Invalid encryption key: {}", e)))?;
+            if key_bytes == index_key_bytes {
+                return Err(AppError::Crypto("Search index key must differ from every encryption key".to_string()).into());
+            }
+        }
+
+        let index_key = hmac::Key::new(hmac::HMAC_SHA256, &index_key_bytes);
         let rng = SystemRandom::new();
 
-        Ok(Self { key, rng })
+        // Peppered hashes carry a fingerprint of the pepper as their key id, so
+        // hashes made before a pepper was configured can still be verified
+        let pepper = config.password_pepper.as_ref().map(|pepper| pepper.as_bytes().to_vec());
+        let pepper_id = pepper.as_ref().map(|pepper| {
+            let digest = ring::digest::digest(&ring::digest::SHA256, pepper);
+            digest.as_ref()[..PEPPER_ID_LEN].to_vec()
+        });
+
+        let mut params_builder = ParamsBuilder::new();
+        params_builder
+            .m_cost(config.password_memory_kib)
+            .t_cost(config.password_iterations)
+            .p_cost(config.password_parallelism);
+        if let Some(ref pepper_id) = pepper_id {
+            let key_id = KeyId::new(pepper_id)
+                .map_err(|e| AppError::Crypto(format!("Invalid pepper key id: {}", e)))?;
+            params_builder.keyid(key_id);
+        }
+        let password_params = params_builder
+            .build()
+            .map_err(|e| AppError::Crypto(format!("Invalid password hashing parameters: {}", e)))?;
+
+        Ok(Self {
+            keyring,
+            index_key,
+            require_aad: config.require_aad,
+            password_params,
+            pepper,
+            pepper_id,
+            rng,
+        })
     }
 
-    /// Encrypt plaintext data
+    /// Encrypt plaintext data with the active key
     pub fn encrypt(&self, plaintext: &str) -> AppResult<String> {
+        self.encrypt_with_aad(plaintext, &[])
+    }
+
+    /// Decrypt ciphertext data
+    pub fn decrypt(&self, ciphertext: &str) -> AppResult<String> {
+        self.decrypt_with_aad(ciphertext, &[])
+    }
+
+    /// Encrypt plaintext bound to associated data (see `crypto::aad`).
+    ///
+    /// The AAD isn't stored; decryption fails unless the caller supplies the
+    /// same bytes, so a ciphertext copied to another row or chat won't open.
+    pub fn encrypt_with_aad(&self, plaintext: &str, aad: &[u8]) -> AppResult<String> {
+        let payload = self.seal(self.keyring.active(), plaintext, aad)?;
+
+        Ok(format!("{}:{}:{}", CIPHERTEXT_V2, self.keyring.active_id(), payload))
+    }
+
+    /// Encrypt plaintext under a per-chat or per-user data key, bound to associated data
+    pub fn encrypt_with_data_key(&self, data_key: &DataKey, plaintext: &str, aad: &[u8]) -> AppResult<String> {
+        let payload = self.seal(&data_key.key, plaintext, aad)?;
+
+        Ok(format!("{}:{}:{}", CIPHERTEXT_V3, data_key.id, payload))
+    }
+
+    /// Decrypt a ciphertext that may be under a data key or, for rows from
+    /// before envelope encryption, under the global keyring
+    pub fn decrypt_with_data_key(&self, ciphertext: &str, aad: &[u8], data_key: Option<&DataKey>) -> AppResult<String> {
+        let Some(rest) = ciphertext
+            .strip_prefix(CIPHERTEXT_V3)
+            .and_then(|rest| rest.strip_prefix(':'))
+        else {
+            return self.decrypt_with_aad(ciphertext, aad);
+        };
+
+        let (key_id, payload) = rest
+            .split_once(':')
+            .ok_or_else(|| AppError::Crypto("Malformed ciphertext header".to_string()))?;
+
+        // A missing data key means it was shredded
+        let data_key = data_key
+            .filter(|data_key| data_key.id.to_string() == key_id)
+            .ok_or_else(|| AppError::Crypto(format!("Data key {} is not available", key_id)))?;
+
+        let encrypted_data = general_purpose::STANDARD
+            .decode(payload)
+            .map_err(|e| AppError::Crypto(format!("Invalid base64: {}", e)))?;
+
+        if encrypted_data.len() < 12 {
+            return Err(AppError::Crypto("Ciphertext too short".to_string()));
+        }
+
+        let (nonce_bytes, ciphertext_with_tag) = encrypted_data.split_at(12);
+        let plaintext_bytes = open(&data_key.key, nonce_bytes, ciphertext_with_tag, aad)?;
+
+        String::from_utf8(plaintext_bytes)
+            .map_err(|e| AppError::Crypto(format!("Invalid UTF-8: {}", e)))
+    }
+
+    /// Decrypt ciphertext bound to associated data.
+    ///
+    /// Ciphertexts from before AAD binding (v1 and headerless) are refused
+    /// unless `ENCRYPTION_REQUIRE_AAD` is turned off, which upgraded
+    /// instances do until the re-encryption job has upgraded every row.
+    pub fn decrypt_with_aad(&self, ciphertext: &str, aad: &[u8]) -> AppResult<String> {
+        let (version, key_id, payload) = match ciphertext.split_once(':') {
+            Some((version @ (CIPHERTEXT_V1 | CIPHERTEXT_V2), rest)) => {
+                let (key_id, payload) = rest
+                    .split_once(':')
+                    .ok_or_else(|| AppError::Crypto("Malformed ciphertext header".to_string()))?;
+                (Some(version), Some(key_id), payload)
+            }
+            Some((version, _)) => {
+                return Err(AppError::Crypto(format!("Unsupported ciphertext version: {}", version)));
+            }
+            None => (None, None, ciphertext),
+        };
+
+        // Only v2 ciphertexts were sealed with associated data
+        let aad = if version == Some(CIPHERTEXT_V2) {
+            aad
+        } else if self.require_aad && !aad.is_empty() {
+            return Err(AppError::Crypto("Ciphertext is not bound to its context".to_string()));
+        } else {
+            &[]
+        };
+
+        let encrypted_data = general_purpose::STANDARD
+            .decode(payload)
+            .map_err(|e| AppError::Crypto(format!("Invalid base64: {}", e)))?;
+
+        if encrypted_data.len() < 12 {
+            return Err(AppError::Crypto("Ciphertext too short".to_string()));
+        }
+
+        let (nonce_bytes, ciphertext_with_tag) = encrypted_data.split_at(12);
+
+        let plaintext_bytes = match key_id {
+            Some(key_id) => {
+                let key = self.keyring.get(key_id)
+                    .ok_or_else(|| AppError::Crypto(format!("Unknown encryption key id: {}", key_id)))?;
+                open(key, nonce_bytes, ciphertext_with_tag, aad)?
+            }
+            // Legacy ciphertexts carry no key id, so try every key in the ring
+            None => self.keyring
+                .candidates()
+                .find_map(|key| open(key, nonce_bytes, ciphertext_with_tag, aad).ok())
+                .ok_or_else(|| AppError::Crypto("Decryption failed with all known keys".to_string()))?,
+        };
+
+        let plaintext = String::from_utf8(plaintext_bytes)
+            .map_err(|e| AppError::Crypto(format!("Invalid UTF-8: {}", e)))?;
+
+        Ok(plaintext)
+    }
+
+    /// Whether a ciphertext should be moved under the given data key
+    pub fn needs_data_key_reencryption(&self, ciphertext: &str, data_key: &DataKey) -> bool {
+        let prefix = format!("{}:{}:", CIPHERTEXT_V3, data_key.id);
+        !ciphertext.starts_with(&prefix)
+    }
+
+    /// Generate fresh key material for a data encryption key
+    pub fn generate_key_bytes(&self) -> AppResult<Vec<u8>> {
+        self.random_bytes(32)
+    }
+
+    /// Generate `len` cryptographically secure random bytes
+    pub fn random_bytes(&self, len: usize) -> AppResult<Vec<u8>> {
+        let mut bytes = vec![0u8; len];
+        self.rng.fill(&mut bytes)
+            .map_err(|e| AppError::Crypto(format!("Failed to generate random bytes: {}", e)))?;
+
+        Ok(bytes)
+    }
+
+    /// Seal plaintext and return base64(nonce || ciphertext || tag)
+    fn seal(&self, key: &LessSafeKey, plaintext: &str, aad: &[u8]) -> AppResult<String> {
         let mut nonce_bytes = [0u8; 12];
         self.rng.fill(&mut nonce_bytes)
             .map_err(|e| AppError::Crypto(format!("Failed to generate nonce: {}", e)))?;
@@ -44,7 +261,7 @@ impl CryptoService {
         let nonce = Nonce::assume_unique_for_key(nonce_bytes);
         let mut in_out = plaintext.as_bytes().to_vec();
 
-        self.key.seal_in_place_append_tag(nonce, Aad::empty(), &mut in_out)
+        key.seal_in_place_append_tag(nonce, Aad::from(aad), &mut in_out)
             .map_err(|e| AppError::Crypto(format!("Encryption failed: {}", e)))?;
 
         // Prepend nonce to encrypted data
@@ -54,34 +271,45 @@ impl CryptoService {
         Ok(general_purpose::STANDARD.encode(result))
     }
 
-    /// Decrypt ciphertext data
-    pub fn decrypt(&self, ciphertext: &str) -> AppResult<String> {
-        let encrypted_data = general_purpose::STANDARD
-            .decode(ciphertext)
-            .map_err(|e| AppError::Crypto(format!("Invalid base64: {}", e)))?;
-
-        if encrypted_data.len() < 12 {
-            return Err(AppError::Crypto("Ciphertext too short".to_string()));
-        }
+    /// Id of the key used for new ciphertexts
+    pub fn active_key_id(&self) -> &str {
+        self.keyring.active_id()
+    }
 
-        let (nonce_bytes, mut ciphertext_with_tag) = encrypted_data.split_at(12);
-        let nonce = Nonce::try_assume_unique_for_key(nonce_bytes)
-            .map_err(|e| AppError::Crypto(format!("Invalid nonce: {}", e)))?;
+    /// Derive a blind index token for a search keyword.
+    ///
+    /// Tokens are scoped (e.g. to a chat id) so the same word produces
+    /// unrelated tokens in different scopes and can't be correlated across them.
+    pub fn blind_index(&self, scope: &str, term: &str) -> String {
+        let mut ctx = hmac::Context::with_key(&self.index_key);
+        ctx.update(BLIND_INDEX_CONTEXT);
+        ctx.update(&[0]);
+        ctx.update(scope.as_bytes());
+        ctx.update(&[0]);
+        ctx.update(term.as_bytes());
+        let tag = ctx.sign();
 
-        let mut ciphertext_vec = ciphertext_with_tag.to_vec();
-        let plaintext_bytes = self.key.open_in_place(nonce, Aad::empty(), &mut ciphertext_vec)
-            .map_err(|e| AppError::Crypto(format!("Decryption failed: {}", e)))?;
+        general_purpose::URL_SAFE_NO_PAD.encode(&tag.as_ref()[..BLIND_INDEX_LEN])
+    }
 
-        let plaintext = String::from_utf8(plaintext_bytes.to_vec())
-            .map_err(|e| AppError::Crypto(format!("Invalid UTF-8: {}", e)))?;
+    /// Keyed digest of a message's plaintext, which the message's signature
+    /// covers instead of the plaintext. Without the search index key it can't
+    /// be checked against guessed messages. Stays the same when the
+    /// encryption keys are rotated.
+    pub fn content_digest(&self, message_id: Uuid, plaintext: &str) -> String {
+        let mut ctx = hmac::Context::with_key(&self.index_key);
+        ctx.update(CONTENT_DIGEST_CONTEXT);
+        ctx.update(&[0]);
+        ctx.update(message_id.as_bytes());
+        ctx.update(plaintext.as_bytes());
 
-        Ok(plaintext)
+        general_purpose::URL_SAFE_NO_PAD.encode(ctx.sign().as_ref())
     }
 
-    /// Hash a password using Argon2
+    /// Hash a password using Argon2id with the configured parameters and pepper
     pub fn hash_password(&self, password: &str) -> AppResult<String> {
         let salt = SaltString::generate(&mut OsRng);
-        let argon2 = Argon2::default();
+        let argon2 = self.password_hasher(self.pepper.as_deref(), self.password_params.clone())?;
         
         let password_hash = argon2.hash_password(password.as_bytes(), &salt)
             .map_err(|e| AppError::Crypto(format!("Password hashing failed: {}", e)))?;
@@ -89,12 +317,27 @@ impl CryptoService {
         Ok(password_hash.to_string())
     }
 
-    /// Verify a password against its hash
+    /// Verify a password against its hash.
+    ///
+    /// The hash's own parameters are used, so hashes made with older settings
+    /// keep verifying; `password_needs_rehash` tells when to upgrade them.
     pub fn verify_password(&self, password: &str, hash: &str) -> AppResult<bool> {
         let parsed_hash = PasswordHash::new(hash)
             .map_err(|e| AppError::Crypto(format!("Invalid password hash: {}", e)))?;
+        let hash_params = Params::try_from(&parsed_hash)
+            .map_err(|e| AppError::Crypto(format!("Invalid password hash parameters: {}", e)))?;
+
+        // A hash peppered with a different (or no longer configured) pepper can't match
+        let pepper = if hash_params.keyid().is_empty() {
+            None
+        } else if self.pepper_id.as_deref() == Some(hash_params.keyid()) {
+            self.pepper.as_deref()
+        } else {
+            warn!("Password hash was made with a pepper that is no longer configured");
+            return Ok(false);
+        };
 
-        let argon2 = Argon2::default();
+        let argon2 = self.password_hasher(pepper, hash_params)?;
         
         match argon2.verify_password(password.as_bytes(), &parsed_hash) {
             Ok(()) => Ok(true),
@@ -102,6 +345,39 @@ impl CryptoService {
         }
     }
 
+    /// Whether a stored hash uses a different algorithm, weaker parameters
+    /// or a different pepper than currently configured
+    pub fn password_needs_rehash(&self, hash: &str) -> bool {
+        let Ok(parsed_hash) = PasswordHash::new(hash) else {
+            return true;
+        };
+        let Ok(hash_params) = Params::try_from(&parsed_hash) else {
+            return true;
+        };
+
+        parsed_hash.algorithm != Algorithm::Argon2id.ident()
+            || parsed_hash.version != Some(Version::V0x13.into())
+            || hash_params.m_cost() < self.password_params.m_cost()
+            || hash_params.t_cost() < self.password_params.t_cost()
+            || hash_params.p_cost() < self.password_params.p_cost()
+            || hash_params.keyid() != self.password_params.keyid()
+    }
+
+    /// Time one password hash with the configured parameters
+    pub fn benchmark_password_hash(&self) -> AppResult<Duration> {
+        let started = Instant::now();
+        self.hash_password("password-hashing-benchmark")?;
+        Ok(started.elapsed())
+    }
+
+    fn password_hasher<'a>(&self, pepper: Option<&'a [u8]>, params: Params) -> AppResult<Argon2<'a>> {
+        match pepper {
+            Some(pepper) => Argon2::new_with_secret(pepper, Algorithm::Argon2id, Version::V0x13, params)
+                .map_err(|e| AppError::Crypto(format!("Invalid password pepper: {}", e))),
+            None => Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params)),
+        }
+    }
+
     /// Generate a random token
     pub fn generate_token(&self) -> AppResult<String> {
         let mut token_bytes = [0u8; 32];
@@ -126,4 +402,106 @@ impl CryptoService {
 
         Ok(general_purpose::STANDARD.encode(id_bytes))
     }
-}
\ No newline at end of file
+}
+
+/// Open a nonce-split AES-GCM payload with one key
+fn open(key: &LessSafeKey, nonce_bytes: &[u8], ciphertext_with_tag: &[u8], aad: &[u8]) -> AppResult<Vec<u8>> {
+    let nonce = Nonce::try_assume_unique_for_key(nonce_bytes)
+        .map_err(|e| AppError::Crypto(format!("Invalid nonce: {}", e)))?;
+
+    let mut ciphertext_vec = ciphertext_with_tag.to_vec();
+    let plaintext_bytes = key.open_in_place(nonce, Aad::from(aad), &mut ciphertext_vec)
+        .map_err(|e| AppError::Crypto(format!("Decryption failed: {}", e)))?;
+
+    Ok(plaintext_bytes.to_vec())
+}
+
+/// Fixed keys and cheap password hashing for tests
+#[cfg(test)]
+pub(crate) fn test_config() -> CryptoConfig {
+    CryptoConfig {
+        encryption_key: general_purpose::STANDARD.encode([1u8; 32]),
+        encryption_key_id: "k1".to_string(),
+        previous_encryption_keys: Vec::new(),
+        reencrypt_on_startup: false,
+        require_aad: true,
+        kek_provider: "local".to_string(),
+        master_key: None,
+        master_key_file: None,
+        kms_key_name: String::new(),
+        signing_key: String::new(),
+        signing_key_id: String::new(),
+        retired_signing_keys: Vec::new(),
+        search_index_key: general_purpose::STANDARD.encode([2u8; 32]),
+        password_memory_kib: 8 * 1024,
+        password_iterations: 1,
+        password_parallelism: 1,
+        password_pepper: None,
+        password_hash_target_ms: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat::search;
+    use crate::crypto::aad;
+
+    #[test]
+    fn blind_index_tokens_never_contain_plaintext() {
+        let crypto = CryptoService::new(&test_config()).unwrap();
+
+        for term in search::tokenize("Meet the harbour warehouse tonight") {
+            let token = crypto.blind_index("chat-a", &term);
+            let tag = general_purpose::URL_SAFE_NO_PAD.decode(&token).unwrap();
+
+            assert!(!token.to_lowercase().contains(&term));
+            assert!(!tag.windows(term.len()).any(|window| window == term.as_bytes()));
+            assert_eq!(tag.len(), BLIND_INDEX_LEN);
+        }
+    }
+
+    #[test]
+    fn blind_index_tokens_are_stable_within_a_chat_and_differ_across_chats() {
+        let crypto = CryptoService::new(&test_config()).unwrap();
+
+        assert_eq!(crypto.blind_index("chat-a", "harbour"), crypto.blind_index("chat-a", "harbour"));
+        assert_ne!(crypto.blind_index("chat-a", "harbour"), crypto.blind_index("chat-b", "harbour"));
+    }
+
+    #[test]
+    fn message_ciphertext_does_not_decrypt_in_another_row() {
+        let crypto = CryptoService::new(&test_config()).unwrap();
+        let (chat_id, sender_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let (first_id, second_id) = (Uuid::new_v4(), Uuid::new_v4());
+
+        let first = crypto
+            .encrypt_with_aad("first", &aad::message_aad(chat_id, first_id, sender_id))
+            .unwrap();
+        let second = crypto
+            .encrypt_with_aad("second", &aad::message_aad(chat_id, second_id, sender_id))
+            .unwrap();
+
+        // Swapping the two rows' ciphertexts must not move the messages
+        assert!(crypto.decrypt_with_aad(&second, &aad::message_aad(chat_id, first_id, sender_id)).is_err());
+        assert!(crypto.decrypt_with_aad(&first, &aad::message_aad(chat_id, second_id, sender_id)).is_err());
+        assert!(crypto.decrypt_with_aad(&first, &aad::message_aad(Uuid::new_v4(), first_id, sender_id)).is_err());
+        assert!(crypto.decrypt_with_aad(&first, &aad::message_aad(chat_id, first_id, Uuid::new_v4())).is_err());
+
+        assert_eq!(
+            crypto.decrypt_with_aad(&first, &aad::message_aad(chat_id, first_id, sender_id)).unwrap(),
+            "first"
+        );
+    }
+
+    #[test]
+    fn search_index_key_must_differ_from_every_encryption_key() {
+        let mut config = test_config();
+        config.search_index_key = config.encryption_key.clone();
+        assert!(CryptoService::new(&config).is_err());
+
+        let mut config = test_config();
+        config.previous_encryption_keys = vec![format!("k0:{}", config.search_index_key)];
+        assert!(CryptoService::new(&config).is_err());
+    }
+}
@@ -0,0 +1,316 @@
+use base64::{Engine as _, engine::general_purpose};
+use ring::signature::{self, Ed25519KeyPair, KeyPair, UnparsedPublicKey};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::{info, warn};
+
+use crate::core::config::CryptoConfig;
+use crate::core::error::{AppError, AppResult};
+use crate::core::types::{Message, Post, Thread};
+use crate::crypto::canonical;
+
+/// Domain separation prefix when deriving an Ed25519 seed from a passphrase
+const SEED_DERIVATION_CONTEXT: &[u8] = b"amogchan/signing-key/v1";
+
+/// A server signature over a canonical representation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContentSignature {
+    pub key_id: String,
+    pub signature: String,
+}
+
+/// A published signing public key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SigningPublicKey {
+    pub key_id: String,
+    pub algorithm: String,
+    pub public_key: String,
+    pub active: bool,
+}
+
+/// Ed25519 signatures over threads, posts and messages.
+///
+/// The active key signs new content. Public keys of retired signing keys are
+/// kept so content signed before a rotation still verifies.
+pub struct SigningService {
+    active_id: String,
+    key_pair: Ed25519KeyPair,
+    public_keys: HashMap<String, Vec<u8>>,
+}
+
+impl SigningService {
+    pub fn new(config: &CryptoConfig) -> AppResult<Self> {
+        validate_key_id(&config.signing_key_id)?;
+
+        let seed = signing_seed(&config.signing_key)?;
+        let key_pair = Ed25519KeyPair::from_seed_unchecked(&seed)
+            .map_err(|e| AppError::Crypto(format!("Failed to create signing key: {}", e)))?;
+
+        let mut public_keys = HashMap::new();
+        public_keys.insert(config.signing_key_id.clone(), key_pair.public_key().as_ref().to_vec());
+
+        // Retired public keys, formatted as "id:base64key,id:base64key"
+        for entry in config.retired_signing_keys.iter() {
+            let (key_id, public_key) = entry
+                .split_once(':')
+                .ok_or_else(|| AppError::Crypto("Retired signing keys must be formatted as id:key".to_string()))?;
+
+            validate_key_id(key_id)?;
+            if public_keys.contains_key(key_id) {
+                return Err(AppError::Crypto(format!("Duplicate signing key id: {}", key_id)));
+            }
+
+            let public_key = general_purpose::STANDARD
+                .decode(public_key)
+                .map_err(|e| AppError::Crypto(format!("Invalid retired signing key: {}", e)))?;
+            if public_key.len() != 32 {
+                return Err(AppError::Crypto("Signing public keys must be 32 bytes".to_string()));
+            }
+
+            public_keys.insert(key_id.to_string(), public_key);
+        }
+
+        info!(
+            "Signing content with Ed25519 key {} ({})",
+            config.signing_key_id,
+            general_purpose::STANDARD.encode(key_pair.public_key().as_ref())
+        );
+
+        Ok(Self {
+            active_id: config.signing_key_id.clone(),
+            key_pair,
+            public_keys,
+        })
+    }
+
+    /// Sign bytes with the active key
+    pub fn sign(&self, data: &[u8]) -> ContentSignature {
+        ContentSignature {
+            key_id: self.active_id.clone(),
+            signature: general_purpose::STANDARD.encode(self.key_pair.sign(data).as_ref()),
+        }
+    }
+
+    /// Verify a signature made by any published key. The recorded key id is
+    /// tried first, then every other key, so content whose key id was lost or
+    /// renamed in a rotation still verifies.
+    pub fn verify(&self, data: &[u8], key_id: Option<&str>, signature_b64: &str) -> AppResult<()> {
+        let signature_bytes = general_purpose::STANDARD
+            .decode(signature_b64)
+            .map_err(|e| AppError::Crypto(format!("Invalid signature encoding: {}", e)))?;
+
+        let recorded = key_id.and_then(|key_id| self.public_keys.get(key_id));
+        let others = self.public_keys
+            .iter()
+            .filter(|(id, _)| Some(id.as_str()) != key_id)
+            .map(|(_, public_key)| public_key);
+
+        let verified = recorded.into_iter().chain(others).any(|public_key| {
+            UnparsedPublicKey::new(&signature::ED25519, public_key)
+                .verify(data, &signature_bytes)
+                .is_ok()
+        });
+
+        if !verified {
+            return Err(AppError::Crypto("Signature verification failed".to_string()));
+        }
+
+        Ok(())
+    }
+
+    pub fn sign_thread(&self, thread: &Thread) -> ContentSignature {
+        self.sign(&canonical::thread(thread))
+    }
+
+    pub fn sign_post(&self, post: &Post) -> ContentSignature {
+        self.sign(&canonical::post(post))
+    }
+
+    /// Sign a message over `content` in place of its own (see `canonical::message`)
+    pub fn sign_message(&self, message: &Message, content: &str) -> ContentSignature {
+        self.sign(&canonical::message(message, content))
+    }
+
+    pub fn verify_thread(&self, thread: &Thread) -> AppResult<()> {
+        let signature = signature(&thread.signature)?;
+        self.verify(&canonical::thread(thread), thread.signing_key_id.as_deref(), signature)
+    }
+
+    pub fn verify_post(&self, post: &Post) -> AppResult<()> {
+        let signature = signature(&post.signature)?;
+        self.verify(&canonical::post(post), post.signing_key_id.as_deref(), signature)
+    }
+
+    /// Verify a message as returned to readers: over the envelope when it is
+    /// end-to-end encrypted, otherwise over its `content_digest`
+    pub fn verify_message(&self, message: &Message) -> AppResult<()> {
+        let signature = signature(&message.signature)?;
+        let content = if message.is_e2ee {
+            message.content.as_str()
+        } else {
+            message.content_digest
+                .as_deref()
+                .ok_or_else(|| AppError::Crypto("Message has no content digest".to_string()))?
+        };
+
+        self.verify(&canonical::message(message, content), message.signing_key_id.as_deref(), signature)
+    }
+
+    /// All public keys that verify content, active key first
+    pub fn public_keys(&self) -> Vec<SigningPublicKey> {
+        let mut keys: Vec<SigningPublicKey> = self.public_keys
+            .iter()
+            .map(|(key_id, public_key)| SigningPublicKey {
+                key_id: key_id.clone(),
+                algorithm: "ed25519".to_string(),
+                public_key: general_purpose::STANDARD.encode(public_key),
+                active: *key_id == self.active_id,
+            })
+            .collect();
+
+        keys.sort_by(|a, b| b.active.cmp(&a.active).then_with(|| a.key_id.cmp(&b.key_id)));
+        keys
+    }
+}
+
+fn signature(signature: &Option<String>) -> AppResult<&str> {
+    signature
+        .as_deref()
+        .ok_or_else(|| AppError::Crypto("Content is not signed".to_string()))
+}
+
+/// `SIGNING_KEY` is either a base64 32-byte Ed25519 seed or, for older
+/// deployments, an arbitrary secret the seed is derived from
+fn signing_seed(signing_key: &str) -> AppResult<Vec<u8>> {
+    if let Ok(seed) = general_purpose::STANDARD.decode(signing_key) {
+        if seed.len() == 32 {
+            return Ok(seed);
+        }
+    }
+
+    if signing_key.len() < 32 {
+        return Err(AppError::Crypto("Signing key must be a base64 32-byte seed or a secret of at least 32 characters".to_string()));
+    }
+
+    warn!("SIGNING_KEY is not a base64 32-byte seed; deriving the Ed25519 seed from it");
+
+    let mut context = ring::digest::Context::new(&ring::digest::SHA256);
+    context.update(SEED_DERIVATION_CONTEXT);
+    context.update(&[0]);
+    context.update(signing_key.as_bytes());
+    Ok(context.finish().as_ref().to_vec())
+}
+
+/// Key ids are published and stored next to every signature
+fn validate_key_id(key_id: &str) -> AppResult<()> {
+    let valid = !key_id.is_empty()
+        && key_id.len() <= 32
+        && key_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    if !valid {
+        return Err(AppError::Crypto(format!("Invalid signing key id: {:?}", key_id)));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::types::MessageType;
+    use crate::crypto::service::test_config;
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    fn config(key_id: &str, seed: u8, retired: Vec<String>) -> CryptoConfig {
+        CryptoConfig {
+            signing_key: general_purpose::STANDARD.encode([seed; 32]),
+            signing_key_id: key_id.to_string(),
+            retired_signing_keys: retired,
+            ..test_config()
+        }
+    }
+
+    /// The service after rotating from `old` to a new active key
+    fn rotated(old: &SigningService) -> SigningService {
+        let retired = old.public_keys()
+            .into_iter()
+            .map(|key| format!("{}:{}", key.key_id, key.public_key))
+            .collect();
+
+        SigningService::new(&config("sig-2", 9, retired)).unwrap()
+    }
+
+    fn message(content: &str) -> Message {
+        Message {
+            id: Uuid::new_v4(),
+            chat_id: Uuid::new_v4(),
+            content: content.to_string(),
+            message_type: MessageType::Text,
+            matrix_event_id: "$event".to_string(),
+            reply_to: None,
+            is_encrypted: true,
+            is_e2ee: false,
+            sender_device_id: None,
+            sender_key_id: None,
+            created_at: Utc::now(),
+            created_by: Uuid::new_v4(),
+            signature: None,
+            signing_key_id: None,
+            content_digest: None,
+            decryption_error: None,
+        }
+    }
+
+    #[test]
+    fn signatures_verify_after_a_rotation() {
+        let old = SigningService::new(&config("sig-1", 7, Vec::new())).unwrap();
+        let signed = old.sign(b"content");
+
+        let new = rotated(&old);
+
+        assert!(new.verify(b"content", Some(&signed.key_id), &signed.signature).is_ok());
+        assert!(new.verify(b"tampered", Some(&signed.key_id), &signed.signature).is_err());
+        assert_eq!(new.sign(b"content").key_id, "sig-2");
+    }
+
+    #[test]
+    fn every_published_key_is_tried() {
+        let old = SigningService::new(&config("sig-1", 7, Vec::new())).unwrap();
+        let signed = old.sign(b"content");
+
+        let new = rotated(&old);
+
+        assert!(new.verify(b"content", None, &signed.signature).is_ok());
+        assert!(new.verify(b"content", Some("sig-2"), &signed.signature).is_ok());
+        assert!(new.verify(b"content", Some("unknown"), &signed.signature).is_ok());
+
+        let unrelated = SigningService::new(&config("sig-1", 8, Vec::new())).unwrap();
+        assert!(unrelated.verify(b"content", Some("sig-1"), &signed.signature).is_err());
+    }
+
+    #[test]
+    fn messages_verify_over_the_returned_digest() {
+        let old = SigningService::new(&config("sig-1", 7, Vec::new())).unwrap();
+
+        let mut signed = message("meet at the harbour at noon");
+        let signature = old.sign_message(&signed, "digest");
+        signed.signature = Some(signature.signature);
+        signed.signing_key_id = Some(signature.key_id);
+
+        let new = rotated(&old);
+        assert!(new.verify_message(&signed).is_err());
+
+        signed.content_digest = Some("digest".to_string());
+        assert!(new.verify_message(&signed).is_ok());
+
+        signed.content_digest = Some("other digest".to_string());
+        assert!(new.verify_message(&signed).is_err());
+    }
+
+    #[test]
+    fn unsigned_content_does_not_verify() {
+        let service = SigningService::new(&config("sig-1", 7, Vec::new())).unwrap();
+        assert!(service.verify_message(&message("hello")).is_err());
+    }
+}
@@ -0,0 +1,326 @@
+use base64::{Engine as _, engine::general_purpose};
+use chrono::{Duration, Utc};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::core::error::{AppError, AppResult};
+use crate::core::types::{OneTimePreKey, PreKeyBundle, SignedPreKey, UploadKeysRequest};
+use crate::storage::database::Database;
+
+/// Cap on one-time pre-keys stored per device
+const MAX_ONE_TIME_PREKEYS: i64 = 100;
+
+/// Bundle claims an account can make per window. Each claim uses up one of
+/// the other user's one-time pre-keys per device, so unlimited claims would
+/// let anyone drain them.
+const MAX_CLAIMS_PER_WINDOW: i64 = 30;
+const CLAIM_WINDOW_MINUTES: i64 = 10;
+
+/// Directory of client public keys for end-to-end encrypted chats.
+///
+/// The server only stores and hands out public keys; private keys and
+/// message plaintext never leave the clients.
+pub struct E2eeService {
+    db: Arc<Database>,
+}
+
+impl E2eeService {
+    pub fn new(db: Arc<Database>) -> Self {
+        Self { db }
+    }
+
+    /// Upload or replace a device's identity and pre-keys.
+    /// Returns the number of one-time pre-keys now stored for the device.
+    pub async fn upload_keys(&self, user_id: Uuid, device_id: &str, request: UploadKeysRequest) -> AppResult<i64> {
+        validate_device_id(device_id)?;
+        let identity_key_bytes = decode_public_key(&request.identity_key)?;
+        decode_public_key(&request.signed_prekey.public_key)?;
+        general_purpose::STANDARD
+            .decode(&request.signed_prekey.signature)
+            .map_err(|_| AppError::InvalidRequest("Invalid signed pre-key signature".to_string()))?;
+
+        let identity_key_id = key_fingerprint(&identity_key_bytes);
+        let now = Utc::now();
+
+        let existing = sqlx::query!(
+            "SELECT identity_key FROM device_keys WHERE user_id = ? AND device_id = ?",
+            user_id.to_string(),
+            device_id
+        )
+        .fetch_optional(self.db.pool())
+        .await?;
+
+        let mut tx = self.db.pool().begin().await?;
+
+        // A new identity key invalidates every pre-key issued for the old one
+        if let Some(existing) = existing {
+            if existing.identity_key != request.identity_key {
+                sqlx::query!(
+                    "DELETE FROM one_time_prekeys WHERE user_id = ? AND device_id = ?",
+                    user_id.to_string(),
+                    device_id
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+
+        sqlx::query!(
+            r#"
+            INSERT INTO device_keys (user_id, device_id, identity_key, identity_key_id, signed_prekey_id, signed_prekey, signed_prekey_signature, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT (user_id, device_id) DO UPDATE SET
+                identity_key = excluded.identity_key,
+                identity_key_id = excluded.identity_key_id,
+                signed_prekey_id = excluded.signed_prekey_id,
+                signed_prekey = excluded.signed_prekey,
+                signed_prekey_signature = excluded.signed_prekey_signature,
+                updated_at = excluded.updated_at
+            "#,
+            user_id.to_string(),
+            device_id,
+            request.identity_key,
+            identity_key_id,
+            request.signed_prekey.key_id,
+            request.signed_prekey.public_key,
+            request.signed_prekey.signature,
+            now.to_rfc3339(),
+            now.to_rfc3339()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        for prekey in &request.one_time_prekeys {
+            decode_public_key(&prekey.public_key)?;
+
+            sqlx::query!(
+                "INSERT OR REPLACE INTO one_time_prekeys (user_id, device_id, key_id, public_key, created_at) VALUES (?, ?, ?, ?, ?)",
+                user_id.to_string(),
+                device_id,
+                prekey.key_id,
+                prekey.public_key,
+                now.to_rfc3339()
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) as "count: i64" FROM one_time_prekeys WHERE user_id = ? AND device_id = ?"#,
+            user_id.to_string(),
+            device_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if count > MAX_ONE_TIME_PREKEYS {
+            return Err(AppError::InvalidRequest(format!(
+                "At most {} one-time pre-keys may be stored per device",
+                MAX_ONE_TIME_PREKEYS
+            )));
+        }
+
+        tx.commit().await?;
+
+        Ok(count)
+    }
+
+    /// Number of unclaimed one-time pre-keys for a device
+    pub async fn prekey_count(&self, user_id: Uuid, device_id: &str) -> AppResult<i64> {
+        let count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) as "count: i64" FROM one_time_prekeys WHERE user_id = ? AND device_id = ?"#,
+            user_id.to_string(),
+            device_id
+        )
+        .fetch_one(self.db.pool())
+        .await?;
+
+        Ok(count)
+    }
+
+    /// Fetch a pre-key bundle for every device of a user, consuming one
+    /// one-time pre-key per device when available. Claims are rate-limited
+    /// per claimant.
+    pub async fn claim_bundles(&self, user_id: Uuid, claimant_id: Uuid) -> AppResult<Vec<PreKeyBundle>> {
+        self.record_claim(user_id, claimant_id).await?;
+
+        let device_records = sqlx::query!(
+            r#"
+            SELECT device_id, identity_key, identity_key_id, signed_prekey_id, signed_prekey, signed_prekey_signature
+            FROM device_keys
+            WHERE user_id = ?
+            ORDER BY device_id
+            "#,
+            user_id.to_string()
+        )
+        .fetch_all(self.db.pool())
+        .await?;
+
+        let mut bundles = Vec::new();
+
+        for record in device_records {
+            let mut tx = self.db.pool().begin().await?;
+
+            let prekey = sqlx::query!(
+                "SELECT key_id, public_key FROM one_time_prekeys WHERE user_id = ? AND device_id = ? ORDER BY key_id LIMIT 1",
+                user_id.to_string(),
+                record.device_id
+            )
+            .fetch_optional(&mut *tx)
+            .await?;
+
+            if let Some(ref prekey) = prekey {
+                sqlx::query!(
+                    "DELETE FROM one_time_prekeys WHERE user_id = ? AND device_id = ? AND key_id = ?",
+                    user_id.to_string(),
+                    record.device_id,
+                    prekey.key_id
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+
+            tx.commit().await?;
+
+            bundles.push(PreKeyBundle {
+                user_id,
+                device_id: record.device_id,
+                identity_key: record.identity_key,
+                identity_key_id: record.identity_key_id,
+                signed_prekey: SignedPreKey {
+                    key_id: record.signed_prekey_id,
+                    public_key: record.signed_prekey,
+                    signature: record.signed_prekey_signature,
+                },
+                one_time_prekey: prekey.map(|prekey| OneTimePreKey {
+                    key_id: prekey.key_id,
+                    public_key: prekey.public_key,
+                }),
+            });
+        }
+
+        Ok(bundles)
+    }
+
+    /// Count a claim against the claimant's limit. The count and the insert
+    /// are one statement, so parallel claims can't exceed it.
+    async fn record_claim(&self, user_id: Uuid, claimant_id: Uuid) -> AppResult<()> {
+        let now = Utc::now();
+        let window_start = (now - Duration::minutes(CLAIM_WINDOW_MINUTES)).to_rfc3339();
+
+        sqlx::query!(
+            "DELETE FROM prekey_claims WHERE claimant_id = ? AND created_at <= ?",
+            claimant_id.to_string(),
+            window_start
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO prekey_claims (claimant_id, user_id, created_at)
+            SELECT ?, ?, ?
+            WHERE (SELECT COUNT(*) FROM prekey_claims WHERE claimant_id = ? AND created_at > ?) < ?
+            "#,
+            claimant_id.to_string(),
+            user_id.to_string(),
+            now.to_rfc3339(),
+            claimant_id.to_string(),
+            window_start,
+            MAX_CLAIMS_PER_WINDOW
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::RateLimit);
+        }
+
+        Ok(())
+    }
+
+    /// Remove a device and its pre-keys
+    pub async fn delete_device(&self, user_id: Uuid, device_id: &str) -> AppResult<()> {
+        let mut tx = self.db.pool().begin().await?;
+
+        sqlx::query!(
+            "DELETE FROM one_time_prekeys WHERE user_id = ? AND device_id = ?",
+            user_id.to_string(),
+            device_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let result = sqlx::query!(
+            "DELETE FROM device_keys WHERE user_id = ? AND device_id = ?",
+            user_id.to_string(),
+            device_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Device not found".to_string()));
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Whether a user has registered at least one device
+    pub async fn has_devices(&self, user_id: Uuid) -> AppResult<bool> {
+        let device = sqlx::query!(
+            "SELECT device_id FROM device_keys WHERE user_id = ? LIMIT 1",
+            user_id.to_string()
+        )
+        .fetch_optional(self.db.pool())
+        .await?;
+
+        Ok(device.is_some())
+    }
+
+    /// Identity key id of a user's device, used to attribute ciphertext envelopes
+    pub async fn identity_key_id(&self, user_id: Uuid, device_id: &str) -> AppResult<String> {
+        let record = sqlx::query!(
+            "SELECT identity_key_id FROM device_keys WHERE user_id = ? AND device_id = ?",
+            user_id.to_string(),
+            device_id
+        )
+        .fetch_optional(self.db.pool())
+        .await?
+        .ok_or_else(|| AppError::InvalidRequest("Unknown sender device".to_string()))?;
+
+        Ok(record.identity_key_id)
+    }
+}
+
+fn validate_device_id(device_id: &str) -> AppResult<()> {
+    let valid = !device_id.is_empty()
+        && device_id.len() <= 64
+        && device_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    if !valid {
+        return Err(AppError::InvalidRequest("Invalid device ID".to_string()));
+    }
+
+    Ok(())
+}
+
+/// Public keys are base64-encoded 32-byte Curve25519/Ed25519 keys
+fn decode_public_key(encoded: &str) -> AppResult<Vec<u8>> {
+    let key_bytes = general_purpose::STANDARD
+        .decode(encoded)
+        .map_err(|_| AppError::InvalidRequest("Invalid public key encoding".to_string()))?;
+
+    if key_bytes.len() != 32 {
+        return Err(AppError::InvalidRequest("Public keys must be 32 bytes".to_string()));
+    }
+
+    Ok(key_bytes)
+}
+
+fn key_fingerprint(key_bytes: &[u8]) -> String {
+    let digest = ring::digest::digest(&ring::digest::SHA256, key_bytes);
+    general_purpose::URL_SAFE_NO_PAD.encode(&digest.as_ref()[..16])
+}
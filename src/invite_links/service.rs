@@ -0,0 +1,419 @@
+use chrono::{DateTime, Duration, Utc};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::auth::totp;
+use crate::board::service::BoardService;
+use crate::chat::service::ChatService;
+use crate::core::error::{AppError, AppResult};
+use crate::core::types::{
+    Board, CreateInviteLinkRequest, InviteLink, InviteLinkPreview, InviteLinkRequest, JoinResult, JoinStatus, User,
+};
+use crate::crypto::service::CryptoService;
+use crate::storage::database::Database;
+use crate::user::service::UserService;
+
+/// Longest an invite link can stay valid
+const MAX_LINK_HOURS: i64 = 24 * 365;
+
+/// Most joins a single link can admit
+const MAX_LINK_USES: i64 = 10_000;
+
+/// What an invite link leads to
+pub enum InviteTarget {
+    Chat(Uuid),
+    Board(Board),
+}
+
+/// Shareable, revocable links into group chats and private boards.
+///
+/// Group admins manage a chat's links; a board's owner, moderators and
+/// site admins manage its links. A link can expire, cap its number of uses
+/// and require approval, in which case opening it files a join request that
+/// a manager approves or rejects. Only a hash of each link's token is stored.
+pub struct InviteLinkService {
+    db: Arc<Database>,
+    crypto: Arc<CryptoService>,
+    chats: Arc<ChatService>,
+    boards: Arc<BoardService>,
+    users: Arc<UserService>,
+}
+
+impl InviteLinkService {
+    pub fn new(
+        db: Arc<Database>,
+        crypto: Arc<CryptoService>,
+        chats: Arc<ChatService>,
+        boards: Arc<BoardService>,
+        users: Arc<UserService>,
+    ) -> Self {
+        Self { db, crypto, chats, boards, users }
+    }
+
+    /// Create a link. Returns its metadata and the token, shown only once.
+    pub async fn create_link(&self, user: &User, target: InviteTarget, request: CreateInviteLinkRequest) -> AppResult<(InviteLink, String)> {
+        self.require_manager(user, &target).await?;
+
+        if let InviteTarget::Board(board) = &target {
+            if !board.is_private {
+                return Err(AppError::InvalidRequest("Public boards don't need invite links".to_string()));
+            }
+        }
+
+        if let Some(max_uses) = request.max_uses {
+            if !(1..=MAX_LINK_USES).contains(&max_uses) {
+                return Err(AppError::InvalidRequest(format!("Invite links can be used 1 to {} times", MAX_LINK_USES)));
+            }
+        }
+
+        let now = Utc::now();
+        let expires_at = match request.expires_in_hours {
+            Some(hours) if !(1..=MAX_LINK_HOURS).contains(&hours) => {
+                return Err(AppError::InvalidRequest(format!(
+                    "Invite links can be valid for 1 to {} hours",
+                    MAX_LINK_HOURS
+                )));
+            }
+            Some(hours) => Some(now + Duration::hours(hours)),
+            None => None,
+        };
+
+        let (chat_id, board_id) = target_ids(&target);
+        let token = totp::base32_encode(&self.crypto.random_bytes(16)?).to_lowercase();
+        let link = InviteLink {
+            id: Uuid::new_v4(),
+            chat_id,
+            board_id,
+            created_by: user.id,
+            max_uses: request.max_uses,
+            uses: 0,
+            expires_at,
+            requires_approval: request.requires_approval,
+            revoked: false,
+            created_at: now,
+        };
+
+        sqlx::query!(
+            r#"
+            INSERT INTO invite_links (id, token_hash, chat_id, board_id, created_by, max_uses, expires_at, requires_approval, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+            link.id.to_string(),
+            self.crypto.hash_data(&token),
+            chat_id.map(|id| id.to_string()),
+            board_id.map(|id| id.to_string()),
+            user.id.to_string(),
+            link.max_uses,
+            expires_at.map(|expires_at| expires_at.to_rfc3339()),
+            link.requires_approval,
+            now.to_rfc3339()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        Ok((link, token))
+    }
+
+    /// Links of a chat or board, newest first
+    pub async fn list_links(&self, user: &User, target: InviteTarget) -> AppResult<Vec<InviteLink>> {
+        self.require_manager(user, &target).await?;
+
+        let (chat_id, board_id) = target_ids(&target);
+        let link_ids = sqlx::query_scalar!(
+            "SELECT id FROM invite_links WHERE chat_id IS ? AND board_id IS ? ORDER BY created_at DESC",
+            chat_id.map(|id| id.to_string()),
+            board_id.map(|id| id.to_string())
+        )
+        .fetch_all(self.db.pool())
+        .await?;
+
+        let mut links = Vec::with_capacity(link_ids.len());
+        for link_id in link_ids {
+            links.push(self.get_link(parse_id(&link_id)?).await?);
+        }
+
+        Ok(links)
+    }
+
+    /// Revoke a link. Pending join requests through it are dropped.
+    pub async fn revoke_link(&self, user: &User, link_id: Uuid) -> AppResult<()> {
+        let link = self.get_link(link_id).await?;
+        self.require_manager(user, &self.link_target(&link).await?).await?;
+
+        sqlx::query!("UPDATE invite_links SET revoked = TRUE WHERE id = ?", link_id.to_string())
+            .execute(self.db.pool())
+            .await?;
+
+        sqlx::query!("DELETE FROM invite_link_requests WHERE link_id = ?", link_id.to_string())
+            .execute(self.db.pool())
+            .await?;
+
+        Ok(())
+    }
+
+    /// Where a link leads, for showing before joining
+    pub async fn preview_link(&self, token: &str) -> AppResult<InviteLinkPreview> {
+        let (link, target) = self.find_usable_link(token).await?;
+
+        let name = match target {
+            InviteTarget::Chat(chat_id) => sqlx::query_scalar!("SELECT name FROM chats WHERE id = ?", chat_id.to_string())
+                .fetch_one(self.db.pool())
+                .await?,
+            InviteTarget::Board(board) => Some(board.title),
+        };
+
+        Ok(InviteLinkPreview {
+            chat_id: link.chat_id,
+            board_id: link.board_id,
+            name,
+            requires_approval: link.requires_approval,
+        })
+    }
+
+    /// Join through a link, or ask to if it requires approval
+    pub async fn join(&self, user: &User, token: &str) -> AppResult<JoinResult> {
+        let (link, target) = self.find_usable_link(token).await?;
+
+        if self.is_member(&target, user.id).await? {
+            return Err(AppError::InvalidRequest("You are already a member".to_string()));
+        }
+
+        if link.requires_approval {
+            sqlx::query!(
+                "INSERT OR IGNORE INTO invite_link_requests (link_id, user_id, created_at) VALUES (?, ?, ?)",
+                link.id.to_string(),
+                user.id.to_string(),
+                Utc::now().to_rfc3339()
+            )
+            .execute(self.db.pool())
+            .await?;
+
+            return Ok(JoinResult { status: JoinStatus::Pending, chat_id: link.chat_id, board_id: link.board_id });
+        }
+
+        let tx = self.db.pool().begin().await?;
+        self.admit(tx, &link, target, user.id).await?;
+
+        Ok(JoinResult { status: JoinStatus::Joined, chat_id: link.chat_id, board_id: link.board_id })
+    }
+
+    /// Pending join requests through a link, oldest first
+    pub async fn list_requests(&self, user: &User, link_id: Uuid) -> AppResult<Vec<InviteLinkRequest>> {
+        let link = self.get_link(link_id).await?;
+        self.require_manager(user, &self.link_target(&link).await?).await?;
+
+        let request_records = sqlx::query!(
+            "SELECT user_id, created_at FROM invite_link_requests WHERE link_id = ? ORDER BY created_at ASC",
+            link_id.to_string()
+        )
+        .fetch_all(self.db.pool())
+        .await?;
+
+        let mut requests = Vec::with_capacity(request_records.len());
+        for record in request_records {
+            requests.push(InviteLinkRequest {
+                link_id,
+                user: self.users.get_public_user(parse_id(&record.user_id)?, user).await?,
+                created_at: parse_date(&record.created_at)?,
+            });
+        }
+
+        Ok(requests)
+    }
+
+    /// Let a requester in. Counts as a use of the link. The request stays
+    /// pending if they can't be admitted.
+    pub async fn approve_request(&self, user: &User, link_id: Uuid, requester_id: Uuid) -> AppResult<()> {
+        let link = self.get_link(link_id).await?;
+        let target = self.link_target(&link).await?;
+        self.require_manager(user, &target).await?;
+
+        let mut tx = self.db.pool().begin().await?;
+        take_request(&mut tx, link_id, requester_id).await?;
+        self.admit(tx, &link, target, requester_id).await
+    }
+
+    /// Turn a requester away
+    pub async fn reject_request(&self, user: &User, link_id: Uuid, requester_id: Uuid) -> AppResult<()> {
+        let link = self.get_link(link_id).await?;
+        self.require_manager(user, &self.link_target(&link).await?).await?;
+
+        let mut conn = self.db.pool().acquire().await?;
+        take_request(&mut conn, link_id, requester_id).await
+    }
+
+    /// Count a use of the link and add the user to its chat or board, all
+    /// in `tx`: a use nobody got is never counted
+    async fn admit(
+        &self,
+        mut tx: sqlx::Transaction<'static, sqlx::Sqlite>,
+        link: &InviteLink,
+        target: InviteTarget,
+        user_id: Uuid,
+    ) -> AppResult<()> {
+        if !self.creator_still_manages(link, &target).await? {
+            return Err(AppError::InvalidRequest("This invite link is no longer valid".to_string()));
+        }
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE invite_links SET uses = uses + 1
+            WHERE id = ? AND revoked = FALSE AND (max_uses IS NULL OR uses < max_uses) AND (expires_at IS NULL OR expires_at > ?)
+            "#,
+            link.id.to_string(),
+            Utc::now().to_rfc3339()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::InvalidRequest("This invite link is no longer valid".to_string()));
+        }
+
+        match target {
+            InviteTarget::Chat(chat_id) => self.chats.join_via_link(tx, chat_id, user_id, link.created_by).await.map(|_| ()),
+            InviteTarget::Board(board) => self.boards.add_member_with(tx, &board, user_id).await,
+        }
+    }
+
+    async fn require_manager(&self, user: &User, target: &InviteTarget) -> AppResult<()> {
+        let allowed = match target {
+            InviteTarget::Chat(chat_id) => self.chats.is_group_admin(*chat_id, user.id).await?,
+            InviteTarget::Board(board) => self.boards.can_manage(board, user).await?,
+        };
+
+        if !allowed {
+            return Err(AppError::Authorization("You can't manage invite links here".to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// A link only works while its creator could still create it: links of
+    /// demoted or removed admins and moderators, and of deleted accounts, stop
+    /// admitting anyone
+    async fn creator_still_manages(&self, link: &InviteLink, target: &InviteTarget) -> AppResult<bool> {
+        match target {
+            InviteTarget::Chat(chat_id) => self.chats.is_current_admin(*chat_id, link.created_by).await,
+            InviteTarget::Board(board) => {
+                let is_site_admin = sqlx::query_scalar!("SELECT is_admin FROM users WHERE id = ?", link.created_by.to_string())
+                    .fetch_optional(self.db.pool())
+                    .await?
+                    .unwrap_or(false);
+
+                Ok(is_site_admin || self.boards.is_owner_or_moderator(board, link.created_by).await?)
+            }
+        }
+    }
+
+    async fn is_member(&self, target: &InviteTarget, user_id: Uuid) -> AppResult<bool> {
+        match target {
+            InviteTarget::Chat(chat_id) => {
+                let participant = sqlx::query!(
+                    "SELECT user_id FROM chat_participants WHERE chat_id = ? AND user_id = ?",
+                    chat_id.to_string(),
+                    user_id.to_string()
+                )
+                .fetch_optional(self.db.pool())
+                .await?;
+
+                Ok(participant.is_some())
+            }
+            InviteTarget::Board(board) => self.boards.is_member(board.id, user_id).await,
+        }
+    }
+
+    async fn link_target(&self, link: &InviteLink) -> AppResult<InviteTarget> {
+        match (link.chat_id, link.board_id) {
+            (Some(chat_id), _) => Ok(InviteTarget::Chat(chat_id)),
+            (None, Some(board_id)) => Ok(InviteTarget::Board(self.boards.get_board_by_id(board_id).await?)),
+            (None, None) => Err(AppError::Internal(format!("Invite link {} has no target", link.id))),
+        }
+    }
+
+    /// A link by token that can still be used, and where it leads
+    async fn find_usable_link(&self, token: &str) -> AppResult<(InviteLink, InviteTarget)> {
+        let link_id = sqlx::query_scalar!(
+            "SELECT id FROM invite_links WHERE token_hash = ?",
+            self.crypto.hash_data(token.trim())
+        )
+        .fetch_optional(self.db.pool())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Invite link not found".to_string()))?;
+
+        let link = self.get_link(parse_id(&link_id)?).await?;
+
+        let exhausted = link.max_uses.is_some_and(|max_uses| link.uses >= max_uses);
+        let expired = link.expires_at.is_some_and(|expires_at| expires_at <= Utc::now());
+        if link.revoked || exhausted || expired {
+            return Err(AppError::InvalidRequest("This invite link is no longer valid".to_string()));
+        }
+
+        let target = self.link_target(&link).await?;
+        if !self.creator_still_manages(&link, &target).await? {
+            return Err(AppError::InvalidRequest("This invite link is no longer valid".to_string()));
+        }
+
+        Ok((link, target))
+    }
+
+    async fn get_link(&self, link_id: Uuid) -> AppResult<InviteLink> {
+        let record = sqlx::query!(
+            r#"
+            SELECT chat_id, board_id, created_by, max_uses, uses, expires_at, requires_approval, revoked, created_at
+            FROM invite_links WHERE id = ?
+            "#,
+            link_id.to_string()
+        )
+        .fetch_optional(self.db.pool())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Invite link not found".to_string()))?;
+
+        Ok(InviteLink {
+            id: link_id,
+            chat_id: record.chat_id.as_deref().map(parse_id).transpose()?,
+            board_id: record.board_id.as_deref().map(parse_id).transpose()?,
+            created_by: parse_id(&record.created_by)?,
+            max_uses: record.max_uses,
+            uses: record.uses,
+            expires_at: record.expires_at.as_deref().map(parse_date).transpose()?,
+            requires_approval: record.requires_approval,
+            revoked: record.revoked,
+            created_at: parse_date(&record.created_at)?,
+        })
+    }
+}
+
+/// Remove a pending join request, failing if there is none
+async fn take_request(conn: &mut sqlx::SqliteConnection, link_id: Uuid, requester_id: Uuid) -> AppResult<()> {
+    let result = sqlx::query!(
+        "DELETE FROM invite_link_requests WHERE link_id = ? AND user_id = ?",
+        link_id.to_string(),
+        requester_id.to_string()
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Join request not found".to_string()));
+    }
+
+    Ok(())
+}
+
+fn target_ids(target: &InviteTarget) -> (Option<Uuid>, Option<Uuid>) {
+    match target {
+        InviteTarget::Chat(chat_id) => (Some(*chat_id), None),
+        InviteTarget::Board(board) => (None, Some(board.id)),
+    }
+}
+
+fn parse_id(value: &str) -> AppResult<Uuid> {
+    Uuid::parse_str(value).map_err(|e| AppError::Internal(format!("Invalid ID: {}", e)))
+}
+
+fn parse_date(value: &str) -> AppResult<DateTime<Utc>> {
+    Ok(DateTime::parse_from_rfc3339(value)
+        .map_err(|e| AppError::Internal(format!("Invalid date: {}", e)))?
+        .with_timezone(&Utc))
+}
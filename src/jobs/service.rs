@@ -0,0 +1,191 @@
+use chrono::Utc;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::core::error::{AppError, AppResult};
+use crate::core::types::{Job, JobStatus};
+use crate::storage::database::Database;
+
+/// Tracks progress of long-running background jobs in the database
+pub struct JobService {
+    db: Arc<Database>,
+}
+
+impl JobService {
+    pub fn new(db: Arc<Database>) -> Self {
+        Self { db }
+    }
+
+    /// Record a newly started job. Returns `None` if a job of this kind is
+    /// already running; the check and the insert are one statement, so two
+    /// callers can't both start one.
+    pub async fn start(&self, kind: &str, total: i64, created_by: Option<Uuid>) -> AppResult<Option<Job>> {
+        let job_id = Uuid::new_v4();
+        let now = Utc::now();
+
+        let result = sqlx::query!(
+            r#"
+            INSERT OR IGNORE INTO background_jobs (id, kind, status, total, created_by, started_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            "#,
+            job_id.to_string(),
+            kind,
+            "running",
+            total,
+            created_by.map(|id| id.to_string()),
+            now.to_rfc3339(),
+            now.to_rfc3339()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        Ok(Some(Job {
+            id: job_id,
+            kind: kind.to_string(),
+            status: JobStatus::Running,
+            total,
+            processed: 0,
+            failed: 0,
+            error: None,
+            started_at: now,
+            updated_at: now,
+            finished_at: None,
+        }))
+    }
+
+    /// Update the progress counters of a running job
+    pub async fn update_progress(&self, job_id: Uuid, processed: i64, failed: i64) -> AppResult<()> {
+        sqlx::query!(
+            "UPDATE background_jobs SET processed = ?, failed = ?, updated_at = ? WHERE id = ?",
+            processed,
+            failed,
+            Utc::now().to_rfc3339(),
+            job_id.to_string()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        Ok(())
+    }
+
+    /// Mark a job as finished, successfully or with an error
+    pub async fn finish(&self, job_id: Uuid, error: Option<String>) -> AppResult<()> {
+        let now = Utc::now();
+        let status = if error.is_some() { JobStatus::Failed } else { JobStatus::Completed };
+
+        sqlx::query!(
+            "UPDATE background_jobs SET status = ?, error = ?, updated_at = ?, finished_at = ? WHERE id = ?",
+            status.as_str(),
+            error,
+            now.to_rfc3339(),
+            now.to_rfc3339(),
+            job_id.to_string()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        Ok(())
+    }
+
+    /// Mark jobs left running by a previous process as failed
+    pub async fn fail_interrupted(&self) -> AppResult<()> {
+        let now = Utc::now();
+
+        sqlx::query!(
+            "UPDATE background_jobs SET status = 'failed', error = 'Interrupted by restart', updated_at = ?, finished_at = ? WHERE status = 'running'",
+            now.to_rfc3339(),
+            now.to_rfc3339()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get a job by ID
+    pub async fn get_job(&self, job_id: Uuid) -> AppResult<Job> {
+        let record = sqlx::query!(
+            r#"
+            SELECT id, kind, status, total, processed, failed, error, started_at, updated_at, finished_at
+            FROM background_jobs WHERE id = ?
+            "#,
+            job_id.to_string()
+        )
+        .fetch_optional(self.db.pool())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Job not found".to_string()))?;
+
+        Ok(Job {
+            id: job_id,
+            kind: record.kind,
+            status: JobStatus::parse(&record.status),
+            total: record.total,
+            processed: record.processed,
+            failed: record.failed,
+            error: record.error,
+            started_at: chrono::DateTime::parse_from_rfc3339(&record.started_at)
+                .map_err(|e| AppError::Internal(format!("Invalid date: {}", e)))?
+                .with_timezone(&Utc),
+            updated_at: chrono::DateTime::parse_from_rfc3339(&record.updated_at)
+                .map_err(|e| AppError::Internal(format!("Invalid date: {}", e)))?
+                .with_timezone(&Utc),
+            finished_at: record.finished_at
+                .as_deref()
+                .map(chrono::DateTime::parse_from_rfc3339)
+                .transpose()
+                .map_err(|e| AppError::Internal(format!("Invalid date: {}", e)))?
+                .map(|finished_at| finished_at.with_timezone(&Utc)),
+        })
+    }
+
+    /// List the most recent jobs
+    pub async fn list_jobs(&self, limit: Option<i64>) -> AppResult<Vec<Job>> {
+        let limit = limit.unwrap_or(50).min(100);
+
+        let job_records = sqlx::query!(
+            r#"
+            SELECT id, kind, status, total, processed, failed, error, started_at, updated_at, finished_at
+            FROM background_jobs
+            ORDER BY started_at DESC
+            LIMIT ?
+            "#,
+            limit
+        )
+        .fetch_all(self.db.pool())
+        .await?;
+
+        let jobs = job_records
+            .into_iter()
+            .map(|record| {
+                Ok(Job {
+                    id: Uuid::parse_str(&record.id)
+                        .map_err(|e| AppError::Internal(format!("Invalid job ID: {}", e)))?,
+                    kind: record.kind,
+                    status: JobStatus::parse(&record.status),
+                    total: record.total,
+                    processed: record.processed,
+                    failed: record.failed,
+                    error: record.error,
+                    started_at: chrono::DateTime::parse_from_rfc3339(&record.started_at)
+                        .map_err(|e| AppError::Internal(format!("Invalid date: {}", e)))?
+                        .with_timezone(&Utc),
+                    updated_at: chrono::DateTime::parse_from_rfc3339(&record.updated_at)
+                        .map_err(|e| AppError::Internal(format!("Invalid date: {}", e)))?
+                        .with_timezone(&Utc),
+                    finished_at: record.finished_at
+                        .as_deref()
+                        .map(chrono::DateTime::parse_from_rfc3339)
+                        .transpose()
+                        .map_err(|e| AppError::Internal(format!("Invalid date: {}", e)))?
+                        .map(|finished_at| finished_at.with_timezone(&Utc)),
+                })
+            })
+            .collect::<AppResult<Vec<_>>>()?;
+
+        Ok(jobs)
+    }
+}
@@ -0,0 +1,136 @@
+// Outgoing mail
+//
+// Account emails go through the `Mailer` trait. SMTP is used in production;
+// the file and log mailers keep messages local for development and tests.
+
+use async_trait::async_trait;
+use chrono::Utc;
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Tokio1Executor,
+};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::core::config::MailConfig;
+use crate::core::error::{AppError, AppResult};
+
+/// A plain-text email
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Delivers emails
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: &Email) -> AppResult<()>;
+}
+
+/// Build the mailer selected by `MAIL_TRANSPORT`
+pub fn from_config(config: &MailConfig) -> AppResult<Arc<dyn Mailer>> {
+    match config.transport.as_str() {
+        "smtp" => {
+            info!("Sending mail through SMTP relay {}:{}", config.smtp_host, config.smtp_port);
+            Ok(Arc::new(SmtpMailer::new(config)?))
+        }
+        "file" => {
+            info!("Writing mail to {}", config.file_dir);
+            Ok(Arc::new(FileMailer::new(PathBuf::from(&config.file_dir))?))
+        }
+        "log" => {
+            info!("Logging mail instead of sending it");
+            Ok(Arc::new(LogMailer))
+        }
+        other => Err(AppError::Internal(format!("Unknown mail transport: {}", other))),
+    }
+}
+
+/// Sends mail through an SMTP relay using STARTTLS
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    pub fn new(config: &MailConfig) -> AppResult<Self> {
+        let mut builder = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.smtp_host)
+            .map_err(|e| AppError::Internal(format!("Invalid SMTP relay: {}", e)))?
+            .port(config.smtp_port);
+
+        if let (Some(username), Some(password)) = (&config.smtp_username, &config.smtp_password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        Ok(Self {
+            transport: builder.build(),
+            from: parse_mailbox(&config.from)?,
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, email: &Email) -> AppResult<()> {
+        let message = lettre::Message::builder()
+            .from(self.from.clone())
+            .to(parse_mailbox(&email.to)?)
+            .subject(email.subject.clone())
+            .header(ContentType::TEXT_PLAIN)
+            .body(email.body.clone())
+            .map_err(|e| AppError::Internal(format!("Failed to build email: {}", e)))?;
+
+        self.transport
+            .send(message)
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to send email: {}", e)))?;
+
+        Ok(())
+    }
+}
+
+/// Writes each email to its own file in a directory
+pub struct FileMailer {
+    dir: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new(dir: PathBuf) -> AppResult<Self> {
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, email: &Email) -> AppResult<()> {
+        let path = self.dir.join(format!("{}-{}.eml", Utc::now().format("%Y%m%dT%H%M%S"), Uuid::new_v4().simple()));
+        let contents = format!("To: {}\nSubject: {}\n\n{}\n", email.to, email.subject, email.body);
+
+        tokio::fs::write(&path, contents).await?;
+        info!("Wrote email for {} to {}", email.to, path.display());
+        Ok(())
+    }
+}
+
+/// Logs emails, links included. Never use this in production.
+pub struct LogMailer;
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, email: &Email) -> AppResult<()> {
+        info!("Email to {}: {}\n{}", email.to, email.subject, email.body);
+        Ok(())
+    }
+}
+
+fn parse_mailbox(address: &str) -> AppResult<Mailbox> {
+    address
+        .parse()
+        .map_err(|e| AppError::InvalidRequest(format!("Invalid email address {:?}: {}", address, e)))
+}
@@ -1,9 +1,17 @@
 mod core;
+mod account;
 mod matrix;
 mod board;
 mod chat;
+mod user;
+mod moderation;
+mod challenge;
 mod auth;
 mod crypto;
+mod e2ee;
+mod invite_links;
+mod jobs;
+mod mail;
 mod web;
 mod storage;
 
@@ -1,8 +1,13 @@
 use anyhow::Result;
 use matrix_sdk::{
     Client, Room, RoomState,
+    media::{MediaFormat, MediaRequest},
     ruma::{
-        RoomId, UserId, EventId,
+        Int, RoomId, UserId, EventId, OwnedMxcUri,
+        events::room::MediaSource,
+        api::client::config::set_global_account_data,
+        api::client::profile::{set_avatar_url, set_display_name},
+        events::ignored_user_list::IgnoredUserListEventContent,
         events::room::message::RoomMessageEventContent,
         events::room::member::MembershipState,
     },
@@ -14,9 +19,14 @@ use uuid::Uuid;
 use crate::core::config::MatrixConfig;
 use crate::core::error::{AppError, AppResult};
 
+/// Event type for end-to-end encrypted chat envelopes
+pub const E2EE_ENVELOPE_EVENT_TYPE: &str = "org.amogchan.e2ee.envelope";
+
 pub struct MatrixClient {
     client: Client,
     config: MatrixConfig,
+    /// For media repository requests the SDK doesn't cover
+    http: reqwest::Client,
 }
 
 impl MatrixClient {
@@ -40,6 +50,7 @@ impl MatrixClient {
         Ok(Self {
             client,
             config: config.clone(),
+            http: reqwest::Client::new(),
         })
     }
 
@@ -67,6 +78,30 @@ impl MatrixClient {
         Ok(response.room_id.to_string())
     }
 
+    /// Create a board's room: invite-only for private boards, publicly
+    /// joinable otherwise. Only private boards can be encrypted.
+    pub async fn create_board_room(&self, name: &str, topic: Option<&str>, is_private: bool, is_encrypted: bool) -> AppResult<String> {
+        use matrix_sdk::ruma::api::client::room::create_room::v3::{Request, RoomPreset};
+        use matrix_sdk::ruma::events::{room::encryption::RoomEncryptionEventContent, InitialStateEvent};
+
+        let mut request = Request::new();
+        request.name = Some(name.to_string());
+        request.topic = topic.map(str::to_string);
+        request.preset = Some(if is_private { RoomPreset::PrivateChat } else { RoomPreset::PublicChat });
+
+        // Megolm, rotating session keys as the spec recommends
+        if is_private && is_encrypted {
+            request.initial_state.push(
+                InitialStateEvent::new(RoomEncryptionEventContent::with_recommended_defaults()).to_raw_any()
+            );
+        }
+
+        let room = self.client.create_room(request).await
+            .map_err(|e| AppError::Matrix(format!("Failed to create room: {}", e)))?;
+
+        Ok(room.room_id().to_string())
+    }
+
     /// Send a message to a Matrix room
     pub async fn send_message(&self, room_id: &str, content: &str) -> AppResult<String> {
         let room_id = RoomId::parse(room_id)
@@ -87,6 +122,42 @@ impl MatrixClient {
         Ok(response.event_id.to_string())
     }
 
+    /// Send a notice (an automated message, e.g. about membership changes)
+    /// to a Matrix room
+    pub async fn send_notice(&self, room_id: &str, content: &str) -> AppResult<String> {
+        let room_id = RoomId::parse(room_id)
+            .map_err(|e| AppError::Matrix(format!("Invalid room ID: {}", e)))?;
+
+        let room = self.client.get_room(&room_id)
+            .ok_or_else(|| AppError::Matrix("Room not found".to_string()))?;
+
+        let content = RoomMessageEventContent::notice_plain(content);
+
+        let response = room.send(content, None).await
+            .map_err(|e| AppError::Matrix(format!("Failed to send notice: {}", e)))?;
+
+        Ok(response.event_id.to_string())
+    }
+
+    /// Relay a client-encrypted envelope to a Matrix room as a custom event.
+    /// The envelope is opaque to the server and to Matrix.
+    pub async fn send_e2ee_envelope(&self, room_id: &str, envelope: serde_json::Value) -> AppResult<String> {
+        let room_id = RoomId::parse(room_id)
+            .map_err(|e| AppError::Matrix(format!("Invalid room ID: {}", e)))?;
+        
+        let room = self.client.get_room(&room_id)
+            .ok_or_else(|| AppError::Matrix("Room not found".to_string()))?;
+
+        if room.state() != RoomState::Joined {
+            return Err(AppError::Matrix("Not a member of this room".to_string()));
+        }
+
+        let response = room.send_raw(E2EE_ENVELOPE_EVENT_TYPE, envelope).await
+            .map_err(|e| AppError::Matrix(format!("Failed to send encrypted envelope: {}", e)))?;
+
+        Ok(response.event_id.to_string())
+    }
+
     /// Send a message with image to a Matrix room
     pub async fn send_message_with_image(&self, room_id: &str, content: &str, image_url: &str) -> AppResult<String> {
         let room_id = RoomId::parse(room_id)
@@ -153,6 +224,182 @@ impl MatrixClient {
         Ok(())
     }
 
+    /// Remove a user from a Matrix room
+    pub async fn kick_user(&self, room_id: &str, user_id: &str, reason: Option<&str>) -> AppResult<()> {
+        let room_id = RoomId::parse(room_id)
+            .map_err(|e| AppError::Matrix(format!("Invalid room ID: {}", e)))?;
+
+        let user_id = UserId::parse(user_id)
+            .map_err(|e| AppError::Matrix(format!("Invalid user ID: {}", e)))?;
+
+        let room = self.client.get_room(&room_id)
+            .ok_or_else(|| AppError::Matrix("Room not found".to_string()))?;
+
+        room.kick_user(&user_id, reason).await
+            .map_err(|e| AppError::Matrix(format!("Failed to kick user: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Set a room's `m.room.name`
+    pub async fn set_room_name(&self, room_id: &str, name: &str) -> AppResult<()> {
+        let room_id = RoomId::parse(room_id)
+            .map_err(|e| AppError::Matrix(format!("Invalid room ID: {}", e)))?;
+
+        let room = self.client.get_room(&room_id)
+            .ok_or_else(|| AppError::Matrix("Room not found".to_string()))?;
+
+        room.set_name(name.to_string()).await
+            .map_err(|e| AppError::Matrix(format!("Failed to set room name: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Set a room's `m.room.topic`; `None` clears it
+    pub async fn set_room_topic(&self, room_id: &str, topic: Option<&str>) -> AppResult<()> {
+        let room_id = RoomId::parse(room_id)
+            .map_err(|e| AppError::Matrix(format!("Invalid room ID: {}", e)))?;
+
+        let room = self.client.get_room(&room_id)
+            .ok_or_else(|| AppError::Matrix("Room not found".to_string()))?;
+
+        room.set_room_topic(topic.unwrap_or_default()).await
+            .map_err(|e| AppError::Matrix(format!("Failed to set room topic: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Set a user's power level in a room
+    pub async fn set_power_level(&self, room_id: &str, user_id: &str, level: i64) -> AppResult<()> {
+        let room_id = RoomId::parse(room_id)
+            .map_err(|e| AppError::Matrix(format!("Invalid room ID: {}", e)))?;
+
+        let user_id = UserId::parse(user_id)
+            .map_err(|e| AppError::Matrix(format!("Invalid user ID: {}", e)))?;
+
+        let room = self.client.get_room(&room_id)
+            .ok_or_else(|| AppError::Matrix("Room not found".to_string()))?;
+
+        room.update_power_levels(vec![(&user_id, Int::new_saturating(level))]).await
+            .map_err(|e| AppError::Matrix(format!("Failed to set power level: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Redact an event, removing its content for everyone in the room
+    pub async fn redact_event(&self, room_id: &str, event_id: &str, reason: Option<&str>) -> AppResult<()> {
+        let room_id = RoomId::parse(room_id)
+            .map_err(|e| AppError::Matrix(format!("Invalid room ID: {}", e)))?;
+
+        let event_id = EventId::parse(event_id)
+            .map_err(|e| AppError::Matrix(format!("Invalid event ID: {}", e)))?;
+
+        let room = self.client.get_room(&room_id)
+            .ok_or_else(|| AppError::Matrix("Room not found".to_string()))?;
+
+        room.redact(&event_id, reason, None).await
+            .map_err(|e| AppError::Matrix(format!("Failed to redact event: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Download a file from the homeserver's media repository
+    pub async fn download_media(&self, mxc_uri: &str) -> AppResult<Vec<u8>> {
+        let request = MediaRequest {
+            source: MediaSource::Plain(OwnedMxcUri::from(mxc_uri)),
+            format: MediaFormat::File,
+        };
+
+        self.client.media().get_media_content(&request, true).await
+            .map_err(|e| AppError::Matrix(format!("Failed to download media: {}", e)))
+    }
+
+    /// Size of a file in the homeserver's media repository, as announced in
+    /// its Content-Length, without downloading it. `None` if the homeserver
+    /// doesn't say.
+    pub async fn media_size(&self, mxc_uri: &str) -> AppResult<Option<u64>> {
+        let mxc_uri = OwnedMxcUri::from(mxc_uri);
+        let (server_name, media_id) = mxc_uri.parts()
+            .map_err(|e| AppError::Matrix(format!("Invalid media URI: {}", e)))?;
+
+        let mut url = self.client.homeserver();
+        url.path_segments_mut()
+            .map_err(|_| AppError::Matrix("Invalid homeserver URL".to_string()))?
+            .pop_if_empty()
+            .extend(["_matrix", "media", "v3", "download", server_name.as_str(), media_id]);
+
+        let mut request = self.http.head(url);
+        if let Some(access_token) = &self.config.access_token {
+            request = request.bearer_auth(access_token);
+        }
+
+        let response = request.send().await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| AppError::Matrix(format!("Failed to look up media: {}", e)))?;
+
+        Ok(response.headers()
+            .get(reqwest::header::CONTENT_LENGTH)
+            .and_then(|length| length.to_str().ok())
+            .and_then(|length| length.parse().ok()))
+    }
+
+    /// Upload a file to the homeserver's media repository, returning its mxc:// URI
+    pub async fn upload_media(&self, content_type: &str, data: Vec<u8>) -> AppResult<String> {
+        let content_type: mime::Mime = content_type.parse()
+            .map_err(|e| AppError::Matrix(format!("Invalid content type: {}", e)))?;
+
+        let response = self.client.media().upload(&content_type, data).await
+            .map_err(|e| AppError::Matrix(format!("Failed to upload media: {}", e)))?;
+
+        Ok(response.content_uri.to_string())
+    }
+
+    /// Set a user's Matrix display name. Only works for users the bot's
+    /// access token may act for, e.g. through an application service.
+    pub async fn set_display_name(&self, user_id: &str, display_name: Option<&str>) -> AppResult<()> {
+        let user_id = UserId::parse(user_id)
+            .map_err(|e| AppError::Matrix(format!("Invalid user ID: {}", e)))?;
+
+        let request = set_display_name::v3::Request::new(user_id, display_name.map(str::to_string));
+        self.client.send(request, None).await
+            .map_err(|e| AppError::Matrix(format!("Failed to set display name: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Set a user's Matrix avatar, with the same restriction as `set_display_name`
+    pub async fn set_avatar_url(&self, user_id: &str, avatar_url: Option<&str>) -> AppResult<()> {
+        let user_id = UserId::parse(user_id)
+            .map_err(|e| AppError::Matrix(format!("Invalid user ID: {}", e)))?;
+
+        let request = set_avatar_url::v3::Request::new(user_id, avatar_url.map(OwnedMxcUri::from));
+        self.client.send(request, None).await
+            .map_err(|e| AppError::Matrix(format!("Failed to set avatar: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Replace a user's Matrix ignored-user list, with the same restriction
+    /// as `set_display_name`
+    pub async fn set_ignored_users(&self, user_id: &str, ignored_user_ids: &[String]) -> AppResult<()> {
+        let user_id = UserId::parse(user_id)
+            .map_err(|e| AppError::Matrix(format!("Invalid user ID: {}", e)))?;
+
+        let ignored_user_ids = ignored_user_ids
+            .iter()
+            .map(|id| UserId::parse(id.as_str()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| AppError::Matrix(format!("Invalid user ID: {}", e)))?;
+
+        let content = IgnoredUserListEventContent::users(ignored_user_ids);
+        let request = set_global_account_data::v3::Request::new(user_id, &content)
+            .map_err(|e| AppError::Matrix(format!("Failed to serialize ignored users: {}", e)))?;
+        self.client.send(request, None).await
+            .map_err(|e| AppError::Matrix(format!("Failed to set ignored users: {}", e)))?;
+
+        Ok(())
+    }
+
     /// Get room members
     pub async fn get_room_members(&self, room_id: &str) -> AppResult<Vec<String>> {
         let room_id = RoomId::parse(room_id)
@@ -0,0 +1,423 @@
+use chrono::{DateTime, Duration, Utc};
+use std::sync::Arc;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::core::config::FiltersConfig;
+use crate::core::error::{AppError, AppResult};
+use crate::core::types::{ContentFilter, CreateContentFilterRequest, FilterAction, FilterKind, ModerationLogEntry};
+use crate::moderation::filters::{self, FilterHit, LinkRule, WordRule};
+use crate::storage::database::Database;
+
+/// Most rules a board, or the global scope, can have
+const MAX_FILTERS_PER_SCOPE: i64 = 200;
+
+/// Most recent threads and replies on a board compared against for
+/// duplicates
+const MAX_DUPLICATE_CANDIDATES: i64 = 1000;
+
+/// A thread or reply after filtering, with the hits to log once it's posted
+pub struct Screened {
+    pub title: Option<String>,
+    pub content: String,
+    pub hits: Vec<FilterHit>,
+}
+
+/// Content filters for new threads and replies.
+///
+/// Every post runs through, in order: the flood detector, word filters,
+/// link filters and duplicate detection. Word and link rules are global
+/// (set by admins) or per board (set by the board's managers); both apply.
+/// Rejections and every other hit go to the moderation log.
+pub struct ContentFilterService {
+    db: Arc<Database>,
+    config: FiltersConfig,
+}
+
+impl ContentFilterService {
+    pub fn new(db: Arc<Database>, config: FiltersConfig) -> Self {
+        Self { db, config }
+    }
+
+    /// A board's own rules, or the global ones without a board, oldest first
+    pub async fn list_filters(&self, board_id: Option<Uuid>) -> AppResult<Vec<ContentFilter>> {
+        let board_id = board_id.map(|id| id.to_string());
+
+        let filter_records = sqlx::query!(
+            r#"
+            SELECT id, board_id, kind, pattern, action, replacement, created_by, created_at
+            FROM content_filters WHERE board_id IS ? ORDER BY created_at ASC
+            "#,
+            board_id
+        )
+        .fetch_all(self.db.pool())
+        .await?;
+
+        filter_records
+            .into_iter()
+            .map(|record| {
+                Ok(ContentFilter {
+                    id: parse_id(&record.id)?,
+                    board_id: record.board_id.as_deref().map(parse_id).transpose()?,
+                    kind: FilterKind::parse(&record.kind),
+                    pattern: record.pattern,
+                    action: FilterAction::parse(&record.action),
+                    replacement: record.replacement,
+                    created_by: parse_id(&record.created_by)?,
+                    created_at: parse_date(&record.created_at)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Add a rule to a board, or a global one without a board
+    pub async fn create_filter(
+        &self,
+        board_id: Option<Uuid>,
+        request: CreateContentFilterRequest,
+        creator_id: Uuid,
+    ) -> AppResult<ContentFilter> {
+        let (pattern, replacement) = filters::validate_rule(
+            request.kind,
+            &request.pattern,
+            request.action,
+            request.replacement.as_deref(),
+        )?;
+
+        let board_id_value = board_id.map(|id| id.to_string());
+        let filter_count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "count!: i64" FROM content_filters WHERE board_id IS ?"#,
+            board_id_value
+        )
+        .fetch_one(self.db.pool())
+        .await?;
+
+        if filter_count >= MAX_FILTERS_PER_SCOPE {
+            return Err(AppError::InvalidRequest(format!(
+                "There can be at most {} filters here",
+                MAX_FILTERS_PER_SCOPE
+            )));
+        }
+
+        let filter = ContentFilter {
+            id: Uuid::new_v4(),
+            board_id,
+            kind: request.kind,
+            pattern,
+            action: request.action,
+            replacement,
+            created_by: creator_id,
+            created_at: Utc::now(),
+        };
+
+        sqlx::query!(
+            r#"
+            INSERT INTO content_filters (id, board_id, kind, pattern, action, replacement, created_by, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+            filter.id.to_string(),
+            board_id_value,
+            filter.kind.as_str(),
+            filter.pattern,
+            filter.action.as_str(),
+            filter.replacement,
+            filter.created_by.to_string(),
+            filter.created_at.to_rfc3339()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        Ok(filter)
+    }
+
+    /// Remove one of a board's rules, or a global one without a board
+    pub async fn delete_filter(&self, board_id: Option<Uuid>, filter_id: Uuid) -> AppResult<()> {
+        let board_id = board_id.map(|id| id.to_string());
+
+        let result = sqlx::query!(
+            "DELETE FROM content_filters WHERE id = ? AND board_id IS ?",
+            filter_id.to_string(),
+            board_id
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Filter not found".to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// A board's moderation log, or every board's without one, newest first
+    pub async fn moderation_log(
+        &self,
+        board_id: Option<Uuid>,
+        limit: Option<i64>,
+        offset: Option<i64>,
+    ) -> AppResult<Vec<ModerationLogEntry>> {
+        let board_id = board_id.map(|id| id.to_string());
+        let limit = limit.unwrap_or(50).min(100); // Max 100 entries per request
+        let offset = offset.unwrap_or(0);
+
+        let entry_records = sqlx::query!(
+            r#"
+            SELECT id, board_id, user_id, thread_id, post_id, filter_id, kind, action, detail, excerpt, created_at
+            FROM moderation_log
+            WHERE ? IS NULL OR board_id = ?
+            ORDER BY created_at DESC
+            LIMIT ? OFFSET ?
+            "#,
+            board_id,
+            board_id,
+            limit,
+            offset
+        )
+        .fetch_all(self.db.pool())
+        .await?;
+
+        entry_records
+            .into_iter()
+            .map(|record| {
+                Ok(ModerationLogEntry {
+                    id: parse_id(&record.id)?,
+                    board_id: parse_id(&record.board_id)?,
+                    user_id: parse_id(&record.user_id)?,
+                    thread_id: record.thread_id.as_deref().map(parse_id).transpose()?,
+                    post_id: record.post_id.as_deref().map(parse_id).transpose()?,
+                    filter_id: record.filter_id.as_deref().map(parse_id).transpose()?,
+                    kind: FilterKind::parse(&record.kind),
+                    action: FilterAction::parse(&record.action),
+                    detail: record.detail,
+                    excerpt: record.excerpt,
+                    created_at: parse_date(&record.created_at)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Run a new thread or reply through the filters. Rejections are logged
+    /// here; the caller logs the other hits once the post exists.
+    pub async fn screen(
+        &self,
+        board_id: Uuid,
+        author_id: Uuid,
+        title: Option<&str>,
+        content: &str,
+        image_url: Option<&str>,
+    ) -> AppResult<Screened> {
+        if let Some(hit) = self.check_flood(author_id).await? {
+            self.record_hits(board_id, author_id, None, None, &[hit], content).await?;
+            return Err(AppError::RateLimit);
+        }
+
+        let (word_rules, link_rules) = self.load_rules(board_id).await?;
+
+        let mut hits = Vec::new();
+        let title = title.map(|title| {
+            let (title, title_hits) = filters::apply_word_rules(title, &word_rules);
+            hits.extend(title_hits);
+            title
+        });
+        let (content, content_hits) = filters::apply_word_rules(content, &word_rules);
+        hits.extend(content_hits);
+
+        let rejections: Vec<FilterHit> = hits.iter()
+            .filter(|hit| hit.action == FilterAction::Reject)
+            .cloned()
+            .collect();
+        if !rejections.is_empty() {
+            self.record_hits(board_id, author_id, None, None, &rejections, &content).await?;
+            return Err(AppError::InvalidRequest("Your post contains something this board doesn't allow".to_string()));
+        }
+
+        let mut hosts = filters::link_hosts(&content);
+        for text in [title.as_deref(), image_url].into_iter().flatten() {
+            for host in filters::link_hosts(text) {
+                if !hosts.contains(&host) {
+                    hosts.push(host);
+                }
+            }
+        }
+        if let Some(hit) = filters::check_links(&hosts, &link_rules) {
+            let message = format!("Links to {} aren't allowed here", hit.detail);
+            self.record_hits(board_id, author_id, None, None, &[hit], &content).await?;
+            return Err(AppError::InvalidRequest(message));
+        }
+
+        if self.is_duplicate(board_id, author_id, &content).await? {
+            let hit = FilterHit {
+                filter_id: None,
+                kind: FilterKind::Duplicate,
+                action: FilterAction::Reject,
+                detail: filters::truncate(&filters::fingerprint(&content)),
+            };
+            self.record_hits(board_id, author_id, None, None, &[hit], &content).await?;
+            return Err(AppError::InvalidRequest("This was already posted recently".to_string()));
+        }
+
+        Ok(Screened { title, content, hits })
+    }
+
+    /// Write filter hits to the moderation log, with the start of the
+    /// original content
+    pub async fn record_hits(
+        &self,
+        board_id: Uuid,
+        author_id: Uuid,
+        thread_id: Option<Uuid>,
+        post_id: Option<Uuid>,
+        hits: &[FilterHit],
+        content: &str,
+    ) -> AppResult<()> {
+        let excerpt = filters::truncate(content);
+        let now = Utc::now().to_rfc3339();
+
+        for hit in hits {
+            sqlx::query!(
+                r#"
+                INSERT INTO moderation_log (id, board_id, user_id, thread_id, post_id, filter_id, kind, action, detail, excerpt, created_at)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                "#,
+                Uuid::new_v4().to_string(),
+                board_id.to_string(),
+                author_id.to_string(),
+                thread_id.map(|id| id.to_string()),
+                post_id.map(|id| id.to_string()),
+                hit.filter_id.map(|id| id.to_string()),
+                hit.kind.as_str(),
+                hit.action.as_str(),
+                hit.detail,
+                excerpt,
+                now
+            )
+            .execute(self.db.pool())
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Global rules first, then the board's own
+    async fn load_rules(&self, board_id: Uuid) -> AppResult<(Vec<WordRule>, Vec<LinkRule>)> {
+        let rule_records = sqlx::query!(
+            r#"
+            SELECT id, kind, pattern, action, replacement FROM content_filters
+            WHERE board_id IS NULL OR board_id = ?
+            ORDER BY board_id IS NOT NULL, created_at ASC
+            "#,
+            board_id.to_string()
+        )
+        .fetch_all(self.db.pool())
+        .await?;
+
+        let mut word_rules = Vec::new();
+        let mut link_rules = Vec::new();
+        for record in rule_records {
+            let id = parse_id(&record.id)?;
+            let action = FilterAction::parse(&record.action);
+
+            match FilterKind::parse(&record.kind) {
+                FilterKind::Word => match filters::compile_pattern(&record.pattern) {
+                    Ok(regex) => word_rules.push(WordRule {
+                        id,
+                        regex,
+                        action,
+                        replacement: record.replacement.unwrap_or_else(|| filters::DEFAULT_REPLACEMENT.to_string()),
+                    }),
+                    // Patterns are checked when added, so this only happens
+                    // if the regex engine changed underneath them
+                    Err(e) => warn!("Skipping content filter {}: {}", id, e),
+                },
+                FilterKind::Link => link_rules.push(LinkRule { id, domain: record.pattern, action }),
+                FilterKind::Duplicate | FilterKind::Flood => {}
+            }
+        }
+
+        Ok((word_rules, link_rules))
+    }
+
+    async fn check_flood(&self, author_id: Uuid) -> AppResult<Option<FilterHit>> {
+        if self.config.flood_max_posts <= 0 {
+            return Ok(None);
+        }
+
+        let now = Utc::now();
+        let since = (now - Duration::seconds(self.config.flood_window_seconds)).to_rfc3339();
+
+        let posted_at = sqlx::query_scalar!(
+            r#"
+            SELECT created_at AS "created_at!: String" FROM threads WHERE created_by = ? AND created_at > ?
+            UNION ALL
+            SELECT created_at FROM posts WHERE created_by = ? AND created_at > ?
+            "#,
+            author_id.to_string(),
+            since,
+            author_id.to_string(),
+            since
+        )
+        .fetch_all(self.db.pool())
+        .await?
+        .iter()
+        .map(|posted_at| parse_date(posted_at))
+        .collect::<AppResult<Vec<_>>>()?;
+
+        if !filters::is_flooding(&posted_at, now, self.config.flood_window_seconds, self.config.flood_max_posts) {
+            return Ok(None);
+        }
+
+        Ok(Some(FilterHit {
+            filter_id: None,
+            kind: FilterKind::Flood,
+            action: FilterAction::Reject,
+            detail: format!("{} posts in {} seconds", posted_at.len(), self.config.flood_window_seconds),
+        }))
+    }
+
+    /// Whether the content was posted on the board within the duplicate
+    /// window by the author or by an anonymous account, which costs nothing
+    /// to replace. Long content counts against every author's posts. Short
+    /// posts by other registered users don't count, so nobody can keep a
+    /// reply off a board by posting it first.
+    async fn is_duplicate(&self, board_id: Uuid, author_id: Uuid, content: &str) -> AppResult<bool> {
+        if self.config.duplicate_window_seconds <= 0 {
+            return Ok(false);
+        }
+
+        let since = (Utc::now() - Duration::seconds(self.config.duplicate_window_seconds)).to_rfc3339();
+        let shared = filters::is_shared_duplicate_candidate(content);
+
+        let recent = sqlx::query_scalar!(
+            r#"
+            SELECT content AS "content!: String" FROM (
+                SELECT content, created_by, created_at FROM threads WHERE board_id = ?1 AND created_at > ?2
+                UNION ALL
+                SELECT content, created_by, created_at FROM posts WHERE board_id = ?1 AND created_at > ?2
+            )
+            WHERE created_by = ?3 OR ?4
+               OR created_by IN (SELECT id FROM users WHERE is_anonymous = TRUE)
+            ORDER BY created_at DESC
+            LIMIT ?5
+            "#,
+            board_id.to_string(),
+            since,
+            author_id.to_string(),
+            shared,
+            MAX_DUPLICATE_CANDIDATES
+        )
+        .fetch_all(self.db.pool())
+        .await?;
+
+        Ok(filters::is_duplicate(content, &recent))
+    }
+}
+
+fn parse_id(value: &str) -> AppResult<Uuid> {
+    Uuid::parse_str(value).map_err(|e| AppError::Internal(format!("Invalid ID: {}", e)))
+}
+
+fn parse_date(value: &str) -> AppResult<DateTime<Utc>> {
+    Ok(DateTime::parse_from_rfc3339(value)
+        .map_err(|e| AppError::Internal(format!("Invalid date: {}", e)))?
+        .with_timezone(&Utc))
+}
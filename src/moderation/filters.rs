@@ -0,0 +1,439 @@
+use chrono::{DateTime, Duration, Utc};
+use regex::{NoExpand, Regex, RegexBuilder};
+use reqwest::Url;
+use uuid::Uuid;
+
+use crate::core::error::{AppError, AppResult};
+use crate::core::types::{FilterAction, FilterKind};
+
+/// Longest regex a word rule can hold
+const MAX_PATTERN_CHARS: usize = 500;
+
+/// Longest replacement of a `replace` rule
+const MAX_REPLACEMENT_CHARS: usize = 100;
+
+/// What `replace` rules without a replacement put in place of a match
+pub const DEFAULT_REPLACEMENT: &str = "***";
+
+/// Cap on a compiled pattern, so no rule makes every post slow to check
+const MAX_COMPILED_PATTERN_BYTES: usize = 1 << 20;
+
+/// Content shorter than this once normalized is never a duplicate, so short
+/// replies like "this" or "same" get through
+const MIN_DUPLICATE_CHARS: usize = 20;
+
+/// Content at least this long once normalized is a duplicate of anyone's
+/// post, which catches spam rotated across accounts
+const MIN_SHARED_DUPLICATE_CHARS: usize = 200;
+
+/// Characters of matched text or content kept in the moderation log
+const MAX_LOGGED_CHARS: usize = 200;
+
+/// A word rule, compiled
+pub struct WordRule {
+    pub id: Uuid,
+    pub regex: Regex,
+    pub action: FilterAction,
+    pub replacement: String,
+}
+
+/// A link rule, with its domain normalized
+pub struct LinkRule {
+    pub id: Uuid,
+    pub domain: String,
+    pub action: FilterAction,
+}
+
+/// A rule or heuristic that matched a post
+#[derive(Debug, Clone)]
+pub struct FilterHit {
+    pub filter_id: Option<Uuid>,
+    pub kind: FilterKind,
+    pub action: FilterAction,
+    pub detail: String,
+}
+
+/// Validate a new rule, returning its pattern and replacement as stored
+pub fn validate_rule(
+    kind: FilterKind,
+    pattern: &str,
+    action: FilterAction,
+    replacement: Option<&str>,
+) -> AppResult<(String, Option<String>)> {
+    match kind {
+        FilterKind::Word => {
+            if !matches!(action, FilterAction::Replace | FilterAction::Reject | FilterAction::Flag) {
+                return Err(AppError::InvalidRequest("Word filters replace, reject or flag".to_string()));
+            }
+            compile_pattern(pattern)?;
+
+            let replacement = match action {
+                FilterAction::Replace => replacement.map(str::to_string),
+                _ => None,
+            };
+            if replacement.as_ref().is_some_and(|replacement| replacement.chars().count() > MAX_REPLACEMENT_CHARS) {
+                return Err(AppError::InvalidRequest(format!(
+                    "Replacements can be at most {} characters",
+                    MAX_REPLACEMENT_CHARS
+                )));
+            }
+
+            Ok((pattern.to_string(), replacement))
+        }
+        FilterKind::Link => {
+            if !matches!(action, FilterAction::Allow | FilterAction::Deny) {
+                return Err(AppError::InvalidRequest("Link filters allow or deny".to_string()));
+            }
+
+            Ok((normalize_domain(pattern)?, None))
+        }
+        FilterKind::Duplicate | FilterKind::Flood => Err(AppError::InvalidRequest(
+            "Duplicate and flood detection are configured by the server, not with rules".to_string(),
+        )),
+    }
+}
+
+/// Compile a word rule's regex. Matching is case-sensitive unless the
+/// pattern starts with `(?i)`.
+pub fn compile_pattern(pattern: &str) -> AppResult<Regex> {
+    if pattern.trim().is_empty() || pattern.chars().count() > MAX_PATTERN_CHARS {
+        return Err(AppError::InvalidRequest(format!(
+            "Patterns must be between 1 and {} characters",
+            MAX_PATTERN_CHARS
+        )));
+    }
+
+    RegexBuilder::new(pattern)
+        .size_limit(MAX_COMPILED_PATTERN_BYTES)
+        .build()
+        .map_err(|e| AppError::InvalidRequest(format!("Invalid pattern: {}", e)))
+}
+
+/// Run word rules over text in order. Matches of `replace` rules are
+/// rewritten, so later rules see the rewritten text. Returns the text with
+/// a hit for every rule that matched.
+pub fn apply_word_rules(text: &str, rules: &[WordRule]) -> (String, Vec<FilterHit>) {
+    let mut text = text.to_string();
+    let mut hits = Vec::new();
+
+    for rule in rules {
+        let Some(found) = rule.regex.find(&text) else {
+            continue;
+        };
+
+        hits.push(FilterHit {
+            filter_id: Some(rule.id),
+            kind: FilterKind::Word,
+            action: rule.action,
+            detail: truncate(found.as_str()),
+        });
+
+        if rule.action == FilterAction::Replace {
+            text = rule.regex.replace_all(&text, NoExpand(&rule.replacement)).into_owned();
+        }
+    }
+
+    (text, hits)
+}
+
+/// Hosts of the `http(s)://` and `www.` links in text, each once. Hosts are
+/// lowercased and internationalized ones punycoded, as browsers resolve them.
+/// A host that doesn't parse is returned as written with an ` (unparsable)`
+/// suffix, which no link rule matches, so allow-lists refuse it.
+pub fn link_hosts(text: &str) -> Vec<String> {
+    let text = text.to_lowercase();
+    let mut hosts: Vec<String> = Vec::new();
+
+    let schemed = text.match_indices("://")
+        .filter(|(index, _)| text[..*index].ends_with("http") || text[..*index].ends_with("https"))
+        .map(|(index, _)| &text[index + 3..]);
+    let bare = text.split(|c: char| c.is_whitespace() || "()<>[]\"'".contains(c))
+        .filter(|word| word.starts_with("www."));
+
+    for link in schemed.chain(bare) {
+        let authority = link.split(ends_authority).next().unwrap_or_default();
+        // Punctuation after a link in running text isn't part of it
+        let authority = authority.trim_end_matches(['.', ',', ';', ':', '!']);
+        if authority.is_empty() {
+            continue;
+        }
+
+        let host = match Url::parse(&format!("http://{}/", authority)) {
+            // Hosts without a dot, like `localhost`, lead nowhere public
+            Ok(url) => match url.host_str().map(|host| host.trim_end_matches('.')) {
+                Some(host) if host.contains('.') => host.to_string(),
+                _ => continue,
+            },
+            Err(_) => format!("{} (unparsable)", authority),
+        };
+
+        if !hosts.contains(&host) {
+            hosts.push(host);
+        }
+    }
+
+    hosts
+}
+
+/// Whether a character ends the authority of a link in running text
+fn ends_authority(c: char) -> bool {
+    c.is_whitespace() || "/?#\\<>\"'()".contains(c)
+}
+
+/// Check link hosts against link rules. Denied domains are refused; once
+/// any allow rule applies, so is every domain not allowed. Returns the hit
+/// refusing the post, if any.
+pub fn check_links(hosts: &[String], rules: &[LinkRule]) -> Option<FilterHit> {
+    for host in hosts {
+        let denied = rules.iter()
+            .find(|rule| rule.action == FilterAction::Deny && domain_matches(host, &rule.domain));
+        if let Some(rule) = denied {
+            return Some(FilterHit {
+                filter_id: Some(rule.id),
+                kind: FilterKind::Link,
+                action: FilterAction::Reject,
+                detail: host.clone(),
+            });
+        }
+    }
+
+    let allowed: Vec<&LinkRule> = rules.iter().filter(|rule| rule.action == FilterAction::Allow).collect();
+    if allowed.is_empty() {
+        return None;
+    }
+
+    hosts.iter()
+        .find(|host| !allowed.iter().any(|rule| domain_matches(host, &rule.domain)))
+        .map(|host| FilterHit {
+            filter_id: None,
+            kind: FilterKind::Link,
+            action: FilterAction::Reject,
+            detail: host.clone(),
+        })
+}
+
+/// Whether a host is the domain or one of its subdomains
+pub fn domain_matches(host: &str, domain: &str) -> bool {
+    host == domain || host.strip_suffix(domain).is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+/// A link rule's domain, from input like `Example.com`, `*.example.com`,
+/// `https://example.com/` or `пример.рф`, punycoded like `link_hosts`
+pub fn normalize_domain(input: &str) -> AppResult<String> {
+    let lowered = input.trim().to_lowercase();
+    let domain = lowered.strip_prefix("https://")
+        .or_else(|| lowered.strip_prefix("http://"))
+        .unwrap_or(&lowered);
+    let domain = domain.split('/').next().unwrap_or_default();
+    let domain = domain.trim_start_matches("*.").trim_matches('.');
+
+    let domain = if domain.is_ascii() {
+        domain.to_string()
+    } else {
+        Url::parse(&format!("http://{}/", domain))
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_default()
+    };
+
+    let valid = domain.len() <= 253
+        && domain.contains('.')
+        && !domain.contains("..")
+        && domain.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.');
+    if !valid {
+        return Err(AppError::InvalidRequest(format!("Invalid domain: {}", input.trim())));
+    }
+
+    Ok(domain)
+}
+
+/// Content reduced to lowercase words, so changes in case, spacing and
+/// punctuation don't make a repost look new
+pub fn fingerprint(content: &str) -> String {
+    content.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Whether content repeats one of the recent contents
+pub fn is_duplicate(content: &str, recent: &[String]) -> bool {
+    let content = fingerprint(content);
+    if content.chars().count() < MIN_DUPLICATE_CHARS {
+        return false;
+    }
+
+    recent.iter().any(|recent| fingerprint(recent) == content)
+}
+
+/// Whether content is long enough to be compared against every author's
+/// posts, not just the author's own and anonymous ones
+pub fn is_shared_duplicate_candidate(content: &str) -> bool {
+    fingerprint(content).chars().count() >= MIN_SHARED_DUPLICATE_CHARS
+}
+
+/// Whether another post would put an account over `max_posts` within the
+/// window, given when it posted recently. A `max_posts` of 0 never floods.
+pub fn is_flooding(posted_at: &[DateTime<Utc>], now: DateTime<Utc>, window_seconds: i64, max_posts: i64) -> bool {
+    if max_posts <= 0 {
+        return false;
+    }
+
+    let since = now - Duration::seconds(window_seconds);
+    posted_at.iter().filter(|posted_at| **posted_at > since).count() as i64 >= max_posts
+}
+
+/// The start of text, as kept in the moderation log
+pub fn truncate(text: &str) -> String {
+    text.chars().take(MAX_LOGGED_CHARS).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word_rule(pattern: &str, action: FilterAction, replacement: &str) -> WordRule {
+        WordRule {
+            id: Uuid::new_v4(),
+            regex: compile_pattern(pattern).unwrap(),
+            action,
+            replacement: replacement.to_string(),
+        }
+    }
+
+    fn link_rule(domain: &str, action: FilterAction) -> LinkRule {
+        LinkRule { id: Uuid::new_v4(), domain: domain.to_string(), action }
+    }
+
+    fn hosts(hosts: &[&str]) -> Vec<String> {
+        hosts.iter().map(|host| host.to_string()).collect()
+    }
+
+    #[test]
+    fn word_rules_see_earlier_replacements() {
+        let rules = [
+            word_rule("(?i)spam", FilterAction::Replace, "ham"),
+            word_rule("ham", FilterAction::Flag, ""),
+            word_rule("eggs", FilterAction::Reject, ""),
+        ];
+
+        let (text, hits) = apply_word_rules("Buy SPAM and spam now", &rules);
+
+        assert_eq!(text, "Buy ham and ham now");
+        assert_eq!(hits.len(), 2);
+        assert_eq!((hits[0].action, hits[0].detail.as_str()), (FilterAction::Replace, "SPAM"));
+        assert_eq!((hits[1].action, hits[1].detail.as_str()), (FilterAction::Flag, "ham"));
+        assert_eq!(hits[1].filter_id, Some(rules[1].id));
+    }
+
+    #[test]
+    fn word_rule_replacements_are_literal() {
+        let rules = [word_rule("(secret)", FilterAction::Replace, "$1")];
+
+        let (text, _) = apply_word_rules("the secret word", &rules);
+
+        assert_eq!(text, "the $1 word");
+    }
+
+    #[test]
+    fn link_hosts_finds_schemed_and_www_links_once() {
+        let text = "see https://User@Example.COM:8080/path and (www.foo.org/x), again http://example.com?q \
+                    but not ftp://files.net, http://localhost/ or plain example.net";
+
+        assert_eq!(link_hosts(text), hosts(&["example.com", "www.foo.org"]));
+    }
+
+    #[test]
+    fn link_hosts_punycode_internationalized_hosts() {
+        let text = "see https://пример.рф/x, www.BÜCHER.de and https://xn--e1afmkfd.xn--p1ai.";
+
+        assert_eq!(link_hosts(text), hosts(&["xn--e1afmkfd.xn--p1ai", "www.xn--bcher-kva.de"]));
+    }
+
+    #[test]
+    fn unparsable_hosts_are_refused_by_allow_lists() {
+        let found = link_hosts("go to http://good.org%zz and http://bad|host.good.org/");
+
+        assert_eq!(found.len(), 2);
+        assert!(found.iter().all(|host| host.ends_with(" (unparsable)")));
+
+        let rules = [link_rule("good.org", FilterAction::Allow)];
+        let hit = check_links(&found, &rules).unwrap();
+        assert_eq!(hit.action, FilterAction::Reject);
+    }
+
+    #[test]
+    fn denied_domains_cover_subdomains_only() {
+        let rules = [link_rule("example.com", FilterAction::Deny)];
+
+        let hit = check_links(&hosts(&["ok.net", "cdn.example.com"]), &rules).unwrap();
+        assert_eq!(hit.filter_id, Some(rules[0].id));
+        assert_eq!(hit.action, FilterAction::Reject);
+        assert_eq!(hit.detail, "cdn.example.com");
+
+        assert!(check_links(&hosts(&["notexample.com"]), &rules).is_none());
+        assert!(check_links(&hosts(&["example.com.evil.net"]), &rules).is_none());
+    }
+
+    #[test]
+    fn allow_rules_refuse_every_other_domain() {
+        let rules = [link_rule("good.org", FilterAction::Allow)];
+
+        assert!(check_links(&hosts(&["good.org", "sub.good.org"]), &rules).is_none());
+
+        let hit = check_links(&hosts(&["good.org", "bad.net"]), &rules).unwrap();
+        assert_eq!(hit.filter_id, None);
+        assert_eq!(hit.detail, "bad.net");
+
+        assert!(check_links(&hosts(&["anything.net"]), &[]).is_none());
+    }
+
+    #[test]
+    fn normalize_domain_accepts_common_forms() {
+        assert_eq!(normalize_domain("  HTTPS://Example.com/path ").unwrap(), "example.com");
+        assert_eq!(normalize_domain("*.Example.com").unwrap(), "example.com");
+        assert_eq!(normalize_domain("sub.example.com.").unwrap(), "sub.example.com");
+        assert_eq!(normalize_domain("Пример.рф").unwrap(), "xn--e1afmkfd.xn--p1ai");
+
+        for invalid in ["localhost", "a..b.com", "exa mple.com", "", "*."] {
+            assert!(normalize_domain(invalid).is_err(), "{:?} should be rejected", invalid);
+        }
+    }
+
+    #[test]
+    fn duplicates_ignore_case_spacing_and_punctuation() {
+        let recent = ["buy   CHEAP watches, at my store... today".to_string()];
+
+        assert!(is_duplicate("Buy cheap watches at my store today!", &recent));
+        assert!(!is_duplicate("Buy cheap clocks at my store today!", &recent));
+    }
+
+    #[test]
+    fn short_content_is_never_a_duplicate() {
+        let recent = ["Same here!".to_string()];
+
+        assert!(!is_duplicate("same here", &recent));
+    }
+
+    #[test]
+    fn only_long_content_is_compared_across_authors() {
+        let short = "Buy cheap watches at my store today!";
+        let long = short.repeat(6);
+
+        assert!(!is_shared_duplicate_candidate(short));
+        assert!(is_shared_duplicate_candidate(&long));
+        assert!(!is_shared_duplicate_candidate(&"!!! ".repeat(100)));
+    }
+
+    #[test]
+    fn flooding_counts_posts_within_the_window() {
+        let now = Utc::now();
+        let posted_at = [now - Duration::seconds(10), now - Duration::seconds(20), now - Duration::seconds(100)];
+
+        assert!(is_flooding(&posted_at, now, 60, 2));
+        assert!(!is_flooding(&posted_at, now, 60, 3));
+        assert!(is_flooding(&posted_at, now, 200, 3));
+        assert!(!is_flooding(&posted_at, now, 60, 0));
+    }
+}
@@ -0,0 +1,3 @@
+pub mod content_filters;
+pub mod filters;
+pub mod reports;
@@ -0,0 +1,149 @@
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::chat::service::ChatService;
+use crate::core::error::{AppError, AppResult};
+use crate::core::types::{BlockUserRequest, Report, ReportChatRequest, ReportStatus, User};
+use crate::storage::database::Database;
+use crate::user::blocks::BlockService;
+
+const MAX_REASON_CHARS: usize = 1000;
+
+/// Reports of abusive users, reviewed by admins
+pub struct ReportService {
+    db: Arc<Database>,
+    chats: Arc<ChatService>,
+    blocks: Arc<BlockService>,
+}
+
+impl ReportService {
+    pub fn new(db: Arc<Database>, chats: Arc<ChatService>, blocks: Arc<BlockService>) -> Self {
+        Self { db, chats, blocks }
+    }
+
+    /// Report the sender of a chat request. The request is declined, and
+    /// the sender optionally blocked.
+    pub async fn report_chat_request(&self, reporter: &User, chat_id: Uuid, request: ReportChatRequest) -> AppResult<Report> {
+        let reason = request.reason.trim();
+        if reason.is_empty() || reason.chars().count() > MAX_REASON_CHARS {
+            return Err(AppError::InvalidRequest(format!(
+                "A reason of at most {} characters is required",
+                MAX_REASON_CHARS
+            )));
+        }
+
+        let chat = self.chats.decline_chat_request(chat_id, reporter.id).await?;
+        let sender_id = self.chats.request_sender(&chat, reporter.id).await?;
+
+        if request.block && !self.blocks.is_blocked(reporter.id, sender_id).await? {
+            self.blocks
+                .block_user(reporter, BlockUserRequest { user: sender_id.to_string() })
+                .await?;
+        }
+
+        let report = Report {
+            id: Uuid::new_v4(),
+            reporter_id: reporter.id,
+            reported_user_id: sender_id,
+            chat_id: Some(chat_id),
+            reason: reason.to_string(),
+            status: ReportStatus::Open,
+            created_at: Utc::now(),
+            resolved_by: None,
+            resolved_at: None,
+        };
+
+        sqlx::query!(
+            r#"
+            INSERT INTO reports (id, reporter_id, reported_user_id, chat_id, reason, status, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            "#,
+            report.id.to_string(),
+            report.reporter_id.to_string(),
+            report.reported_user_id.to_string(),
+            chat_id.to_string(),
+            report.reason,
+            report.status.as_str(),
+            report.created_at.to_rfc3339()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        Ok(report)
+    }
+
+    /// Reports with the given status, oldest first
+    pub async fn list_reports(&self, status: ReportStatus) -> AppResult<Vec<Report>> {
+        let report_ids = sqlx::query_scalar!(
+            "SELECT id FROM reports WHERE status = ? ORDER BY created_at ASC",
+            status.as_str()
+        )
+        .fetch_all(self.db.pool())
+        .await?;
+
+        let mut reports = Vec::with_capacity(report_ids.len());
+        for report_id in report_ids {
+            let report_id = Uuid::parse_str(&report_id)
+                .map_err(|e| AppError::Internal(format!("Invalid report ID: {}", e)))?;
+            reports.push(self.get_report(report_id).await?);
+        }
+
+        Ok(reports)
+    }
+
+    /// Mark a report as dealt with
+    pub async fn resolve_report(&self, report_id: Uuid, admin_id: Uuid) -> AppResult<Report> {
+        let result = sqlx::query!(
+            "UPDATE reports SET status = ?, resolved_by = ?, resolved_at = ? WHERE id = ? AND status = ?",
+            ReportStatus::Resolved.as_str(),
+            admin_id.to_string(),
+            Utc::now().to_rfc3339(),
+            report_id.to_string(),
+            ReportStatus::Open.as_str()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Open report not found".to_string()));
+        }
+
+        self.get_report(report_id).await
+    }
+
+    async fn get_report(&self, report_id: Uuid) -> AppResult<Report> {
+        let record = sqlx::query!(
+            r#"
+            SELECT id, reporter_id, reported_user_id, chat_id, reason, status, created_at, resolved_by, resolved_at
+            FROM reports WHERE id = ?
+            "#,
+            report_id.to_string()
+        )
+        .fetch_optional(self.db.pool())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Report not found".to_string()))?;
+
+        Ok(Report {
+            id: report_id,
+            reporter_id: parse_id(&record.reporter_id)?,
+            reported_user_id: parse_id(&record.reported_user_id)?,
+            chat_id: record.chat_id.as_deref().map(parse_id).transpose()?,
+            reason: record.reason,
+            status: ReportStatus::parse(&record.status),
+            created_at: parse_date(&record.created_at)?,
+            resolved_by: record.resolved_by.as_deref().map(parse_id).transpose()?,
+            resolved_at: record.resolved_at.as_deref().map(parse_date).transpose()?,
+        })
+    }
+}
+
+fn parse_id(value: &str) -> AppResult<Uuid> {
+    Uuid::parse_str(value).map_err(|e| AppError::Internal(format!("Invalid ID: {}", e)))
+}
+
+fn parse_date(value: &str) -> AppResult<DateTime<Utc>> {
+    Ok(DateTime::parse_from_rfc3339(value)
+        .map_err(|e| AppError::Internal(format!("Invalid date: {}", e)))?
+        .with_timezone(&Utc))
+}
@@ -1,5 +1,7 @@
 use anyhow::Result;
+use sqlx::sqlite::SqliteConnectOptions;
 use sqlx::{Pool, Sqlite, SqlitePool};
+use std::str::FromStr;
 use tracing::info;
 
 use crate::core::config::DatabaseConfig;
@@ -12,7 +14,11 @@ impl Database {
     pub async fn new(config: &DatabaseConfig) -> Result<Self> {
         info!("Connecting to database: {}", config.url);
         
-        let pool = SqlitePool::connect(&config.url).await?;
+        // Overwrite deleted content on disk so shredded data keys don't linger in free pages
+        let options = SqliteConnectOptions::from_str(&config.url)?
+            .pragma("secure_delete", "ON");
+
+        let pool = SqlitePool::connect_with(options).await?;
         
         Ok(Self { pool })
     }
@@ -0,0 +1,170 @@
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::core::error::{AppError, AppResult};
+use crate::core::types::{BlockUserRequest, BlockedUser, User};
+use crate::matrix::client::MatrixClient;
+use crate::storage::database::Database;
+use crate::user::service::UserService;
+
+/// Users a single account may block
+const MAX_BLOCKS: i64 = 5000;
+
+/// Blocked users.
+///
+/// A block hides the blocked user's posts and messages from the blocker and
+/// keeps them from starting a direct message with, or adding to a group, the
+/// blocker. The blocked user isn't told.
+///
+/// Each change is mirrored to the blocker's Matrix ignored-user list so
+/// Matrix clients hide the same people. Like profile updates, that only
+/// works where the bot may act for the user, so failures are logged.
+pub struct BlockService {
+    db: Arc<Database>,
+    matrix_client: Arc<MatrixClient>,
+    users: Arc<UserService>,
+}
+
+impl BlockService {
+    pub fn new(db: Arc<Database>, matrix_client: Arc<MatrixClient>, users: Arc<UserService>) -> Self {
+        Self { db, matrix_client, users }
+    }
+
+    /// Everyone the user has blocked, most recent first
+    pub async fn list_blocks(&self, blocker: &User) -> AppResult<Vec<BlockedUser>> {
+        let block_records = sqlx::query!(
+            "SELECT blocked_id, created_at FROM blocks WHERE blocker_id = ? ORDER BY created_at DESC",
+            blocker.id.to_string()
+        )
+        .fetch_all(self.db.pool())
+        .await?;
+
+        let mut blocks = Vec::with_capacity(block_records.len());
+        for record in block_records {
+            let blocked_id = Uuid::parse_str(&record.blocked_id)
+                .map_err(|e| AppError::Internal(format!("Invalid user ID: {}", e)))?;
+
+            blocks.push(BlockedUser {
+                user: self.users.get_public_user(blocked_id, blocker).await?,
+                created_at: parse_date(&record.created_at)?,
+            });
+        }
+
+        Ok(blocks)
+    }
+
+    /// Block someone by user ID, username or Matrix ID. They are also removed
+    /// from the blocker's contacts.
+    pub async fn block_user(&self, blocker: &User, request: BlockUserRequest) -> AppResult<BlockedUser> {
+        let blocked_id = self.users.resolve_user(&request.user).await?;
+        if blocked_id == blocker.id {
+            return Err(AppError::InvalidRequest("You can't block yourself".to_string()));
+        }
+
+        let block_count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) as "count: i64" FROM blocks WHERE blocker_id = ?"#,
+            blocker.id.to_string()
+        )
+        .fetch_one(self.db.pool())
+        .await?;
+
+        if block_count >= MAX_BLOCKS {
+            return Err(AppError::InvalidRequest(format!("At most {} blocked users per account", MAX_BLOCKS)));
+        }
+
+        let now = Utc::now();
+        let result = sqlx::query!(
+            "INSERT OR IGNORE INTO blocks (blocker_id, blocked_id, created_at) VALUES (?, ?, ?)",
+            blocker.id.to_string(),
+            blocked_id.to_string(),
+            now.to_rfc3339()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::InvalidRequest("Already blocked".to_string()));
+        }
+
+        sqlx::query!(
+            "DELETE FROM contacts WHERE owner_id = ? AND contact_id = ?",
+            blocker.id.to_string(),
+            blocked_id.to_string()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        self.sync_ignored_users(blocker).await;
+
+        Ok(BlockedUser {
+            user: self.users.get_public_user(blocked_id, blocker).await?,
+            created_at: now,
+        })
+    }
+
+    /// Lift a block
+    pub async fn unblock_user(&self, blocker: &User, blocked_id: Uuid) -> AppResult<()> {
+        let result = sqlx::query!(
+            "DELETE FROM blocks WHERE blocker_id = ? AND blocked_id = ?",
+            blocker.id.to_string(),
+            blocked_id.to_string()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("User is not blocked".to_string()));
+        }
+
+        self.sync_ignored_users(blocker).await;
+
+        Ok(())
+    }
+
+    /// Whether `blocker` has blocked `blocked`
+    pub async fn is_blocked(&self, blocker_id: Uuid, blocked_id: Uuid) -> AppResult<bool> {
+        let block = sqlx::query!(
+            "SELECT blocker_id FROM blocks WHERE blocker_id = ? AND blocked_id = ?",
+            blocker_id.to_string(),
+            blocked_id.to_string()
+        )
+        .fetch_optional(self.db.pool())
+        .await?;
+
+        Ok(block.is_some())
+    }
+
+    /// Whether either user has blocked the other
+    pub async fn is_blocked_either_way(&self, a: Uuid, b: Uuid) -> AppResult<bool> {
+        Ok(self.is_blocked(a, b).await? || self.is_blocked(b, a).await?)
+    }
+
+    /// Replace the user's Matrix ignored-user list with their current blocks
+    async fn sync_ignored_users(&self, blocker: &User) {
+        let ignored_user_ids = match sqlx::query_scalar!(
+            "SELECT u.matrix_user_id FROM blocks b JOIN users u ON u.id = b.blocked_id WHERE b.blocker_id = ?",
+            blocker.id.to_string()
+        )
+        .fetch_all(self.db.pool())
+        .await
+        {
+            Ok(ids) => ids,
+            Err(e) => {
+                warn!("Failed to load blocks of {}: {}", blocker.id, e);
+                return;
+            }
+        };
+
+        if let Err(e) = self.matrix_client.set_ignored_users(&blocker.matrix_user_id, &ignored_user_ids).await {
+            warn!("Failed to update Matrix ignored users of {}: {}", blocker.matrix_user_id, e);
+        }
+    }
+}
+
+fn parse_date(value: &str) -> AppResult<DateTime<Utc>> {
+    Ok(DateTime::parse_from_rfc3339(value)
+        .map_err(|e| AppError::Internal(format!("Invalid date: {}", e)))?
+        .with_timezone(&Utc))
+}
@@ -0,0 +1,169 @@
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::core::error::{AppError, AppResult};
+use crate::core::types::{AddContactRequest, Contact, UpdateContactRequest, User};
+use crate::storage::database::Database;
+use crate::user::service::UserService;
+
+/// Contacts a single account may hold
+const MAX_CONTACTS: i64 = 1000;
+
+const MAX_NICKNAME_CHARS: usize = 64;
+
+/// Personal contact lists with private nicknames.
+///
+/// Adding someone doesn't notify them or need their consent; it's an
+/// address book, not a friendship.
+pub struct ContactService {
+    db: Arc<Database>,
+    users: Arc<UserService>,
+}
+
+impl ContactService {
+    pub fn new(db: Arc<Database>, users: Arc<UserService>) -> Self {
+        Self { db, users }
+    }
+
+    /// A user's contacts, by nickname or username
+    pub async fn list_contacts(&self, owner: &User) -> AppResult<Vec<Contact>> {
+        let contact_records = sqlx::query!(
+            r#"
+            SELECT c.contact_id, c.nickname, c.created_at
+            FROM contacts c JOIN users u ON u.id = c.contact_id
+            WHERE c.owner_id = ?
+            ORDER BY lower(COALESCE(c.nickname, u.display_name, u.username))
+            "#,
+            owner.id.to_string()
+        )
+        .fetch_all(self.db.pool())
+        .await?;
+
+        let mut contacts = Vec::with_capacity(contact_records.len());
+        for record in contact_records {
+            let contact_id = Uuid::parse_str(&record.contact_id)
+                .map_err(|e| AppError::Internal(format!("Invalid user ID: {}", e)))?;
+
+            contacts.push(Contact {
+                user: self.users.get_public_user(contact_id, owner).await?,
+                nickname: record.nickname,
+                created_at: parse_date(&record.created_at)?,
+            });
+        }
+
+        Ok(contacts)
+    }
+
+    /// Add someone by user ID, username or Matrix ID
+    pub async fn add_contact(&self, owner: &User, request: AddContactRequest) -> AppResult<Contact> {
+        let contact_id = self.users.resolve_user(&request.user).await?;
+        if contact_id == owner.id {
+            return Err(AppError::InvalidRequest("You can't add yourself as a contact".to_string()));
+        }
+
+        let nickname = validate_nickname(request.nickname.as_deref())?;
+
+        let contact_count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) as "count: i64" FROM contacts WHERE owner_id = ?"#,
+            owner.id.to_string()
+        )
+        .fetch_one(self.db.pool())
+        .await?;
+
+        if contact_count >= MAX_CONTACTS {
+            return Err(AppError::InvalidRequest(format!("At most {} contacts per account", MAX_CONTACTS)));
+        }
+
+        let now = Utc::now();
+        let result = sqlx::query!(
+            "INSERT OR IGNORE INTO contacts (owner_id, contact_id, nickname, created_at) VALUES (?, ?, ?, ?)",
+            owner.id.to_string(),
+            contact_id.to_string(),
+            nickname,
+            now.to_rfc3339()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::InvalidRequest("Already in your contacts".to_string()));
+        }
+
+        Ok(Contact {
+            user: self.users.get_public_user(contact_id, owner).await?,
+            nickname,
+            created_at: now,
+        })
+    }
+
+    /// Change or clear a contact's nickname
+    pub async fn update_contact(&self, owner: &User, contact_id: Uuid, request: UpdateContactRequest) -> AppResult<Contact> {
+        let nickname = validate_nickname(request.nickname.as_deref())?;
+
+        let result = sqlx::query!(
+            "UPDATE contacts SET nickname = ? WHERE owner_id = ? AND contact_id = ?",
+            nickname,
+            owner.id.to_string(),
+            contact_id.to_string()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Contact not found".to_string()));
+        }
+
+        let created_at = sqlx::query_scalar!(
+            "SELECT created_at FROM contacts WHERE owner_id = ? AND contact_id = ?",
+            owner.id.to_string(),
+            contact_id.to_string()
+        )
+        .fetch_one(self.db.pool())
+        .await?;
+
+        Ok(Contact {
+            user: self.users.get_public_user(contact_id, owner).await?,
+            nickname,
+            created_at: parse_date(&created_at)?,
+        })
+    }
+
+    /// Remove someone from the list
+    pub async fn remove_contact(&self, owner_id: Uuid, contact_id: Uuid) -> AppResult<()> {
+        let result = sqlx::query!(
+            "DELETE FROM contacts WHERE owner_id = ? AND contact_id = ?",
+            owner_id.to_string(),
+            contact_id.to_string()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Contact not found".to_string()));
+        }
+
+        Ok(())
+    }
+}
+
+fn validate_nickname(nickname: Option<&str>) -> AppResult<Option<String>> {
+    let Some(nickname) = nickname.map(str::trim).filter(|nickname| !nickname.is_empty()) else {
+        return Ok(None);
+    };
+
+    if nickname.chars().count() > MAX_NICKNAME_CHARS || nickname.chars().any(char::is_control) {
+        return Err(AppError::InvalidRequest(format!(
+            "Nicknames must be at most {} characters, without control characters",
+            MAX_NICKNAME_CHARS
+        )));
+    }
+
+    Ok(Some(nickname.to_string()))
+}
+
+fn parse_date(value: &str) -> AppResult<DateTime<Utc>> {
+    Ok(DateTime::parse_from_rfc3339(value)
+        .map_err(|e| AppError::Internal(format!("Invalid date: {}", e)))?
+        .with_timezone(&Utc))
+}
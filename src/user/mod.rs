@@ -0,0 +1,3 @@
+pub mod blocks;
+pub mod contacts;
+pub mod service;
@@ -0,0 +1,549 @@
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::core::error::{AppError, AppResult};
+use crate::core::types::{
+    Audience, ContentPreferences, PrivacySettings, Profile, PublicUser, UpdateContentPreferencesRequest, UpdatePrivacyRequest,
+    UpdateProfileRequest, User,
+};
+use crate::matrix::client::MatrixClient;
+use crate::storage::database::Database;
+
+/// Largest avatar image accepted
+pub const MAX_AVATAR_BYTES: usize = 1024 * 1024;
+
+const MAX_DISPLAY_NAME_CHARS: usize = 64;
+const MAX_BIO_CHARS: usize = 500;
+
+/// Accounts read per page while looking for near matches of a search
+const SEARCH_PAGE_SIZE: i64 = 500;
+
+/// Pages read per search before giving up on near matches
+const MAX_FUZZY_PAGES: usize = 4;
+
+/// Query length per tolerated typo; shorter queries must match exactly
+const CHARS_PER_TYPO: usize = 4;
+
+/// Profiles, privacy settings and what other users get to see of an account.
+///
+/// Display names and avatars are also pushed to the account's Matrix
+/// profile. That only succeeds where the homeserver lets the bot act for the
+/// user (e.g. an application service), so failures are logged, not returned.
+pub struct UserService {
+    db: Arc<Database>,
+    matrix_client: Arc<MatrixClient>,
+}
+
+impl UserService {
+    pub fn new(db: Arc<Database>, matrix_client: Arc<MatrixClient>) -> Self {
+        Self { db, matrix_client }
+    }
+
+    /// Get a user's own profile and privacy settings
+    pub async fn get_profile(&self, user_id: Uuid) -> AppResult<Profile> {
+        let user_record = sqlx::query!(
+            r#"
+            SELECT display_name, bio, avatar_url, last_seen_visibility, dm_policy, discoverable,
+                   show_nsfw, blur_images, hide_spoilers
+            FROM users WHERE id = ?
+            "#,
+            user_id.to_string()
+        )
+        .fetch_optional(self.db.pool())
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+        Ok(Profile {
+            display_name: user_record.display_name,
+            bio: user_record.bio,
+            avatar_url: user_record.avatar_url,
+            privacy: PrivacySettings {
+                last_seen_visibility: Audience::parse(&user_record.last_seen_visibility),
+                dm_policy: Audience::parse(&user_record.dm_policy),
+                discoverable: user_record.discoverable,
+            },
+            content: ContentPreferences {
+                show_nsfw: user_record.show_nsfw,
+                blur_images: user_record.blur_images,
+                hide_spoilers: user_record.hide_spoilers,
+            },
+        })
+    }
+
+    /// Change the display name and bio
+    pub async fn update_profile(&self, user: &User, request: UpdateProfileRequest) -> AppResult<Profile> {
+        let display_name = request.display_name
+            .map(|display_name| validate_text(&display_name, "Display name", MAX_DISPLAY_NAME_CHARS, false))
+            .transpose()?;
+        let bio = request.bio
+            .map(|bio| validate_text(&bio, "Bio", MAX_BIO_CHARS, true))
+            .transpose()?;
+
+        if let Some(display_name) = &display_name {
+            sqlx::query!(
+                "UPDATE users SET display_name = ? WHERE id = ?",
+                display_name,
+                user.id.to_string()
+            )
+            .execute(self.db.pool())
+            .await?;
+
+            if let Err(e) = self.matrix_client.set_display_name(&user.matrix_user_id, display_name.as_deref()).await {
+                warn!("Failed to update Matrix display name of {}: {}", user.matrix_user_id, e);
+            }
+        }
+
+        if let Some(bio) = &bio {
+            sqlx::query!(
+                "UPDATE users SET bio = ? WHERE id = ?",
+                bio,
+                user.id.to_string()
+            )
+            .execute(self.db.pool())
+            .await?;
+        }
+
+        self.get_profile(user.id).await
+    }
+
+    /// Upload a new avatar to the Matrix media repository and use it.
+    /// PNG, JPEG, GIF and WebP images are accepted.
+    pub async fn set_avatar(&self, user: &User, image: Vec<u8>) -> AppResult<Profile> {
+        if image.len() > MAX_AVATAR_BYTES {
+            return Err(AppError::InvalidRequest(format!(
+                "Avatars can be at most {} KiB",
+                MAX_AVATAR_BYTES / 1024
+            )));
+        }
+
+        // Trust the bytes, not the client's Content-Type
+        let content_type = image_type(&image)
+            .ok_or_else(|| AppError::InvalidRequest("Avatar must be a PNG, JPEG, GIF or WebP image".to_string()))?;
+
+        let avatar_url = self.matrix_client.upload_media(content_type, image).await?;
+
+        sqlx::query!(
+            "UPDATE users SET avatar_url = ? WHERE id = ?",
+            avatar_url,
+            user.id.to_string()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        if let Err(e) = self.matrix_client.set_avatar_url(&user.matrix_user_id, Some(&avatar_url)).await {
+            warn!("Failed to update Matrix avatar of {}: {}", user.matrix_user_id, e);
+        }
+
+        self.get_profile(user.id).await
+    }
+
+    /// Go back to no avatar
+    pub async fn remove_avatar(&self, user: &User) -> AppResult<Profile> {
+        sqlx::query!(
+            "UPDATE users SET avatar_url = NULL WHERE id = ?",
+            user.id.to_string()
+        )
+        .execute(self.db.pool())
+        .await?;
+
+        if let Err(e) = self.matrix_client.set_avatar_url(&user.matrix_user_id, None).await {
+            warn!("Failed to remove Matrix avatar of {}: {}", user.matrix_user_id, e);
+        }
+
+        self.get_profile(user.id).await
+    }
+
+    /// Change privacy settings
+    pub async fn update_privacy(&self, user_id: Uuid, request: UpdatePrivacyRequest) -> AppResult<Profile> {
+        if let Some(visibility) = request.last_seen_visibility {
+            sqlx::query!(
+                "UPDATE users SET last_seen_visibility = ? WHERE id = ?",
+                visibility.as_str(),
+                user_id.to_string()
+            )
+            .execute(self.db.pool())
+            .await?;
+        }
+
+        if let Some(dm_policy) = request.dm_policy {
+            sqlx::query!(
+                "UPDATE users SET dm_policy = ? WHERE id = ?",
+                dm_policy.as_str(),
+                user_id.to_string()
+            )
+            .execute(self.db.pool())
+            .await?;
+        }
+
+        if let Some(discoverable) = request.discoverable {
+            sqlx::query!(
+                "UPDATE users SET discoverable = ? WHERE id = ?",
+                discoverable,
+                user_id.to_string()
+            )
+            .execute(self.db.pool())
+            .await?;
+        }
+
+        self.get_profile(user_id).await
+    }
+
+    /// Change content preferences
+    pub async fn update_content_preferences(&self, user_id: Uuid, request: UpdateContentPreferencesRequest) -> AppResult<Profile> {
+        if let Some(show_nsfw) = request.show_nsfw {
+            sqlx::query!(
+                "UPDATE users SET show_nsfw = ? WHERE id = ?",
+                show_nsfw,
+                user_id.to_string()
+            )
+            .execute(self.db.pool())
+            .await?;
+        }
+
+        if let Some(blur_images) = request.blur_images {
+            sqlx::query!(
+                "UPDATE users SET blur_images = ? WHERE id = ?",
+                blur_images,
+                user_id.to_string()
+            )
+            .execute(self.db.pool())
+            .await?;
+        }
+
+        if let Some(hide_spoilers) = request.hide_spoilers {
+            sqlx::query!(
+                "UPDATE users SET hide_spoilers = ? WHERE id = ?",
+                hide_spoilers,
+                user_id.to_string()
+            )
+            .execute(self.db.pool())
+            .await?;
+        }
+
+        self.get_profile(user_id).await
+    }
+
+    /// A user as `viewer` may see them
+    pub async fn get_public_user(&self, user_id: Uuid, viewer: &User) -> AppResult<PublicUser> {
+        let user_record = sqlx::query!(
+            r#"
+            SELECT username, display_name, bio, avatar_url, matrix_user_id, is_anonymous, created_at, last_seen, last_seen_visibility
+            FROM users WHERE id = ?
+            "#,
+            user_id.to_string()
+        )
+        .fetch_optional(self.db.pool())
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+        let show_last_seen = viewer.id == user_id
+            || Audience::parse(&user_record.last_seen_visibility).includes(viewer);
+
+        Ok(PublicUser {
+            id: user_id,
+            username: user_record.username,
+            display_name: user_record.display_name,
+            bio: user_record.bio,
+            avatar_url: user_record.avatar_url,
+            matrix_user_id: user_record.matrix_user_id,
+            is_anonymous: user_record.is_anonymous,
+            created_at: parse_date(&user_record.created_at)?,
+            last_seen: user_record.last_seen
+                .filter(|_| show_last_seen)
+                .as_deref()
+                .map(parse_date)
+                .transpose()?,
+        })
+    }
+
+    /// Find a user by ID, username or Matrix ID
+    pub async fn resolve_user(&self, reference: &str) -> AppResult<Uuid> {
+        let reference = reference.trim();
+
+        if let Ok(user_id) = Uuid::parse_str(reference) {
+            let exists = sqlx::query!("SELECT id FROM users WHERE id = ?", reference)
+                .fetch_optional(self.db.pool())
+                .await?;
+
+            return exists
+                .map(|_| user_id)
+                .ok_or_else(|| AppError::NotFound(format!("User not found: {}", reference)));
+        }
+
+        let user_id = if reference.starts_with('@') {
+            sqlx::query_scalar!("SELECT id FROM users WHERE matrix_user_id = ?", reference)
+                .fetch_optional(self.db.pool())
+                .await?
+        } else {
+            sqlx::query_scalar!("SELECT id FROM users WHERE username = ?", reference)
+                .fetch_optional(self.db.pool())
+                .await?
+        }
+        .ok_or_else(|| AppError::NotFound(format!("User not found: {}", reference)))?;
+
+        Uuid::parse_str(&user_id)
+            .map_err(|e| AppError::Internal(format!("Invalid user ID: {}", e)))
+    }
+
+    /// Resolve several references, keeping their order and dropping duplicates
+    pub async fn resolve_users(&self, references: &[String]) -> AppResult<Vec<Uuid>> {
+        let mut user_ids = Vec::with_capacity(references.len());
+
+        for reference in references {
+            let user_id = self.resolve_user(reference).await?;
+            if !user_ids.contains(&user_id) {
+                user_ids.push(user_id);
+            }
+        }
+
+        Ok(user_ids)
+    }
+
+    /// Search discoverable, registered accounts by username or display name.
+    /// Users blocked in either direction are left out.
+    ///
+    /// Exact matches rank first, then prefixes, then substrings, then names
+    /// within a small edit distance of the query, so typos still find people.
+    pub async fn search_users(&self, query: &str, viewer: &User, limit: Option<i64>) -> AppResult<Vec<PublicUser>> {
+        let query = query.trim().to_lowercase();
+        if query.chars().count() < 2 {
+            return Err(AppError::InvalidRequest("Search for at least 2 characters".to_string()));
+        }
+
+        let limit = limit.unwrap_or(20).clamp(1, 50);
+        let escaped = escape_like(&query);
+        let substring = format!("%{}%", escaped);
+        let prefix = format!("{}%", escaped);
+
+        // Exact matches first, then prefixes, then substrings
+        let matches = sqlx::query!(
+            r#"
+            SELECT id FROM users
+            WHERE discoverable = TRUE AND is_anonymous = FALSE AND approval_status = 'approved' AND id != ?1
+              AND id NOT IN (SELECT blocked_id FROM blocks WHERE blocker_id = ?1)
+              AND id NOT IN (SELECT blocker_id FROM blocks WHERE blocked_id = ?1)
+              AND (lower(username) LIKE ?2 ESCAPE '\' OR lower(display_name) LIKE ?2 ESCAPE '\')
+            ORDER BY
+              CASE
+                WHEN lower(username) = ?3 OR lower(display_name) = ?3 THEN 0
+                WHEN lower(username) LIKE ?4 ESCAPE '\' OR lower(display_name) LIKE ?4 ESCAPE '\' THEN 1
+                ELSE 2
+              END,
+              username ASC
+            LIMIT ?5
+            "#,
+            viewer.id.to_string(),
+            substring,
+            query,
+            prefix,
+            limit
+        )
+        .fetch_all(self.db.pool())
+        .await?;
+
+        let mut user_ids: Vec<String> = matches.into_iter().map(|record| record.id).collect();
+
+        // Fill up with names within a few typos, which SQL can't rank. Only the
+        // first few pages are read so a search never walks the whole table.
+        let mut after = String::new();
+        let mut pages = 0;
+        while (user_ids.len() as i64) < limit && query.chars().count() >= CHARS_PER_TYPO && pages < MAX_FUZZY_PAGES {
+            pages += 1;
+            let page = sqlx::query!(
+                r#"
+                SELECT id, username, display_name FROM users
+                WHERE discoverable = TRUE AND is_anonymous = FALSE AND approval_status = 'approved' AND id != ?1
+                  AND id NOT IN (SELECT blocked_id FROM blocks WHERE blocker_id = ?1)
+                  AND id NOT IN (SELECT blocker_id FROM blocks WHERE blocked_id = ?1)
+                  AND lower(username) NOT LIKE ?2 ESCAPE '\' AND lower(coalesce(display_name, '')) NOT LIKE ?2 ESCAPE '\'
+                  AND username > ?3
+                ORDER BY username ASC
+                LIMIT ?4
+                "#,
+                viewer.id.to_string(),
+                substring,
+                after,
+                SEARCH_PAGE_SIZE
+            )
+            .fetch_all(self.db.pool())
+            .await?;
+
+            let exhausted = (page.len() as i64) < SEARCH_PAGE_SIZE;
+
+            for record in page {
+                let close = std::iter::once(Some(record.username.to_lowercase()))
+                    .chain(std::iter::once(record.display_name.map(|name| name.to_lowercase())))
+                    .flatten()
+                    .any(|name| match_score(&query, &name).is_some());
+
+                if close && (user_ids.len() as i64) < limit {
+                    user_ids.push(record.id);
+                }
+                after = record.username;
+            }
+
+            if exhausted {
+                break;
+            }
+        }
+
+        let mut users = Vec::with_capacity(user_ids.len());
+        for user_id in user_ids {
+            let user_id = Uuid::parse_str(&user_id)
+                .map_err(|e| AppError::Internal(format!("Invalid user ID: {}", e)))?;
+            users.push(self.get_public_user(user_id, viewer).await?);
+        }
+
+        Ok(users)
+    }
+
+    /// Check that `sender` may start a direct message with the recipient
+    pub async fn check_direct_message_allowed(&self, sender_id: Uuid, recipient_id: Uuid) -> AppResult<()> {
+        let records = sqlx::query!(
+            "SELECT id, is_anonymous, dm_policy FROM users WHERE id IN (?, ?)",
+            sender_id.to_string(),
+            recipient_id.to_string()
+        )
+        .fetch_all(self.db.pool())
+        .await?;
+
+        let sender = records.iter().find(|record| record.id == sender_id.to_string());
+        let recipient = records.iter().find(|record| record.id == recipient_id.to_string());
+
+        let (Some(sender), Some(recipient)) = (sender, recipient) else {
+            return Err(AppError::NotFound("User not found".to_string()));
+        };
+
+        let allowed = match Audience::parse(&recipient.dm_policy) {
+            Audience::Everyone => true,
+            Audience::Registered => !sender.is_anonymous,
+            Audience::Nobody => false,
+        };
+
+        if !allowed {
+            return Err(AppError::Authorization("This user doesn't accept direct messages from you".to_string()));
+        }
+
+        Ok(())
+    }
+}
+
+/// Trim and check a profile text. Empty input clears the field.
+fn validate_text(value: &str, field: &str, max_chars: usize, multiline: bool) -> AppResult<Option<String>> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(None);
+    }
+
+    if value.chars().count() > max_chars {
+        return Err(AppError::InvalidRequest(format!("{} can be at most {} characters", field, max_chars)));
+    }
+
+    if value.chars().any(|c| c.is_control() && !(multiline && c == '\n')) {
+        return Err(AppError::InvalidRequest(format!("{} contains invalid characters", field)));
+    }
+
+    Ok(Some(value.to_string()))
+}
+
+/// Rank how well a name matches a search, lower is better
+fn match_score(query: &str, name: &str) -> Option<u32> {
+    if name == query {
+        return Some(0);
+    }
+    if name.starts_with(query) {
+        return Some(1);
+    }
+    if name.contains(query) {
+        return Some(2);
+    }
+
+    // Compare against the start of the name, so long names aren't penalized
+    let allowed = (query.chars().count() / CHARS_PER_TYPO) as u32;
+    let prefix: String = name.chars().take(query.chars().count()).collect();
+    let distance = edit_distance(query, &prefix);
+
+    (allowed > 0 && distance <= allowed).then_some(3 + distance)
+}
+
+fn edit_distance(a: &str, b: &str) -> u32 {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<u32> = (0..=b.len() as u32).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i as u32 + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + u32::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+fn escape_like(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+/// Content type of a supported image, from its leading bytes
+fn image_type(image: &[u8]) -> Option<&'static str> {
+    if image.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if image.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some("image/jpeg")
+    } else if image.starts_with(b"GIF87a") || image.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if image.len() >= 12 && &image[0..4] == b"RIFF" && &image[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else {
+        None
+    }
+}
+
+fn parse_date(value: &str) -> AppResult<DateTime<Utc>> {
+    Ok(DateTime::parse_from_rfc3339(value)
+        .map_err(|e| AppError::Internal(format!("Invalid date: {}", e)))?
+        .with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edit_distance_counts_single_character_edits() {
+        assert_eq!(edit_distance("alice", "alice"), 0);
+        assert_eq!(edit_distance("alice", "alcie"), 2);
+        assert_eq!(edit_distance("alice", "alic"), 1);
+        assert_eq!(edit_distance("alice", "malice"), 1);
+        assert_eq!(edit_distance("alice", "alexe"), 2);
+        assert_eq!(edit_distance("", "bob"), 3);
+        assert_eq!(edit_distance("bob", ""), 3);
+        assert_eq!(edit_distance("zoë", "zoe"), 1);
+    }
+
+    #[test]
+    fn match_score_ranks_exact_then_prefix_then_substring() {
+        assert_eq!(match_score("alice", "alice"), Some(0));
+        assert_eq!(match_score("alice", "alice_smith"), Some(1));
+        assert_eq!(match_score("alice", "not_alice"), Some(2));
+    }
+
+    #[test]
+    fn match_score_tolerates_a_typo_per_four_characters() {
+        assert_eq!(match_score("alcie", "alice"), None);
+        assert_eq!(match_score("alixe", "alice"), Some(4));
+        assert_eq!(match_score("alixe", "alice_smith"), Some(4));
+        assert_eq!(match_score("alixe_smoth", "alice_smith"), Some(5));
+        assert_eq!(match_score("xlixe", "alice"), None);
+    }
+
+    #[test]
+    fn match_score_requires_exact_matches_for_short_queries() {
+        assert_eq!(match_score("bob", "bob"), Some(0));
+        assert_eq!(match_score("bob", "bib"), None);
+    }
+}
@@ -0,0 +1,37 @@
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header, StatusCode},
+    response::{IntoResponse, Json},
+    Extension,
+};
+use std::sync::Arc;
+use tokio_util::io::ReaderStream;
+
+use crate::core::app::AppState;
+use crate::core::types::User;
+use crate::web::handlers::auth::ErrorResponse;
+
+/// Download a zip archive of everything stored about the account
+pub async fn export_account(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    match state.export_service.export_account(user.id).await {
+        Ok(archive) => {
+            let body = Body::from_stream(ReaderStream::new(tokio::fs::File::from_std(archive)));
+
+            Ok((
+                [
+                    (header::CONTENT_TYPE, "application/zip".to_string()),
+                    (header::CONTENT_DISPOSITION, format!("attachment; filename=\"export-{}.zip\"", user.id.simple())),
+                ],
+                body,
+            ))
+        }
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
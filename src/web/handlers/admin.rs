@@ -0,0 +1,285 @@
+use axum::{
+    extract::{State, Path, Query},
+    http::StatusCode,
+    response::Json,
+    Extension,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::core::app::AppState;
+use crate::core::types::{
+    DeletedAuthorContent, User, Job, Report, ReportStatus, ContentFilter, CreateContentFilterRequest, ModerationLogEntry,
+};
+use crate::web::handlers::auth::ErrorResponse;
+use crate::web::handlers::board::PaginationQuery;
+
+#[derive(Deserialize)]
+pub struct JobListQuery {
+    pub limit: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct ReportListQuery {
+    /// Defaults to open reports
+    pub status: Option<ReportStatus>,
+}
+
+/// Reject callers without admin privileges
+pub fn require_admin(user: &User) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    if user.is_admin {
+        Ok(())
+    } else {
+        Err((StatusCode::FORBIDDEN, Json(ErrorResponse { error: "Admin privileges required".to_string() })))
+    }
+}
+
+pub async fn start_reencryption(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Job>, (StatusCode, Json<ErrorResponse>)> {
+    require_admin(&user)?;
+
+    match state.key_rotation_service.start_reencryption(Some(user.id)).await {
+        Ok(job) => Ok(Json(job)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn list_jobs(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<JobListQuery>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Vec<Job>>, (StatusCode, Json<ErrorResponse>)> {
+    require_admin(&user)?;
+
+    match state.job_service.list_jobs(query.limit).await {
+        Ok(jobs) => Ok(Json(jobs)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn get_job(
+    State(state): State<Arc<AppState>>,
+    Path(job_id): Path<String>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Job>, (StatusCode, Json<ErrorResponse>)> {
+    require_admin(&user)?;
+
+    let job_uuid = Uuid::parse_str(&job_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid job ID".to_string() })))?;
+
+    match state.job_service.get_job(job_uuid).await {
+        Ok(job) => Ok(Json(job)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn list_pending_registrations(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Vec<User>>, (StatusCode, Json<ErrorResponse>)> {
+    require_admin(&user)?;
+
+    match state.auth_service.list_pending_registrations().await {
+        Ok(users) => Ok(Json(users)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn approve_registration(
+    State(state): State<Arc<AppState>>,
+    Path(user_id): Path<String>,
+    Extension(user): Extension<User>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    require_admin(&user)?;
+
+    let user_uuid = Uuid::parse_str(&user_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid user ID".to_string() })))?;
+
+    match state.auth_service.approve_registration(user_uuid).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn reject_registration(
+    State(state): State<Arc<AppState>>,
+    Path(user_id): Path<String>,
+    Extension(user): Extension<User>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    require_admin(&user)?;
+
+    let user_uuid = Uuid::parse_str(&user_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid user ID".to_string() })))?;
+
+    match state.auth_service.reject_registration(user_uuid).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct BoardChallengeRequest {
+    /// Omit or null to use `CHALLENGE_DIFFICULTY`, 0 to turn challenges off
+    pub difficulty: Option<u8>,
+}
+
+pub async fn set_board_challenge(
+    State(state): State<Arc<AppState>>,
+    Path(board_name): Path<String>,
+    Extension(user): Extension<User>,
+    Json(request): Json<BoardChallengeRequest>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    require_admin(&user)?;
+
+    match state.board_service.set_thread_challenge_difficulty(&board_name, request.difficulty).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct BoardDeletionPolicyRequest {
+    pub deleted_author_content: DeletedAuthorContent,
+}
+
+pub async fn set_board_deletion_policy(
+    State(state): State<Arc<AppState>>,
+    Path(board_name): Path<String>,
+    Extension(user): Extension<User>,
+    Json(request): Json<BoardDeletionPolicyRequest>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    require_admin(&user)?;
+
+    match state.board_service.set_deleted_author_content(&board_name, request.deleted_author_content).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn list_reports(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Query(query): Query<ReportListQuery>,
+) -> Result<Json<Vec<Report>>, (StatusCode, Json<ErrorResponse>)> {
+    require_admin(&user)?;
+
+    match state.report_service.list_reports(query.status.unwrap_or(ReportStatus::Open)).await {
+        Ok(reports) => Ok(Json(reports)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn resolve_report(
+    State(state): State<Arc<AppState>>,
+    Path(report_id): Path<String>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Report>, (StatusCode, Json<ErrorResponse>)> {
+    require_admin(&user)?;
+
+    let report_uuid = Uuid::parse_str(&report_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid report ID".to_string() })))?;
+
+    match state.report_service.resolve_report(report_uuid, user.id).await {
+        Ok(report) => Ok(Json(report)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn list_filters(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Vec<ContentFilter>>, (StatusCode, Json<ErrorResponse>)> {
+    require_admin(&user)?;
+
+    match state.content_filter_service.list_filters(None).await {
+        Ok(filters) => Ok(Json(filters)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn create_filter(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Json(request): Json<CreateContentFilterRequest>,
+) -> Result<Json<ContentFilter>, (StatusCode, Json<ErrorResponse>)> {
+    require_admin(&user)?;
+
+    match state.content_filter_service.create_filter(None, request, user.id).await {
+        Ok(filter) => Ok(Json(filter)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn delete_filter(
+    State(state): State<Arc<AppState>>,
+    Path(filter_id): Path<String>,
+    Extension(user): Extension<User>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    require_admin(&user)?;
+
+    let filter_uuid = Uuid::parse_str(&filter_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid filter ID".to_string() })))?;
+
+    match state.content_filter_service.delete_filter(None, filter_uuid).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn moderation_log(
+    State(state): State<Arc<AppState>>,
+    Query(pagination): Query<PaginationQuery>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Vec<ModerationLogEntry>>, (StatusCode, Json<ErrorResponse>)> {
+    require_admin(&user)?;
+
+    match state.content_filter_service.moderation_log(None, pagination.limit, pagination.offset).await {
+        Ok(entries) => Ok(Json(entries)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
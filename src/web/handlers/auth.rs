@@ -1,15 +1,19 @@
 use axum::{
-    extract::State,
+    extract::{State, Path, Query},
     http::StatusCode,
     response::Json,
     Extension,
 };
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use uuid::Uuid;
+use webauthn_rs::prelude::{CreationChallengeResponse, PublicKeyCredential, RegisterPublicKeyCredential, RequestChallengeResponse};
 
+use crate::auth::service::{LoginOutcome, RegisterOutcome};
 use crate::core::app::AppState;
 use crate::core::error::AppError;
-use crate::core::types::{User, CreateUserRequest, LoginRequest};
+use crate::core::types::{AccountDeletion, User, CreateUserRequest, LoginRequest, PasskeyInfo, TotpEnrollment};
 
 #[derive(Serialize)]
 pub struct AuthResponse {
@@ -17,22 +21,174 @@ pub struct AuthResponse {
     pub token: String,
 }
 
+/// Returned instead of a session when the account must be approved by an admin first
+#[derive(Serialize)]
+pub struct PendingApprovalResponse {
+    pub user: User,
+    pub approval_pending: bool,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum RegisterResponse {
+    Authenticated(AuthResponse),
+    PendingApproval(PendingApprovalResponse),
+}
+
+/// Returned instead of a session when the account has two-factor authentication
+#[derive(Serialize)]
+pub struct TwoFactorChallengeResponse {
+    pub two_factor_required: bool,
+    pub challenge_token: String,
+    pub expires_at: DateTime<Utc>,
+    pub methods: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum LoginResponse {
+    Authenticated(AuthResponse),
+    TwoFactorRequired(TwoFactorChallengeResponse),
+}
+
+#[derive(Deserialize)]
+pub struct VerifyTwoFactorRequest {
+    pub challenge_token: String,
+    pub code: Option<String>,
+    pub recovery_code: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct TotpCodeRequest {
+    pub code: String,
+}
+
+#[derive(Deserialize)]
+pub struct DisableTotpRequest {
+    pub password: String,
+    pub code: String,
+}
+
+#[derive(Serialize)]
+pub struct RecoveryCodesResponse {
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct PasskeyRegistrationOptions {
+    pub ceremony_id: Uuid,
+    pub options: CreationChallengeResponse,
+}
+
+#[derive(Serialize)]
+pub struct PasskeyAuthenticationOptions {
+    pub ceremony_id: Uuid,
+    pub options: RequestChallengeResponse,
+}
+
+#[derive(Deserialize)]
+pub struct FinishPasskeyRegistrationRequest {
+    pub ceremony_id: Uuid,
+    pub name: String,
+    pub credential: RegisterPublicKeyCredential,
+}
+
+#[derive(Deserialize)]
+pub struct StartPasskeyLoginRequest {
+    pub username: String,
+}
+
+#[derive(Deserialize)]
+pub struct FinishPasskeyLoginRequest {
+    pub ceremony_id: Uuid,
+    pub credential: PublicKeyCredential,
+}
+
+#[derive(Deserialize)]
+pub struct StartPasskeySecondFactorRequest {
+    pub challenge_token: String,
+}
+
+#[derive(Deserialize)]
+pub struct FinishPasskeySecondFactorRequest {
+    pub challenge_token: String,
+    pub ceremony_id: Uuid,
+    pub credential: PublicKeyCredential,
+}
+
+#[derive(Deserialize)]
+pub struct EmailTokenRequest {
+    pub token: String,
+}
+
+#[derive(Deserialize)]
+pub struct RequestPasswordResetRequest {
+    pub email: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResetPasswordRequest {
+    pub token: String,
+    pub password: String,
+}
+
+#[derive(Serialize)]
+pub struct OidcProvidersResponse {
+    pub providers: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct OidcStartResponse {
+    pub authorization_url: String,
+}
+
+/// Query string of the provider's redirect back to us
+#[derive(Deserialize)]
+pub struct OidcCallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
+
 #[derive(Serialize)]
 pub struct ErrorResponse {
     pub error: String,
 }
 
+#[derive(Deserialize)]
+pub struct DeleteAccountRequest {
+    pub password: Option<String>,
+}
+
 pub async fn register(
     State(state): State<Arc<AppState>>,
     Json(request): Json<CreateUserRequest>,
-) -> Result<Json<AuthResponse>, (StatusCode, Json<ErrorResponse>)> {
+) -> Result<Json<RegisterResponse>, (StatusCode, Json<ErrorResponse>)> {
     match state.auth_service.register(request).await {
-        Ok(user) => {
+        Ok(outcome) => {
+            let (RegisterOutcome::Active(user) | RegisterOutcome::PendingApproval(user)) = &outcome;
+
+            // A lost verification email shouldn't fail the registration, it can be resent
+            if user.email.is_some() {
+                let _ = state.account_email_service.send_verification(user).await;
+            }
+
+            let user = match outcome {
+                RegisterOutcome::Active(user) => user,
+                RegisterOutcome::PendingApproval(user) => {
+                    return Ok(Json(RegisterResponse::PendingApproval(PendingApprovalResponse {
+                        user,
+                        approval_pending: true,
+                    })));
+                }
+            };
+
             match state.auth_service.create_session(user.id).await {
-                Ok(session) => Ok(Json(AuthResponse {
+                Ok(session) => Ok(Json(RegisterResponse::Authenticated(AuthResponse {
                     user,
                     token: session.token,
-                })),
+                }))),
                 Err(e) => Err((
                     StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
                     Json(ErrorResponse { error: e.to_string() }),
@@ -49,8 +205,30 @@ pub async fn register(
 pub async fn login(
     State(state): State<Arc<AppState>>,
     Json(request): Json<LoginRequest>,
-) -> Result<Json<AuthResponse>, (StatusCode, Json<ErrorResponse>)> {
+) -> Result<Json<LoginResponse>, (StatusCode, Json<ErrorResponse>)> {
     match state.auth_service.login(request).await {
+        Ok(LoginOutcome::Authenticated(user, session)) => Ok(Json(LoginResponse::Authenticated(AuthResponse {
+            user,
+            token: session.token,
+        }))),
+        Ok(LoginOutcome::TwoFactorRequired(challenge)) => Ok(Json(LoginResponse::TwoFactorRequired(TwoFactorChallengeResponse {
+            two_factor_required: true,
+            challenge_token: challenge.token,
+            expires_at: challenge.expires_at,
+            methods: challenge.methods,
+        }))),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn verify_two_factor(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<VerifyTwoFactorRequest>,
+) -> Result<Json<AuthResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.auth_service.verify_login_challenge(&request.challenge_token, request.code.as_deref(), request.recovery_code.as_deref()).await {
         Ok((user, session)) => Ok(Json(AuthResponse {
             user,
             token: session.token,
@@ -62,6 +240,61 @@ pub async fn login(
     }
 }
 
+pub async fn setup_totp(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+) -> Result<Json<TotpEnrollment>, (StatusCode, Json<ErrorResponse>)> {
+    match state.auth_service.setup_totp(user.id).await {
+        Ok(enrollment) => Ok(Json(enrollment)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn confirm_totp(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Json(request): Json<TotpCodeRequest>,
+) -> Result<Json<RecoveryCodesResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.auth_service.confirm_totp(user.id, &request.code).await {
+        Ok(recovery_codes) => Ok(Json(RecoveryCodesResponse { recovery_codes })),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn disable_totp(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Json(request): Json<DisableTotpRequest>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    match state.auth_service.disable_totp(user.id, &request.password, &request.code).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn regenerate_recovery_codes(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Json(request): Json<TotpCodeRequest>,
+) -> Result<Json<RecoveryCodesResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.auth_service.regenerate_recovery_codes(user.id, &request.code).await {
+        Ok(recovery_codes) => Ok(Json(RecoveryCodesResponse { recovery_codes })),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
 pub async fn logout(
     State(state): State<Arc<AppState>>,
     Extension(user): Extension<User>,
@@ -75,4 +308,261 @@ pub async fn me(
     Extension(user): Extension<User>,
 ) -> Json<User> {
     Json(user)
-}
\ No newline at end of file
+}
+
+pub async fn delete_account(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Json(request): Json<DeleteAccountRequest>,
+) -> Result<Json<AccountDeletion>, (StatusCode, Json<ErrorResponse>)> {
+    match state.auth_service.request_account_deletion(user.id, request.password.as_deref()).await {
+        Ok(scheduled_for) => Ok(Json(AccountDeletion { scheduled_for: Some(scheduled_for) })),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn get_account_deletion(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+) -> Result<Json<AccountDeletion>, (StatusCode, Json<ErrorResponse>)> {
+    match state.auth_service.scheduled_deletion(user.id).await {
+        Ok(scheduled_for) => Ok(Json(AccountDeletion { scheduled_for })),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn cancel_account_deletion(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    match state.auth_service.cancel_account_deletion(user.id).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn start_passkey_registration(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+) -> Result<Json<PasskeyRegistrationOptions>, (StatusCode, Json<ErrorResponse>)> {
+    match state.passkey_service.start_registration(&user).await {
+        Ok((ceremony_id, options)) => Ok(Json(PasskeyRegistrationOptions { ceremony_id, options })),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn finish_passkey_registration(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Json(request): Json<FinishPasskeyRegistrationRequest>,
+) -> Result<Json<PasskeyInfo>, (StatusCode, Json<ErrorResponse>)> {
+    match state.passkey_service.finish_registration(user.id, request.ceremony_id, &request.name, &request.credential).await {
+        Ok(passkey) => Ok(Json(passkey)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn list_passkeys(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Vec<PasskeyInfo>>, (StatusCode, Json<ErrorResponse>)> {
+    match state.passkey_service.list_passkeys(user.id).await {
+        Ok(passkeys) => Ok(Json(passkeys)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn delete_passkey(
+    State(state): State<Arc<AppState>>,
+    Path(passkey_id): Path<String>,
+    Extension(user): Extension<User>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let passkey_uuid = Uuid::parse_str(&passkey_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid passkey ID".to_string() })))?;
+
+    match state.passkey_service.delete_passkey(user.id, passkey_uuid).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn start_passkey_login(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<StartPasskeyLoginRequest>,
+) -> Result<Json<PasskeyAuthenticationOptions>, (StatusCode, Json<ErrorResponse>)> {
+    match state.passkey_service.start_login(&request.username).await {
+        Ok((ceremony_id, options)) => Ok(Json(PasskeyAuthenticationOptions { ceremony_id, options })),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn finish_passkey_login(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<FinishPasskeyLoginRequest>,
+) -> Result<Json<AuthResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.passkey_service.finish_login(request.ceremony_id, &request.credential).await {
+        Ok((user, session)) => Ok(Json(AuthResponse {
+            user,
+            token: session.token,
+        })),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn start_passkey_second_factor(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<StartPasskeySecondFactorRequest>,
+) -> Result<Json<PasskeyAuthenticationOptions>, (StatusCode, Json<ErrorResponse>)> {
+    match state.passkey_service.start_second_factor(&request.challenge_token).await {
+        Ok((ceremony_id, options)) => Ok(Json(PasskeyAuthenticationOptions { ceremony_id, options })),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn finish_passkey_second_factor(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<FinishPasskeySecondFactorRequest>,
+) -> Result<Json<AuthResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.passkey_service.finish_second_factor(&request.challenge_token, request.ceremony_id, &request.credential).await {
+        Ok((user, session)) => Ok(Json(AuthResponse {
+            user,
+            token: session.token,
+        })),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn send_email_verification(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    match state.account_email_service.send_verification(&user).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn verify_email(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<EmailTokenRequest>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    match state.account_email_service.verify_email(&request.token).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn request_password_reset(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<RequestPasswordResetRequest>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    match state.account_email_service.request_password_reset(&request.email).await {
+        Ok(_) => Ok(StatusCode::ACCEPTED),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn reset_password(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ResetPasswordRequest>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    match state.account_email_service.reset_password(&request.token, &request.password).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn list_oidc_providers(
+    State(state): State<Arc<AppState>>,
+) -> Json<OidcProvidersResponse> {
+    Json(OidcProvidersResponse {
+        providers: state.oidc_service.provider_names(),
+    })
+}
+
+pub async fn start_oidc_login(
+    State(state): State<Arc<AppState>>,
+    Path(provider): Path<String>,
+) -> Result<Json<OidcStartResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.oidc_service.start_login(&provider).await {
+        Ok(authorization_url) => Ok(Json(OidcStartResponse { authorization_url })),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn finish_oidc_login(
+    State(state): State<Arc<AppState>>,
+    Path(provider): Path<String>,
+    Query(query): Query<OidcCallbackQuery>,
+) -> Result<Json<AuthResponse>, (StatusCode, Json<ErrorResponse>)> {
+    if let Some(error) = query.error {
+        let description = query.error_description.unwrap_or_default();
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(ErrorResponse { error: format!("Login at the identity provider failed: {} {}", error, description).trim().to_string() }),
+        ));
+    }
+
+    let (Some(code), Some(oidc_state)) = (query.code, query.state) else {
+        return Err((StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Missing code or state".to_string() })));
+    };
+
+    match state.oidc_service.finish_login(&provider, &code, &oidc_state).await {
+        Ok((user, session)) => Ok(Json(AuthResponse {
+            user,
+            token: session.token,
+        })),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
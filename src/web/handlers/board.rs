@@ -1,6 +1,6 @@
 use axum::{
     extract::{State, Path, Query},
-    http::StatusCode,
+    http::{StatusCode, HeaderMap},
     response::Json,
     Extension,
 };
@@ -9,7 +9,11 @@ use std::sync::Arc;
 use uuid::Uuid;
 
 use crate::core::app::AppState;
-use crate::core::types::{User, Board, Thread, Post, CreateBoardRequest, CreateThreadRequest, CreatePostRequest};
+use crate::core::types::{
+    User, Board, Thread, Post, CreateBoardRequest, CreateThreadRequest, CreatePostRequest, BoardMember,
+    AddBoardMemberRequest, BoardJoinRequest, CreateBoardJoinRequest, ContentRating, UpdateBoardRequest,
+    BoardModerator, ContentFilter, CreateContentFilterRequest, ModerationLogEntry,
+};
 use crate::web::handlers::auth::ErrorResponse;
 
 #[derive(Deserialize)]
@@ -18,10 +22,36 @@ pub struct PaginationQuery {
     pub offset: Option<i64>,
 }
 
+#[derive(Deserialize)]
+pub struct TransferBoardOwnershipRequest {
+    pub user_id: Uuid,
+}
+
+#[derive(Deserialize)]
+pub struct BoardListQuery {
+    /// Only SFW or only NSFW boards
+    pub rating: Option<ContentRating>,
+}
+
+/// Visitors without an account confirm they are adults with this header
+/// before NSFW boards are shown to them
+const AGE_CONFIRMED_HEADER: &str = "x-age-confirmed";
+
+fn age_confirmed(headers: &HeaderMap) -> bool {
+    headers.get(AGE_CONFIRMED_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.eq_ignore_ascii_case("true"))
+}
+
 pub async fn list_boards(
     State(state): State<Arc<AppState>>,
+    viewer: Option<Extension<User>>,
+    headers: HeaderMap,
+    Query(query): Query<BoardListQuery>,
 ) -> Result<Json<Vec<Board>>, (StatusCode, Json<ErrorResponse>)> {
-    match state.board_service.get_boards().await {
+    let viewer = viewer.map(|Extension(user)| user);
+
+    match state.board_service.get_boards(viewer.as_ref(), age_confirmed(&headers), query.rating).await {
         Ok(boards) => Ok(Json(boards)),
         Err(e) => Err((
             StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
@@ -33,8 +63,12 @@ pub async fn list_boards(
 pub async fn get_board(
     State(state): State<Arc<AppState>>,
     Path(name): Path<String>,
+    viewer: Option<Extension<User>>,
+    headers: HeaderMap,
 ) -> Result<Json<Board>, (StatusCode, Json<ErrorResponse>)> {
-    match state.board_service.get_board(&name).await {
+    let viewer = viewer.map(|Extension(user)| user);
+
+    match state.board_service.get_readable_board(&name, viewer.as_ref(), age_confirmed(&headers)).await {
         Ok(board) => Ok(Json(board)),
         Err(e) => Err((
             StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
@@ -48,7 +82,7 @@ pub async fn create_board(
     Extension(user): Extension<User>,
     Json(request): Json<CreateBoardRequest>,
 ) -> Result<Json<Board>, (StatusCode, Json<ErrorResponse>)> {
-    match state.board_service.create_board(request, user.id).await {
+    match state.board_service.create_board(request, &user).await {
         Ok(board) => Ok(Json(board)),
         Err(e) => Err((
             StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
@@ -57,12 +91,45 @@ pub async fn create_board(
     }
 }
 
+pub async fn update_board(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Extension(user): Extension<User>,
+    Json(request): Json<UpdateBoardRequest>,
+) -> Result<Json<Board>, (StatusCode, Json<ErrorResponse>)> {
+    match state.board_service.update_board(&name, request, &user).await {
+        Ok(board) => Ok(Json(board)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn delete_board(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Extension(user): Extension<User>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    match state.board_service.delete_board(&name, &user).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
 pub async fn list_threads(
     State(state): State<Arc<AppState>>,
     Path(board_name): Path<String>,
+    viewer: Option<Extension<User>>,
+    headers: HeaderMap,
     Query(pagination): Query<PaginationQuery>,
 ) -> Result<Json<Vec<Thread>>, (StatusCode, Json<ErrorResponse>)> {
-    match state.board_service.get_threads(&board_name, pagination.limit, pagination.offset).await {
+    let viewer = viewer.map(|Extension(user)| user);
+
+    match state.board_service.get_threads(&board_name, viewer.as_ref(), age_confirmed(&headers), pagination.limit, pagination.offset).await {
         Ok(threads) => Ok(Json(threads)),
         Err(e) => Err((
             StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
@@ -74,11 +141,14 @@ pub async fn list_threads(
 pub async fn get_thread(
     State(state): State<Arc<AppState>>,
     Path(thread_id): Path<String>,
+    viewer: Option<Extension<User>>,
+    headers: HeaderMap,
 ) -> Result<Json<Thread>, (StatusCode, Json<ErrorResponse>)> {
     let thread_uuid = Uuid::parse_str(&thread_id)
         .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid thread ID".to_string() })))?;
+    let viewer = viewer.map(|Extension(user)| user);
     
-    match state.board_service.get_thread(thread_uuid).await {
+    match state.board_service.get_visible_thread(thread_uuid, viewer.as_ref(), age_confirmed(&headers)).await {
         Ok(thread) => Ok(Json(thread)),
         Err(e) => Err((
             StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
@@ -93,7 +163,7 @@ pub async fn create_thread(
     Extension(user): Extension<User>,
     Json(request): Json<CreateThreadRequest>,
 ) -> Result<Json<Thread>, (StatusCode, Json<ErrorResponse>)> {
-    match state.board_service.create_thread(&board_name, request, user.id).await {
+    match state.board_service.create_thread(&board_name, request, &user).await {
         Ok(thread) => Ok(Json(thread)),
         Err(e) => Err((
             StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
@@ -105,12 +175,15 @@ pub async fn create_thread(
 pub async fn list_posts(
     State(state): State<Arc<AppState>>,
     Path(thread_id): Path<String>,
+    viewer: Option<Extension<User>>,
+    headers: HeaderMap,
     Query(pagination): Query<PaginationQuery>,
 ) -> Result<Json<Vec<Post>>, (StatusCode, Json<ErrorResponse>)> {
     let thread_uuid = Uuid::parse_str(&thread_id)
         .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid thread ID".to_string() })))?;
+    let viewer = viewer.map(|Extension(user)| user);
     
-    match state.board_service.get_posts(thread_uuid, pagination.limit, pagination.offset).await {
+    match state.board_service.get_posts(thread_uuid, viewer.as_ref(), age_confirmed(&headers), pagination.limit, pagination.offset).await {
         Ok(posts) => Ok(Json(posts)),
         Err(e) => Err((
             StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
@@ -128,11 +201,258 @@ pub async fn create_post(
     let thread_uuid = Uuid::parse_str(&thread_id)
         .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid thread ID".to_string() })))?;
     
-    match state.board_service.create_post(thread_uuid, request, user.id).await {
+    match state.board_service.create_post(thread_uuid, request, &user).await {
         Ok(post) => Ok(Json(post)),
         Err(e) => Err((
             StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
             Json(ErrorResponse { error: e.to_string() }),
         )),
     }
-}
\ No newline at end of file
+}
+
+pub async fn list_members(
+    State(state): State<Arc<AppState>>,
+    Path(board_name): Path<String>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Vec<BoardMember>>, (StatusCode, Json<ErrorResponse>)> {
+    match state.board_member_service.list_members(&board_name, &user).await {
+        Ok(members) => Ok(Json(members)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn add_member(
+    State(state): State<Arc<AppState>>,
+    Path(board_name): Path<String>,
+    Extension(user): Extension<User>,
+    Json(request): Json<AddBoardMemberRequest>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    match state.board_member_service.add_member(&board_name, request, &user).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn remove_member(
+    State(state): State<Arc<AppState>>,
+    Path((board_name, user_id)): Path<(String, String)>,
+    Extension(user): Extension<User>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let user_uuid = Uuid::parse_str(&user_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid user ID".to_string() })))?;
+
+    match state.board_member_service.remove_member(&board_name, user_uuid, &user).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn leave_board(
+    State(state): State<Arc<AppState>>,
+    Path(board_name): Path<String>,
+    Extension(user): Extension<User>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    match state.board_member_service.leave(&board_name, &user).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn request_to_join(
+    State(state): State<Arc<AppState>>,
+    Path(board_name): Path<String>,
+    Extension(user): Extension<User>,
+    Json(request): Json<CreateBoardJoinRequest>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    match state.board_member_service.request_to_join(&board_name, request, &user).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn list_join_requests(
+    State(state): State<Arc<AppState>>,
+    Path(board_name): Path<String>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Vec<BoardJoinRequest>>, (StatusCode, Json<ErrorResponse>)> {
+    match state.board_member_service.list_join_requests(&board_name, &user).await {
+        Ok(requests) => Ok(Json(requests)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn approve_join_request(
+    State(state): State<Arc<AppState>>,
+    Path((board_name, user_id)): Path<(String, String)>,
+    Extension(user): Extension<User>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let user_uuid = Uuid::parse_str(&user_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid user ID".to_string() })))?;
+
+    match state.board_member_service.approve_join_request(&board_name, user_uuid, &user).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn reject_join_request(
+    State(state): State<Arc<AppState>>,
+    Path((board_name, user_id)): Path<(String, String)>,
+    Extension(user): Extension<User>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let user_uuid = Uuid::parse_str(&user_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid user ID".to_string() })))?;
+
+    match state.board_member_service.reject_join_request(&board_name, user_uuid, &user).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn list_moderators(
+    State(state): State<Arc<AppState>>,
+    Path(board_name): Path<String>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Vec<BoardModerator>>, (StatusCode, Json<ErrorResponse>)> {
+    match state.board_role_service.list_moderators(&board_name, &user).await {
+        Ok(moderators) => Ok(Json(moderators)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn add_moderator(
+    State(state): State<Arc<AppState>>,
+    Path((board_name, user_id)): Path<(String, String)>,
+    Extension(user): Extension<User>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let user_uuid = Uuid::parse_str(&user_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid user ID".to_string() })))?;
+
+    match state.board_role_service.add_moderator(&board_name, user_uuid, &user).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn remove_moderator(
+    State(state): State<Arc<AppState>>,
+    Path((board_name, user_id)): Path<(String, String)>,
+    Extension(user): Extension<User>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let user_uuid = Uuid::parse_str(&user_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid user ID".to_string() })))?;
+
+    match state.board_role_service.remove_moderator(&board_name, user_uuid, &user).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn transfer_ownership(
+    State(state): State<Arc<AppState>>,
+    Path(board_name): Path<String>,
+    Extension(user): Extension<User>,
+    Json(request): Json<TransferBoardOwnershipRequest>,
+) -> Result<Json<Board>, (StatusCode, Json<ErrorResponse>)> {
+    match state.board_role_service.transfer_ownership(&board_name, request.user_id, &user).await {
+        Ok(board) => Ok(Json(board)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn list_filters(
+    State(state): State<Arc<AppState>>,
+    Path(board_name): Path<String>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Vec<ContentFilter>>, (StatusCode, Json<ErrorResponse>)> {
+    match state.board_filter_service.list_filters(&board_name, &user).await {
+        Ok(filters) => Ok(Json(filters)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn create_filter(
+    State(state): State<Arc<AppState>>,
+    Path(board_name): Path<String>,
+    Extension(user): Extension<User>,
+    Json(request): Json<CreateContentFilterRequest>,
+) -> Result<Json<ContentFilter>, (StatusCode, Json<ErrorResponse>)> {
+    match state.board_filter_service.create_filter(&board_name, request, &user).await {
+        Ok(filter) => Ok(Json(filter)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn delete_filter(
+    State(state): State<Arc<AppState>>,
+    Path((board_name, filter_id)): Path<(String, String)>,
+    Extension(user): Extension<User>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let filter_uuid = Uuid::parse_str(&filter_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid filter ID".to_string() })))?;
+
+    match state.board_filter_service.delete_filter(&board_name, filter_uuid, &user).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn moderation_log(
+    State(state): State<Arc<AppState>>,
+    Path(board_name): Path<String>,
+    Query(pagination): Query<PaginationQuery>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Vec<ModerationLogEntry>>, (StatusCode, Json<ErrorResponse>)> {
+    match state.board_filter_service.moderation_log(&board_name, &user, pagination.limit, pagination.offset).await {
+        Ok(entries) => Ok(Json(entries)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
@@ -0,0 +1,57 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::Json,
+    Extension,
+};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::challenge::service::ChallengePurpose;
+use crate::core::app::AppState;
+use crate::core::error::AppResult;
+use crate::core::types::{Challenge, CreateChallengeRequest, User};
+use crate::web::handlers::auth::ErrorResponse;
+
+/// Issue a challenge for anonymous registration or an anonymous thread
+pub async fn create_challenge(
+    State(state): State<Arc<AppState>>,
+    viewer: Option<Extension<User>>,
+    Json(request): Json<CreateChallengeRequest>,
+) -> Result<Json<Challenge>, (StatusCode, Json<ErrorResponse>)> {
+    let viewer = viewer.map(|Extension(user)| user);
+
+    let (purpose, difficulty) = match (request.purpose.as_str(), request.board.as_deref()) {
+        ("register", _) => (ChallengePurpose::Register, state.challenge_service.default_difficulty()),
+        ("thread", Some(board_name)) => match thread_difficulty(&state, board_name, viewer.as_ref()).await {
+            Ok((board_id, difficulty)) => (ChallengePurpose::Thread(board_id), difficulty),
+            Err(e) => {
+                return Err((
+                    StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+                    Json(ErrorResponse { error: e.to_string() }),
+                ));
+            }
+        },
+        _ => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse { error: "Purpose must be \"register\", or \"thread\" with a board".to_string() }),
+            ));
+        }
+    };
+
+    match state.challenge_service.issue(purpose, difficulty) {
+        Ok(challenge) => Ok(Json(challenge)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+/// A board's thread challenge difficulty. Private boards look like missing
+/// ones to outsiders, so their settings aren't revealed either.
+async fn thread_difficulty(state: &AppState, board_name: &str, viewer: Option<&User>) -> AppResult<(Uuid, u8)> {
+    let board = state.board_service.get_visible_board(board_name, viewer).await?;
+    state.board_service.thread_challenge_difficulty(&board.name).await
+}
@@ -9,7 +9,7 @@ use std::sync::Arc;
 use uuid::Uuid;
 
 use crate::core::app::AppState;
-use crate::core::types::{User, Chat, Message, CreateChatRequest, SendMessageRequest};
+use crate::core::types::{User, Chat, ChatMember, ChatRequest, Message, CreateChatRequest, Report, ReportChatRequest, SendMessageRequest};
 use crate::web::handlers::auth::ErrorResponse;
 use crate::web::handlers::board::PaginationQuery;
 
@@ -18,6 +18,27 @@ pub struct AddParticipantRequest {
     pub user_id: Uuid,
 }
 
+#[derive(Deserialize)]
+pub struct RenameChatRequest {
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct TransferOwnershipRequest {
+    pub user_id: Uuid,
+}
+
+#[derive(Deserialize)]
+pub struct SetSearchRequest {
+    pub enabled: bool,
+}
+
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    pub q: String,
+    pub limit: Option<i64>,
+}
+
 pub async fn list_chats(
     State(state): State<Arc<AppState>>,
     Extension(user): Extension<User>,
@@ -134,4 +155,245 @@ pub async fn remove_participant(
             Json(ErrorResponse { error: e.to_string() }),
         )),
     }
-}
\ No newline at end of file
+}
+
+pub async fn list_participants(
+    State(state): State<Arc<AppState>>,
+    Path(chat_id): Path<String>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Vec<ChatMember>>, (StatusCode, Json<ErrorResponse>)> {
+    let chat_uuid = Uuid::parse_str(&chat_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid chat ID".to_string() })))?;
+
+    match state.chat_service.list_members(chat_uuid, &user).await {
+        Ok(members) => Ok(Json(members)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn leave_chat(
+    State(state): State<Arc<AppState>>,
+    Path(chat_id): Path<String>,
+    Extension(user): Extension<User>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let chat_uuid = Uuid::parse_str(&chat_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid chat ID".to_string() })))?;
+
+    match state.chat_service.leave_chat(chat_uuid, user.id).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn rename_chat(
+    State(state): State<Arc<AppState>>,
+    Path(chat_id): Path<String>,
+    Extension(user): Extension<User>,
+    Json(request): Json<RenameChatRequest>,
+) -> Result<Json<Chat>, (StatusCode, Json<ErrorResponse>)> {
+    let chat_uuid = Uuid::parse_str(&chat_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid chat ID".to_string() })))?;
+
+    match state.chat_service.rename_chat(chat_uuid, &request.name, user.id).await {
+        Ok(chat) => Ok(Json(chat)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn promote_admin(
+    State(state): State<Arc<AppState>>,
+    Path((chat_id, user_id)): Path<(String, String)>,
+    Extension(admin_user): Extension<User>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let chat_uuid = Uuid::parse_str(&chat_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid chat ID".to_string() })))?;
+
+    let user_uuid = Uuid::parse_str(&user_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid user ID".to_string() })))?;
+
+    match state.chat_service.promote_member(chat_uuid, user_uuid, admin_user.id).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn demote_admin(
+    State(state): State<Arc<AppState>>,
+    Path((chat_id, user_id)): Path<(String, String)>,
+    Extension(user): Extension<User>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let chat_uuid = Uuid::parse_str(&chat_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid chat ID".to_string() })))?;
+
+    let user_uuid = Uuid::parse_str(&user_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid user ID".to_string() })))?;
+
+    match state.chat_service.demote_member(chat_uuid, user_uuid, user.id).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn transfer_ownership(
+    State(state): State<Arc<AppState>>,
+    Path(chat_id): Path<String>,
+    Extension(user): Extension<User>,
+    Json(request): Json<TransferOwnershipRequest>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let chat_uuid = Uuid::parse_str(&chat_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid chat ID".to_string() })))?;
+
+    match state.chat_service.transfer_ownership(chat_uuid, request.user_id, user.id).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn set_search(
+    State(state): State<Arc<AppState>>,
+    Path(chat_id): Path<String>,
+    Extension(user): Extension<User>,
+    Json(request): Json<SetSearchRequest>,
+) -> Result<Json<Chat>, (StatusCode, Json<ErrorResponse>)> {
+    let chat_uuid = Uuid::parse_str(&chat_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid chat ID".to_string() })))?;
+
+    match state.chat_service.set_search_enabled(chat_uuid, request.enabled, user.id).await {
+        Ok(chat) => Ok(Json(chat)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn search_chat_messages(
+    State(state): State<Arc<AppState>>,
+    Path(chat_id): Path<String>,
+    Query(query): Query<SearchQuery>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Vec<Message>>, (StatusCode, Json<ErrorResponse>)> {
+    let chat_uuid = Uuid::parse_str(&chat_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid chat ID".to_string() })))?;
+
+    match state.chat_service.search_messages(user.id, Some(chat_uuid), &query.q, query.limit).await {
+        Ok(messages) => Ok(Json(messages)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn search_messages(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<SearchQuery>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Vec<Message>>, (StatusCode, Json<ErrorResponse>)> {
+    match state.chat_service.search_messages(user.id, None, &query.q, query.limit).await {
+        Ok(messages) => Ok(Json(messages)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn delete_chat(
+    State(state): State<Arc<AppState>>,
+    Path(chat_id): Path<String>,
+    Extension(user): Extension<User>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let chat_uuid = Uuid::parse_str(&chat_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid chat ID".to_string() })))?;
+
+    match state.chat_service.delete_chat(chat_uuid, &user).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+pub async fn list_chat_requests(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Vec<ChatRequest>>, (StatusCode, Json<ErrorResponse>)> {
+    match state.chat_service.list_chat_requests(&user).await {
+        Ok(requests) => Ok(Json(requests)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn accept_chat_request(
+    State(state): State<Arc<AppState>>,
+    Path(chat_id): Path<String>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Chat>, (StatusCode, Json<ErrorResponse>)> {
+    let chat_uuid = Uuid::parse_str(&chat_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid chat ID".to_string() })))?;
+
+    match state.chat_service.accept_chat_request(chat_uuid, user.id).await {
+        Ok(chat) => Ok(Json(chat)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn decline_chat_request(
+    State(state): State<Arc<AppState>>,
+    Path(chat_id): Path<String>,
+    Extension(user): Extension<User>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let chat_uuid = Uuid::parse_str(&chat_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid chat ID".to_string() })))?;
+
+    match state.chat_service.decline_chat_request(chat_uuid, user.id).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn report_chat_request(
+    State(state): State<Arc<AppState>>,
+    Path(chat_id): Path<String>,
+    Extension(user): Extension<User>,
+    Json(request): Json<ReportChatRequest>,
+) -> Result<Json<Report>, (StatusCode, Json<ErrorResponse>)> {
+    let chat_uuid = Uuid::parse_str(&chat_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid chat ID".to_string() })))?;
+
+    match state.report_service.report_chat_request(&user, chat_uuid, request).await {
+        Ok(report) => Ok(Json(report)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
@@ -0,0 +1,210 @@
+use axum::{
+    extract::{State, Path},
+    http::StatusCode,
+    response::Json,
+    Extension,
+};
+use serde::Serialize;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::core::app::AppState;
+use crate::core::types::{
+    CreateInviteLinkRequest, InviteLink, InviteLinkPreview, InviteLinkRequest, JoinResult, User,
+};
+use crate::invite_links::service::InviteTarget;
+use crate::web::handlers::auth::ErrorResponse;
+
+/// A new invite link; the token is never shown again
+#[derive(Serialize)]
+pub struct CreatedInviteLinkResponse {
+    #[serde(flatten)]
+    pub link: InviteLink,
+    pub token: String,
+    /// Where to send people: shows the link's target, POST to join
+    pub url: String,
+}
+
+pub async fn create_chat_link(
+    State(state): State<Arc<AppState>>,
+    Path(chat_id): Path<String>,
+    Extension(user): Extension<User>,
+    Json(request): Json<CreateInviteLinkRequest>,
+) -> Result<Json<CreatedInviteLinkResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let chat_uuid = Uuid::parse_str(&chat_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid chat ID".to_string() })))?;
+
+    create_link(&state, &user, InviteTarget::Chat(chat_uuid), request).await
+}
+
+pub async fn list_chat_links(
+    State(state): State<Arc<AppState>>,
+    Path(chat_id): Path<String>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Vec<InviteLink>>, (StatusCode, Json<ErrorResponse>)> {
+    let chat_uuid = Uuid::parse_str(&chat_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid chat ID".to_string() })))?;
+
+    match state.invite_link_service.list_links(&user, InviteTarget::Chat(chat_uuid)).await {
+        Ok(links) => Ok(Json(links)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn create_board_link(
+    State(state): State<Arc<AppState>>,
+    Path(board_name): Path<String>,
+    Extension(user): Extension<User>,
+    Json(request): Json<CreateInviteLinkRequest>,
+) -> Result<Json<CreatedInviteLinkResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let board = state.board_service.get_visible_board(&board_name, Some(&user)).await
+        .map_err(|e| (
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        ))?;
+
+    create_link(&state, &user, InviteTarget::Board(board), request).await
+}
+
+pub async fn list_board_links(
+    State(state): State<Arc<AppState>>,
+    Path(board_name): Path<String>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Vec<InviteLink>>, (StatusCode, Json<ErrorResponse>)> {
+    let board = state.board_service.get_visible_board(&board_name, Some(&user)).await
+        .map_err(|e| (
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        ))?;
+
+    match state.invite_link_service.list_links(&user, InviteTarget::Board(board)).await {
+        Ok(links) => Ok(Json(links)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn revoke_link(
+    State(state): State<Arc<AppState>>,
+    Path(link_id): Path<String>,
+    Extension(user): Extension<User>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let link_uuid = Uuid::parse_str(&link_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid invite link ID".to_string() })))?;
+
+    match state.invite_link_service.revoke_link(&user, link_uuid).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn preview_link(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+) -> Result<Json<InviteLinkPreview>, (StatusCode, Json<ErrorResponse>)> {
+    match state.invite_link_service.preview_link(&token).await {
+        Ok(preview) => Ok(Json(preview)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn join(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+    Extension(user): Extension<User>,
+) -> Result<Json<JoinResult>, (StatusCode, Json<ErrorResponse>)> {
+    match state.invite_link_service.join(&user, &token).await {
+        Ok(result) => Ok(Json(result)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn list_requests(
+    State(state): State<Arc<AppState>>,
+    Path(link_id): Path<String>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Vec<InviteLinkRequest>>, (StatusCode, Json<ErrorResponse>)> {
+    let link_uuid = Uuid::parse_str(&link_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid invite link ID".to_string() })))?;
+
+    match state.invite_link_service.list_requests(&user, link_uuid).await {
+        Ok(requests) => Ok(Json(requests)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn approve_request(
+    State(state): State<Arc<AppState>>,
+    Path((link_id, user_id)): Path<(String, String)>,
+    Extension(user): Extension<User>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let link_uuid = Uuid::parse_str(&link_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid invite link ID".to_string() })))?;
+
+    let user_uuid = Uuid::parse_str(&user_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid user ID".to_string() })))?;
+
+    match state.invite_link_service.approve_request(&user, link_uuid, user_uuid).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn reject_request(
+    State(state): State<Arc<AppState>>,
+    Path((link_id, user_id)): Path<(String, String)>,
+    Extension(user): Extension<User>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let link_uuid = Uuid::parse_str(&link_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid invite link ID".to_string() })))?;
+
+    let user_uuid = Uuid::parse_str(&user_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid user ID".to_string() })))?;
+
+    match state.invite_link_service.reject_request(&user, link_uuid, user_uuid).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+async fn create_link(
+    state: &AppState,
+    user: &User,
+    target: InviteTarget,
+    request: CreateInviteLinkRequest,
+) -> Result<Json<CreatedInviteLinkResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.invite_link_service.create_link(user, target, request).await {
+        Ok((link, token)) => Ok(Json(CreatedInviteLinkResponse {
+            url: format!("{}/api/join/{}", state.config.server.base_url, token),
+            link,
+            token,
+        })),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
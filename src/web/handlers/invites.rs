@@ -0,0 +1,65 @@
+use axum::{
+    extract::{State, Path},
+    http::StatusCode,
+    response::Json,
+    Extension,
+};
+use serde::Serialize;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::core::app::AppState;
+use crate::core::types::{CreateInviteRequest, InviteCode, User};
+use crate::web::handlers::auth::ErrorResponse;
+
+/// A new invite code; the code is never shown again
+#[derive(Serialize)]
+pub struct CreatedInviteResponse {
+    #[serde(flatten)]
+    pub invite: InviteCode,
+    pub code: String,
+}
+
+pub async fn list_invites(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Vec<InviteCode>>, (StatusCode, Json<ErrorResponse>)> {
+    match state.invite_service.list_invites(user.id).await {
+        Ok(invites) => Ok(Json(invites)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn create_invite(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Json(request): Json<CreateInviteRequest>,
+) -> Result<Json<CreatedInviteResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.invite_service.create_invite(&user, request).await {
+        Ok((invite, code)) => Ok(Json(CreatedInviteResponse { invite, code })),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn revoke_invite(
+    State(state): State<Arc<AppState>>,
+    Path(invite_id): Path<String>,
+    Extension(user): Extension<User>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let invite_uuid = Uuid::parse_str(&invite_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid invite ID".to_string() })))?;
+
+    match state.invite_service.revoke_invite(&user, invite_uuid).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
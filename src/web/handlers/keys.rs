@@ -0,0 +1,86 @@
+use axum::{
+    extract::{State, Path},
+    http::StatusCode,
+    response::Json,
+    Extension,
+};
+use serde::Serialize;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::core::app::AppState;
+use crate::core::types::{User, PreKeyBundle, UploadKeysRequest};
+use crate::crypto::signing::SigningPublicKey;
+use crate::web::handlers::auth::ErrorResponse;
+
+#[derive(Serialize)]
+pub struct PreKeyCountResponse {
+    pub one_time_prekey_count: i64,
+}
+
+/// Server signing keys used to verify threads, posts and messages
+pub async fn signing_keys(
+    State(state): State<Arc<AppState>>,
+) -> Json<Vec<SigningPublicKey>> {
+    Json(state.signing_service.public_keys())
+}
+
+pub async fn upload_keys(
+    State(state): State<Arc<AppState>>,
+    Path(device_id): Path<String>,
+    Extension(user): Extension<User>,
+    Json(request): Json<UploadKeysRequest>,
+) -> Result<Json<PreKeyCountResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.e2ee_service.upload_keys(user.id, &device_id, request).await {
+        Ok(count) => Ok(Json(PreKeyCountResponse { one_time_prekey_count: count })),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn prekey_count(
+    State(state): State<Arc<AppState>>,
+    Path(device_id): Path<String>,
+    Extension(user): Extension<User>,
+) -> Result<Json<PreKeyCountResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.e2ee_service.prekey_count(user.id, &device_id).await {
+        Ok(count) => Ok(Json(PreKeyCountResponse { one_time_prekey_count: count })),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn delete_device(
+    State(state): State<Arc<AppState>>,
+    Path(device_id): Path<String>,
+    Extension(user): Extension<User>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    match state.e2ee_service.delete_device(user.id, &device_id).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn claim_bundles(
+    State(state): State<Arc<AppState>>,
+    Path(user_id): Path<String>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Vec<PreKeyBundle>>, (StatusCode, Json<ErrorResponse>)> {
+    let user_uuid = Uuid::parse_str(&user_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid user ID".to_string() })))?;
+
+    match state.e2ee_service.claim_bundles(user_uuid, user.id).await {
+        Ok(bundles) => Ok(Json(bundles)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
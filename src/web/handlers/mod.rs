@@ -1,4 +1,11 @@
+pub mod account;
+pub mod admin;
 pub mod auth;
 pub mod board;
+pub mod challenge;
 pub mod chat;
+pub mod invite_links;
+pub mod invites;
+pub mod keys;
+pub mod tokens;
 pub mod user;
\ No newline at end of file
@@ -0,0 +1,65 @@
+use axum::{
+    extract::{State, Path},
+    http::StatusCode,
+    response::Json,
+    Extension,
+};
+use serde::Serialize;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::core::app::AppState;
+use crate::core::types::{ApiToken, CreateApiTokenRequest, User};
+use crate::web::handlers::auth::ErrorResponse;
+
+/// A new token; the secret is never shown again
+#[derive(Serialize)]
+pub struct CreatedApiTokenResponse {
+    #[serde(flatten)]
+    pub token: ApiToken,
+    pub secret: String,
+}
+
+pub async fn list_tokens(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Vec<ApiToken>>, (StatusCode, Json<ErrorResponse>)> {
+    match state.api_token_service.list_tokens(user.id).await {
+        Ok(tokens) => Ok(Json(tokens)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn create_token(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Json(request): Json<CreateApiTokenRequest>,
+) -> Result<Json<CreatedApiTokenResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match state.api_token_service.create_token(&user, request).await {
+        Ok((token, secret)) => Ok(Json(CreatedApiTokenResponse { token, secret })),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn revoke_token(
+    State(state): State<Arc<AppState>>,
+    Path(token_id): Path<String>,
+    Extension(user): Extension<User>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let token_uuid = Uuid::parse_str(&token_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid token ID".to_string() })))?;
+
+    match state.api_token_service.revoke_token(user.id, token_uuid).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
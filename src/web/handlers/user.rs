@@ -1,27 +1,243 @@
 use axum::{
-    extract::{State, Path},
+    body::Bytes,
+    extract::{State, Path, Query},
     http::StatusCode,
     response::Json,
+    Extension,
 };
+use serde::Deserialize;
 use std::sync::Arc;
 use uuid::Uuid;
 
 use crate::core::app::AppState;
-use crate::core::types::User;
+use crate::core::types::{
+    AddContactRequest, BlockUserRequest, BlockedUser, Contact, Profile, PublicUser, UpdateContactRequest,
+    UpdateContentPreferencesRequest, UpdatePrivacyRequest, UpdateProfileRequest, User,
+};
 use crate::web::handlers::auth::ErrorResponse;
 
+#[derive(Deserialize)]
+pub struct DirectoryQuery {
+    pub q: String,
+    pub limit: Option<i64>,
+}
+
 pub async fn get_user(
     State(state): State<Arc<AppState>>,
     Path(user_id): Path<String>,
-) -> Result<Json<User>, (StatusCode, Json<ErrorResponse>)> {
+    Extension(viewer): Extension<User>,
+) -> Result<Json<PublicUser>, (StatusCode, Json<ErrorResponse>)> {
     let user_uuid = Uuid::parse_str(&user_id)
         .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid user ID".to_string() })))?;
     
-    match state.auth_service.get_user(user_uuid).await {
+    match state.user_service.get_public_user(user_uuid, &viewer).await {
         Ok(user) => Ok(Json(user)),
         Err(e) => Err((
             StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
             Json(ErrorResponse { error: e.to_string() }),
         )),
     }
-}
\ No newline at end of file
+}
+
+pub async fn get_profile(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Profile>, (StatusCode, Json<ErrorResponse>)> {
+    match state.user_service.get_profile(user.id).await {
+        Ok(profile) => Ok(Json(profile)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn update_profile(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Json(request): Json<UpdateProfileRequest>,
+) -> Result<Json<Profile>, (StatusCode, Json<ErrorResponse>)> {
+    match state.user_service.update_profile(&user, request).await {
+        Ok(profile) => Ok(Json(profile)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+/// Replace the avatar with the image in the request body
+pub async fn set_avatar(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    image: Bytes,
+) -> Result<Json<Profile>, (StatusCode, Json<ErrorResponse>)> {
+    match state.user_service.set_avatar(&user, image.to_vec()).await {
+        Ok(profile) => Ok(Json(profile)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn remove_avatar(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Profile>, (StatusCode, Json<ErrorResponse>)> {
+    match state.user_service.remove_avatar(&user).await {
+        Ok(profile) => Ok(Json(profile)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn update_privacy(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Json(request): Json<UpdatePrivacyRequest>,
+) -> Result<Json<Profile>, (StatusCode, Json<ErrorResponse>)> {
+    match state.user_service.update_privacy(user.id, request).await {
+        Ok(profile) => Ok(Json(profile)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn update_content_preferences(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Json(request): Json<UpdateContentPreferencesRequest>,
+) -> Result<Json<Profile>, (StatusCode, Json<ErrorResponse>)> {
+    match state.user_service.update_content_preferences(user.id, request).await {
+        Ok(profile) => Ok(Json(profile)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn search_directory(
+    State(state): State<Arc<AppState>>,
+    Extension(viewer): Extension<User>,
+    Query(query): Query<DirectoryQuery>,
+) -> Result<Json<Vec<PublicUser>>, (StatusCode, Json<ErrorResponse>)> {
+    match state.user_service.search_users(&query.q, &viewer, query.limit).await {
+        Ok(users) => Ok(Json(users)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn list_contacts(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Vec<Contact>>, (StatusCode, Json<ErrorResponse>)> {
+    match state.contact_service.list_contacts(&user).await {
+        Ok(contacts) => Ok(Json(contacts)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn add_contact(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Json(request): Json<AddContactRequest>,
+) -> Result<Json<Contact>, (StatusCode, Json<ErrorResponse>)> {
+    match state.contact_service.add_contact(&user, request).await {
+        Ok(contact) => Ok(Json(contact)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn update_contact(
+    State(state): State<Arc<AppState>>,
+    Path(contact_id): Path<String>,
+    Extension(user): Extension<User>,
+    Json(request): Json<UpdateContactRequest>,
+) -> Result<Json<Contact>, (StatusCode, Json<ErrorResponse>)> {
+    let contact_uuid = Uuid::parse_str(&contact_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid user ID".to_string() })))?;
+
+    match state.contact_service.update_contact(&user, contact_uuid, request).await {
+        Ok(contact) => Ok(Json(contact)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn remove_contact(
+    State(state): State<Arc<AppState>>,
+    Path(contact_id): Path<String>,
+    Extension(user): Extension<User>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let contact_uuid = Uuid::parse_str(&contact_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid user ID".to_string() })))?;
+
+    match state.contact_service.remove_contact(user.id, contact_uuid).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn list_blocks(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Vec<BlockedUser>>, (StatusCode, Json<ErrorResponse>)> {
+    match state.block_service.list_blocks(&user).await {
+        Ok(blocks) => Ok(Json(blocks)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn block_user(
+    State(state): State<Arc<AppState>>,
+    Extension(user): Extension<User>,
+    Json(request): Json<BlockUserRequest>,
+) -> Result<Json<BlockedUser>, (StatusCode, Json<ErrorResponse>)> {
+    match state.block_service.block_user(&user, request).await {
+        Ok(blocked) => Ok(Json(blocked)),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
+
+pub async fn unblock_user(
+    State(state): State<Arc<AppState>>,
+    Path(blocked_id): Path<String>,
+    Extension(user): Extension<User>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let blocked_uuid = Uuid::parse_str(&blocked_id)
+        .map_err(|_| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: "Invalid user ID".to_string() })))?;
+
+    match state.block_service.unblock_user(&user, blocked_uuid).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(e) => Err((
+            StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            Json(ErrorResponse { error: e.to_string() }),
+        )),
+    }
+}
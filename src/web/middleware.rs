@@ -6,8 +6,13 @@ use axum::{
 };
 use std::sync::Arc;
 
+use crate::auth::tokens::TOKEN_PREFIX;
 use crate::core::app::AppState;
-use crate::core::types::User;
+use crate::core::types::{TokenScope, User};
+
+/// Scope a personal access token needs for the route, set by `require_scope`
+#[derive(Debug, Clone, Copy)]
+pub struct RequiredScope(pub TokenScope);
 
 pub async fn auth_middleware(
     State(state): State<Arc<AppState>>,
@@ -22,6 +27,23 @@ pub async fn auth_middleware(
 
     if let Some(auth_header) = auth_header {
         if let Some(token) = auth_header.strip_prefix("Bearer ") {
+            if token.starts_with(TOKEN_PREFIX) {
+                // Personal access tokens only reach routes that declare a
+                // scope, and only if the token was granted it
+                let required = request.extensions().get::<RequiredScope>().copied();
+
+                return match state.api_token_service.validate_token(token).await {
+                    Ok((user, scopes)) => match required {
+                        Some(RequiredScope(scope)) if scopes.contains(&scope) => {
+                            request.extensions_mut().insert(user);
+                            Ok(next.run(request).await)
+                        }
+                        _ => Err(StatusCode::FORBIDDEN),
+                    },
+                    Err(_) => Err(StatusCode::UNAUTHORIZED),
+                };
+            }
+
             // Validate the token
             match state.auth_service.validate_session(token).await {
                 Ok(user) => {
@@ -37,4 +59,56 @@ pub async fn auth_middleware(
     } else {
         Err(StatusCode::UNAUTHORIZED)
     }
-}
\ No newline at end of file
+}
+
+/// Identify the caller if they sent valid credentials, for public routes
+/// that tailor their response to the viewer. Missing or invalid credentials
+/// are not an error; the request just proceeds anonymously. Personal access
+/// tokens count only if granted the route's scope, as with `auth_middleware`.
+pub async fn optional_auth_middleware(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let token = request
+        .headers()
+        .get("Authorization")
+        .and_then(|header| header.to_str().ok())
+        .and_then(|header| header.strip_prefix("Bearer "))
+        .map(str::to_string);
+
+    if let Some(token) = token {
+        let user = if token.starts_with(TOKEN_PREFIX) {
+            let required = request.extensions().get::<RequiredScope>().copied();
+
+            match state.api_token_service.validate_token(&token).await {
+                Ok((user, scopes)) => match required {
+                    Some(RequiredScope(scope)) if scopes.contains(&scope) => Some(user),
+                    _ => None,
+                },
+                Err(_) => None,
+            }
+        } else {
+            state.auth_service.validate_session(&token).await.ok()
+        };
+
+        if let Some(user) = user {
+            request.extensions_mut().insert(user);
+        }
+    }
+
+    next.run(request).await
+}
+
+/// Declare the scope a personal access token needs for a route. Must wrap
+/// `auth_middleware`, i.e. be added as the outer layer:
+///
+/// `.layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::ReadChats, require_scope))`
+pub async fn require_scope(
+    State(scope): State<TokenScope>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    request.extensions_mut().insert(RequiredScope(scope));
+    next.run(request).await
+}
@@ -1,6 +1,6 @@
 use axum::{
     Router,
-    routing::{get, post, put, delete},
+    routing::{get, post, put, patch, delete},
     middleware::from_fn_with_state,
 };
 use std::sync::Arc;
@@ -8,8 +8,9 @@ use tower_http::cors::CorsLayer;
 use tower_http::services::ServeDir;
 
 use crate::core::app::AppState;
-use crate::web::handlers::{auth, board, chat, user};
-use crate::web::middleware::auth_middleware;
+use crate::core::types::TokenScope;
+use crate::web::handlers::{account, admin, auth, board, challenge, chat, invite_links, invites, keys, tokens, user};
+use crate::web::middleware::{auth_middleware, optional_auth_middleware, require_scope};
 
 pub fn create_router(state: Arc<AppState>) -> Router {
     Router::new()
@@ -22,26 +23,133 @@ pub fn create_router(state: Arc<AppState>) -> Router {
         // Public routes (no auth required)
         .route("/api/auth/register", post(auth::register))
         .route("/api/auth/login", post(auth::login))
-        .route("/api/boards", get(board::list_boards))
-        .route("/api/boards/:name", get(board::get_board))
-        .route("/api/boards/:name/threads", get(board::list_threads))
-        .route("/api/threads/:id", get(board::get_thread))
-        .route("/api/threads/:id/posts", get(board::list_posts))
+        .route("/api/auth/2fa/verify", post(auth::verify_two_factor))
+        .route("/api/auth/2fa/passkey/start", post(auth::start_passkey_second_factor))
+        .route("/api/auth/2fa/passkey/finish", post(auth::finish_passkey_second_factor))
+        .route("/api/auth/passkeys/login/start", post(auth::start_passkey_login))
+        .route("/api/auth/passkeys/login/finish", post(auth::finish_passkey_login))
+        .route("/api/auth/email/verify", post(auth::verify_email))
+        .route("/api/auth/password/forgot", post(auth::request_password_reset))
+        .route("/api/auth/password/reset", post(auth::reset_password))
+        .route("/api/auth/oidc/providers", get(auth::list_oidc_providers))
+        .route("/api/auth/oidc/:provider/start", post(auth::start_oidc_login))
+        .route("/api/auth/oidc/:provider/callback", get(auth::finish_oidc_login))
+        .route("/api/boards", get(board::list_boards).layer(from_fn_with_state(state.clone(), optional_auth_middleware)).layer(from_fn_with_state(TokenScope::ReadBoards, require_scope)))
+        .route("/api/boards/:name", get(board::get_board).layer(from_fn_with_state(state.clone(), optional_auth_middleware)).layer(from_fn_with_state(TokenScope::ReadBoards, require_scope)))
+        .route("/api/boards/:name/threads", get(board::list_threads).layer(from_fn_with_state(state.clone(), optional_auth_middleware)).layer(from_fn_with_state(TokenScope::ReadBoards, require_scope)))
+        .route("/api/threads/:id", get(board::get_thread).layer(from_fn_with_state(state.clone(), optional_auth_middleware)).layer(from_fn_with_state(TokenScope::ReadBoards, require_scope)))
+        .route("/api/threads/:id/posts", get(board::list_posts).layer(from_fn_with_state(state.clone(), optional_auth_middleware)).layer(from_fn_with_state(TokenScope::ReadBoards, require_scope)))
+        .route("/api/keys/signing", get(keys::signing_keys))
+        .route("/api/challenges", post(challenge::create_challenge).layer(from_fn_with_state(state.clone(), optional_auth_middleware)))
         
         // Protected routes (auth required) - Apply middleware to specific routes
         .route("/api/auth/logout", post(auth::logout).layer(from_fn_with_state(state.clone(), auth_middleware)))
         .route("/api/auth/me", get(auth::me).layer(from_fn_with_state(state.clone(), auth_middleware)))
-        .route("/api/boards", post(board::create_board).layer(from_fn_with_state(state.clone(), auth_middleware)))
-        .route("/api/boards/:name/threads", post(board::create_thread).layer(from_fn_with_state(state.clone(), auth_middleware)))
-        .route("/api/threads/:id/posts", post(board::create_post).layer(from_fn_with_state(state.clone(), auth_middleware)))
-        .route("/api/chats", get(chat::list_chats).layer(from_fn_with_state(state.clone(), auth_middleware)))
-        .route("/api/chats", post(chat::create_chat).layer(from_fn_with_state(state.clone(), auth_middleware)))
-        .route("/api/chats/:id", get(chat::get_chat).layer(from_fn_with_state(state.clone(), auth_middleware)))
-        .route("/api/chats/:id/messages", get(chat::list_messages).layer(from_fn_with_state(state.clone(), auth_middleware)))
-        .route("/api/chats/:id/messages", post(chat::send_message).layer(from_fn_with_state(state.clone(), auth_middleware)))
-        .route("/api/chats/:id/participants", post(chat::add_participant).layer(from_fn_with_state(state.clone(), auth_middleware)))
-        .route("/api/chats/:id/participants/:user_id", delete(chat::remove_participant).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/auth/me", delete(auth::delete_account).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/auth/me/deletion", get(auth::get_account_deletion).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/auth/me/deletion", delete(auth::cancel_account_deletion).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/auth/me/export", get(account::export_account).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/auth/email/verification", post(auth::send_email_verification).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/auth/2fa/totp", post(auth::setup_totp).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/auth/2fa/totp", delete(auth::disable_totp).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/auth/2fa/totp/confirm", post(auth::confirm_totp).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/auth/2fa/recovery-codes", post(auth::regenerate_recovery_codes).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/auth/passkeys", get(auth::list_passkeys).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/auth/passkeys/:id", delete(auth::delete_passkey).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/auth/passkeys/register/start", post(auth::start_passkey_registration).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/auth/passkeys/register/finish", post(auth::finish_passkey_registration).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/auth/tokens", get(tokens::list_tokens).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/auth/tokens", post(tokens::create_token).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/auth/tokens/:id", delete(tokens::revoke_token).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/invites", get(invites::list_invites).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/invites", post(invites::create_invite).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/invites/:id", delete(invites::revoke_invite).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/chats/:id/invite-links", get(invite_links::list_chat_links).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/chats/:id/invite-links", post(invite_links::create_chat_link).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/boards/:name", patch(board::update_board).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/boards/:name", delete(board::delete_board).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/boards/:name/members", get(board::list_members).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/boards/:name/members", post(board::add_member).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/boards/:name/members/:user_id", delete(board::remove_member).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/boards/:name/leave", post(board::leave_board).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/boards/:name/join-requests", get(board::list_join_requests).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::Moderate, require_scope)))
+        .route("/api/boards/:name/join-requests", post(board::request_to_join).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/boards/:name/join-requests/:user_id/approve", post(board::approve_join_request).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::Moderate, require_scope)))
+        .route("/api/boards/:name/join-requests/:user_id/reject", post(board::reject_join_request).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::Moderate, require_scope)))
+        .route("/api/boards/:name/moderators", get(board::list_moderators).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::Moderate, require_scope)))
+        .route("/api/boards/:name/moderators/:user_id", put(board::add_moderator).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::Moderate, require_scope)))
+        .route("/api/boards/:name/moderators/:user_id", delete(board::remove_moderator).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::Moderate, require_scope)))
+        .route("/api/boards/:name/owner", put(board::transfer_ownership).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/boards/:name/filters", get(board::list_filters).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::Moderate, require_scope)))
+        .route("/api/boards/:name/filters", post(board::create_filter).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::Moderate, require_scope)))
+        .route("/api/boards/:name/filters/:id", delete(board::delete_filter).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::Moderate, require_scope)))
+        .route("/api/boards/:name/moderation-log", get(board::moderation_log).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::Moderate, require_scope)))
+        .route("/api/boards/:name/invite-links", get(invite_links::list_board_links).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/boards/:name/invite-links", post(invite_links::create_board_link).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/invite-links/:id", delete(invite_links::revoke_link).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/invite-links/:id/requests", get(invite_links::list_requests).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/invite-links/:id/requests/:user_id/approve", post(invite_links::approve_request).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/invite-links/:id/requests/:user_id/reject", post(invite_links::reject_request).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/join/:token", get(invite_links::preview_link).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/join/:token", post(invite_links::join).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        // Routes with a scope also accept personal access tokens granted it
+        .route("/api/boards", post(board::create_board).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::PostBoards, require_scope)))
+        .route("/api/boards/:name/threads", post(board::create_thread).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::PostBoards, require_scope)))
+        .route("/api/threads/:id/posts", post(board::create_post).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::PostBoards, require_scope)))
+        .route("/api/chats", get(chat::list_chats).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::ReadChats, require_scope)))
+        .route("/api/chats", post(chat::create_chat).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::SendMessages, require_scope)))
+        .route("/api/chats/:id", get(chat::get_chat).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::ReadChats, require_scope)))
+        .route("/api/chats/:id", delete(chat::delete_chat).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/chats/:id/messages", get(chat::list_messages).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::ReadChats, require_scope)))
+        .route("/api/chats/:id/messages", post(chat::send_message).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::SendMessages, require_scope)))
+        .route("/api/chats/:id/participants", post(chat::add_participant).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::ManageChats, require_scope)))
+        .route("/api/chats/:id/participants/:user_id", delete(chat::remove_participant).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::ManageChats, require_scope)))
+        .route("/api/chats/:id", put(chat::rename_chat).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::ManageChats, require_scope)))
+        .route("/api/chats/:id/participants", get(chat::list_participants).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::ReadChats, require_scope)))
+        .route("/api/chats/:id/leave", post(chat::leave_chat).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::SendMessages, require_scope)))
+        .route("/api/chats/:id/owner", put(chat::transfer_ownership).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::ManageChats, require_scope)))
+        .route("/api/chats/:id/admins/:user_id", put(chat::promote_admin).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::ManageChats, require_scope)))
+        .route("/api/chats/:id/admins/:user_id", delete(chat::demote_admin).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::ManageChats, require_scope)))
+        .route("/api/chats/:id/search", get(chat::search_chat_messages).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::ReadChats, require_scope)))
+        .route("/api/chats/:id/search", put(chat::set_search).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::ManageChats, require_scope)))
+        .route("/api/chat-requests", get(chat::list_chat_requests).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::ReadChats, require_scope)))
+        .route("/api/chat-requests/:id/accept", post(chat::accept_chat_request).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::SendMessages, require_scope)))
+        .route("/api/chat-requests/:id/decline", post(chat::decline_chat_request).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::SendMessages, require_scope)))
+        .route("/api/chat-requests/:id/report", post(chat::report_chat_request).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/messages/search", get(chat::search_messages).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::ReadChats, require_scope)))
+        .route("/api/profile", get(user::get_profile).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/profile", put(user::update_profile).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/profile/avatar", put(user::set_avatar).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/profile/avatar", delete(user::remove_avatar).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/profile/privacy", put(user::update_privacy).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/profile/content", put(user::update_content_preferences).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/directory", get(user::search_directory).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/contacts", get(user::list_contacts).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/contacts", post(user::add_contact).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/contacts/:id", put(user::update_contact).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/contacts/:id", delete(user::remove_contact).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/blocks", get(user::list_blocks).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/blocks", post(user::block_user).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/blocks/:id", delete(user::unblock_user).layer(from_fn_with_state(state.clone(), auth_middleware)))
         .route("/api/users/:id", get(user::get_user).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/users/:id/keys", get(keys::claim_bundles).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/keys/devices/:device_id", put(keys::upload_keys).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/keys/devices/:device_id", delete(keys::delete_device).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/keys/devices/:device_id/count", get(keys::prekey_count).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/admin/crypto/reencrypt", post(admin::start_reencryption).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/admin/jobs", get(admin::list_jobs).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/admin/jobs/:id", get(admin::get_job).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/admin/boards/:name/challenge", put(admin::set_board_challenge).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/admin/boards/:name/deletion-policy", put(admin::set_board_deletion_policy).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/admin/reports", get(admin::list_reports).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::Moderate, require_scope)))
+        .route("/api/admin/reports/:id/resolve", post(admin::resolve_report).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::Moderate, require_scope)))
+        .route("/api/admin/filters", get(admin::list_filters).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::Moderate, require_scope)))
+        .route("/api/admin/filters", post(admin::create_filter).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::Moderate, require_scope)))
+        .route("/api/admin/filters/:id", delete(admin::delete_filter).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::Moderate, require_scope)))
+        .route("/api/admin/moderation-log", get(admin::moderation_log).layer(from_fn_with_state(state.clone(), auth_middleware)).layer(from_fn_with_state(TokenScope::Moderate, require_scope)))
+        .route("/api/admin/registrations", get(admin::list_pending_registrations).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/admin/registrations/:id/approve", post(admin::approve_registration).layer(from_fn_with_state(state.clone(), auth_middleware)))
+        .route("/api/admin/registrations/:id/reject", post(admin::reject_registration).layer(from_fn_with_state(state.clone(), auth_middleware)))
         
         // Health check
         .route("/health", get(health_check))